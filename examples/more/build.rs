@@ -1,5 +1,15 @@
 extern crate varlink_generator;
 
 fn main() {
-    varlink_generator::cargo_build_tosource("src/org.example.more.varlink", true);
+    varlink_generator::cargo_build_tosource_options(
+        "src/org.example.more.varlink",
+        true,
+        &varlink_generator::GeneratorOptions {
+            more_methods: Some(vec!["TestMore"]),
+            oneway_methods: Some(vec!["Ping"]),
+            direct_client: true,
+            dyn_client: true,
+            ..Default::default()
+        },
+    );
 }
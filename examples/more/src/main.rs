@@ -9,6 +9,8 @@ use crate::org_example_more::*;
 use chainerror::*;
 
 // Dynamically build the varlink rust code.
+// The generated code has to compile without warnings.
+#[deny(warnings)]
 mod org_example_more;
 
 #[cfg(test)]
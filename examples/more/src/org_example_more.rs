@@ -1,12 +1,14 @@
 #![doc = "This file was automatically generated by the varlink rust generator"]
 #![allow(non_camel_case_types)]
 #![allow(non_snake_case)]
+#![allow(dead_code)]
 use chainerror::*;
 use serde_derive::{Deserialize, Serialize};
-use serde_json;
 use std::io::BufRead;
 use std::sync::{Arc, RwLock};
 use varlink::{self, CallTrait};
+#[doc = " Enum, returning either start, progress or end"]
+#[doc = " progress: [0-100]"]
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct r#State {
     pub r#start: Option<bool>,
@@ -24,6 +26,12 @@ pub trait VarlinkCallError: varlink::CallTrait {
             Some(serde_json::to_value(TestMoreError_Args { r#reason }).map_err(minto_cherr!())?),
         ))
     }
+    fn reply_test_more_error_struct(&mut self, args: TestMoreError_Args) -> varlink::Result<()> {
+        self.reply_struct(varlink::Reply::error(
+            "org.example.more.TestMoreError",
+            Some(serde_json::to_value(args).map_err(minto_cherr!())?),
+        ))
+    }
 }
 impl<'a> VarlinkCallError for varlink::Call<'a> {}
 #[allow(dead_code)]
@@ -34,13 +42,16 @@ pub enum ErrorKind {
     Varlink_Error,
     VarlinkReply_Error(varlink::Reply),
     Generic,
+    #[doc = " Something failed in TestMore"]
     TestMoreError(Option<TestMoreError_Args>),
 }
 impl ::std::fmt::Display for ErrorKind {
     fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
         match self {
             ErrorKind::Io_Error(_) => write!(f, "IO error"),
-            ErrorKind::SerdeJson_Error(_) => write!(f, "(De)Serialization Error"),
+            ErrorKind::SerdeJson_Error(_) => {
+                write!(f, "(De)Serialization Error")
+            }
             ErrorKind::Varlink_Error => write!(f, "Varlink Error"),
             ErrorKind::VarlinkReply_Error(v) => write!(f, "Unknown error reply: '{:#?}'", v),
             ErrorKind::Generic => Ok(()),
@@ -121,6 +132,12 @@ pub struct Ping_Reply {
     pub r#pong: String,
 }
 impl varlink::VarlinkReply for Ping_Reply {}
+impl varlink::VarlinkReplyValues for Ping_Reply {
+    type Values = String;
+    fn into_values(self) -> Self::Values {
+        self.r#pong
+    }
+}
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct Ping_Args {
     pub r#ping: String,
@@ -134,6 +151,10 @@ impl<'a> Call_Ping for varlink::Call<'a> {}
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct StopServing_Reply {}
 impl varlink::VarlinkReply for StopServing_Reply {}
+impl varlink::VarlinkReplyValues for StopServing_Reply {
+    type Values = ();
+    fn into_values(self) -> Self::Values {}
+}
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct StopServing_Args {}
 pub trait Call_StopServing: VarlinkCallError {
@@ -147,6 +168,12 @@ pub struct TestMore_Reply {
     pub r#state: State,
 }
 impl varlink::VarlinkReply for TestMore_Reply {}
+impl varlink::VarlinkReplyValues for TestMore_Reply {
+    type Values = State;
+    fn into_values(self) -> Self::Values {
+        self.r#state
+    }
+}
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct TestMore_Args {
     pub r#n: i64,
@@ -157,24 +184,47 @@ pub trait Call_TestMore: VarlinkCallError {
     }
 }
 impl<'a> Call_TestMore for varlink::Call<'a> {}
+#[doc = " The replies of a `more` call of `TestMore`."]
+pub struct TestMore_MoreReply(varlink::MethodCall<TestMore_Args, TestMore_Reply, Error>);
+impl Iterator for TestMore_MoreReply {
+    type Item = Result<TestMore_Reply>;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+}
 pub trait VarlinkInterface {
-    fn ping(&self, call: &mut Call_Ping, r#ping: String) -> varlink::Result<()>;
-    fn stop_serving(&self, call: &mut Call_StopServing) -> varlink::Result<()>;
-    fn test_more(&self, call: &mut Call_TestMore, r#n: i64) -> varlink::Result<()>;
+    #[doc = " Returns the same string"]
+    fn ping(&self, call: &mut dyn Call_Ping, r#ping: String) -> varlink::Result<()>;
+    #[doc = " Stop serving"]
+    fn stop_serving(&self, call: &mut dyn Call_StopServing) -> varlink::Result<()>;
+    #[doc = " Dummy progress method"]
+    #[doc = " n: number of progress steps"]
+    fn test_more(&self, call: &mut dyn Call_TestMore, r#n: i64) -> varlink::Result<()>;
     fn call_upgraded(
         &self,
         _call: &mut varlink::Call,
-        _bufreader: &mut BufRead,
+        _bufreader: &mut dyn BufRead,
     ) -> varlink::Result<Vec<u8>> {
         Ok(Vec::new())
     }
 }
 pub trait VarlinkClientInterface {
+    #[doc = " Returns the same string"]
     fn ping(&mut self, r#ping: String) -> varlink::MethodCall<Ping_Args, Ping_Reply, Error>;
+    #[doc = " Stop serving"]
     fn stop_serving(&mut self) -> varlink::MethodCall<StopServing_Args, StopServing_Reply, Error>;
+    #[doc = " Dummy progress method"]
+    #[doc = " n: number of progress steps"]
     fn test_more(&mut self, r#n: i64) -> varlink::MethodCall<TestMore_Args, TestMore_Reply, Error>;
 }
+#[doc = r" A client of the interface."]
+#[doc = r""]
+#[doc = r" Clones share the connection, so they are cheap to hand to other threads."]
+#[doc = r" Calls of all clones are serialized through the lock of the shared connection,"]
+#[doc = r" while a `more()` call is still receiving replies, other calls fail with"]
+#[doc = r" `ConnectionBusy`."]
 #[allow(dead_code)]
+#[derive(Clone)]
 pub struct VarlinkClient {
     connection: Arc<RwLock<varlink::Connection>>,
 }
@@ -183,6 +233,12 @@ impl VarlinkClient {
     pub fn new(connection: Arc<RwLock<varlink::Connection>>) -> Self {
         VarlinkClient { connection }
     }
+    #[doc = " Call `TestMore` with `more` and return the iterator over the replies."]
+    pub fn test_more_more(&mut self, r#n: i64) -> Result<TestMore_MoreReply> {
+        let mut call = self.test_more(r#n);
+        call.more()?;
+        Ok(TestMore_MoreReply(call))
+    }
 }
 impl VarlinkClientInterface for VarlinkClient {
     fn ping(&mut self, r#ping: String) -> varlink::MethodCall<Ping_Args, Ping_Reply, Error> {
@@ -207,14 +263,245 @@ impl VarlinkClientInterface for VarlinkClient {
         )
     }
 }
+pub trait VarlinkClientDirectInterface {
+    #[doc = " Returns the same string"]
+    fn ping(&mut self, r#ping: String) -> Result<Ping_Reply>;
+    fn ping_more(
+        &mut self,
+        r#ping: String,
+    ) -> Result<varlink::MethodCall<Ping_Args, Ping_Reply, Error>>;
+    fn ping_oneway(&mut self, r#ping: String) -> Result<()>;
+    #[doc = " Stop serving"]
+    fn stop_serving(&mut self) -> Result<StopServing_Reply>;
+    fn stop_serving_more(
+        &mut self,
+    ) -> Result<varlink::MethodCall<StopServing_Args, StopServing_Reply, Error>>;
+    fn stop_serving_oneway(&mut self) -> Result<()>;
+    #[doc = " Dummy progress method"]
+    #[doc = " n: number of progress steps"]
+    fn test_more(&mut self, r#n: i64) -> Result<TestMore_Reply>;
+    fn test_more_more(
+        &mut self,
+        r#n: i64,
+    ) -> Result<varlink::MethodCall<TestMore_Args, TestMore_Reply, Error>>;
+    fn test_more_oneway(&mut self, r#n: i64) -> Result<()>;
+}
+#[doc = r" A client of the interface, whose methods perform the call directly."]
+#[allow(dead_code)]
+#[derive(Clone)]
+pub struct VarlinkDirectClient {
+    client: VarlinkClient,
+}
+impl VarlinkDirectClient {
+    #[allow(dead_code)]
+    pub fn new(connection: Arc<RwLock<varlink::Connection>>) -> Self {
+        VarlinkDirectClient {
+            client: VarlinkClient::new(connection),
+        }
+    }
+}
+impl VarlinkClientDirectInterface for VarlinkDirectClient {
+    fn ping(&mut self, r#ping: String) -> Result<Ping_Reply> {
+        self.client.ping(r#ping).call()
+    }
+    fn ping_more(
+        &mut self,
+        r#ping: String,
+    ) -> Result<varlink::MethodCall<Ping_Args, Ping_Reply, Error>> {
+        let mut call = self.client.ping(r#ping);
+        call.more()?;
+        Ok(call)
+    }
+    fn ping_oneway(&mut self, r#ping: String) -> Result<()> {
+        self.client.ping(r#ping).oneway()
+    }
+    fn stop_serving(&mut self) -> Result<StopServing_Reply> {
+        self.client.stop_serving().call()
+    }
+    fn stop_serving_more(
+        &mut self,
+    ) -> Result<varlink::MethodCall<StopServing_Args, StopServing_Reply, Error>> {
+        let mut call = self.client.stop_serving();
+        call.more()?;
+        Ok(call)
+    }
+    fn stop_serving_oneway(&mut self) -> Result<()> {
+        self.client.stop_serving().oneway()
+    }
+    fn test_more(&mut self, r#n: i64) -> Result<TestMore_Reply> {
+        self.client.test_more(r#n).call()
+    }
+    fn test_more_more(
+        &mut self,
+        r#n: i64,
+    ) -> Result<varlink::MethodCall<TestMore_Args, TestMore_Reply, Error>> {
+        let mut call = self.client.test_more(r#n);
+        call.more()?;
+        Ok(call)
+    }
+    fn test_more_oneway(&mut self, r#n: i64) -> Result<()> {
+        self.client.test_more(r#n).oneway()
+    }
+}
+pub trait VarlinkClientDynInterface {
+    #[doc = " Returns the same string"]
+    fn ping(&mut self, r#ping: String) -> Result<Ping_Reply>;
+    fn ping_more(
+        &mut self,
+        r#ping: String,
+    ) -> Result<Box<dyn Iterator<Item = Result<Ping_Reply>> + Send + Sync>>;
+    fn ping_oneway(&mut self, r#ping: String) -> Result<()>;
+    #[doc = " Stop serving"]
+    fn stop_serving(&mut self) -> Result<StopServing_Reply>;
+    fn stop_serving_more(
+        &mut self,
+    ) -> Result<Box<dyn Iterator<Item = Result<StopServing_Reply>> + Send + Sync>>;
+    fn stop_serving_oneway(&mut self) -> Result<()>;
+    #[doc = " Dummy progress method"]
+    #[doc = " n: number of progress steps"]
+    fn test_more(&mut self, r#n: i64) -> Result<TestMore_Reply>;
+    fn test_more_more(
+        &mut self,
+        r#n: i64,
+    ) -> Result<Box<dyn Iterator<Item = Result<TestMore_Reply>> + Send + Sync>>;
+    fn test_more_oneway(&mut self, r#n: i64) -> Result<()>;
+}
+#[doc = r" A client of the interface, which can be used as a"]
+#[doc = r" `Box<dyn VarlinkClientDynInterface + Send + Sync>`."]
+#[allow(dead_code)]
+#[derive(Clone)]
+pub struct VarlinkDynClient {
+    client: VarlinkClient,
+}
+impl VarlinkDynClient {
+    #[allow(dead_code)]
+    pub fn new(connection: Arc<RwLock<varlink::Connection>>) -> Self {
+        VarlinkDynClient {
+            client: VarlinkClient::new(connection),
+        }
+    }
+    #[doc = r" The client as a trait object."]
+    #[allow(dead_code)]
+    pub fn boxed(
+        connection: Arc<RwLock<varlink::Connection>>,
+    ) -> Box<dyn VarlinkClientDynInterface + Send + Sync> {
+        Box::new(Self::new(connection))
+    }
+}
+impl VarlinkClientDynInterface for VarlinkDynClient {
+    fn ping(&mut self, r#ping: String) -> Result<Ping_Reply> {
+        self.client.ping(r#ping).call()
+    }
+    fn ping_more(
+        &mut self,
+        r#ping: String,
+    ) -> Result<Box<dyn Iterator<Item = Result<Ping_Reply>> + Send + Sync>> {
+        let mut call = self.client.ping(r#ping);
+        call.more()?;
+        Ok(Box::new(call))
+    }
+    fn ping_oneway(&mut self, r#ping: String) -> Result<()> {
+        self.client.ping(r#ping).oneway()
+    }
+    fn stop_serving(&mut self) -> Result<StopServing_Reply> {
+        self.client.stop_serving().call()
+    }
+    fn stop_serving_more(
+        &mut self,
+    ) -> Result<Box<dyn Iterator<Item = Result<StopServing_Reply>> + Send + Sync>> {
+        let mut call = self.client.stop_serving();
+        call.more()?;
+        Ok(Box::new(call))
+    }
+    fn stop_serving_oneway(&mut self) -> Result<()> {
+        self.client.stop_serving().oneway()
+    }
+    fn test_more(&mut self, r#n: i64) -> Result<TestMore_Reply> {
+        self.client.test_more(r#n).call()
+    }
+    fn test_more_more(
+        &mut self,
+        r#n: i64,
+    ) -> Result<Box<dyn Iterator<Item = Result<TestMore_Reply>> + Send + Sync>> {
+        let mut call = self.client.test_more(r#n);
+        call.more()?;
+        Ok(Box::new(call))
+    }
+    fn test_more_oneway(&mut self, r#n: i64) -> Result<()> {
+        self.client.test_more(r#n).oneway()
+    }
+}
 #[allow(dead_code)]
 pub struct VarlinkInterfaceProxy {
-    inner: Box<VarlinkInterface + Send + Sync>,
+    inner: Box<dyn VarlinkInterface + Send + Sync>,
 }
 #[allow(dead_code)]
-pub fn new(inner: Box<VarlinkInterface + Send + Sync>) -> VarlinkInterfaceProxy {
+pub fn new(inner: Box<dyn VarlinkInterface + Send + Sync>) -> VarlinkInterfaceProxy {
     VarlinkInterfaceProxy { inner }
 }
+impl VarlinkInterfaceProxy {
+    #[doc = r" The dispatch functions of the methods, sorted by the method name like"]
+    #[doc = r" `idl.methods`, for the binary search."]
+    const METHODS: &'static [(
+        &'static str,
+        fn(&VarlinkInterfaceProxy, &mut varlink::Call) -> varlink::Result<()>,
+    )] = &[
+        (
+            "org.example.more.Ping",
+            VarlinkInterfaceProxy::dispatch_ping,
+        ),
+        (
+            "org.example.more.StopServing",
+            VarlinkInterfaceProxy::dispatch_stop_serving,
+        ),
+        (
+            "org.example.more.TestMore",
+            VarlinkInterfaceProxy::dispatch_test_more,
+        ),
+    ];
+    fn dispatch_ping(&self, call: &mut varlink::Call) -> varlink::Result<()> {
+        let req = call.request.unwrap();
+        if call.wants_more() {
+            return call.reply_invalid_parameter("more".into());
+        }
+        let args: Ping_Args = match req.parameters_as() {
+            Some(Ok(v)) => v,
+            Some(Err(e)) => {
+                let es = format!("{}", e);
+                let _ = call.reply_invalid_parameter(es.clone());
+                return Err(into_cherr!(varlink::ErrorKind::SerdeJsonDe(es)));
+            }
+            None => return call.reply_invalid_parameter("parameters".into()),
+        };
+        self.inner.ping(call as &mut dyn Call_Ping, args.r#ping)
+    }
+    fn dispatch_stop_serving(&self, call: &mut varlink::Call) -> varlink::Result<()> {
+        if call.wants_more() {
+            return call.reply_invalid_parameter("more".into());
+        }
+        if call.is_oneway() {
+            return call.reply_invalid_parameter("oneway".into());
+        }
+        self.inner.stop_serving(call as &mut dyn Call_StopServing)
+    }
+    fn dispatch_test_more(&self, call: &mut varlink::Call) -> varlink::Result<()> {
+        let req = call.request.unwrap();
+        if call.is_oneway() {
+            return call.reply_invalid_parameter("oneway".into());
+        }
+        let args: TestMore_Args = match req.parameters_as() {
+            Some(Ok(v)) => v,
+            Some(Err(e)) => {
+                let es = format!("{}", e);
+                let _ = call.reply_invalid_parameter(es.clone());
+                return Err(into_cherr!(varlink::ErrorKind::SerdeJsonDe(es)));
+            }
+            None => return call.reply_invalid_parameter("parameters".into()),
+        };
+        self.inner
+            .test_more(call as &mut dyn Call_TestMore, args.r#n)
+    }
+}
 impl varlink::Interface for VarlinkInterfaceProxy {
     fn get_description(&self) -> &'static str {
         "# Example Varlink service\ninterface org.example.more\n\n# Enum, returning either start, progress or end\n# progress: [0-100]\ntype State (\n  start: ?bool,\n  progress: ?int,\n  end: ?bool\n)\n\n# Returns the same string\nmethod Ping(ping: string) -> (pong: string)\n\n# Dummy progress method\n# n: number of progress steps\nmethod TestMore(n: int) -> (state: State)\n\n# Stop serving\nmethod StopServing() -> ()\n\n# Something failed in TestMore\nerror TestMoreError (reason: string)\n"
@@ -225,47 +512,15 @@ impl varlink::Interface for VarlinkInterfaceProxy {
     fn call_upgraded(
         &self,
         call: &mut varlink::Call,
-        bufreader: &mut BufRead,
+        bufreader: &mut dyn BufRead,
     ) -> varlink::Result<Vec<u8>> {
         self.inner.call_upgraded(call, bufreader)
     }
     fn call(&self, call: &mut varlink::Call) -> varlink::Result<()> {
         let req = call.request.unwrap();
-        match req.method.as_ref() {
-            "org.example.more.Ping" => {
-                if let Some(args) = req.parameters.clone() {
-                    let args: Ping_Args = match serde_json::from_value(args) {
-                        Ok(v) => v,
-                        Err(e) => {
-                            let es = format!("{}", e);
-                            let _ = call.reply_invalid_parameter(es.clone());
-                            return Err(into_cherr!(varlink::ErrorKind::SerdeJsonDe(es)));
-                        }
-                    };
-                    self.inner.ping(call as &mut Call_Ping, args.r#ping)
-                } else {
-                    call.reply_invalid_parameter("parameters".into())
-                }
-            }
-            "org.example.more.StopServing" => {
-                self.inner.stop_serving(call as &mut Call_StopServing)
-            }
-            "org.example.more.TestMore" => {
-                if let Some(args) = req.parameters.clone() {
-                    let args: TestMore_Args = match serde_json::from_value(args) {
-                        Ok(v) => v,
-                        Err(e) => {
-                            let es = format!("{}", e);
-                            let _ = call.reply_invalid_parameter(es.clone());
-                            return Err(into_cherr!(varlink::ErrorKind::SerdeJsonDe(es)));
-                        }
-                    };
-                    self.inner.test_more(call as &mut Call_TestMore, args.r#n)
-                } else {
-                    call.reply_invalid_parameter("parameters".into())
-                }
-            }
-            m => call.reply_method_not_found(String::from(m)),
+        match Self::METHODS.binary_search_by(|&(name, _)| name.cmp(&*req.method)) {
+            Ok(i) => (Self::METHODS[i].1)(self, call),
+            Err(_) => call.reply_method_not_found(req.method.to_string()),
         }
     }
 }
@@ -1,7 +1,11 @@
+use crate::org_example_more::{
+    Ping_Args, Ping_Reply, TestMore_MoreReply, VarlinkClient, VarlinkClientDirectInterface,
+    VarlinkClientDynInterface, VarlinkDirectClient, VarlinkDynClient,
+};
 use crate::Result;
 use chainerror::*;
 use std::{thread, time};
-use varlink::Connection;
+use varlink::{Connection, MethodCall};
 
 fn run_self_test(address: String) -> Result<()> {
     let client_address = address.clone();
@@ -38,3 +42,224 @@ fn test_unix() -> Result<()> {
 fn test_tcp() -> Result<()> {
     run_self_test("tcp:127.0.0.1:12345".into())
 }
+
+#[test]
+fn test_more_not_supported() -> Result<()> {
+    let address = "unix:org.example.more_not_supported";
+
+    let child = thread::spawn(move || {
+        if let Err(e) = crate::run_server(address, 4, 100) {
+            match e.kind() {
+                ::varlink::ErrorKind::Timeout => {}
+                _ => panic!("error: {}", e),
+            }
+        }
+    });
+
+    // give server time to start
+    thread::sleep(time::Duration::from_secs(1));
+
+    {
+        let connection = Connection::with_address(address)?;
+        let mut call = MethodCall::<Ping_Args, Ping_Reply, varlink::ErrorKind>::new(
+            connection,
+            "org.example.more.Ping",
+            Ping_Args {
+                ping: "Test".into(),
+            },
+        );
+
+        match call.more()?.next() {
+            Some(Err(e)) => assert_eq!(
+                e.kind(),
+                &varlink::ErrorKind::InvalidParameter("more".into())
+            ),
+            r => panic!("unexpected reply: {:?}", r),
+        }
+    }
+
+    if let Err(_) = child.join() {
+        Err(strerr!("Error joining thread").into())
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn test_oneway_not_supported() -> Result<()> {
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::UnixStream;
+
+    let address = "unix:org.example.more_oneway_not_supported";
+
+    let child = thread::spawn(move || {
+        if let Err(e) = crate::run_server(address, 4, 100) {
+            match e.kind() {
+                ::varlink::ErrorKind::Timeout => {}
+                _ => panic!("error: {}", e),
+            }
+        }
+    });
+
+    // give server time to start
+    thread::sleep(time::Duration::from_secs(1));
+
+    {
+        let mut stream = UnixStream::connect(&address[5..])?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut call = |request: &[u8]| -> Result<serde_json::Value> {
+            stream.write_all(request)?;
+            let mut buf = Vec::new();
+            reader.read_until(0, &mut buf)?;
+            buf.pop();
+            Ok(serde_json::from_slice(&buf)?)
+        };
+
+        // the call is refused with an error reply, not by dropping the connection
+        let reply = call(
+            b"{\"method\":\"org.example.more.TestMore\",\"oneway\":true,\"parameters\":{\"n\":1}}\0",
+        )?;
+        assert_eq!(
+            reply,
+            serde_json::json!({
+                "error": "org.varlink.service.InvalidParameter",
+                "parameters": {"parameter": "oneway"}
+            })
+        );
+
+        let reply =
+            call(b"{\"method\":\"org.example.more.Ping\",\"parameters\":{\"ping\":\"Test\"}}\0")?;
+        assert_eq!(reply["parameters"]["pong"], "Test");
+    }
+
+    if child.join().is_err() {
+        Err(strerr!("Error joining thread").into())
+    } else {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_direct_client() -> Result<()> {
+    let address = "unix:org.example.more_direct";
+
+    let child = thread::spawn(move || {
+        if let Err(e) = crate::run_server(address, 4, 100) {
+            match e.kind() {
+                ::varlink::ErrorKind::Timeout => {}
+                _ => panic!("error: {}", e),
+            }
+        }
+    });
+
+    // give server time to start
+    thread::sleep(time::Duration::from_secs(1));
+
+    {
+        let mut iface = VarlinkDirectClient::new(Connection::with_address(address)?);
+
+        let reply = iface.ping("Test".into())?;
+        assert_eq!(reply.pong, "Test");
+
+        let states = iface
+            .test_more_more(2)?
+            .map(|r| r.map(|r| r.state))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        assert_eq!(states.len(), 5);
+        assert_eq!(states[0].start, Some(true));
+        assert_eq!(states[4].end, Some(true));
+
+        iface.ping_oneway("Oneway".into())?;
+    }
+
+    if child.join().is_err() {
+        Err(strerr!("Error joining thread").into())
+    } else {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_dyn_client() -> Result<()> {
+    let address = "unix:org.example.more_dyn";
+
+    let child = thread::spawn(move || {
+        if let Err(e) = crate::run_server(address, 4, 100) {
+            match e.kind() {
+                ::varlink::ErrorKind::Timeout => {}
+                _ => panic!("error: {}", e),
+            }
+        }
+    });
+
+    // give server time to start
+    thread::sleep(time::Duration::from_secs(1));
+
+    {
+        let mut iface: Box<dyn VarlinkClientDynInterface + Send + Sync> =
+            VarlinkDynClient::boxed(Connection::with_address(address)?);
+
+        let reply = iface.ping("Test".into())?;
+        assert_eq!(reply.pong, "Test");
+
+        let states = iface
+            .test_more_more(2)?
+            .map(|r| r.map(|r| r.state))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        assert_eq!(states.len(), 5);
+        assert_eq!(states[0].start, Some(true));
+        assert_eq!(states[4].end, Some(true));
+
+        iface.ping_oneway("Oneway".into())?;
+    }
+
+    if child.join().is_err() {
+        Err(strerr!("Error joining thread").into())
+    } else {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_named_more_reply() -> Result<()> {
+    let address = "unix:org.example.more_named";
+
+    // the iterator over the replies can be kept in a struct
+    struct Progress {
+        replies: TestMore_MoreReply,
+    }
+
+    let child = thread::spawn(move || {
+        if let Err(e) = crate::run_server(address, 4, 100) {
+            match e.kind() {
+                ::varlink::ErrorKind::Timeout => {}
+                _ => panic!("error: {}", e),
+            }
+        }
+    });
+
+    // give server time to start
+    thread::sleep(time::Duration::from_secs(1));
+
+    {
+        let mut iface = VarlinkClient::new(Connection::with_address(address)?);
+        let progress = Progress {
+            replies: iface.test_more_more(2)?,
+        };
+
+        let states = progress
+            .replies
+            .map(|r| r.map(|r| r.state))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        assert_eq!(states.len(), 5);
+        assert_eq!(states[0].start, Some(true));
+        assert_eq!(states[4].end, Some(true));
+    }
+
+    if child.join().is_err() {
+        Err(strerr!("Error joining thread").into())
+    } else {
+        Ok(())
+    }
+}
@@ -9,6 +9,8 @@ use crate::org_example_ping::*;
 use chainerror::*;
 
 // Dynamically build the varlink rust code.
+// The generated code has to compile without warnings.
+#[deny(warnings)]
 mod org_example_ping;
 
 #[cfg(test)]
@@ -87,7 +89,7 @@ fn run_client(connection: &Arc<RwLock<varlink::Connection>>) -> Result<()> {
         assert_eq!(ping, reply.pong);
         eprintln!("Pong: '{}'", reply.pong);
 
-        let reply = iface.ping(ping.clone()).call()?;
+        let reply = iface.ping_sync(ping.clone())?;
         assert_eq!(ping, reply.pong);
         eprintln!("Pong: '{}'", reply.pong);
 
@@ -1,9 +1,9 @@
 #![doc = "This file was automatically generated by the varlink rust generator"]
 #![allow(non_camel_case_types)]
 #![allow(non_snake_case)]
+#![allow(dead_code)]
 use chainerror::*;
 use serde_derive::{Deserialize, Serialize};
-use serde_json;
 use std::io::BufRead;
 use std::sync::{Arc, RwLock};
 use varlink::{self, CallTrait};
@@ -18,6 +18,12 @@ pub trait VarlinkCallError: varlink::CallTrait {
             Some(serde_json::to_value(PingError_Args { r#parameter }).map_err(minto_cherr!())?),
         ))
     }
+    fn reply_ping_error_struct(&mut self, args: PingError_Args) -> varlink::Result<()> {
+        self.reply_struct(varlink::Reply::error(
+            "org.example.ping.PingError",
+            Some(serde_json::to_value(args).map_err(minto_cherr!())?),
+        ))
+    }
 }
 impl<'a> VarlinkCallError for varlink::Call<'a> {}
 #[allow(dead_code)]
@@ -34,7 +40,9 @@ impl ::std::fmt::Display for ErrorKind {
     fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
         match self {
             ErrorKind::Io_Error(_) => write!(f, "IO error"),
-            ErrorKind::SerdeJson_Error(_) => write!(f, "(De)Serialization Error"),
+            ErrorKind::SerdeJson_Error(_) => {
+                write!(f, "(De)Serialization Error")
+            }
             ErrorKind::Varlink_Error => write!(f, "Varlink Error"),
             ErrorKind::VarlinkReply_Error(v) => write!(f, "Unknown error reply: '{:#?}'", v),
             ErrorKind::Generic => Ok(()),
@@ -115,6 +123,12 @@ pub struct Ping_Reply {
     pub r#pong: String,
 }
 impl varlink::VarlinkReply for Ping_Reply {}
+impl varlink::VarlinkReplyValues for Ping_Reply {
+    type Values = String;
+    fn into_values(self) -> Self::Values {
+        self.r#pong
+    }
+}
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct Ping_Args {
     pub r#ping: String,
@@ -128,6 +142,10 @@ impl<'a> Call_Ping for varlink::Call<'a> {}
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct Upgrade_Reply {}
 impl varlink::VarlinkReply for Upgrade_Reply {}
+impl varlink::VarlinkReplyValues for Upgrade_Reply {
+    type Values = ();
+    fn into_values(self) -> Self::Values {}
+}
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct Upgrade_Args {}
 pub trait Call_Upgrade: VarlinkCallError {
@@ -137,21 +155,38 @@ pub trait Call_Upgrade: VarlinkCallError {
 }
 impl<'a> Call_Upgrade for varlink::Call<'a> {}
 pub trait VarlinkInterface {
-    fn ping(&self, call: &mut Call_Ping, r#ping: String) -> varlink::Result<()>;
-    fn upgrade(&self, call: &mut Call_Upgrade) -> varlink::Result<()>;
+    #[doc = " Returns the same string"]
+    fn ping(&self, call: &mut dyn Call_Ping, r#ping: String) -> varlink::Result<()>;
+    fn upgrade(&self, call: &mut dyn Call_Upgrade) -> varlink::Result<()>;
     fn call_upgraded(
         &self,
         _call: &mut varlink::Call,
-        _bufreader: &mut BufRead,
+        _bufreader: &mut dyn BufRead,
     ) -> varlink::Result<Vec<u8>> {
         Ok(Vec::new())
     }
 }
 pub trait VarlinkClientInterface {
+    #[doc = " Returns the same string"]
     fn ping(&mut self, r#ping: String) -> varlink::MethodCall<Ping_Args, Ping_Reply, Error>;
+    #[doc = " Call `Ping` and return its reply."]
+    fn ping_sync(&mut self, r#ping: String) -> Result<Ping_Reply> {
+        self.ping(r#ping).call()
+    }
     fn upgrade(&mut self) -> varlink::MethodCall<Upgrade_Args, Upgrade_Reply, Error>;
+    #[doc = " Call `Upgrade` and return its reply."]
+    fn upgrade_sync(&mut self) -> Result<Upgrade_Reply> {
+        self.upgrade().call()
+    }
 }
+#[doc = r" A client of the interface."]
+#[doc = r""]
+#[doc = r" Clones share the connection, so they are cheap to hand to other threads."]
+#[doc = r" Calls of all clones are serialized through the lock of the shared connection,"]
+#[doc = r" while a `more()` call is still receiving replies, other calls fail with"]
+#[doc = r" `ConnectionBusy`."]
 #[allow(dead_code)]
+#[derive(Clone)]
 pub struct VarlinkClient {
     connection: Arc<RwLock<varlink::Connection>>,
 }
@@ -179,15 +214,48 @@ impl VarlinkClientInterface for VarlinkClient {
 }
 #[allow(dead_code)]
 pub struct VarlinkInterfaceProxy {
-    inner: Box<VarlinkInterface + Send + Sync>,
+    inner: Box<dyn VarlinkInterface + Send + Sync>,
 }
 #[allow(dead_code)]
-pub fn new(inner: Box<VarlinkInterface + Send + Sync>) -> VarlinkInterfaceProxy {
+pub fn new(inner: Box<dyn VarlinkInterface + Send + Sync>) -> VarlinkInterfaceProxy {
     VarlinkInterfaceProxy { inner }
 }
+impl VarlinkInterfaceProxy {
+    #[doc = r" The dispatch functions of the methods, sorted by the method name like"]
+    #[doc = r" `idl.methods`, for the binary search."]
+    const METHODS: &'static [(
+        &'static str,
+        fn(&VarlinkInterfaceProxy, &mut varlink::Call) -> varlink::Result<()>,
+    )] = &[
+        (
+            "org.example.ping.Ping",
+            VarlinkInterfaceProxy::dispatch_ping,
+        ),
+        (
+            "org.example.ping.Upgrade",
+            VarlinkInterfaceProxy::dispatch_upgrade,
+        ),
+    ];
+    fn dispatch_ping(&self, call: &mut varlink::Call) -> varlink::Result<()> {
+        let req = call.request.unwrap();
+        let args: Ping_Args = match req.parameters_as() {
+            Some(Ok(v)) => v,
+            Some(Err(e)) => {
+                let es = format!("{}", e);
+                let _ = call.reply_invalid_parameter(es.clone());
+                return Err(into_cherr!(varlink::ErrorKind::SerdeJsonDe(es)));
+            }
+            None => return call.reply_invalid_parameter("parameters".into()),
+        };
+        self.inner.ping(call as &mut dyn Call_Ping, args.r#ping)
+    }
+    fn dispatch_upgrade(&self, call: &mut varlink::Call) -> varlink::Result<()> {
+        self.inner.upgrade(call as &mut dyn Call_Upgrade)
+    }
+}
 impl varlink::Interface for VarlinkInterfaceProxy {
     fn get_description(&self) -> &'static str {
-        "# Example service\ninterface org.example.ping\n\n# Returns the same string\nmethod Ping(ping: string) -> (pong: string)\n\nmethod Upgrade() -> ()\n\nerror PingError(parameter: int)"
+        "# Example service\ninterface org.example.ping\n\n# Returns the same string\nmethod Ping(ping: string) -> (pong: string)\n\nmethod Upgrade() -> ()\n\nerror PingError(parameter: int)\n"
     }
     fn get_name(&self) -> &'static str {
         "org.example.ping"
@@ -195,30 +263,15 @@ impl varlink::Interface for VarlinkInterfaceProxy {
     fn call_upgraded(
         &self,
         call: &mut varlink::Call,
-        bufreader: &mut BufRead,
+        bufreader: &mut dyn BufRead,
     ) -> varlink::Result<Vec<u8>> {
         self.inner.call_upgraded(call, bufreader)
     }
     fn call(&self, call: &mut varlink::Call) -> varlink::Result<()> {
         let req = call.request.unwrap();
-        match req.method.as_ref() {
-            "org.example.ping.Ping" => {
-                if let Some(args) = req.parameters.clone() {
-                    let args: Ping_Args = match serde_json::from_value(args) {
-                        Ok(v) => v,
-                        Err(e) => {
-                            let es = format!("{}", e);
-                            let _ = call.reply_invalid_parameter(es.clone());
-                            return Err(into_cherr!(varlink::ErrorKind::SerdeJsonDe(es)));
-                        }
-                    };
-                    self.inner.ping(call as &mut Call_Ping, args.r#ping)
-                } else {
-                    call.reply_invalid_parameter("parameters".into())
-                }
-            }
-            "org.example.ping.Upgrade" => self.inner.upgrade(call as &mut Call_Upgrade),
-            m => call.reply_method_not_found(String::from(m)),
+        match Self::METHODS.binary_search_by(|&(name, _)| name.cmp(&*req.method)) {
+            Ok(i) => (Self::METHODS[i].1)(self, call),
+            Err(_) => call.reply_method_not_found(req.method.to_string()),
         }
     }
 }
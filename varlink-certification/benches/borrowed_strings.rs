@@ -0,0 +1,72 @@
+//! Compares deserializing a reply with many strings to the owned types with deserializing it
+//! to the types borrowing the strings from the input (`GeneratorOptions::borrowed_types`).
+//!
+//! Run with `cargo bench --bench borrowed_strings`.
+
+use std::borrow::Cow;
+use std::time::{Duration, Instant};
+
+#[allow(non_camel_case_types)]
+#[allow(non_snake_case)]
+#[allow(dead_code)]
+mod strings {
+    include!(concat!(env!("OUT_DIR"), "/strings.rs"));
+}
+
+/// The reply parameters with `entries` entries.
+fn reply(entries: usize) -> Vec<u8> {
+    let entries: Vec<serde_json::Value> = (0..entries)
+        .map(|i| {
+            serde_json::json!({
+                "name": format!("file-{}.txt", i),
+                "path": format!("/srv/data/projects/project-{}/files/file-{}.txt", i % 100, i),
+                "owner": format!("user{}", i % 1000),
+                "size": i * 1024
+            })
+        })
+        .collect();
+
+    serde_json::to_vec(&serde_json::json!({ "entries": entries })).unwrap()
+}
+
+fn run<F>(name: &str, iterations: u32, f: F) -> Duration
+where
+    F: Fn(),
+{
+    let start = Instant::now();
+
+    for _ in 0..iterations {
+        f();
+    }
+
+    let elapsed = start.elapsed();
+    println!(
+        "{:>8}: {:>10.3} ms/reply",
+        name,
+        elapsed.as_secs_f64() * 1000.0 / f64::from(iterations)
+    );
+    elapsed
+}
+
+fn main() {
+    for &(entries, iterations) in &[(100, 1000), (100_000, 10)] {
+        let reply = reply(entries);
+        println!("reply with {} entries, {} bytes", entries, reply.len());
+
+        let borrowed: strings::borrowed::List_Reply = serde_json::from_slice(&reply).unwrap();
+        assert_eq!(borrowed.entries.len(), entries);
+        match borrowed.entries[0].path {
+            Cow::Borrowed(_) => {}
+            Cow::Owned(_) => panic!("string not borrowed"),
+        }
+
+        run("owned", iterations, || {
+            let r: strings::List_Reply = serde_json::from_slice(&reply).unwrap();
+            assert_eq!(r.entries.len(), entries);
+        });
+        run("borrowed", iterations, || {
+            let r: strings::borrowed::List_Reply = serde_json::from_slice(&reply).unwrap();
+            assert_eq!(r.entries.len(), entries);
+        });
+    }
+}
@@ -0,0 +1,108 @@
+//! Compares forwarding a large `object` field as `serde_json::Value` with forwarding it as
+//! `Box<serde_json::value::RawValue>` (`GeneratorOptions::raw_object`).
+//!
+//! Run with `cargo bench --bench raw_object`.
+
+use std::io::BufReader;
+use std::time::{Duration, Instant};
+
+use varlink::{ConnectionHandler, VarlinkService};
+
+#[allow(non_camel_case_types)]
+#[allow(non_snake_case)]
+#[allow(dead_code)]
+mod forward_value {
+    include!(concat!(env!("OUT_DIR"), "/forward_value.rs"));
+}
+
+#[allow(non_camel_case_types)]
+#[allow(non_snake_case)]
+#[allow(dead_code)]
+mod forward_raw {
+    include!(concat!(env!("OUT_DIR"), "/forward_raw.rs"));
+}
+
+struct ValueForwarder;
+
+impl forward_value::VarlinkInterface for ValueForwarder {
+    fn forward(
+        &self,
+        call: &mut dyn forward_value::Call_Forward,
+        data: serde_json::Value,
+    ) -> varlink::Result<()> {
+        call.reply(data)
+    }
+}
+
+struct RawForwarder;
+
+impl forward_raw::VarlinkInterface for RawForwarder {
+    fn forward(
+        &self,
+        call: &mut dyn forward_raw::Call_Forward,
+        data: Box<serde_json::value::RawValue>,
+    ) -> varlink::Result<()> {
+        call.reply(data)
+    }
+}
+
+fn service(interface: Box<dyn varlink::Interface + Send + Sync>) -> VarlinkService {
+    VarlinkService::new(
+        "org.varlink",
+        "forward benchmark",
+        "0.1",
+        "http://varlink.org",
+        vec![interface],
+    )
+}
+
+/// A request with an object of `entries` nested objects.
+fn request(entries: usize) -> Vec<u8> {
+    let data: serde_json::Map<String, serde_json::Value> = (0..entries)
+        .map(|i| {
+            (
+                format!("key{}", i),
+                serde_json::json!({ "name": format!("entry {}", i), "values": [i, i + 1, i + 2] }),
+            )
+        })
+        .collect();
+
+    let mut request = serde_json::to_vec(&serde_json::json!({
+        "method": "org.example.forward.Forward",
+        "parameters": { "data": data }
+    }))
+    .unwrap();
+    request.push(0);
+    request
+}
+
+fn run(name: &str, service: &VarlinkService, request: &[u8], iterations: u32) -> Duration {
+    let start = Instant::now();
+
+    for _ in 0..iterations {
+        let mut reader = BufReader::new(request);
+        let mut out: Vec<u8> = Vec::new();
+        service.handle(&mut reader, &mut out, None).unwrap();
+        assert!(out.len() > request.len() / 2);
+    }
+
+    let elapsed = start.elapsed();
+    println!(
+        "{:>6}: {:>10.3} ms/call",
+        name,
+        elapsed.as_secs_f64() * 1000.0 / f64::from(iterations)
+    );
+    elapsed
+}
+
+fn main() {
+    let value_service = service(Box::new(forward_value::new(Box::new(ValueForwarder))));
+    let raw_service = service(Box::new(forward_raw::new(Box::new(RawForwarder))));
+
+    for &(entries, iterations) in &[(100, 1000), (10_000, 50)] {
+        let request = request(entries);
+        println!("object with {} entries, {} bytes", entries, request.len());
+        run("value", &value_service, &request, iterations);
+        run("raw", &raw_service, &request, iterations);
+    }
+}
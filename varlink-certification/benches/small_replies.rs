@@ -0,0 +1,117 @@
+//! Counts the allocations for receiving many small replies of a `more` call, which are read
+//! into a buffer reused for all replies, and compares the framing with reading every message
+//! into a fresh buffer.
+//!
+//! Run with `cargo bench --bench small_replies`.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Instant;
+
+use serde_json::{json, Value};
+use varlink::{Connection, MessageReader, MethodCall};
+
+/// Counts the allocations of the process.
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+/// `count` small replies of a `more` call, the last one without `continues`.
+fn replies(count: usize) -> Vec<u8> {
+    let mut replies = Vec::new();
+    for i in 0..count {
+        let mut reply = json!({ "parameters": { "n": i, "state": "running" } });
+        if i + 1 < count {
+            reply["continues"] = json!(true);
+        }
+        serde_json::to_writer(&mut replies, &reply).unwrap();
+        replies.push(0);
+    }
+    replies
+}
+
+/// Run `f` and print the allocations and the time per reply.
+fn run<F: FnOnce()>(name: &str, count: usize, f: F) {
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let start = Instant::now();
+
+    f();
+
+    let elapsed = start.elapsed();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations;
+    println!(
+        "{:>8}: {:>6.2} allocations/reply, {:>8.3} µs/reply",
+        name,
+        allocations as f64 / count as f64,
+        elapsed.as_secs_f64() * 1_000_000.0 / count as f64
+    );
+}
+
+fn main() {
+    const COUNT: usize = 100_000;
+    let replies = replies(COUNT);
+
+    // the framing alone: a fresh buffer for every message versus the reused one
+    run("fresh", COUNT, || {
+        let mut reader = BufReader::new(&replies[..]);
+        loop {
+            let mut buf = Vec::new();
+            reader.read_until(0, &mut buf).unwrap();
+            if buf.pop().is_none() {
+                break;
+            }
+            let _: Value = serde_json::from_slice(&buf).unwrap();
+        }
+    });
+    run("reused", COUNT, || {
+        let mut reader = MessageReader::new(&replies[..]);
+        while reader.read_message().unwrap().is_some() {}
+    });
+
+    // the replies of a call on a connection
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = format!("tcp:{}", listener.local_addr().unwrap());
+    let service = thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut request = [0u8; 1];
+        while stream.read(&mut request).unwrap() == 1 && request[0] != 0 {}
+        stream.write_all(&replies).unwrap();
+    });
+
+    let connection = Connection::with_address(&address).unwrap();
+    let mut call = MethodCall::<Value, Value, varlink::ErrorKind>::new(
+        connection,
+        "org.example.more.TestMore",
+        json!({}),
+    );
+    call.more().unwrap();
+    run("recv", COUNT, || {
+        for _ in 0..COUNT {
+            call.recv().unwrap();
+        }
+    });
+
+    service.join().unwrap();
+}
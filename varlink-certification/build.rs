@@ -1,5 +1,180 @@
 extern crate varlink_generator;
 
+use std::env;
+use std::fs::File;
+use std::path::Path;
+
 fn main() {
-    varlink_generator::cargo_build_tosource("src/org.varlink.certification.varlink", true);
+    varlink_generator::cargo_build_tosource_options(
+        "src/org.varlink.certification.varlink",
+        true,
+        &varlink_generator::GeneratorOptions {
+            http_status: Some(vec![("ClientIdError", 401), ("CertificationError", 422)]),
+            newtype_fields: Some(vec![("client_id", "ClientId")]),
+            emit_tests: true,
+            integer_enums: true,
+            enum_discriminants: Some(vec![("MyType_enum.one", 1)]),
+            reply_bridge: true,
+            emit_description_test: true,
+            reply_try_from: true,
+            ..Default::default()
+        },
+    );
+
+    // The `raw_object` benchmark compares the code generated with and without the option.
+    let input_path = "benches/org.example.forward.varlink";
+    let out_dir = env::var("OUT_DIR").unwrap();
+
+    for (name, raw_object) in &[("forward_value.rs", false), ("forward_raw.rs", true)] {
+        let mut reader = File::open(input_path).unwrap();
+        let mut writer = File::create(Path::new(&out_dir).join(name)).unwrap();
+        varlink_generator::generate_with_options(
+            &mut reader,
+            &mut writer,
+            &varlink_generator::GeneratorOptions {
+                raw_object: *raw_object,
+                ..Default::default()
+            },
+            false,
+        )
+        .unwrap();
+    }
+    println!("cargo:rerun-if-changed={}", input_path);
+
+    // The `borrowed_strings` benchmark compares the owned and the borrowed types.
+    let input_path = "benches/org.example.strings.varlink";
+    let mut reader = File::open(input_path).unwrap();
+    let mut writer = File::create(Path::new(&out_dir).join("strings.rs")).unwrap();
+    varlink_generator::generate_with_options(
+        &mut reader,
+        &mut writer,
+        &varlink_generator::GeneratorOptions {
+            borrowed_types: true,
+            ..Default::default()
+        },
+        false,
+    )
+    .unwrap();
+    println!("cargo:rerun-if-changed={}", input_path);
+
+    // The `conversions` test converts between the types of two versions of an interface.
+    varlink_generator::cargo_build_options_many(
+        &[
+            "tests/org.example.migrate.v1.varlink",
+            "tests/org.example.migrate.v2.varlink",
+        ],
+        &varlink_generator::GeneratorOptions {
+            structural_conversions: true,
+            ..Default::default()
+        },
+    );
+
+    // The `ordered_maps` test iterates the dictionaries of the generated types.
+    varlink_generator::cargo_build_options(
+        "tests/org.example.ordered.varlink",
+        &varlink_generator::GeneratorOptions {
+            ordered_maps: true,
+            ..Default::default()
+        },
+    );
+
+    // The `unknown_fields` test round-trips parameters unknown to the interface.
+    varlink_generator::cargo_build_options(
+        "tests/org.example.extra.varlink",
+        &varlink_generator::GeneratorOptions {
+            preserve_unknown_fields: true,
+            ..Default::default()
+        },
+    );
+
+    // The `default_fields` test deserializes messages with missing fields.
+    varlink_generator::cargo_build_options(
+        "tests/org.example.skew.varlink",
+        &varlink_generator::GeneratorOptions {
+            default_fields: true,
+            ..Default::default()
+        },
+    );
+
+    // The `default_values` test uses the `Default` of a type with declared default values.
+    varlink_generator::cargo_build("tests/org.example.defaults.varlink");
+
+    // The `builders` test builds the types of the same interface.
+    let mut reader = File::open("tests/org.example.skew.varlink").unwrap();
+    let mut writer = File::create(Path::new(&out_dir).join("builders_skew.rs")).unwrap();
+    varlink_generator::generate_with_options(
+        &mut reader,
+        &mut writer,
+        &varlink_generator::GeneratorOptions {
+            builders: true,
+            ..Default::default()
+        },
+        false,
+    )
+    .unwrap();
+
+    // The `deprecated` test denies warnings about deprecated items in the generated code.
+    varlink_generator::cargo_build_options(
+        "tests/org.example.deprecated.varlink",
+        &varlink_generator::GeneratorOptions {
+            builders: true,
+            direct_client: true,
+            dyn_client: true,
+            emit_tests: true,
+            borrowed_types: true,
+            ..Default::default()
+        },
+    );
+
+    // The `description_check` test renames a method in the generated code, so the check of
+    // the embedded description fails, also in the generated test expected to panic.
+    let mut reader = File::open("tests/org.example.skew.varlink").unwrap();
+    let mut out = Vec::new();
+    varlink_generator::generate_with_options(
+        &mut reader,
+        &mut out,
+        &varlink_generator::GeneratorOptions {
+            emit_description_test: true,
+            ..Default::default()
+        },
+        false,
+    )
+    .unwrap();
+    let code = String::from_utf8(out)
+        .unwrap()
+        .replace(
+            "\"org.example.skew.GetItem\"",
+            "\"org.example.skew.FetchItem\"",
+        )
+        .replace(
+            "# [ test ] fn description_matches_generated_code",
+            "# [ test ] # [ should_panic ] fn description_matches_generated_code",
+        );
+    std::fs::write(Path::new(&out_dir).join("description_renamed.rs"), code).unwrap();
+
+    // The `multi_interface` test uses a varlink file with two interfaces.
+    varlink_generator::cargo_build("tests/org.example.multi.varlink");
+
+    // The `ident_prefix` test includes the code of two interfaces into the same module.
+    for (input_path, name, prefix) in &[
+        (
+            "tests/org.example.ordered.varlink",
+            "prefix_ordered.rs",
+            "Ordered",
+        ),
+        ("tests/org.example.skew.varlink", "prefix_skew.rs", "Skew"),
+    ] {
+        let mut reader = File::open(input_path).unwrap();
+        let mut writer = File::create(Path::new(&out_dir).join(name)).unwrap();
+        varlink_generator::generate_with_options(
+            &mut reader,
+            &mut writer,
+            &varlink_generator::GeneratorOptions {
+                ident_prefix: Some(prefix.to_string()),
+                ..Default::default()
+            },
+            false,
+        )
+        .unwrap();
+    }
 }
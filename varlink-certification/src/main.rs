@@ -1,7 +1,7 @@
 use std::collections::{hash_map::DefaultHasher, VecDeque};
 use std::env;
 use std::hash::{Hash, Hasher};
-use std::io;
+use std::io::{self, Write};
 use std::process::exit;
 use std::sync::{Arc, RwLock};
 use std::time::Instant;
@@ -10,16 +10,21 @@ use chainerror::*;
 
 pub type Result<T> = std::result::Result<T, Box<std::error::Error>>;
 
-use varlink::{Connection, StringHashMap, StringHashSet, VarlinkService};
+use varlink::{BeginMore, Connection, StringHashMap, StringHashSet, VarlinkService};
 
 use crate::org_varlink_certification::*;
 
+// The generated code has to compile without warnings.
+#[deny(warnings)]
 mod org_varlink_certification;
 #[cfg(test)]
 mod test; // Main
 
 fn print_usage(program: &str, opts: &getopts::Options) {
-    let brief = format!("Usage: {} [--varlink=<address>] [--client]", program);
+    let brief = format!(
+        "Usage: {} [--varlink=<address>...] [--addresses=<file>] [--client]",
+        program
+    );
     print!("{}", opts.usage(&brief));
 }
 
@@ -28,7 +33,18 @@ fn main() -> Result<()> {
     let program = args[0].clone();
 
     let mut opts = getopts::Options::new();
-    opts.optopt("", "varlink", "varlink address URL", "<ADDRESS>");
+    opts.optmulti(
+        "",
+        "varlink",
+        "varlink address URL, can be given multiple times in client mode",
+        "<ADDRESS>",
+    );
+    opts.optopt(
+        "",
+        "addresses",
+        "file with varlink address URLs to test in client mode, one per line",
+        "<FILE>",
+    );
     opts.optopt(
         "b",
         "bridge",
@@ -36,8 +52,14 @@ fn main() -> Result<()> {
         "<COMMAND>",
     );
     opts.optflag("", "client", "run in client mode");
+    opts.optflag("", "list", "list the certification tests");
     opts.optflag("h", "help", "print this help menu");
-    opts.optopt("", "timeout", "server timeout", "<seconds>");
+    opts.optopt(
+        "",
+        "timeout",
+        "server timeout, exits with status 2, if no client completed the certification",
+        "<seconds>",
+    );
 
     let matches = match opts.parse(&args[1..]) {
         Ok(m) => m,
@@ -53,6 +75,11 @@ fn main() -> Result<()> {
         return Ok(());
     }
 
+    if matches.opt_present("list") {
+        list_tests(&mut io::stdout())?;
+        return Ok(());
+    }
+
     let client_mode = matches.opt_present("client");
 
     let timeout = matches
@@ -61,7 +88,20 @@ fn main() -> Result<()> {
         .parse::<u64>()
         .unwrap_or(0);
 
-    if client_mode {
+    let mut addresses = matches.opt_strs("varlink");
+    if let Some(file) = matches.opt_str("addresses") {
+        let content =
+            std::fs::read_to_string(&file).map_err(mstrerr!("Failed to read '{}'", file))?;
+        addresses.extend(parse_addresses(&content));
+    }
+
+    if client_mode && (addresses.len() > 1 || matches.opt_present("addresses")) {
+        let results = run_client_matrix(&addresses);
+        print_matrix(&mut io::stdout(), &results)?;
+        if results.iter().any(|r| r.error.is_some()) {
+            exit(1);
+        }
+    } else if client_mode {
         let connection = match matches.opt_str("varlink") {
             None => match matches.opt_str("bridge") {
                 Some(bridge) => Connection::with_bridge(&bridge)
@@ -77,7 +117,12 @@ fn main() -> Result<()> {
         };
         run_client(connection)?
     } else if let Some(address) = matches.opt_str("varlink") {
-        run_server(&address, timeout)?
+        let completed = run_server(&address, timeout)?;
+        eprintln!("Completed certification runs: {}", completed);
+        // Tell a harness apart a server, which no client ran the certification against.
+        if completed == 0 {
+            exit(2);
+        }
     } else {
         print_usage(&program, &opts);
         eprintln!("Need varlink address in server mode.");
@@ -89,33 +134,146 @@ fn main() -> Result<()> {
 
 // Client
 
+/// The certification tests in the order `run_client` calls them.
+const CERTIFICATION_TESTS: &[(&str, &str)] = &[
+    ("Start", "get a new client_id"),
+    ("Test01", "return a bool"),
+    ("Test02", "pass a bool, return an int"),
+    ("Test03", "pass an int, return a float"),
+    ("Test04", "pass a float, return a string"),
+    ("Test05", "pass a string, return four basic types"),
+    ("Test06", "pass four basic types, return a struct"),
+    ("Test07", "pass a struct, return a map"),
+    ("Test08", "pass a map, return a set"),
+    ("Test09", "pass a set, return a complex type"),
+    ("Test10", "pass a complex type, reply with \"more\""),
+    ("Test11", "pass the Test10 replies \"oneway\""),
+    ("End", "check, that all tests were passed"),
+];
+
+fn list_tests(writer: &mut dyn Write) -> io::Result<()> {
+    for (name, description) in CERTIFICATION_TESTS {
+        writeln!(writer, "{:<8}{}", name, description)?;
+    }
+    Ok(())
+}
+
+/// The result of running the certification tests against one service.
+struct CertificationResult {
+    address: String,
+    /// The names of the passed tests in the order they were run.
+    passed: Vec<&'static str>,
+    /// The error of the first failed test, which stopped the run.
+    error: Option<String>,
+}
+
+/// Parse a list of addresses, one per line. Empty lines and `#` comments are skipped.
+fn parse_addresses(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(String::from)
+        .collect()
+}
+
+/// Run the certification tests against every service in `addresses`.
+fn run_client_matrix(addresses: &[String]) -> Vec<CertificationResult> {
+    addresses
+        .iter()
+        .map(|address| {
+            let mut passed = Vec::new();
+            let error = Connection::with_address(address)
+                .map_err(|e| e.into())
+                .and_then(|connection| run_client_tests(connection, &mut passed))
+                .err()
+                .map(|e| e.to_string());
+            CertificationResult {
+                address: address.clone(),
+                passed,
+                error,
+            }
+        })
+        .collect()
+}
+
+/// Print a pass/fail matrix with a row for every test and a column for every service.
+fn print_matrix(writer: &mut dyn Write, results: &[CertificationResult]) -> io::Result<()> {
+    write!(writer, "{:<8}", "Test")?;
+    for i in 1..=results.len() {
+        write!(writer, "{:>6}", i)?;
+    }
+    writeln!(writer)?;
+
+    for (name, _) in CERTIFICATION_TESTS {
+        write!(writer, "{:<8}", name)?;
+        for result in results {
+            // The run stops at the first failed test.
+            let failed = CERTIFICATION_TESTS.get(result.passed.len()).map(|t| t.0);
+            let state = if result.passed.contains(name) {
+                "ok"
+            } else if result.error.is_some() && failed == Some(*name) {
+                "FAIL"
+            } else {
+                "-"
+            };
+            write!(writer, "{:>6}", state)?;
+        }
+        writeln!(writer)?;
+    }
+
+    writeln!(writer)?;
+    for (i, result) in results.iter().enumerate() {
+        match result.error {
+            None => writeln!(writer, "{} {}: passed", i + 1, result.address)?,
+            Some(ref e) => writeln!(writer, "{} {}: failed: {}", i + 1, result.address, e)?,
+        }
+    }
+    Ok(())
+}
+
 fn run_client(connection: Arc<RwLock<varlink::Connection>>) -> Result<()> {
+    run_client_tests(connection, &mut Vec::new())
+}
+
+/// Run the certification tests and push the name of every passed test to `passed`.
+fn run_client_tests(
+    connection: Arc<RwLock<varlink::Connection>>,
+    passed: &mut Vec<&'static str>,
+) -> Result<()> {
     let mut iface = VarlinkClient::new(connection);
 
     let ret = iface.start().call()?;
     eprintln!("{:#?}", ret);
+    passed.push("Start");
 
     let client_id = ret.client_id;
 
     let ret = iface.test01(client_id.clone()).call()?;
     eprintln!("{:#?}", ret);
+    passed.push("Test01");
 
     let ret = iface.test02(client_id.clone(), ret.bool).call()?;
     eprintln!("{:#?}", ret);
+    passed.push("Test02");
 
     let ret = iface.test03(client_id.clone(), ret.int).call()?;
     eprintln!("{:#?}", ret);
+    passed.push("Test03");
 
     let ret = iface.test04(client_id.clone(), ret.float).call()?;
     eprintln!("{:#?}", ret);
+    passed.push("Test04");
 
     let ret = iface.test05(client_id.clone(), ret.string).call()?;
     eprintln!("{:#?}", ret);
+    passed.push("Test05");
 
     let ret = iface
         .test06(client_id.clone(), ret.bool, ret.int, ret.float, ret.string)
         .call()?;
     eprintln!("{:#?}", ret);
+    passed.push("Test06");
 
     let ret = iface
         .test07(
@@ -129,25 +287,30 @@ fn run_client(connection: Arc<RwLock<varlink::Connection>>) -> Result<()> {
         )
         .call()?;
     eprintln!("{:#?}", ret);
+    passed.push("Test07");
 
     let ret = iface.test08(client_id.clone(), ret.map).call()?;
     eprintln!("{:#?}", ret);
+    passed.push("Test08");
 
     let ret = iface.test09(client_id.clone(), ret.set).call()?;
     eprintln!("{:#?}", ret);
+    passed.push("Test09");
 
-    let mut ret_array = Vec::new();
-
-    for ret in iface.test10(client_id.clone(), ret.mytype).more()? {
-        let ret = ret?;
-        eprintln!("{:#?}", ret);
-        ret_array.push(ret.string.clone());
-    }
+    let ret_array = iface
+        .test10(client_id.clone(), ret.mytype)
+        .more()?
+        .values()
+        .collect::<std::result::Result<Vec<String>, _>>()?;
+    eprintln!("{:#?}", ret_array);
+    passed.push("Test10");
 
     iface.test11(client_id.clone(), ret_array).oneway()?;
+    passed.push("Test11");
 
     let ret = iface.end(client_id.clone()).call()?;
     eprintln!("{:#?}", ret);
+    passed.push("End");
 
     Ok(())
 }
@@ -388,12 +551,12 @@ impl VarlinkInterface for CertInterface {
             }
         );
 
-        call.reply(self.new_client_id())
+        call.reply(self.new_client_id().into())
     }
 
-    fn test01(&self, call: &mut Call_Test01, client_id: String) -> varlink::Result<()> {
-        if !self.check_client_id(&client_id, "Test01", "Test02") {
-            return call.reply_client_id_error();
+    fn test01(&self, call: &mut Call_Test01, client_id: ClientId) -> varlink::Result<()> {
+        if let Err(wants) = self.check_client_id(&client_id, "Test01", "Test02") {
+            return call.reply_client_id_error(wants, "Test01".into());
         }
 
         check_call_normal!(
@@ -409,11 +572,11 @@ impl VarlinkInterface for CertInterface {
     fn test02(
         &self,
         call: &mut Call_Test02,
-        client_id: String,
+        client_id: ClientId,
         _bool_: bool,
     ) -> varlink::Result<()> {
-        if !self.check_client_id(&client_id, "Test02", "Test03") {
-            return call.reply_client_id_error();
+        if let Err(wants) = self.check_client_id(&client_id, "Test02", "Test03") {
+            return call.reply_client_id_error(wants, "Test02".into());
         }
 
         check_call_normal!(
@@ -428,9 +591,14 @@ impl VarlinkInterface for CertInterface {
         call.reply(1)
     }
 
-    fn test03(&self, call: &mut Call_Test03, client_id: String, _int: i64) -> varlink::Result<()> {
-        if !self.check_client_id(&client_id, "Test03", "Test04") {
-            return call.reply_client_id_error();
+    fn test03(
+        &self,
+        call: &mut Call_Test03,
+        client_id: ClientId,
+        _int: i64,
+    ) -> varlink::Result<()> {
+        if let Err(wants) = self.check_client_id(&client_id, "Test03", "Test04") {
+            return call.reply_client_id_error(wants, "Test03".into());
         }
         check_call_normal!(
             call,
@@ -445,11 +613,11 @@ impl VarlinkInterface for CertInterface {
     fn test04(
         &self,
         call: &mut Call_Test04,
-        client_id: String,
+        client_id: ClientId,
         _float: f64,
     ) -> varlink::Result<()> {
-        if !self.check_client_id(&client_id, "Test04", "Test05") {
-            return call.reply_client_id_error();
+        if let Err(wants) = self.check_client_id(&client_id, "Test04", "Test05") {
+            return call.reply_client_id_error(wants, "Test04".into());
         }
         check_call_normal!(
             call,
@@ -467,11 +635,11 @@ impl VarlinkInterface for CertInterface {
     fn test05(
         &self,
         call: &mut Call_Test05,
-        client_id: String,
+        client_id: ClientId,
         _string: String,
     ) -> varlink::Result<()> {
-        if !self.check_client_id(&client_id, "Test05", "Test06") {
-            return call.reply_client_id_error();
+        if let Err(wants) = self.check_client_id(&client_id, "Test05", "Test06") {
+            return call.reply_client_id_error(wants, "Test05".into());
         }
         check_call_normal!(
             call,
@@ -489,14 +657,14 @@ impl VarlinkInterface for CertInterface {
     fn test06(
         &self,
         call: &mut Call_Test06,
-        client_id: String,
+        client_id: ClientId,
         _bool_: bool,
         _int: i64,
         _float: f64,
         _string: String,
     ) -> varlink::Result<()> {
-        if !self.check_client_id(&client_id, "Test06", "Test07") {
-            return call.reply_client_id_error();
+        if let Err(wants) = self.check_client_id(&client_id, "Test06", "Test07") {
+            return call.reply_client_id_error(wants, "Test06".into());
         }
         check_call_normal!(
             call,
@@ -522,11 +690,11 @@ impl VarlinkInterface for CertInterface {
     fn test07(
         &self,
         call: &mut Call_Test07,
-        client_id: String,
+        client_id: ClientId,
         _struct_: Test07_Args_struct,
     ) -> varlink::Result<()> {
-        if !self.check_client_id(&client_id, "Test07", "Test08") {
-            return call.reply_client_id_error();
+        if let Err(wants) = self.check_client_id(&client_id, "Test07", "Test08") {
+            return call.reply_client_id_error(wants, "Test07".into());
         }
         check_call_normal!(
             call,
@@ -552,11 +720,11 @@ impl VarlinkInterface for CertInterface {
     fn test08(
         &self,
         call: &mut Call_Test08,
-        client_id: String,
+        client_id: ClientId,
         _map: ::std::collections::HashMap<String, String>,
     ) -> varlink::Result<()> {
-        if !self.check_client_id(&client_id, "Test08", "Test09") {
-            return call.reply_client_id_error();
+        if let Err(wants) = self.check_client_id(&client_id, "Test08", "Test09") {
+            return call.reply_client_id_error(wants, "Test08".into());
         }
         let mut map: StringHashMap<String> = StringHashMap::new();
         map.insert("bar".into(), "Bar".into());
@@ -579,11 +747,11 @@ impl VarlinkInterface for CertInterface {
     fn test09(
         &self,
         call: &mut Call_Test09,
-        client_id: String,
+        client_id: ClientId,
         _set: varlink::StringHashSet,
     ) -> varlink::Result<()> {
-        if !self.check_client_id(&client_id, "Test09", "Test10") {
-            return call.reply_client_id_error();
+        if let Err(wants) = self.check_client_id(&client_id, "Test09", "Test10") {
+            return call.reply_client_id_error(wants, "Test09".into());
         }
         let mut set = StringHashSet::new();
         set.insert("one".into());
@@ -603,11 +771,11 @@ impl VarlinkInterface for CertInterface {
     fn test10(
         &self,
         call: &mut Call_Test10,
-        client_id: String,
+        client_id: ClientId,
         _mytype: MyType,
     ) -> varlink::Result<()> {
-        if !self.check_client_id(&client_id, "Test10", "Test11") {
-            return call.reply_client_id_error();
+        if let Err(wants) = self.check_client_id(&client_id, "Test10", "Test11") {
+            return call.reply_client_id_error(wants, "Test10".into());
         }
         check_call_more!(
             call,
@@ -619,12 +787,12 @@ impl VarlinkInterface for CertInterface {
             }
         );
 
-        call.set_continues(true);
+        let mut stream = call.begin_more();
         for i in 1..11 {
             if i == 10 {
-                call.set_continues(false);
+                stream.set_continues(false);
             }
-            call.reply(format!("Reply number {}", i))?
+            stream.reply(format!("Reply number {}", i))?
         }
         Ok(())
     }
@@ -632,11 +800,11 @@ impl VarlinkInterface for CertInterface {
     fn test11(
         &self,
         call: &mut Call_Test11,
-        client_id: String,
+        client_id: ClientId,
         _last_more_replies: Vec<String>,
     ) -> varlink::Result<()> {
-        if !self.check_client_id(&client_id, "Test11", "End") {
-            return call.reply_client_id_error();
+        if let Err(wants) = self.check_client_id(&client_id, "Test11", "End") {
+            return call.reply_client_id_error(wants, "Test11".into());
         }
         let mut last_more_replies: Vec<String> = Vec::new();
 
@@ -657,22 +825,26 @@ impl VarlinkInterface for CertInterface {
         Ok(())
     }
 
-    fn end(&self, call: &mut Call_End, client_id: String) -> varlink::Result<()> {
-        if !self.check_client_id(&client_id, "End", "End") {
-            return call.reply_client_id_error();
+    fn end(&self, call: &mut Call_End, client_id: ClientId) -> varlink::Result<()> {
+        if let Err(wants) = self.check_client_id(&client_id, "End", "End") {
+            return call.reply_client_id_error(wants, "End".into());
         }
         check_call_normal!(
             call,
             "org.varlink.certification.End",
             End_Args,
-            End_Args { client_id }
+            End_Args {
+                client_id: client_id.clone()
+            }
         );
 
+        self.complete(&client_id);
         call.reply(true)
     }
 }
 
 struct Context {
+    /// The test the client has to call next.
     test: String,
 }
 
@@ -680,22 +852,33 @@ struct ClientIds {
     lifetimes: VecDeque<(Instant, String)>,
     contexts: StringHashMap<Context>,
     max_lifetime: u64,
+    /// The number of clients, which called all tests up to `End`.
+    completed: usize,
 }
 
 impl ClientIds {
-    fn check_client_id(&mut self, client_id: &str, test: &str, next_test: &str) -> bool {
+    /// Advance `client_id` from `test` to `next_test`.
+    ///
+    /// If the client has to call another test, that one is returned as the error, `None` for
+    /// an unknown or expired `client_id`.
+    fn check_client_id(
+        &mut self,
+        client_id: &str,
+        test: &str,
+        next_test: &str,
+    ) -> ::std::result::Result<(), Option<String>> {
         self.check_lifetime_timeout();
 
         match self.contexts.get_mut(client_id) {
             Some(context) => {
                 if context.test != test {
-                    false
+                    Err(Some(context.test.clone()))
                 } else {
                     context.test = next_test.into();
-                    true
+                    Ok(())
                 }
             }
-            _ => false,
+            _ => Err(None),
         }
     }
 
@@ -721,6 +904,13 @@ impl ClientIds {
         }
     }
 
+    /// Forget `client_id` after its successful `End` and count the run as completed.
+    fn complete(&mut self, client_id: &str) {
+        if self.contexts.remove(client_id).is_some() {
+            self.completed += 1;
+        }
+    }
+
     fn new_client_id(&mut self) -> String {
         let now = Instant::now();
         let mut hasher = DefaultHasher::new();
@@ -742,24 +932,39 @@ struct CertInterface {
 }
 
 impl CertInterface {
-    fn check_client_id(&self, client_id: &str, test: &str, next_test: &str) -> bool {
+    fn check_client_id(
+        &self,
+        client_id: &str,
+        test: &str,
+        next_test: &str,
+    ) -> ::std::result::Result<(), Option<String>> {
         let mut client_ids = self.client_ids.write().unwrap();
         client_ids.check_client_id(client_id, test, next_test)
     }
 
+    fn complete(&self, client_id: &str) {
+        let mut client_ids = self.client_ids.write().unwrap();
+        client_ids.complete(client_id)
+    }
+
     fn new_client_id(&self) -> String {
         let mut client_ids = self.client_ids.write().unwrap();
         client_ids.new_client_id()
     }
 }
 
-pub fn run_server(address: &str, timeout: u64) -> varlink::Result<()> {
+/// Run the certification service, until it is idle for `timeout` seconds.
+///
+/// Returns the number of clients, which completed the certification up to `End`.
+pub fn run_server(address: &str, timeout: u64) -> varlink::Result<usize> {
+    let client_ids = Arc::new(RwLock::new(ClientIds {
+        lifetimes: VecDeque::new(),
+        contexts: StringHashMap::new(),
+        max_lifetime: 60 * 60 * 12,
+        completed: 0,
+    }));
     let certinterface = CertInterface {
-        client_ids: Arc::new(RwLock::new(ClientIds {
-            lifetimes: VecDeque::new(),
-            contexts: StringHashMap::new(),
-            max_lifetime: 60 * 60 * 12,
-        })),
+        client_ids: client_ids.clone(),
     };
 
     let myinterface = new(Box::new(certinterface));
@@ -777,5 +982,6 @@ pub fn run_server(address: &str, timeout: u64) -> varlink::Result<()> {
             _ => Err(e)?,
         }
     }
-    Ok(())
+    let completed = client_ids.read().unwrap().completed;
+    Ok(completed)
 }
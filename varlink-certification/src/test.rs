@@ -7,13 +7,9 @@ pub type Result<T> = std::result::Result<T, Box<std::error::Error>>;
 fn run_self_test(address: String) -> Result<()> {
     let client_address = address.clone();
 
-    let child = thread::spawn(move || {
-        if let Err(e) = crate::run_server(&address, 4) {
-            match e.kind() {
-                ::varlink::ErrorKind::Timeout => {}
-                _ => panic!("error: {}", e),
-            }
-        }
+    let child = thread::spawn(move || match crate::run_server(&address, 4) {
+        Ok(completed) => completed,
+        Err(e) => panic!("error: {}", e),
     });
 
     // give server time to start
@@ -23,10 +19,12 @@ fn run_self_test(address: String) -> Result<()> {
     if let Err(e) = ret {
         panic!("error: {:?}", e);
     }
-    if let Err(_) = child.join() {
-        Err(strerr!("Error joining thread").into())
-    } else {
-        Ok(())
+    match child.join() {
+        Ok(completed) => {
+            assert_eq!(completed, 1);
+            Ok(())
+        }
+        Err(_) => Err(strerr!("Error joining thread").into()),
     }
 }
 
@@ -68,3 +66,409 @@ fn test_exec() -> Result<()> {
 fn test_wrong_address_1() {
     assert!(crate::run_server("tcpd:0.0.0.0:12345".into(), 1).is_err());
 }
+
+#[test]
+fn test_list() -> Result<()> {
+    let mut out = Vec::new();
+    crate::list_tests(&mut out)?;
+    let out = String::from_utf8(out)?;
+
+    for name in &[
+        "Start", "Test01", "Test02", "Test03", "Test04", "Test05", "Test06", "Test07", "Test08",
+        "Test09", "Test10", "Test11", "End",
+    ] {
+        assert!(out.contains(name), "missing {} in:\n{}", name, out);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_http_status() {
+    use crate::org_varlink_certification::{http_status, ErrorKind};
+    use chainerror::ChainError;
+
+    let e: ChainError<ErrorKind> = into_cherr!(ErrorKind::ClientIdError(None));
+    assert_eq!(http_status(&e), 401);
+
+    let e: ChainError<ErrorKind> = into_cherr!(ErrorKind::CertificationError(None));
+    assert_eq!(http_status(&e), 422);
+
+    let e: ChainError<ErrorKind> =
+        into_cherr!(varlink::ErrorKind::InvalidParameter("client_id".into()));
+    assert_eq!(http_status(&e), 400);
+
+    let e: ChainError<ErrorKind> = into_cherr!(varlink::ErrorKind::MethodNotFound("Test12".into()));
+    assert_eq!(http_status(&e), 404);
+
+    let e: ChainError<ErrorKind> = into_cherr!(ErrorKind::Generic);
+    assert_eq!(http_status(&e), 500);
+}
+
+#[test]
+fn test_matrix() -> Result<()> {
+    let addresses = crate::parse_addresses(
+        "# two instances of the certification server\n\
+         unix:org.varlink.certification.matrix1\n\
+         \n\
+         tcp:127.0.0.1:23457\n",
+    );
+    assert_eq!(
+        addresses,
+        vec![
+            "unix:org.varlink.certification.matrix1",
+            "tcp:127.0.0.1:23457"
+        ]
+    );
+
+    let servers = addresses
+        .iter()
+        .cloned()
+        .map(|address| {
+            thread::spawn(move || {
+                if let Err(e) = crate::run_server(&address, 4) {
+                    panic!("error: {}", e);
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+
+    // give the servers time to start
+    thread::sleep(time::Duration::from_secs(1));
+
+    let mut all = addresses.clone();
+    all.push("unix:org.varlink.certification.notrunning".into());
+
+    let results = crate::run_client_matrix(&all);
+
+    for result in &results[..2] {
+        assert!(
+            result.error.is_none(),
+            "{}: {:?}",
+            result.address,
+            result.error
+        );
+        assert_eq!(result.passed.len(), crate::CERTIFICATION_TESTS.len());
+    }
+    assert!(results[2].error.is_some());
+    assert!(results[2].passed.is_empty());
+
+    let mut out = Vec::new();
+    crate::print_matrix(&mut out, &results)?;
+    let out = String::from_utf8(out)?;
+    assert!(out.contains("Start       ok    ok  FAIL"), "{}", out);
+    assert!(out.contains("End         ok    ok     -"), "{}", out);
+    assert!(out.contains("1 unix:org.varlink.certification.matrix1: passed"));
+    assert!(out.contains("3 unix:org.varlink.certification.notrunning: failed"));
+
+    for server in servers {
+        if server.join().is_err() {
+            return Err(strerr!("Error joining thread").into());
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_reply_error_struct() -> Result<()> {
+    use crate::org_varlink_certification::{CertificationError_Args, VarlinkCallError};
+
+    let request = varlink::Request {
+        more: None,
+        oneway: None,
+        upgrade: None,
+        method: "org.varlink.certification.Test01".into(),
+        parameters: None,
+    };
+
+    let mut out: Vec<u8> = Vec::new();
+    {
+        let mut call = varlink::Call::new(&mut out, &request);
+        call.reply_certification_error_struct(CertificationError_Args {
+            wants: serde_json::json!({ "bool": true }),
+            got: serde_json::json!({ "bool": false }),
+        })?;
+    }
+
+    assert_eq!(out.pop(), Some(0));
+    assert_eq!(
+        serde_json::from_slice::<serde_json::Value>(&out)?,
+        serde_json::json!({
+            "error": "org.varlink.certification.CertificationError",
+            "parameters": {
+                "wants": { "bool": true },
+                "got": { "bool": false }
+            }
+        })
+    );
+    Ok(())
+}
+
+#[test]
+fn test_clone_client() -> Result<()> {
+    use crate::org_varlink_certification::{VarlinkClient, VarlinkClientInterface};
+
+    let address = "unix:org.varlink.certification.clone_client";
+    let server_address = address.to_string();
+
+    let server = thread::spawn(move || {
+        if let Err(e) = crate::run_server(&server_address, 3) {
+            match e.kind() {
+                ::varlink::ErrorKind::Timeout => {}
+                _ => panic!("error: {}", e),
+            }
+        }
+    });
+
+    // give server time to start
+    thread::sleep(time::Duration::from_secs(1));
+
+    let client = VarlinkClient::new(Connection::with_address(address)?);
+
+    let clients: Vec<_> = (0..2)
+        .map(|_| {
+            let mut client = client.clone();
+            thread::spawn(move || {
+                for _ in 0..20 {
+                    let client_id = client.start().call().unwrap().client_id;
+                    let ret = client.test01(client_id).call().unwrap();
+                    assert!(ret.bool);
+                }
+            })
+        })
+        .collect();
+
+    for c in clients {
+        assert!(c.join().is_ok());
+    }
+
+    drop(client);
+    assert!(server.join().is_ok());
+    Ok(())
+}
+
+#[test]
+fn test_client_id_newtype() {
+    use crate::org_varlink_certification::*;
+
+    // The methods take a `ClientId`, a plain `String` does not compile here. Checked by
+    // coercing the method to a function pointer with the expected argument type.
+    let _: fn(
+        &mut VarlinkClient,
+        ClientId,
+    ) -> varlink::MethodCall<Test01_Args, Test01_Reply, Error> =
+        <VarlinkClient as VarlinkClientInterface>::test01;
+
+    let client_id = ClientId::from("0123abcd");
+    assert_eq!(client_id.len(), 8);
+    assert_eq!(String::from(client_id.clone()), "0123abcd");
+
+    // on the wire, it is still a plain string
+    let args = serde_json::to_value(Test02_Args {
+        client_id,
+        bool: true,
+    })
+    .unwrap();
+    assert_eq!(
+        args,
+        serde_json::json!({"client_id": "0123abcd", "bool": true})
+    );
+    let args: Test02_Args = serde_json::from_value(args).unwrap();
+    assert_eq!(&*args.client_id, "0123abcd");
+}
+
+#[test]
+fn test_integer_enums() {
+    use crate::org_varlink_certification::*;
+
+    // `one` has the explicit discriminant `1`, the others follow
+    assert_eq!(MyType_enum::one as i64, 1);
+    assert_eq!(MyType_enum::three as i64, 3);
+    assert_eq!(MyType_enum::from_i64(2), Some(MyType_enum::two));
+    assert_eq!(MyType_enum::from_i64(0), None);
+    assert_eq!(Interface_foo::foo as i64, 0);
+
+    // on the wire, it is still the name
+    assert_eq!(
+        serde_json::to_value(MyType_enum::three).unwrap(),
+        serde_json::json!("three")
+    );
+    let e: MyType_enum = serde_json::from_value(serde_json::json!("two")).unwrap();
+    assert_eq!(e as i64, 2);
+}
+
+#[test]
+fn test_clone_types() -> Result<()> {
+    use crate::org_varlink_certification::*;
+
+    // generated types derive `Clone`, replies can be kept and reused
+    let mytype = crate::new_mytype()?;
+    let m2 = mytype.clone();
+    assert_eq!(m2, mytype);
+
+    let reply = Test09_Reply { mytype };
+    assert_eq!(reply.clone(), reply);
+    Ok(())
+}
+
+#[test]
+fn test_reply_try_from() {
+    use crate::org_varlink_certification::*;
+    use std::convert::TryFrom;
+
+    let reply = Test01_Reply::try_from(serde_json::json!({"bool": true})).unwrap();
+    assert_eq!(reply, Test01_Reply { bool: true });
+
+    let e = Test01_Reply::try_from(serde_json::json!({"int": 1})).unwrap_err();
+    match e.kind() {
+        ErrorKind::SerdeJson_Error(_) => {}
+        kind => panic!("unexpected error: {:?}", kind),
+    }
+}
+
+#[test]
+fn test_pump() -> Result<()> {
+    use crate::org_varlink_certification::*;
+
+    let address = "unix:org.varlink.certification.pump";
+    let server_address = address.to_string();
+
+    let server = thread::spawn(move || {
+        if let Err(e) = crate::run_server(&server_address, 2) {
+            match e.kind() {
+                ::varlink::ErrorKind::Timeout => {}
+                _ => panic!("error: {}", e),
+            }
+        }
+    });
+
+    // give server time to start
+    thread::sleep(time::Duration::from_secs(1));
+
+    let mut iface = VarlinkClient::new(Connection::with_address(address)?);
+
+    // Test10 is only allowed after all the previous tests
+    let client_id = iface.start().call()?.client_id;
+    let ret = iface.test01(client_id.clone()).call()?;
+    let ret = iface.test02(client_id.clone(), ret.bool).call()?;
+    let ret = iface.test03(client_id.clone(), ret.int).call()?;
+    let ret = iface.test04(client_id.clone(), ret.float).call()?;
+    let ret = iface.test05(client_id.clone(), ret.string).call()?;
+    let ret = iface
+        .test06(client_id.clone(), ret.bool, ret.int, ret.float, ret.string)
+        .call()?;
+    let ret = iface
+        .test07(
+            client_id.clone(),
+            Test07_Args_struct {
+                bool: ret.r#struct.bool,
+                int: ret.r#struct.int,
+                float: ret.r#struct.float,
+                string: ret.r#struct.string,
+            },
+        )
+        .call()?;
+    let ret = iface.test08(client_id.clone(), ret.map).call()?;
+    let mytype = iface.test09(client_id.clone(), ret.set).call()?.mytype;
+
+    let request = varlink::Request {
+        more: Some(true),
+        oneway: None,
+        upgrade: None,
+        method: "org.varlink.certification.Test10".into(),
+        parameters: None,
+    };
+
+    let mut out: Vec<u8> = Vec::new();
+    {
+        let mut call = varlink::Call::new(&mut out, &request);
+        iface
+            .test10(client_id.clone(), mytype.clone())
+            .more()?
+            .pump(&mut call, |reply| Test10_Reply {
+                string: reply.string.to_uppercase(),
+            })?;
+    }
+
+    let replies = out
+        .split(|b| *b == 0)
+        .filter(|m| !m.is_empty())
+        .map(serde_json::from_slice)
+        .collect::<std::result::Result<Vec<serde_json::Value>, _>>()?;
+    assert_eq!(replies.len(), 10);
+    for (i, reply) in replies.iter().enumerate() {
+        let mut expected = serde_json::json!({
+            "parameters": { "string": format!("REPLY NUMBER {}", i + 1) }
+        });
+        if i < 9 {
+            expected["continues"] = serde_json::json!(true);
+        }
+        assert_eq!(*reply, expected);
+    }
+
+    // Test10 was called already, so the error reply is passed on and ends the stream
+    let mut out: Vec<u8> = Vec::new();
+    {
+        let mut call = varlink::Call::new(&mut out, &request);
+        iface
+            .test10(client_id, mytype)
+            .more()?
+            .pump(&mut call, |reply| reply)?;
+    }
+
+    assert_eq!(out.pop(), Some(0));
+    assert_eq!(
+        serde_json::from_slice::<serde_json::Value>(&out)?,
+        serde_json::json!({
+            "error": "org.varlink.certification.ClientIdError",
+            "parameters": { "wants": "Test11", "got": "Test10" }
+        })
+    );
+
+    drop(iface);
+    assert!(server.join().is_ok());
+    Ok(())
+}
+
+#[test]
+fn test_client_id_errors() {
+    let mut client_ids = crate::ClientIds {
+        lifetimes: std::collections::VecDeque::new(),
+        contexts: varlink::StringHashMap::new(),
+        max_lifetime: 60,
+        completed: 0,
+    };
+    let client_id = client_ids.new_client_id();
+
+    assert_eq!(
+        client_ids.check_client_id(&client_id, "Test01", "Test02"),
+        Ok(())
+    );
+    assert_eq!(
+        client_ids.check_client_id(&client_id, "Test01", "Test02"),
+        Err(Some("Test02".into()))
+    );
+    assert_eq!(
+        client_ids.check_client_id(&client_id, "Test03", "Test04"),
+        Err(Some("Test02".into()))
+    );
+    assert_eq!(
+        client_ids.check_client_id("unknown", "Test01", "Test02"),
+        Err(None)
+    );
+
+    client_ids.complete(&client_id);
+    client_ids.complete(&client_id);
+    assert_eq!(client_ids.completed, 1);
+    assert_eq!(
+        client_ids.check_client_id(&client_id, "End", "End"),
+        Err(None)
+    );
+}
+
+#[test]
+fn test_description_check() {
+    use crate::org_varlink_certification::generated_description_tests::check_description;
+
+    let description = include_str!("org.varlink.certification.varlink");
+    assert_eq!(check_description(description), Ok(()));
+}
@@ -0,0 +1,43 @@
+//! Builders for the struct types (`GeneratorOptions::builders`).
+
+#[allow(non_camel_case_types)]
+#[allow(non_snake_case)]
+#[allow(dead_code)]
+mod org_example_skew {
+    include!(concat!(env!("OUT_DIR"), "/builders_skew.rs"));
+}
+
+use crate::org_example_skew::Item;
+
+#[test]
+fn test_builders() {
+    let item = Item::builder()
+        .name("apple".into())
+        .count(3)
+        .tags(vec!["fruit".into()])
+        .note("fresh".into())
+        .build()
+        .unwrap();
+    assert_eq!(
+        item,
+        Item {
+            name: "apple".into(),
+            count: 3,
+            tags: vec!["fruit".into()],
+            note: Some("fresh".into()),
+        }
+    );
+
+    // optional fields stay `None`
+    let item = Item::builder()
+        .name("pear".into())
+        .count(1)
+        .tags(Vec::new())
+        .build()
+        .unwrap();
+    assert_eq!(item.note, None);
+
+    let e = Item::builder().name("plum".into()).build().unwrap_err();
+    assert_eq!(e, varlink::MissingField("count"));
+    assert_eq!(e.to_string(), "missing field `count`");
+}
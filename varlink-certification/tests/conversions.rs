@@ -0,0 +1,61 @@
+//! Conversions between the structurally equal types of two versions of an interface
+//! (`GeneratorOptions::structural_conversions`).
+
+#[allow(non_camel_case_types)]
+#[allow(non_snake_case)]
+#[allow(dead_code)]
+mod org_example_migrate_v1 {
+    include!(concat!(env!("OUT_DIR"), "/org.example.migrate.v1.rs"));
+}
+
+#[allow(non_camel_case_types)]
+#[allow(non_snake_case)]
+#[allow(dead_code)]
+mod org_example_migrate_v2 {
+    include!(concat!(env!("OUT_DIR"), "/org.example.migrate.v2.rs"));
+}
+
+use crate::org_example_migrate_v1 as v1;
+use crate::org_example_migrate_v2 as v2;
+
+#[test]
+fn test_structural_conversions() {
+    let job = v1::Job {
+        id: 2,
+        name: "build".into(),
+        state: v1::State::running,
+        tags: vec!["ci".into()],
+        owner: Some(v1::Job_owner {
+            name: "root".into(),
+            uid: 0,
+        }),
+        parent: Some(Box::new(v1::Job {
+            id: 1,
+            name: "pipeline".into(),
+            state: v1::State::stopped,
+            tags: vec![],
+            owner: None,
+            parent: None,
+        })),
+    };
+
+    let converted: v2::Job = job.clone().into();
+    assert_eq!(converted.id, 2);
+    assert_eq!(converted.name, "build");
+    assert_eq!(converted.state, v2::State::running);
+    assert_eq!(converted.tags, vec!["ci".to_string()]);
+    assert_eq!(
+        converted.owner,
+        Some(v2::Job_owner {
+            name: "root".into(),
+            uid: 0,
+        })
+    );
+    assert_eq!(
+        converted.parent.as_ref().map(|p| p.state.clone()),
+        Some(v2::State::stopped)
+    );
+
+    let back: v1::Job = converted.into();
+    assert_eq!(back, job);
+}
@@ -0,0 +1,34 @@
+//! Missing fields deserialized to their `Default` (`GeneratorOptions::default_fields`).
+
+#[allow(non_camel_case_types)]
+#[allow(non_snake_case)]
+#[allow(dead_code)]
+mod org_example_skew {
+    include!(concat!(env!("OUT_DIR"), "/org.example.skew.rs"));
+}
+
+use crate::org_example_skew::{GetItem_Reply, Item, ItemNotFound_Args};
+
+#[test]
+fn test_default_fields() {
+    let reply: GetItem_Reply = serde_json::from_str(r#"{ "item": { "name": "apple" } }"#).unwrap();
+    assert_eq!(
+        reply,
+        GetItem_Reply {
+            item: Item {
+                name: "apple".into(),
+                count: 0,
+                tags: Vec::new(),
+                note: None,
+            },
+            total: 0,
+            cached: false,
+        }
+    );
+
+    let args: ItemNotFound_Args = serde_json::from_str("{}").unwrap();
+    assert_eq!(args.name, "");
+
+    // a field of a type stays required
+    assert!(serde_json::from_str::<GetItem_Reply>(r#"{ "total": 1 }"#).is_err());
+}
@@ -0,0 +1,25 @@
+//! The `Default` of a type with declared default values.
+
+#[allow(non_camel_case_types)]
+#[allow(non_snake_case)]
+#[allow(dead_code)]
+mod org_example_defaults {
+    include!(concat!(env!("OUT_DIR"), "/org.example.defaults.rs"));
+}
+
+use crate::org_example_defaults::Order;
+
+#[test]
+fn test_default_values() {
+    // the fields without a declared default value use their `Default`
+    assert_eq!(
+        Order::default(),
+        Order {
+            count: 1,
+            express: false,
+            kind: None,
+            items: Vec::new(),
+            note: None,
+        }
+    );
+}
@@ -0,0 +1,27 @@
+//! Types and methods marked with `# @deprecated` in the interface description.
+
+#[allow(non_camel_case_types)]
+#[allow(non_snake_case)]
+#[allow(dead_code)]
+// the generated code has to use its own deprecated items without a warning
+#[deny(deprecated)]
+mod org_example_deprecated {
+    include!(concat!(env!("OUT_DIR"), "/org.example.deprecated.rs"));
+}
+
+#[test]
+#[allow(deprecated)]
+fn test_deprecated_types() {
+    use crate::org_example_deprecated::{Get_Reply, Item, Kind};
+
+    let reply: Get_Reply =
+        serde_json::from_value(serde_json::json!({ "item": { "name": "a", "kind": "old" } }))
+            .unwrap();
+    assert_eq!(
+        reply.item,
+        Item {
+            name: "a".into(),
+            kind: Kind::old,
+        }
+    );
+}
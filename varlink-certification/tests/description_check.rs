@@ -0,0 +1,20 @@
+//! The check of the embedded description (`GeneratorOptions::emit_description_test`) against
+//! generated code, in which a method was renamed.
+
+#[allow(non_camel_case_types)]
+#[allow(non_snake_case)]
+#[allow(dead_code)]
+mod org_example_skew {
+    include!(concat!(env!("OUT_DIR"), "/description_renamed.rs"));
+}
+
+use crate::org_example_skew::generated_description_tests::check_description;
+
+#[test]
+fn test_renamed_method() {
+    let description = include_str!("org.example.skew.varlink");
+    assert_eq!(
+        check_description(description),
+        Err("methods [\"GetItem\"] are generated as [\"FetchItem\"]".to_string())
+    );
+}
@@ -0,0 +1,81 @@
+//! The code of two interfaces included into the same module (`GeneratorOptions::ident_prefix`).
+
+#[allow(non_camel_case_types)]
+#[allow(non_snake_case)]
+#[allow(dead_code)]
+mod interfaces {
+    include!(concat!(env!("OUT_DIR"), "/prefix_ordered.rs"));
+    include!(concat!(env!("OUT_DIR"), "/prefix_skew.rs"));
+}
+
+use chainerror::ChainErrorFrom;
+use serde_json::json;
+
+use crate::interfaces::*;
+
+struct Ordered;
+
+impl OrderedVarlinkInterface for Ordered {
+    fn get_labels(&self, call: &mut dyn Call_GetLabels) -> varlink::Result<()> {
+        let mut labels = Labels {
+            labels: Default::default(),
+            nested: Default::default(),
+        };
+        labels.labels.insert("app".into(), "web".into());
+        call.reply(labels)
+    }
+}
+
+struct Skew;
+
+impl SkewVarlinkInterface for Skew {
+    fn get_item(&self, call: &mut dyn Call_GetItem, name: String) -> varlink::Result<()> {
+        call.reply_item_not_found(name)
+    }
+}
+
+/// The reply of `interface` to a call of `method`.
+fn call(
+    interface: &dyn varlink::Interface,
+    method: &str,
+    parameters: serde_json::Value,
+) -> varlink::Reply {
+    let request = varlink::Request::create(method, Some(parameters));
+    let mut out: Vec<u8> = Vec::new();
+    {
+        let mut call = varlink::Call::new(&mut out, &request);
+        interface.call(&mut call).unwrap();
+    }
+    out.pop();
+    serde_json::from_slice(&out).unwrap()
+}
+
+#[test]
+fn test_ident_prefix() {
+    let ordered = ordered_new(Box::new(Ordered));
+    let skew = skew_new(Box::new(Skew));
+    assert_eq!(
+        varlink::Interface::get_name(&ordered),
+        "org.example.ordered"
+    );
+    assert_eq!(varlink::Interface::get_name(&skew), "org.example.skew");
+
+    let reply = call(&ordered, "org.example.ordered.GetLabels", json!({}));
+    assert_eq!(
+        reply.parameters,
+        Some(json!({ "labels": { "labels": { "app": "web" }, "nested": {} } }))
+    );
+
+    let reply = call(&skew, "org.example.skew.GetItem", json!({ "name": "pear" }));
+    assert_eq!(
+        reply.error.as_ref().map(|e| e.as_ref()),
+        Some("org.example.skew.ItemNotFound")
+    );
+
+    // each interface has its own errors
+    let e = <SkewErrorKind as ChainErrorFrom<varlink::Reply>>::chain_error_from(reply, None);
+    match e.kind() {
+        SkewErrorKind::ItemNotFound(Some(args)) => assert_eq!(args.name, "pear"),
+        kind => panic!("{:?}", kind),
+    }
+}
@@ -0,0 +1,69 @@
+//! Several interfaces in one varlink file, each generated as its own module.
+
+#[allow(non_camel_case_types)]
+#[allow(non_snake_case)]
+#[allow(dead_code)]
+mod org_example_multi {
+    include!(concat!(env!("OUT_DIR"), "/org.example.multi.rs"));
+}
+
+use crate::org_example_multi::{org_example_admin, org_example_service};
+use varlink::Interface;
+
+struct Service;
+
+impl org_example_service::VarlinkInterface for Service {
+    fn ping(
+        &self,
+        call: &mut dyn org_example_service::Call_Ping,
+        ping: String,
+    ) -> varlink::Result<()> {
+        call.reply(ping)
+    }
+}
+
+struct Admin;
+
+impl org_example_admin::VarlinkInterface for Admin {
+    fn ping(&self, call: &mut dyn org_example_admin::Call_Ping) -> varlink::Result<()> {
+        call.reply()
+    }
+
+    fn stop(
+        &self,
+        call: &mut dyn org_example_admin::Call_Stop,
+        _reason: String,
+    ) -> varlink::Result<()> {
+        call.reply()
+    }
+}
+
+// Both modules have their own client
+#[allow(dead_code)]
+fn clients(_service: org_example_service::VarlinkClient, _admin: org_example_admin::VarlinkClient) {
+}
+
+#[test]
+fn test_multi_interface() {
+    let service = org_example_service::new(Box::new(Service));
+    let admin = org_example_admin::new(Box::new(Admin));
+
+    assert_eq!(service.get_name(), "org.example.service");
+    assert_eq!(admin.get_name(), "org.example.admin");
+
+    assert!(service
+        .get_description()
+        .starts_with("# The interface of a service\ninterface org.example.service\n"));
+    assert!(!service.get_description().contains("org.example.admin"));
+    assert!(admin
+        .get_description()
+        .starts_with("# The admin interface of the same service\ninterface org.example.admin\n"));
+
+    let args = org_example_admin::Stop_Args {
+        reason: "upgrade".into(),
+    };
+    assert_eq!(
+        serde_json::to_value(&args).unwrap(),
+        serde_json::json!({ "reason": "upgrade" })
+    );
+}
@@ -0,0 +1,36 @@
+//! Dictionaries iterating in the order of their keys (`GeneratorOptions::ordered_maps`).
+
+#[allow(non_camel_case_types)]
+#[allow(non_snake_case)]
+#[allow(dead_code)]
+mod org_example_ordered {
+    include!(concat!(env!("OUT_DIR"), "/org.example.ordered.rs"));
+}
+
+use crate::org_example_ordered::Labels;
+
+#[test]
+fn test_ordered_maps() {
+    let labels: Labels = serde_json::from_str(
+        r#"{
+            "labels": { "zone": "b", "app": "web", "tier": "front", "env": "prod" },
+            "nested": { "y": { "b": 2, "a": 1 }, "x": { "c": 3 } }
+        }"#,
+    )
+    .unwrap();
+
+    let keys: Vec<&str> = labels.labels.keys().map(String::as_str).collect();
+    assert_eq!(keys, vec!["app", "env", "tier", "zone"]);
+
+    let nested: Vec<(&str, Vec<&str>)> = labels
+        .nested
+        .iter()
+        .map(|(k, v)| (k.as_str(), v.keys().map(String::as_str).collect()))
+        .collect();
+    assert_eq!(nested, vec![("x", vec!["c"]), ("y", vec!["a", "b"])]);
+
+    assert_eq!(
+        serde_json::to_string(&labels.labels).unwrap(),
+        r#"{"app":"web","env":"prod","tier":"front","zone":"b"}"#
+    );
+}
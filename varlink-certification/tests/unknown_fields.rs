@@ -0,0 +1,41 @@
+//! Parameters unknown to the interface kept for round-tripping
+//! (`GeneratorOptions::preserve_unknown_fields`).
+
+#[allow(non_camel_case_types)]
+#[allow(non_snake_case)]
+#[allow(dead_code)]
+mod org_example_extra {
+    include!(concat!(env!("OUT_DIR"), "/org.example.extra.rs"));
+}
+
+use crate::org_example_extra::{GetStatus_Args, GetStatus_Reply};
+
+#[test]
+fn test_unknown_fields_round_trip() {
+    let input = serde_json::json!({
+        "state": "running",
+        "pid": 42,
+        "uptime": 3600,
+        "labels": { "tier": "front" }
+    });
+
+    let reply: GetStatus_Reply = serde_json::from_value(input.clone()).unwrap();
+    assert_eq!(reply.state, "running");
+    assert_eq!(reply.pid, 42);
+    assert_eq!(reply.extra.len(), 2);
+    assert_eq!(reply.extra["uptime"], serde_json::json!(3600));
+
+    assert_eq!(serde_json::to_value(&reply).unwrap(), input);
+}
+
+#[test]
+fn test_unknown_fields_empty() {
+    let args = GetStatus_Args {
+        name: "web".into(),
+        extra: Default::default(),
+    };
+    assert_eq!(
+        serde_json::to_value(&args).unwrap(),
+        serde_json::json!({ "name": "web" })
+    );
+}
@@ -19,7 +19,7 @@ use varlink::{
 use varlink_parser::{Format, FormatColored, IDL};
 use varlink_stdinterfaces::org_varlink_resolver::{VarlinkClient, VarlinkClientInterface};
 
-use crate::proxy::{handle, handle_connect};
+use crate::proxy::handle;
 
 #[cfg(test)]
 mod test;
@@ -422,7 +422,7 @@ fn varlink_bridge(
             }
         },
     };
-    handle_connect(connection, in_buffer, out_writer).map_err(mstrerr!("Bridging"))?;
+    varlink::bridge_connection(connection, in_buffer, out_writer).map_err(mstrerr!("Bridging"))?;
 
     Ok(())
 }
@@ -0,0 +1,159 @@
+//! An async client connection on tokio, enabled with the `async` feature
+
+use std::sync::Arc;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::sync::Mutex;
+
+use crate::client::tcp_host_port;
+use crate::error::*;
+use crate::{nesting_too_deep, Reply, Request, DEFAULT_MAX_NESTING_DEPTH};
+use chainerror::*;
+
+trait AsyncStream: AsyncRead + AsyncWrite + Unpin + Send + Sync {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send + Sync> AsyncStream for T {}
+
+struct State {
+    stream: BufReader<Box<dyn AsyncStream>>,
+    /// A call was dropped after sending its request, so the next message on the stream
+    /// is not the reply to the next call.
+    broken: bool,
+}
+
+/// An async client connection to a varlink service.
+///
+/// Only plain calls are supported, no `more`, `oneway` or `upgrade` calls. Calls from
+/// several tasks are serialized through a lock of the connection. The `AsyncVarlinkClient`
+/// generated with the `async_client` option of `varlink_generator` uses this connection.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// let connection = AsyncConnection::with_address("unix:/run/org.example.ping").await?;
+/// let client = org_example_ping::AsyncVarlinkClient::new(connection);
+/// let reply = client.ping("hello".into()).await?;
+/// ```
+pub struct AsyncConnection {
+    state: Mutex<State>,
+    address: String,
+}
+
+impl AsyncConnection {
+    /// Connect to the `tcp:` or `unix:` `address`.
+    pub async fn with_address<S: ?Sized + AsRef<str>>(address: &S) -> Result<Arc<Self>> {
+        let address = address.as_ref();
+        let stream: Box<dyn AsyncStream> = if address.starts_with("tcp:") {
+            let tcp = TcpStream::connect(tcp_host_port(&address[4..])?)
+                .await
+                .map_err(minto_cherr!())?;
+            Box::new(tcp)
+        } else if address.starts_with("unix:") {
+            connect_unix(address).await?
+        } else {
+            return Err(into_cherr!(ErrorKind::InvalidAddress));
+        };
+
+        Ok(Arc::new(AsyncConnection {
+            state: Mutex::new(State {
+                stream: BufReader::new(stream),
+                broken: false,
+            }),
+            address: address.into(),
+        }))
+    }
+
+    pub fn address(&self) -> String {
+        self.address.clone()
+    }
+
+    /// Call `method` with `parameters` and return the parameters of the reply.
+    ///
+    /// An error reply of the service is returned as an error converted from the `Reply`, like
+    /// with `MethodCall::call()`. If the returned future is dropped after the request was
+    /// sent, but before the reply arrived, all further calls on the connection fail with
+    /// `ErrorKind::ConnectionClosed`.
+    pub async fn call<MRequest, MReply, MError>(
+        &self,
+        method: &str,
+        parameters: MRequest,
+    ) -> ChainResult<MReply, MError>
+    where
+        MRequest: Serialize,
+        MReply: DeserializeOwned,
+        MError: chainerror::ChainErrorFrom<ErrorKind>
+            + chainerror::ChainErrorFrom<Reply>
+            + chainerror::ChainErrorFrom<serde_json::error::Error>
+            + chainerror::ChainErrorFrom<::std::io::Error>,
+    {
+        let parameters = serde_json::to_value(parameters).map_err(minto_cherr!())?;
+        let mut buf = serde_json::to_vec(&Request::create(method, Some(parameters)))
+            .map_err(minto_cherr!())?;
+        buf.push(0);
+
+        let mut state = self.state.lock().await;
+        if state.broken {
+            return Err(into_cherr!(ErrorKind::ConnectionClosed));
+        }
+        state.broken = true;
+
+        state.stream.write_all(&buf).await.map_err(minto_cherr!())?;
+        state.stream.flush().await.map_err(minto_cherr!())?;
+
+        buf.clear();
+        state
+            .stream
+            .read_until(0, &mut buf)
+            .await
+            .map_err(minto_cherr!())?;
+        // an empty buffer or a message without the null byte at the end of the stream
+        if buf.pop() != Some(0) {
+            return Err(into_cherr!(ErrorKind::ConnectionClosed));
+        }
+        state.broken = false;
+        drop(state);
+
+        if nesting_too_deep(&buf, DEFAULT_MAX_NESTING_DEPTH) {
+            return Err(into_cherr!(ErrorKind::NestingTooDeep(
+                DEFAULT_MAX_NESTING_DEPTH
+            )));
+        }
+        let reply: Reply = serde_json::from_slice(&buf).map_err(minto_cherr!())?;
+        if reply.error != None {
+            return Err(into_cherr!(reply));
+        }
+
+        let parameters = reply
+            .parameters
+            .unwrap_or_else(|| Value::Object(Map::new()));
+        serde_json::from_value(parameters).map_err(minto_cherr!())
+    }
+}
+
+#[cfg(unix)]
+async fn connect_unix(address: &str) -> Result<Box<dyn AsyncStream>> {
+    use crate::client::get_abstract_unixstream;
+    use tokio::net::UnixStream;
+
+    let addr = address[5..].split(';').next().unwrap();
+    if addr.starts_with('@') {
+        // tokio has no abstract sockets, so connect blocking and register the socket
+        let stream = get_abstract_unixstream(&addr.replacen('@', "\0", 1))?;
+        stream.set_nonblocking(true).map_err(minto_cherr!())?;
+        return Ok(Box::new(
+            UnixStream::from_std(stream).map_err(minto_cherr!())?,
+        ));
+    }
+    Ok(Box::new(
+        UnixStream::connect(addr).await.map_err(minto_cherr!())?,
+    ))
+}
+
+#[cfg(not(unix))]
+async fn connect_unix(_address: &str) -> Result<Box<dyn AsyncStream>> {
+    Err(into_cherr!(ErrorKind::InvalidAddress))
+}
@@ -0,0 +1,160 @@
+//! Forward varlink calls between two transports
+
+use std::io::{self, copy, BufRead, Write};
+use std::sync::{Arc, RwLock};
+use std::thread;
+
+use chainerror::*;
+use serde_json::from_slice;
+
+use crate::error::*;
+use crate::{Call, Connection, Reply, Request};
+
+/// Forward the varlink calls read from `client_reader` to the service at `address` and
+/// write the replies to `client_writer`, e.g. to tunnel varlink through the stdin and stdout
+/// of `ssh my.example.org -- varlink bridge`.
+///
+/// See [bridge_connection](fn.bridge_connection.html).
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// let stdin = std::io::BufReader::new(std::io::stdin());
+/// varlink::bridge(stdin, std::io::stdout(), "unix:/run/org.example.ftl")?;
+/// # Ok::<(), varlink::Error>(())
+/// ```
+pub fn bridge<R, W, S>(client_reader: R, client_writer: W, address: &S) -> Result<()>
+where
+    R: BufRead + Send + Sync + 'static,
+    W: Write + Send + Sync + 'static,
+    S: ?Sized + AsRef<str>,
+{
+    bridge_connection(
+        Connection::with_address(address)?,
+        client_reader,
+        client_writer,
+    )
+}
+
+/// Forward the varlink calls read from `client_reader` to `connection` and write the
+/// replies to `client_writer`.
+///
+/// The calls are forwarded unchanged, one at a time. All replies of a `more` call are
+/// relayed, before the next call is read. After an `upgrade` call, the data is copied
+/// in both directions, until one side closes the connection.
+///
+/// Returns, when `client_reader` is closed, or with the first I/O error. The connection
+/// can be used again after an error, unless the call was an `upgrade` call.
+pub fn bridge_connection<R, W>(
+    connection: Arc<RwLock<Connection>>,
+    mut client_reader: R,
+    mut client_writer: W,
+) -> Result<()>
+where
+    R: BufRead + Send + Sync + 'static,
+    W: Write + Send + Sync + 'static,
+{
+    let mut conn = connection.write().unwrap();
+
+    loop {
+        let mut buf = Vec::new();
+        if client_reader
+            .read_until(b'\0', &mut buf)
+            .map_err(minto_cherr!())?
+            == 0
+        {
+            return Ok(());
+        }
+
+        // pop the last zero byte
+        buf.pop();
+
+        let req: Request = from_slice(&buf).map_err(minto_cherr!())?;
+
+        if req.method.rfind('.').is_none() {
+            let method: String = String::from(req.method.as_ref());
+            let mut call = Call::new(&mut client_writer, &req);
+            call.reply_interface_not_found(Some(method))?;
+            return Ok(());
+        }
+
+        if conn.reader.is_none() || conn.writer.is_none() {
+            return Err(into_cherr!(ErrorKind::ConnectionBusy));
+        }
+        let (mut service_reader, mut service_writer) =
+            (conn.reader.take().unwrap(), conn.writer.take().unwrap());
+
+        let upgraded = req.upgrade.unwrap_or(false);
+
+        buf.push(0);
+        let r = forward_call(
+            &buf,
+            req.oneway.unwrap_or(false),
+            upgraded,
+            &mut service_reader,
+            &mut service_writer,
+            &mut client_writer,
+        );
+        if r.is_err() || !upgraded {
+            conn.reader = Some(service_reader);
+            conn.writer = Some(service_writer);
+            r?;
+            continue;
+        }
+
+        // Copy back and forth, until someone disconnects.
+        let copy1 = thread::spawn(move || copy(&mut client_reader, &mut service_writer));
+        let copy2 = thread::spawn(move || copy(&mut service_reader, &mut client_writer));
+        let r = copy1.join();
+        r.unwrap_or_else(|_| Err(io::Error::from(io::ErrorKind::ConnectionAborted)))
+            .map_err(minto_cherr!())?;
+        let r = copy2.join();
+        r.unwrap_or_else(|_| Err(io::Error::from(io::ErrorKind::ConnectionAborted)))
+            .map_err(minto_cherr!())?;
+        return Ok(());
+    }
+}
+
+/// Write the call `buf` to the service and relay its replies to `client_writer`.
+fn forward_call<R, W, C>(
+    buf: &[u8],
+    oneway: bool,
+    upgraded: bool,
+    service_reader: &mut R,
+    service_writer: &mut W,
+    client_writer: &mut C,
+) -> Result<()>
+where
+    R: BufRead,
+    W: Write,
+    C: Write,
+{
+    service_writer.write_all(buf).map_err(minto_cherr!())?;
+    service_writer.flush().map_err(minto_cherr!())?;
+
+    if oneway {
+        return Ok(());
+    }
+
+    loop {
+        let mut buf = Vec::new();
+
+        let n = service_reader
+            .read_until(0, &mut buf)
+            .map_err(minto_cherr!())?;
+        if n == 0 {
+            return Err(into_cherr!(ErrorKind::ConnectionClosed));
+        }
+
+        client_writer.write_all(&buf).map_err(minto_cherr!())?;
+        client_writer.flush().map_err(minto_cherr!())?;
+
+        buf.pop();
+
+        let reply: Reply = from_slice(&buf).map_err(minto_cherr!())?;
+
+        if upgraded || !reply.continues.unwrap_or(false) {
+            return Ok(());
+        }
+    }
+}
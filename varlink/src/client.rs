@@ -3,77 +3,247 @@
 #![allow(dead_code)]
 
 #[cfg(unix)]
-use libc::{close, dup2, getpid};
-use std::io::{Read, Write};
-use std::net::{Shutdown, TcpStream};
-use std::process::Child;
+use libc::{close, dup2, fcntl, FD_CLOEXEC, F_GETFD, F_SETFD};
+use std::collections::VecDeque;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{Shutdown, TcpStream, ToSocketAddrs};
+use std::process::{Child, ChildStderr};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 use tempfile::TempDir;
 
 #[cfg(unix)]
-use std::os::unix::io::IntoRawFd;
+use std::env;
+#[cfg(windows)]
+use std::fs::{File, OpenOptions};
+#[cfg(unix)]
+use std::os::unix::io::{AsRawFd, IntoRawFd, RawFd};
 #[cfg(unix)]
 use std::os::unix::net::UnixStream;
+#[cfg(unix)]
+use std::path::{Path, PathBuf};
 #[cfg(windows)]
 use uds_windows::UnixStream;
 
 use crate::error::*;
+#[cfg(feature = "tls")]
+use crate::tls::{TlsClientConfig, TlsStream};
 use chainerror::*;
 
 pub enum VarlinkStream {
     TCP(TcpStream),
     UNIX(UnixStream),
+    /// A Windows named pipe, connected for a `unix:\\.\pipe\<name>` address.
+    #[cfg(windows)]
+    PIPE(File),
+    /// A TLS session, connected for a `tcps:` address.
+    #[cfg(feature = "tls")]
+    TLS(TlsStream),
+}
+
+/// The transport of a [VarlinkStream](enum.VarlinkStream.html), see
+/// [Connection::transport](../struct.Connection.html#method.transport).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transport {
+    /// A TCP connection, also with TLS for a `tcps:` address.
+    Tcp,
+    /// A unix socket, also the socket pair of an activated service or a bridge, or a Windows
+    /// named pipe.
+    Unix,
+}
+
+/// The prefix of the paths of Windows named pipes.
+#[cfg(windows)]
+const PIPE_PREFIX: &str = r"\\.\pipe\";
+
+/// How long to wait for a free instance of a Windows named pipe, before retrying to open it.
+#[cfg(windows)]
+const PIPE_BUSY_TIMEOUT_MS: u32 = 5000;
+
+/// Open the Windows named pipe `path`.
+///
+/// While all instances of the pipe are connected to other clients, opening it fails with
+/// `ERROR_PIPE_BUSY`. Then `WaitNamedPipeW()` waits for a free instance and the open is
+/// retried, as another client may have taken the instance meanwhile. No free instance within
+/// `PIPE_BUSY_TIMEOUT_MS` fails with `io::ErrorKind::TimedOut`.
+#[cfg(windows)]
+fn open_pipe(path: &str) -> Result<File> {
+    use std::ffi::OsStr;
+    use std::os::windows::ffi::OsStrExt;
+    use winapi::shared::winerror::ERROR_PIPE_BUSY;
+    use winapi::um::winbase::WaitNamedPipeW;
+
+    let wide: Vec<u16> = OsStr::new(path).encode_wide().chain(Some(0)).collect();
+    loop {
+        match OpenOptions::new().read(true).write(true).open(path) {
+            Ok(pipe) => return Ok(pipe),
+            Err(ref e) if e.raw_os_error() == Some(ERROR_PIPE_BUSY as i32) => {
+                if unsafe { WaitNamedPipeW(wide.as_ptr(), PIPE_BUSY_TIMEOUT_MS) } == 0 {
+                    return Err(into_cherr!(io::Error::last_os_error()));
+                }
+            }
+            Err(e) => return Err(into_cherr!(e)),
+        }
+    }
+}
+
+/// The last lines written to stderr by a service started with `varlink_exec()`.
+pub struct StderrTail {
+    shared: Arc<(Mutex<TailLines>, Condvar)>,
+}
+
+struct TailLines {
+    lines: VecDeque<String>,
+    closed: bool,
+}
+
+impl StderrTail {
+    const MAX_LINES: usize = 20;
+
+    /// Read `stderr` in a background thread, keeping the last lines.
+    pub fn capture(stderr: ChildStderr) -> Self {
+        let shared = Arc::new((
+            Mutex::new(TailLines {
+                lines: VecDeque::new(),
+                closed: false,
+            }),
+            Condvar::new(),
+        ));
+        let thread_shared = shared.clone();
+
+        thread::spawn(move || {
+            let (ref tail, ref cvar) = *thread_shared;
+            let mut reader = BufReader::new(stderr);
+            let mut buf = Vec::new();
+            loop {
+                buf.clear();
+                match reader.read_until(b'\n', &mut buf) {
+                    Ok(0) | Err(_) => break,
+                    Ok(_) => {
+                        let line = String::from_utf8_lossy(&buf).trim_end().to_string();
+                        let mut tail = tail.lock().unwrap();
+                        if tail.lines.len() == Self::MAX_LINES {
+                            tail.lines.pop_front();
+                        }
+                        tail.lines.push_back(line);
+                    }
+                }
+            }
+            tail.lock().unwrap().closed = true;
+            cvar.notify_all();
+        });
+
+        StderrTail { shared }
+    }
+
+    /// The last lines of stderr.
+    ///
+    /// Waits up to `timeout` for the service to close stderr, so the output of a service,
+    /// which is just exiting, is complete.
+    pub fn last_lines(&self, timeout: Duration) -> String {
+        let (ref tail, ref cvar) = *self.shared;
+        let deadline = Instant::now() + timeout;
+        let mut tail = tail.lock().unwrap();
+        while !tail.closed {
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            tail = cvar.wait_timeout(tail, deadline - now).unwrap().0;
+        }
+        tail.lines.iter().cloned().collect::<Vec<_>>().join("\n")
+    }
 }
 
 #[cfg(windows)]
 pub fn varlink_exec<S: ?Sized + AsRef<str>>(
     _address: &S,
+    _capture_stderr: bool,
 ) -> Result<(Child, String, Option<TempDir>)> {
     return Err(into_cherr!(ErrorKind::MethodNotImplemented(
         "varlink_exec".into()
     )));
 }
 
+/// Create the temporary directory for the socket of `varlink_exec()`.
+///
+/// The directories in `parents` are tried in order, the error of the last one is returned
+/// as the cause of `ErrorKind::ExecTempDir`.
+#[cfg(unix)]
+pub(crate) fn exec_tempdir<P: AsRef<Path>>(executable: &str, parents: &[P]) -> Result<TempDir> {
+    let mut last_err = None;
+
+    for parent in parents {
+        match tempfile::tempdir_in(parent) {
+            Ok(dir) => return Ok(dir),
+            Err(e) => last_err = Some(e),
+        }
+    }
+
+    let kind = ErrorKind::ExecTempDir(executable.into());
+    Err(match last_err {
+        Some(e) => cherr!(e, kind),
+        None => into_cherr!(kind),
+    })
+}
+
 #[cfg(unix)]
 pub fn varlink_exec<S: ?Sized + AsRef<str>>(
     address: &S,
+    capture_stderr: bool,
 ) -> Result<(Child, String, Option<TempDir>)> {
-    use std::env;
     use std::os::unix::process::CommandExt;
-    use std::process::Command;
-    use tempfile::tempdir;
+    use std::process::{Command, Stdio};
 
-    let executable = String::from("exec ") + address.as_ref();
+    // The environment must not be changed between fork and exec, so the shell sets `LISTEN_PID`.
+    let executable = String::from("export LISTEN_PID=$$; exec ") + address.as_ref();
 
     use unix_socket::UnixListener;
 
-    let dir = tempdir().map_err(minto_cherr!())?;
+    // `env::temp_dir()` honors `$TMPDIR`, fall back to `$XDG_RUNTIME_DIR` and `/tmp`, if the
+    // directory is not writable.
+    let mut parents = vec![env::temp_dir()];
+    parents.extend(env::var_os("XDG_RUNTIME_DIR").map(PathBuf::from));
+    parents.push(PathBuf::from("/tmp"));
+    parents.dedup();
+    let dir = exec_tempdir(address.as_ref(), &parents)?;
     let file_path = dir.path().join("varlink-socket");
 
     let listener = UnixListener::bind(file_path.clone()).map_err(minto_cherr!())?;
     let fd = listener.into_raw_fd();
 
-    let child = Command::new("sh")
+    let mut command = Command::new("sh");
+    if capture_stderr {
+        command.stderr(Stdio::piped());
+    }
+
+    let child = command
         .arg("-c")
         .arg(executable)
-        .before_exec({
-            let file_path = file_path.clone();
-            move || {
-                unsafe {
-                    if fd != 3 {
-                        close(3);
-                        dup2(fd, 3);
-                    }
-                    env::set_var("VARLINK_ADDRESS", format!("unix:{}", file_path.display()));
-                    env::set_var("LISTEN_FDS", "1");
-                    env::set_var("LISTEN_FDNAMES", "varlink");
-                    env::set_var("LISTEN_PID", format!("{}", getpid()));
+        .env("VARLINK_ADDRESS", format!("unix:{}", file_path.display()))
+        .env("LISTEN_FDS", "1")
+        .env("LISTEN_FDNAMES", "varlink")
+        .before_exec(move || {
+            unsafe {
+                if fd != 3 {
+                    close(3);
+                    dup2(fd, 3);
+                } else {
+                    fcntl(3, F_SETFD, fcntl(3, F_GETFD) & !FD_CLOEXEC);
                 }
-                Ok(())
             }
+            Ok(())
         })
         .spawn()
-        .map_err(minto_cherr!())?;
-    Ok((child, format!("unix:{}", file_path.display()), Some(dir)))
+        .map_err(minto_cherr!());
+
+    // Only the service listens, so connecting fails after it exited.
+    unsafe {
+        close(fd);
+    }
+
+    Ok((child?, format!("unix:{}", file_path.display()), Some(dir)))
 }
 
 #[cfg(windows)]
@@ -126,8 +296,170 @@ pub fn varlink_bridge<S: ?Sized + AsRef<str>>(address: &S) -> Result<(Child, Var
     Ok((child, VarlinkStream::UNIX(stream0)))
 }
 
+/// Set `SO_LINGER` on the socket `fd`.
+///
+/// With `Some(timeout)`, closing the socket blocks until the queued data is sent or `timeout`
+/// has passed, a zero `timeout` discards the queued data and resets the connection. With
+/// `None`, closing returns immediately and the queued data is sent in the background.
+///
+/// `SO_LINGER` takes whole seconds, so a fraction of a second is rounded up. Otherwise a
+/// `timeout` below one second would become zero and reset the connection.
+#[cfg(unix)]
+pub(crate) fn set_linger(fd: RawFd, linger: Option<Duration>) -> Result<()> {
+    let secs = |d: Duration| d.as_secs() + (d.subsec_nanos() > 0) as u64;
+    let linger = libc::linger {
+        l_onoff: linger.is_some() as libc::c_int,
+        l_linger: linger.map_or(0, |d| {
+            secs(d).min(libc::c_int::max_value() as u64) as libc::c_int
+        }),
+    };
+
+    let ret = unsafe {
+        libc::setsockopt(
+            fd,
+            libc::SOL_SOCKET,
+            libc::SO_LINGER,
+            &linger as *const libc::linger as *const libc::c_void,
+            ::std::mem::size_of::<libc::linger>() as libc::socklen_t,
+        )
+    };
+
+    if ret != 0 {
+        return Err(into_cherr!(::std::io::Error::last_os_error()));
+    }
+    Ok(())
+}
+
+/// Send `fds` on the unix socket `fd` as `SCM_RIGHTS` ancillary data of a single `\0` byte.
+#[cfg(unix)]
+pub(crate) fn send_fds(fd: RawFd, fds: &[RawFd]) -> Result<()> {
+    let mut byte = [0u8];
+    let mut iov = libc::iovec {
+        iov_base: byte.as_mut_ptr() as *mut libc::c_void,
+        iov_len: byte.len(),
+    };
+    let payload = ::std::mem::size_of_val(fds);
+    let space = unsafe { libc::CMSG_SPACE(payload as libc::c_uint) } as usize;
+    // u64 for the alignment of the `cmsghdr`
+    let mut control = vec![0u64; (space + 7) / 8];
+
+    let mut msg: libc::msghdr = unsafe { ::std::mem::zeroed() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+    if !fds.is_empty() {
+        msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+        msg.msg_controllen = space as _;
+        unsafe {
+            let cmsg = libc::CMSG_FIRSTHDR(&msg);
+            (*cmsg).cmsg_level = libc::SOL_SOCKET;
+            (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+            (*cmsg).cmsg_len = libc::CMSG_LEN(payload as libc::c_uint) as _;
+            ::std::ptr::copy_nonoverlapping(
+                fds.as_ptr() as *const u8,
+                libc::CMSG_DATA(cmsg),
+                payload,
+            );
+        }
+    }
+
+    if unsafe { libc::sendmsg(fd, &msg, 0) } < 0 {
+        return Err(into_cherr!(::std::io::Error::last_os_error()));
+    }
+    Ok(())
+}
+
+/// Receive up to `max` file descriptors sent with `send_fds()` on the unix socket `fd`.
+///
+/// The received file descriptors are owned by the caller and have `FD_CLOEXEC` set, with
+/// `MSG_CMSG_CLOEXEC`, so they never leak into a child spawned by another thread meanwhile.
+/// macOS lacks the flag and sets `FD_CLOEXEC` after the receive.
+#[cfg(unix)]
+pub(crate) fn recv_fds(fd: RawFd, max: usize) -> Result<Vec<RawFd>> {
+    let mut byte = [0u8];
+    let mut iov = libc::iovec {
+        iov_base: byte.as_mut_ptr() as *mut libc::c_void,
+        iov_len: byte.len(),
+    };
+    let payload = max * ::std::mem::size_of::<RawFd>();
+    let space = unsafe { libc::CMSG_SPACE(payload as libc::c_uint) } as usize;
+    let mut control = vec![0u64; (space + 7) / 8];
+
+    let mut msg: libc::msghdr = unsafe { ::std::mem::zeroed() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+    msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+    msg.msg_controllen = space as _;
+
+    #[cfg(not(any(target_os = "macos", target_os = "ios")))]
+    let flags = libc::MSG_CMSG_CLOEXEC;
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    let flags = 0;
+
+    let ret = unsafe { libc::recvmsg(fd, &mut msg, flags) };
+    if ret < 0 {
+        return Err(into_cherr!(::std::io::Error::last_os_error()));
+    }
+    if ret == 0 {
+        return Err(into_cherr!(ErrorKind::ConnectionClosed));
+    }
+
+    let mut fds = Vec::new();
+    unsafe {
+        let mut cmsg = libc::CMSG_FIRSTHDR(&msg);
+        while !cmsg.is_null() {
+            if (*cmsg).cmsg_level == libc::SOL_SOCKET && (*cmsg).cmsg_type == libc::SCM_RIGHTS {
+                let data = libc::CMSG_DATA(cmsg) as *const RawFd;
+                let len = (*cmsg).cmsg_len as usize - libc::CMSG_LEN(0) as usize;
+                for i in 0..len / ::std::mem::size_of::<RawFd>() {
+                    let fd = ::std::ptr::read_unaligned(data.add(i));
+                    #[cfg(any(target_os = "macos", target_os = "ios"))]
+                    fcntl(fd, F_SETFD, fcntl(fd, F_GETFD) | FD_CLOEXEC);
+                    fds.push(fd);
+                }
+            }
+            cmsg = libc::CMSG_NXTHDR(&msg, cmsg);
+        }
+    }
+
+    // CMSG_SPACE() pads the buffer, so it may hold more than `max` without truncation
+    if msg.msg_flags & libc::MSG_CTRUNC != 0 || fds.len() > max {
+        for fd in fds {
+            unsafe { close(fd) };
+        }
+        return Err(into_cherr!(::std::io::Error::new(
+            ::std::io::ErrorKind::InvalidData,
+            format!("more than {} file descriptors received", max),
+        )));
+    }
+    Ok(fds)
+}
+
+/// Split the `host:port` part of a `tcp:` address into the host and the port.
+///
+/// An IPv6 address has to be put in brackets, like `[::1]:12345`, the brackets are removed
+/// from the returned host.
+pub(crate) fn tcp_host_port(address: &str) -> Result<(&str, u16)> {
+    let i = address
+        .rfind(':')
+        .ok_or_else(|| into_cherr!(ErrorKind::InvalidAddress))?;
+    let (host, port) = (&address[..i], &address[i + 1..]);
+    let port = port
+        .parse::<u16>()
+        .map_err(|_| into_cherr!(ErrorKind::InvalidAddress))?;
+
+    if host.starts_with('[') && host.ends_with(']') {
+        let host = &host[1..host.len() - 1];
+        if host.contains(':') {
+            return Ok((host, port));
+        }
+    } else if !host.is_empty() && !host.contains(|c| c == ':' || c == '[' || c == ']') {
+        return Ok((host, port));
+    }
+    Err(into_cherr!(ErrorKind::InvalidAddress))
+}
+
 #[cfg(any(target_os = "linux", target_os = "android"))]
-fn get_abstract_unixstream(addr: &str) -> Result<UnixStream> {
+pub(crate) fn get_abstract_unixstream(addr: &str) -> Result<UnixStream> {
     // FIXME: abstract unix domains sockets still not in std
     // FIXME: https://github.com/rust-lang/rust/issues/14194
     use std::os::unix::io::FromRawFd;
@@ -142,9 +474,44 @@ fn get_abstract_unixstream(addr: &str) -> Result<UnixStream> {
     }
 }
 
+/// The error for a `unix:@` address on a platform without abstract namespace sockets.
 #[cfg(not(any(target_os = "linux", target_os = "android")))]
-fn get_abstract_unixstream(_addr: &str) -> Result<UnixStream> {
-    Err(into_cherr!(ErrorKind::InvalidAddress))
+pub(crate) fn abstract_socket_unsupported() -> Error {
+    into_cherr!(io::Error::new(
+        io::ErrorKind::Other,
+        "abstract sockets not supported on this platform"
+    ))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+pub(crate) fn get_abstract_unixstream(_addr: &str) -> Result<UnixStream> {
+    Err(abstract_socket_unsupported())
+}
+
+/// Connect to the `host:port` `address`, trying its socket addresses until `deadline`.
+fn tcp_connect_deadline(
+    address: &str,
+    deadline: Instant,
+    timed_out: &dyn Fn() -> Error,
+) -> Result<TcpStream> {
+    let mut last_err = None;
+    for addr in tcp_host_port(address)?
+        .to_socket_addrs()
+        .map_err(minto_cherr!())?
+    {
+        let now = Instant::now();
+        if now >= deadline {
+            break;
+        }
+        match TcpStream::connect_timeout(&addr, deadline - now) {
+            Ok(s) => return Ok(s),
+            Err(e) => last_err = Some(e),
+        }
+    }
+    Err(match last_err {
+        Some(e) => into_cherr!(e),
+        None => timed_out(),
+    })
 }
 
 impl<'a> VarlinkStream {
@@ -154,9 +521,21 @@ impl<'a> VarlinkStream {
 
         if new_address.starts_with("tcp:") {
             Ok((
-                VarlinkStream::TCP(TcpStream::connect(&new_address[4..]).map_err(minto_cherr!())?),
+                VarlinkStream::TCP(
+                    TcpStream::connect(tcp_host_port(&new_address[4..])?)
+                        .map_err(minto_cherr!())?,
+                ),
                 new_address,
             ))
+        } else if new_address.starts_with("tcps:") {
+            #[cfg(feature = "tls")]
+            {
+                Self::connect_tls(address, &TlsClientConfig::new()?)
+            }
+            #[cfg(not(feature = "tls"))]
+            {
+                Err(into_cherr!(ErrorKind::InvalidAddress))
+            }
         } else if new_address.starts_with("unix:") {
             let mut addr = String::from(new_address[5..].split(';').next().unwrap());
             if addr.starts_with('@') {
@@ -164,6 +543,12 @@ impl<'a> VarlinkStream {
                 return get_abstract_unixstream(&addr)
                     .and_then(|v| Ok((VarlinkStream::UNIX(v), new_address)));
             }
+            #[cfg(windows)]
+            {
+                if addr.starts_with(PIPE_PREFIX) {
+                    return Ok((VarlinkStream::PIPE(open_pipe(&addr)?), new_address));
+                }
+            }
             Ok((
                 VarlinkStream::UNIX(UnixStream::connect(addr).map_err(minto_cherr!())?),
                 new_address,
@@ -173,6 +558,121 @@ impl<'a> VarlinkStream {
         }
     }
 
+    /// Connect to a `tcps:` address, verifying the service with `config`.
+    ///
+    /// [connect](#method.connect) verifies a `tcps:` service with the root certificates of
+    /// the system.
+    #[cfg(feature = "tls")]
+    pub fn connect_tls<S: ?Sized + AsRef<str>>(
+        address: &S,
+        config: &TlsClientConfig,
+    ) -> Result<(Self, String)> {
+        let address = address.as_ref();
+        if !address.starts_with("tcps:") {
+            return Err(into_cherr!(ErrorKind::InvalidAddress));
+        }
+        let tcp = TcpStream::connect(tcp_host_port(&address[5..])?).map_err(minto_cherr!())?;
+        Ok((
+            VarlinkStream::TLS(config.connect(&address[5..], tcp)?),
+            address.into(),
+        ))
+    }
+
+    /// Like [connect](#method.connect), but fail with `ErrorKind::Io(io::ErrorKind::TimedOut)`,
+    /// if the connection is not established within `timeout`.
+    ///
+    /// A `tcp:` address is connected with `TcpStream::connect_timeout()`. For a `tcps:`
+    /// address, the timeout bounds both the TCP connect and the TLS handshake. A `unix:` socket,
+    /// which does not exist yet or does not accept connections yet, e.g. because the service
+    /// is still starting, is retried until the deadline.
+    ///
+    /// An `exec:` address fails with `ErrorKind::InvalidAddress`, as waiting for the started
+    /// service cannot be bounded. Use
+    /// [Connection::with_activate](../struct.Connection.html#method.with_activate) for it.
+    pub fn connect_timeout<S: ?Sized + AsRef<str>>(
+        address: &S,
+        timeout: Duration,
+    ) -> Result<(Self, String)> {
+        let address = address.as_ref();
+        let deadline = Instant::now() + timeout;
+        let timed_out = || -> Error {
+            into_cherr!(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("could not connect to `{}` within {:?}", address, timeout)
+            ))
+        };
+
+        if address.starts_with("tcp:") {
+            let tcp = tcp_connect_deadline(&address[4..], deadline, &timed_out)?;
+            return Ok((VarlinkStream::TCP(tcp), address.into()));
+        }
+
+        #[cfg(feature = "tls")]
+        {
+            if address.starts_with("tcps:") {
+                let tcp = tcp_connect_deadline(&address[5..], deadline, &timed_out)?;
+                // The handshake reads and writes with the remaining time as socket timeouts.
+                let now = Instant::now();
+                if now >= deadline {
+                    return Err(timed_out());
+                }
+                tcp.set_read_timeout(Some(deadline - now))
+                    .map_err(minto_cherr!())?;
+                tcp.set_write_timeout(Some(deadline - now))
+                    .map_err(minto_cherr!())?;
+                let tls = match TlsClientConfig::new()?.connect(&address[5..], tcp) {
+                    Err(ref e) if *e.kind() == ErrorKind::Io(io::ErrorKind::WouldBlock) => {
+                        return Err(timed_out());
+                    }
+                    r => r?,
+                };
+                tls.socket()
+                    .set_read_timeout(None)
+                    .map_err(minto_cherr!())?;
+                tls.socket()
+                    .set_write_timeout(None)
+                    .map_err(minto_cherr!())?;
+                return Ok((VarlinkStream::TLS(tls), address.into()));
+            }
+        }
+
+        if address.starts_with("exec:") {
+            return Err(into_cherr!(ErrorKind::InvalidAddress));
+        }
+
+        if !address.starts_with("unix:") {
+            return Self::connect(address);
+        }
+
+        loop {
+            match Self::connect(address) {
+                Ok(r) => return Ok(r),
+                Err(e) => match *e.kind() {
+                    ErrorKind::Io(io::ErrorKind::NotFound)
+                    | ErrorKind::Io(io::ErrorKind::ConnectionRefused) => {}
+                    _ => return Err(e),
+                },
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(timed_out());
+            }
+            thread::sleep(::std::cmp::min(deadline - now, Duration::from_millis(10)));
+        }
+    }
+
+    /// The transport of the stream.
+    pub fn transport(&self) -> Transport {
+        match *self {
+            VarlinkStream::TCP(_) => Transport::Tcp,
+            VarlinkStream::UNIX(_) => Transport::Unix,
+            #[cfg(windows)]
+            VarlinkStream::PIPE(_) => Transport::Unix,
+            #[cfg(feature = "tls")]
+            VarlinkStream::TLS(_) => Transport::Tcp,
+        }
+    }
+
     pub fn split(&mut self) -> Result<(Box<Read + Send + Sync>, Box<Write + Send + Sync>)> {
         match *self {
             VarlinkStream::TCP(ref mut s) => Ok((
@@ -183,6 +683,16 @@ impl<'a> VarlinkStream {
                 Box::new(s.try_clone().map_err(minto_cherr!())?),
                 Box::new(s.try_clone().map_err(minto_cherr!())?),
             )),
+            #[cfg(windows)]
+            VarlinkStream::PIPE(ref mut s) => Ok((
+                Box::new(s.try_clone().map_err(minto_cherr!())?),
+                Box::new(s.try_clone().map_err(minto_cherr!())?),
+            )),
+            #[cfg(feature = "tls")]
+            VarlinkStream::TLS(ref mut s) => Ok((
+                Box::new(s.try_clone().map_err(minto_cherr!())?),
+                Box::new(s.try_clone().map_err(minto_cherr!())?),
+            )),
         }
     }
 
@@ -190,6 +700,11 @@ impl<'a> VarlinkStream {
         match *self {
             VarlinkStream::TCP(ref mut s) => s.shutdown(Shutdown::Both).map_err(minto_cherr!())?,
             VarlinkStream::UNIX(ref mut s) => s.shutdown(Shutdown::Both).map_err(minto_cherr!())?,
+            // A pipe has no shutdown, it is closed with the last handle.
+            #[cfg(windows)]
+            VarlinkStream::PIPE(_) => {}
+            #[cfg(feature = "tls")]
+            VarlinkStream::TLS(ref mut s) => s.shutdown().map_err(minto_cherr!())?,
         }
         Ok(())
     }
@@ -198,9 +713,126 @@ impl<'a> VarlinkStream {
         match *self {
             VarlinkStream::TCP(ref l) => l.set_nonblocking(b).map_err(minto_cherr!())?,
             VarlinkStream::UNIX(ref l) => l.set_nonblocking(b).map_err(minto_cherr!())?,
+            #[cfg(feature = "tls")]
+            VarlinkStream::TLS(ref l) => l.socket().set_nonblocking(b).map_err(minto_cherr!())?,
+            #[cfg(windows)]
+            VarlinkStream::PIPE(_) if !b => {}
+            #[cfg(windows)]
+            VarlinkStream::PIPE(_) => Err(into_cherr!(ErrorKind::MethodNotImplemented(
+                "set_nonblocking".into()
+            )))?,
         }
         Ok(())
     }
+
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> Result<()> {
+        match *self {
+            VarlinkStream::TCP(ref l) => l.set_read_timeout(timeout).map_err(minto_cherr!())?,
+            VarlinkStream::UNIX(ref l) => l.set_read_timeout(timeout).map_err(minto_cherr!())?,
+            #[cfg(feature = "tls")]
+            VarlinkStream::TLS(ref l) => l
+                .socket()
+                .set_read_timeout(timeout)
+                .map_err(minto_cherr!())?,
+            #[cfg(windows)]
+            VarlinkStream::PIPE(_) if timeout.is_none() => {}
+            #[cfg(windows)]
+            VarlinkStream::PIPE(_) => Err(into_cherr!(ErrorKind::MethodNotImplemented(
+                "set_read_timeout".into()
+            )))?,
+        }
+        Ok(())
+    }
+
+    pub fn set_write_timeout(&self, timeout: Option<Duration>) -> Result<()> {
+        match *self {
+            VarlinkStream::TCP(ref l) => l.set_write_timeout(timeout).map_err(minto_cherr!())?,
+            VarlinkStream::UNIX(ref l) => l.set_write_timeout(timeout).map_err(minto_cherr!())?,
+            #[cfg(feature = "tls")]
+            VarlinkStream::TLS(ref l) => l
+                .socket()
+                .set_write_timeout(timeout)
+                .map_err(minto_cherr!())?,
+            #[cfg(windows)]
+            VarlinkStream::PIPE(_) if timeout.is_none() => {}
+            #[cfg(windows)]
+            VarlinkStream::PIPE(_) => Err(into_cherr!(ErrorKind::MethodNotImplemented(
+                "set_write_timeout".into()
+            )))?,
+        }
+        Ok(())
+    }
+
+    /// Set `SO_LINGER`, whether closing the connection waits for the queued data to be sent.
+    ///
+    /// `Some(timeout)` waits up to `timeout` (in whole seconds, rounded up),
+    /// `Some(Duration::from_secs(0))` discards the queued data and `None` restores the default
+    /// of sending it in the background.
+    #[cfg(unix)]
+    pub fn set_linger(&self, linger: Option<Duration>) -> Result<()> {
+        set_linger(self.as_raw_fd(), linger)
+    }
+
+    #[cfg(windows)]
+    pub fn set_linger(&self, _linger: Option<Duration>) -> Result<()> {
+        Err(into_cherr!(ErrorKind::MethodNotImplemented(
+            "set_linger".into()
+        )))
+    }
+    /// Send the file descriptors `fds` to the peer of a unix socket, as `SCM_RIGHTS` ancillary
+    /// data of a single `\0` byte, e.g. on a connection upgraded to a custom protocol.
+    ///
+    /// The peer receives them with [recv_fds](#method.recv_fds), which also consumes the
+    /// `\0` byte. Data read before by a buffered reader must not include this byte, or the file
+    /// descriptors are lost, so the protocol has to let the peer wait for them.
+    ///
+    /// Other transports return `ErrorKind::MethodNotImplemented`.
+    #[cfg(unix)]
+    pub fn send_fds(&self, fds: &[RawFd]) -> Result<()> {
+        match *self {
+            VarlinkStream::UNIX(ref s) => send_fds(s.as_raw_fd(), fds),
+            _ => Err(into_cherr!(ErrorKind::MethodNotImplemented(
+                "send_fds".into()
+            ))),
+        }
+    }
+
+    /// Receive up to `max` file descriptors sent with [send_fds](#method.send_fds).
+    ///
+    /// The returned file descriptors are owned by the caller. More than `max` file
+    /// descriptors fail with an `ErrorKind::Io` error, the surplus ones are closed.
+    #[cfg(unix)]
+    pub fn recv_fds(&self, max: usize) -> Result<Vec<RawFd>> {
+        match *self {
+            VarlinkStream::UNIX(ref s) => recv_fds(s.as_raw_fd(), max),
+            _ => Err(into_cherr!(ErrorKind::MethodNotImplemented(
+                "recv_fds".into()
+            ))),
+        }
+    }
+}
+
+#[cfg(unix)]
+impl AsRawFd for VarlinkStream {
+    fn as_raw_fd(&self) -> RawFd {
+        match *self {
+            VarlinkStream::TCP(ref s) => s.as_raw_fd(),
+            VarlinkStream::UNIX(ref s) => s.as_raw_fd(),
+            #[cfg(feature = "tls")]
+            VarlinkStream::TLS(ref s) => s.socket().as_raw_fd(),
+        }
+    }
+}
+
+/// Give up the ownership of the socket without shutting it down, e.g. to pass it to
+/// another process.
+#[cfg(unix)]
+impl IntoRawFd for VarlinkStream {
+    fn into_raw_fd(self) -> RawFd {
+        let fd = self.as_raw_fd();
+        ::std::mem::forget(self);
+        fd
+    }
 }
 
 impl Drop for VarlinkStream {
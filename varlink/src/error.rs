@@ -10,8 +10,11 @@ pub enum ErrorKind {
     InvalidParameter(String),
     MethodNotFound(String),
     MethodNotImplemented(String),
+    PermissionDenied,
+    ExpectedMore,
     VarlinkErrorReply(crate::Reply),
     CallContinuesMismatch,
+    CallRepliedAlready,
     MethodCalledAlready,
     ConnectionBusy,
     IteratorOldReply,
@@ -19,6 +22,12 @@ pub enum ErrorKind {
     Timeout,
     ConnectionClosed,
     InvalidAddress,
+    ServiceFailed(String),
+    NestingTooDeep(usize),
+    ExecTempDir(String),
+    SocketDirMissing(String),
+    Tls(String),
+    InvalidInterfaceDescription(String),
     Generic,
 }
 
@@ -34,11 +43,17 @@ impl ::std::fmt::Display for ErrorKind {
             ErrorKind::InvalidParameter(v) => write!(f, "Invalid parameter: '{}'", v),
             ErrorKind::MethodNotFound(v) => write!(f, "Method not found: '{}'", v),
             ErrorKind::MethodNotImplemented(v) => write!(f, "Method not implemented: '{}'", v),
+            ErrorKind::PermissionDenied => write!(f, "Permission denied"),
+            ErrorKind::ExpectedMore => write!(f, "Method expects to be called with 'more'"),
             ErrorKind::VarlinkErrorReply(v) => write!(f, "Varlink error reply: '{:#?}'", v),
             ErrorKind::CallContinuesMismatch => write!(
                 f,
                 "Call::reply() called with continues, but without more in the request"
             ),
+            ErrorKind::CallRepliedAlready => write!(
+                f,
+                "Call::reply() called again after the last reply, without set_continues(true)"
+            ),
             ErrorKind::MethodCalledAlready => write!(f, "Varlink: method called already"),
             ErrorKind::ConnectionBusy => write!(f, "Varlink: connection busy with other method"),
             ErrorKind::IteratorOldReply => write!(f, "Varlink: Iterator called on old reply"),
@@ -46,6 +61,16 @@ impl ::std::fmt::Display for ErrorKind {
             ErrorKind::Timeout => write!(f, "Timeout Error"),
             ErrorKind::ConnectionClosed => write!(f, "Connection Closed"),
             ErrorKind::InvalidAddress => write!(f, "Invalid varlink address URI"),
+            ErrorKind::ServiceFailed(v) => write!(f, "Varlink service failed with: '{}'", v),
+            ErrorKind::NestingTooDeep(v) => write!(f, "JSON message nested deeper than {}", v),
+            ErrorKind::ExecTempDir(v) => {
+                write!(f, "could not create temp socket dir for exec: {}", v)
+            }
+            ErrorKind::SocketDirMissing(v) => write!(f, "parent directory does not exist: {}", v),
+            ErrorKind::Tls(v) => write!(f, "TLS error: {}", v),
+            ErrorKind::InvalidInterfaceDescription(v) => {
+                write!(f, "invalid interface description: {}", v)
+            }
             ErrorKind::Generic => Ok(()),
         }
     }
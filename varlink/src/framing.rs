@@ -0,0 +1,159 @@
+//! The framing of varlink messages: JSON objects, each terminated by a single null byte
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+
+use serde::Serialize;
+use serde_json::Value;
+
+/// Read a null terminated message including the null byte into `buf`, replacing its content.
+///
+/// The allocation of `buf` is kept, so a reader passing the same buffer for every message
+/// allocates only for a message longer than all before, and not at all once the buffer has
+/// grown to the typical message size. A buffer grown beyond `MAX_REUSED_CAPACITY` (or
+/// `capacity`, if larger) by a single large message is released again. With a `capacity`,
+/// the buffer is allocated once for messages up to that size.
+///
+/// At the end of the stream `buf` is left empty. If the stream ends in the middle of a
+/// message, `io::ErrorKind::UnexpectedEof` is returned.
+pub(crate) fn read_message(
+    reader: &mut BufRead,
+    buf: &mut Vec<u8>,
+    capacity: Option<usize>,
+) -> io::Result<()> {
+    let limit = capacity.map_or(MAX_REUSED_CAPACITY, |c| c.max(MAX_REUSED_CAPACITY));
+    if buf.capacity() > limit {
+        *buf = Vec::new();
+    }
+    buf.clear();
+    if let Some(capacity) = capacity {
+        buf.reserve_exact(capacity);
+    }
+    reader.read_until(0, buf)?;
+    if buf.last().map_or(false, |&b| b != 0) {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "stream ended in the middle of a message",
+        ));
+    }
+    Ok(())
+}
+
+/// The largest buffer kept by `read_message()` for the next message.
+const MAX_REUSED_CAPACITY: usize = 1024 * 1024;
+
+/// Reads the null terminated varlink messages from a stream, e.g. the reading half of
+/// [VarlinkStream::split](struct.VarlinkStream.html#method.split).
+///
+/// # Examples
+///
+/// ```rust
+/// use varlink::MessageReader;
+///
+/// let mut reader = MessageReader::new(&b"{\"method\":\"org.example.ping.Ping\"}\0"[..]);
+/// let message = reader.read_message()?.unwrap();
+/// assert_eq!(message["method"], "org.example.ping.Ping");
+/// assert!(reader.read_message()?.is_none());
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub struct MessageReader<R> {
+    reader: BufReader<R>,
+    /// The buffer of `read_message()`, reused for all messages.
+    buf: Vec<u8>,
+}
+
+impl<R: Read> MessageReader<R> {
+    pub fn new(reader: R) -> Self {
+        MessageReader {
+            reader: BufReader::new(reader),
+            buf: Vec::new(),
+        }
+    }
+
+    /// Read the next message without the terminating null byte.
+    ///
+    /// Returns `None` at the end of the stream. A stream ending in the middle of a message
+    /// returns an `io::ErrorKind::UnexpectedEof` error.
+    pub fn read_frame(&mut self) -> io::Result<Option<Vec<u8>>> {
+        let mut buf = Vec::new();
+        read_message(&mut self.reader, &mut buf, None)?;
+        if buf.pop().is_none() {
+            return Ok(None);
+        }
+        Ok(Some(buf))
+    }
+
+    /// Read and parse the next message.
+    ///
+    /// Returns `None` at the end of the stream. A stream ending in the middle of a message
+    /// returns an `io::ErrorKind::UnexpectedEof` error, a message, which is not valid JSON,
+    /// an `io::ErrorKind::InvalidData` error.
+    ///
+    /// The message is read into a buffer kept by the reader, so reading many messages of
+    /// similar size does not allocate for the framing.
+    pub fn read_message(&mut self) -> io::Result<Option<Value>> {
+        read_message(&mut self.reader, &mut self.buf, None)?;
+        if self.buf.pop().is_none() {
+            return Ok(None);
+        }
+        // the frame is complete, so truncated JSON is invalid data, not an early EOF
+        let message = serde_json::from_slice(&self.buf)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Ok(Some(message))
+    }
+
+    /// Return the underlying reader. Data buffered, but not yet returned as a message, is
+    /// lost.
+    pub fn into_inner(self) -> R {
+        self.reader.into_inner()
+    }
+}
+
+/// Writes null terminated varlink messages to a stream, e.g. the writing half of
+/// [VarlinkStream::split](struct.VarlinkStream.html#method.split).
+///
+/// # Examples
+///
+/// ```rust
+/// use varlink::MessageWriter;
+///
+/// let mut writer = MessageWriter::new(Vec::new());
+/// writer.write_message(&serde_json::json!({"method": "org.example.ping.Ping"}))?;
+/// assert_eq!(writer.into_inner(), b"{\"method\":\"org.example.ping.Ping\"}\0");
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub struct MessageWriter<W> {
+    writer: W,
+}
+
+impl<W: Write> MessageWriter<W> {
+    pub fn new(writer: W) -> Self {
+        MessageWriter { writer }
+    }
+
+    /// Write `frame` followed by the terminating null byte and flush the stream.
+    ///
+    /// `frame` must not contain a null byte, else `io::ErrorKind::InvalidInput` is returned.
+    pub fn write_frame(&mut self, frame: &[u8]) -> io::Result<()> {
+        if frame.contains(&0) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "message contains a null byte",
+            ));
+        }
+        self.writer.write_all(frame)?;
+        self.writer.write_all(b"\0")?;
+        self.writer.flush()
+    }
+
+    /// Serialize `message` as JSON, write it followed by the terminating null byte and flush
+    /// the stream.
+    pub fn write_message<T: ?Sized + Serialize>(&mut self, message: &T) -> io::Result<()> {
+        let buf = serde_json::to_vec(message)?;
+        self.write_frame(&buf)
+    }
+
+    /// Return the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
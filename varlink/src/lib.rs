@@ -181,6 +181,8 @@
 //! Currently supported address URIs are:
 //!
 //! - TCP `tcp:127.0.0.1:12345` hostname/IP address and port
+//! - TLS over TCP `tcps:127.0.0.1:12345` (with the `tls` feature), the certificate and key
+//!   are set in [ListenConfig::tls](struct.ListenConfig.html#structfield.tls)
 //! - UNIX socket `unix:/run/org.example.ftl` optional access `;mode=0666` parameter
 //! - UNIX abstract namespace socket `unix:@org.example.ftl` (on Linux only)
 //!
@@ -222,13 +224,19 @@
 )]
 
 use std::borrow::Cow;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::convert::From;
-use std::io::{BufRead, BufReader, Read, Write};
+use std::io::{self, BufRead, BufReader, Read, Write};
 use std::marker::PhantomData;
+use std::net::SocketAddr;
 use std::ops::{Deref, DerefMut};
-use std::process::Child;
-use std::sync::{Arc, RwLock};
+#[cfg(unix)]
+use std::os::unix::io::RawFd;
+use std::path::PathBuf;
+use std::process::{Child, ExitStatus};
+use std::sync::{Arc, RwLock, TryLockError};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
 
 use chainerror::*;
 use serde::de::{self, DeserializeOwned};
@@ -237,18 +245,37 @@ use serde_derive::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use tempfile::TempDir;
 
-pub use crate::client::VarlinkStream;
-use crate::client::{varlink_bridge, varlink_exec};
+#[cfg(feature = "async")]
+pub use crate::async_connection::AsyncConnection;
+pub use crate::bridge::{bridge, bridge_connection};
+use crate::client::{varlink_bridge, varlink_exec, StderrTail};
+pub use crate::client::{Transport, VarlinkStream};
 pub use crate::error::{Error, ErrorKind, Result};
+use crate::framing::read_message;
+pub use crate::framing::{MessageReader, MessageWriter};
 pub use crate::server::Stream as ServerStream;
-pub use crate::server::{listen, Listener};
-
+pub use crate::server::{listen, listen_with_config, ListenConfig, Listener, ShutdownHandle};
+#[cfg(feature = "tls")]
+pub use crate::tls::{TlsClientConfig, TlsServerConfig, TlsStream};
+#[cfg(feature = "validate")]
+pub use crate::validate::{validate_interfaces, Mismatch};
+
+#[cfg(feature = "async")]
+mod async_connection;
+mod bridge;
 mod client;
 
 mod error;
+mod framing;
+pub mod org_varlink_service;
 mod server;
+mod stream;
 #[cfg(test)]
 mod test;
+#[cfg(feature = "tls")]
+mod tls;
+#[cfg(feature = "validate")]
+mod validate;
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Default)]
 pub struct ErrorInterfaceNotFound {
@@ -321,6 +348,12 @@ impl From<Reply> for ErrorKind {
                 },
                 _ => ErrorKind::MethodNotImplemented(String::new()),
             },
+            Reply {
+                error: Some(ref t), ..
+            } if t == "org.varlink.service.PermissionDenied" => ErrorKind::PermissionDenied,
+            Reply {
+                error: Some(ref t), ..
+            } if t == "org.varlink.service.ExpectedMore" => ErrorKind::ExpectedMore,
             _ => ErrorKind::VarlinkErrorReply(e),
         }
     }
@@ -334,6 +367,8 @@ impl ErrorKind {
                 "org.varlink.service.InterfaceNotFound" => true,
                 "org.varlink.service.MethodNotFound" => true,
                 "org.varlink.service.MethodNotImplemented" => true,
+                "org.varlink.service.PermissionDenied" => true,
+                "org.varlink.service.ExpectedMore" => true,
                 _ => false,
             },
             _ => false,
@@ -341,6 +376,44 @@ impl ErrorKind {
     }
 }
 
+/// Conversion of the error of a called method back into its error reply.
+///
+/// A proxy uses this to pass the error of the called service on to its own caller, see
+/// [MethodCall::pump](struct.MethodCall.html#method.pump). Implemented by the rust code
+/// generated by the varlink-rust-generator with the `reply_bridge` option.
+pub trait VarlinkErrorReply: Sized {
+    /// The error reply for `e`, or `None` if `e` was not received as an error reply, e.g.
+    /// for a closed connection.
+    fn into_reply(e: &ChainError<Self>) -> Option<Reply>;
+}
+
+impl VarlinkErrorReply for ErrorKind {
+    fn into_reply(e: &ChainError<Self>) -> Option<Reply> {
+        let (name, parameters) = match e.kind() {
+            ErrorKind::InterfaceNotFound(v) => (
+                "org.varlink.service.InterfaceNotFound",
+                json!({ "interface": v }),
+            ),
+            ErrorKind::InvalidParameter(v) => (
+                "org.varlink.service.InvalidParameter",
+                json!({ "parameter": v }),
+            ),
+            ErrorKind::MethodNotFound(v) => {
+                ("org.varlink.service.MethodNotFound", json!({ "method": v }))
+            }
+            ErrorKind::MethodNotImplemented(v) => (
+                "org.varlink.service.MethodNotImplemented",
+                json!({ "method": v }),
+            ),
+            ErrorKind::PermissionDenied => ("org.varlink.service.PermissionDenied", json!({})),
+            ErrorKind::ExpectedMore => ("org.varlink.service.ExpectedMore", json!({})),
+            ErrorKind::VarlinkErrorReply(reply) => return Some(reply.clone()),
+            _ => return None,
+        };
+        Some(Reply::error(name, Some(parameters)))
+    }
+}
+
 /// This trait has to be implemented by any varlink interface implementor.
 /// All methods are generated by the varlink-rust-generator, so you don't have to care
 /// about them.
@@ -377,10 +450,36 @@ impl<'a> Request<'a> {
             parameters,
         }
     }
+
+    /// Deserialize the parameters by reference, without cloning the `Value`.
+    ///
+    /// Returns `None`, if the request has no parameters.
+    pub fn parameters_as<'de, T: de::Deserialize<'de>>(&'de self) -> Option<serde_json::Result<T>> {
+        self.parameters.as_ref().map(T::deserialize)
+    }
 }
 
 pub type StringHashMap<T> = HashMap<String, T>;
 
+/// A dictionary, which iterates and serializes in the order of its keys.
+///
+/// Generated for the `[string]` dictionaries with the `ordered_maps` option of the
+/// varlink-rust-generator.
+pub type StringBTreeMap<T> = BTreeMap<String, T>;
+
+/// The error of the `build()` method of a builder generated with the `builders` option of the
+/// varlink-rust-generator: the required field with the contained name was not set.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct MissingField(pub &'static str);
+
+impl ::std::fmt::Display for MissingField {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        write!(f, "missing field `{}`", self.0)
+    }
+}
+
+impl ::std::error::Error for MissingField {}
+
 #[derive(Debug, PartialEq, Default, Clone)]
 pub struct StringHashSet {
     inner: HashSet<String>,
@@ -454,6 +553,7 @@ impl<'de> de::Deserialize<'de> for StringHashSet {
                 let mut values = StringHashSet::new();
 
                 while let Some(key) = visitor.next_key()? {
+                    visitor.next_value::<de::IgnoredAny>()?;
                     values.insert(key);
                 }
 
@@ -471,6 +571,17 @@ impl<'de> de::Deserialize<'de> for StringHashSet {
 /// See the [CallTrait](trait.CallTrait.html) to use with the first Call parameter
 pub trait VarlinkReply {}
 
+/// Conversion of a reply struct into the values of its fields
+///
+/// Implemented by the rust code generated by the varlink-rust-generator.
+/// A reply with a single field converts into the value of this field, a reply with more
+/// fields into a tuple of the values.
+/// See [MethodCall::values](struct.MethodCall.html#method.values)
+pub trait VarlinkReplyValues {
+    type Values;
+    fn into_values(self) -> Self::Values;
+}
+
 /// The structure of a varlink reply. Used to deserialize it into json.
 ///
 /// There should be no need to use this directly.
@@ -547,12 +658,57 @@ pub struct Call<'a> {
     pub writer: &'a mut Write,
     pub request: Option<&'a Request<'a>>,
     continues: bool,
+    /// The last reply to the request was sent.
+    replied: bool,
     upgraded: bool,
+    close: bool,
+    context: CallContext,
+}
+
+/// Credentials of the process on the other end of a unix socket connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PeerCredentials {
+    pub pid: i32,
+    pub uid: u32,
+    pub gid: u32,
+}
+
+/// Information about the connection and the request a method is called for.
+///
+/// The context is filled in by the serving loop and can be accessed by the interface
+/// methods with `call.context()`, e.g. for logging or access control.
+#[derive(Debug, Clone, Default)]
+pub struct CallContext {
+    /// The credentials of the peer, if connected via a unix socket (Linux only).
+    pub peer_credentials: Option<PeerCredentials>,
+    /// A number identifying the connection, unique for the lifetime of the server.
+    pub connection_id: u64,
+    /// The time the request was received.
+    pub received: Option<SystemTime>,
+    /// The interface the connection was upgraded to, if any.
+    pub upgraded_interface: Option<String>,
+    /// The socket of the connection, if it is a unix socket, to pass file descriptors with
+    /// [Call::send_fds](struct.Call.html#method.send_fds).
+    #[cfg(unix)]
+    pub unix_socket: Option<RawFd>,
 }
 
 /// CallTrait provides convenience methods for the `Call` struct, which is passed as
 /// the first argument to the interface methods.
 ///
+/// Every interface method can reply with the errors of the `org.varlink.service` interface,
+/// which every service implements: `reply_method_not_found()`,
+/// `reply_method_not_implemented()`, `reply_invalid_parameter()`, `reply_permission_denied()`
+/// and `reply_expected_more()`. The errors declared by an interface itself are added by the
+/// generated `VarlinkCallError` trait. If an interface declares an error with the same name,
+/// e.g. its own `PermissionDenied`, call it as `VarlinkCallError::reply_permission_denied(call)`.
+///
+/// The dispatcher generated by the varlink-rust-generator replies with
+/// `reply_method_not_found()` to an unknown method and with `reply_invalid_parameter()` to
+/// parameters, which do not match the method. The server replies with
+/// [Call::reply_interface_not_found](struct.Call.html#method.reply_interface_not_found) to a
+/// method of an interface it does not implement.
+///
 /// # Examples
 ///
 /// For an invalid parameter:
@@ -614,6 +770,9 @@ pub trait CallTrait {
 
     /// Set this to `true` to indicate, that more replies are following.
     ///
+    /// Without it, only one reply can be sent, a second `reply()` returns
+    /// `ErrorKind::CallRepliedAlready` instead of sending it to the client.
+    ///
     /// # Examples
     ///
     /// ```rust
@@ -645,14 +804,47 @@ pub trait CallTrait {
 
     fn to_upgraded(&mut self);
 
+    /// Request the connection to be closed after this call.
+    ///
+    /// The server finishes the current call with all its replies and then closes the
+    /// connection, without handling any further requests on it. The client gets
+    /// `ErrorKind::ConnectionClosed` for its next call and can reconnect, e.g. to a restarted
+    /// service.
+    fn request_connection_close(&mut self);
+
     /// True, if this request does not want a reply.
     fn is_oneway(&self) -> bool;
 
     /// True, if this request accepts more than one reply.
     fn wants_more(&self) -> bool;
 
+    /// True, if the connection is upgraded to a custom protocol after the reply.
+    fn is_upgrade(&self) -> bool;
+
+    /// True, if the call has ended with its last reply or with an error reply.
+    fn is_replied(&self) -> bool;
+
     fn get_request(&self) -> Option<&Request>;
 
+    /// The context of this call, like the peer credentials and the receive time.
+    fn context(&self) -> &CallContext;
+
+    /// The credentials of the peer, if connected via a unix socket (Linux only).
+    ///
+    /// # Examples
+    ///
+    /// ```rust,ignore
+    /// fn stop(&self, call: &mut dyn Call_Stop) -> varlink::Result<()> {
+    ///     match call.peer_credentials() {
+    ///         Some(ref c) if c.uid == 0 => call.reply(),
+    ///         _ => call.reply_permission_denied(),
+    ///     }
+    /// }
+    /// ```
+    fn peer_credentials(&self) -> Option<PeerCredentials> {
+        self.context().peer_credentials
+    }
+
     /// reply with the standard varlink `org.varlink.service.MethodNotFound` error
     fn reply_method_not_found(&mut self, method_name: String) -> Result<()> {
         self.reply_struct(Reply::error(
@@ -691,6 +883,16 @@ pub trait CallTrait {
             ),
         ))
     }
+
+    /// reply with the standard varlink `org.varlink.service.PermissionDenied` error
+    fn reply_permission_denied(&mut self) -> Result<()> {
+        self.reply_struct(Reply::error("org.varlink.service.PermissionDenied", None))
+    }
+
+    /// reply with the standard varlink `org.varlink.service.ExpectedMore` error
+    fn reply_expected_more(&mut self) -> Result<()> {
+        self.reply_struct(Reply::error("org.varlink.service.ExpectedMore", None))
+    }
 }
 
 impl<'a> CallTrait for Call<'a> {
@@ -698,7 +900,12 @@ impl<'a> CallTrait for Call<'a> {
         if self.continues && (!self.wants_more()) {
             return Err(into_cherr!(ErrorKind::CallContinuesMismatch));
         }
-        if self.continues {
+        if self.replied {
+            return Err(into_cherr!(ErrorKind::CallRepliedAlready));
+        }
+        // an error ends the call, even in the middle of a stream of replies
+        let error = reply.error.is_some();
+        if self.continues && !error {
             reply.continues = Some(true);
         }
         // serde_json::to_writer(&mut *self.writer, &reply)?;
@@ -708,6 +915,8 @@ impl<'a> CallTrait for Call<'a> {
             .write_all(b.as_bytes())
             .map_err(minto_cherr!())?;
         self.writer.flush().map_err(minto_cherr!())?;
+        // the raw protocol of an upgraded connection is not varlink
+        self.replied = (!self.continues || error) && self.request.is_some();
         Ok(())
     }
 
@@ -719,6 +928,10 @@ impl<'a> CallTrait for Call<'a> {
         self.upgraded = true;
     }
 
+    fn request_connection_close(&mut self) {
+        self.close = true;
+    }
+
     /// True, if this request does not want a reply.
     fn is_oneway(&self) -> bool {
         match self.request {
@@ -738,9 +951,29 @@ impl<'a> CallTrait for Call<'a> {
             _ => false,
         }
     }
+
+    /// True, if the connection is upgraded to a custom protocol after the reply.
+    fn is_upgrade(&self) -> bool {
+        match self.request {
+            Some(Request {
+                upgrade: Some(true),
+                ..
+            }) => true,
+            _ => false,
+        }
+    }
+
+    fn is_replied(&self) -> bool {
+        self.replied
+    }
+
     fn get_request(&self) -> Option<&Request> {
         self.request
     }
+
+    fn context(&self) -> &CallContext {
+        &self.context
+    }
 }
 
 impl<'a> Call<'a> {
@@ -749,7 +982,10 @@ impl<'a> Call<'a> {
             writer,
             request: Some(request),
             continues: false,
+            replied: false,
             upgraded: false,
+            close: false,
+            context: CallContext::default(),
         }
     }
     fn new_upgraded(writer: &'a mut Write) -> Self {
@@ -757,7 +993,10 @@ impl<'a> Call<'a> {
             writer,
             request: None,
             continues: false,
+            replied: false,
             upgraded: true,
+            close: false,
+            context: CallContext::default(),
         }
     }
 
@@ -774,6 +1013,44 @@ impl<'a> Call<'a> {
         ))
     }
 
+    /// Send the file descriptors `fds` to the client of an upgraded unix socket connection,
+    /// see [VarlinkStream::send_fds](enum.VarlinkStream.html#method.send_fds).
+    ///
+    /// Returns `ErrorKind::MethodNotImplemented`, if the connection is not upgraded or is not
+    /// a unix socket.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,ignore
+    /// fn call_upgraded(&self, call: &mut Call, bufreader: &mut BufRead) -> Result<Vec<u8>> {
+    ///     let file = File::open("/run/org.example.log")?;
+    ///     call.send_fds(&[file.as_raw_fd()])?;
+    ///     Ok(Vec::new())
+    /// }
+    /// ```
+    #[cfg(unix)]
+    pub fn send_fds(&mut self, fds: &[RawFd]) -> Result<()> {
+        client::send_fds(self.upgraded_unix_socket("send_fds")?, fds)
+    }
+
+    /// Receive up to `max` file descriptors from the client of an upgraded unix socket
+    /// connection, see [VarlinkStream::recv_fds](enum.VarlinkStream.html#method.recv_fds).
+    ///
+    /// The `\0` byte carrying the file descriptors is read from the socket directly, so it
+    /// must not have been read into the buffer of the `BufRead` passed to `call_upgraded()`.
+    #[cfg(unix)]
+    pub fn recv_fds(&mut self, max: usize) -> Result<Vec<RawFd>> {
+        client::recv_fds(self.upgraded_unix_socket("recv_fds")?, max)
+    }
+
+    #[cfg(unix)]
+    fn upgraded_unix_socket(&self, method: &str) -> Result<RawFd> {
+        match self.context.unix_socket {
+            Some(fd) if self.upgraded => Ok(fd),
+            _ => Err(into_cherr!(ErrorKind::MethodNotImplemented(method.into()))),
+        }
+    }
+
     fn reply_parameters(&mut self, parameters: Value) -> Result<()> {
         let reply = Reply::parameters(Some(parameters));
         //serde_json::to_writer(&mut *self.writer, &reply)?;
@@ -787,6 +1064,98 @@ impl<'a> Call<'a> {
     }
 }
 
+/// A stream of replies to a call, which wants more than one reply, as returned by
+/// [begin_more()](trait.BeginMore.html#tymethod.begin_more).
+///
+/// The replies are sent through the wrapped call, which the stream dereferences to, e.g. with
+/// the generated `reply()`, and have `continues: true` set.
+/// [finish()](#method.finish) sends the last reply with `continues: false` and no parameters.
+/// Dropping the stream without `finish()`, e.g. on an early return with `?`, sends this last
+/// reply, too, so the `more()` iterator of the client does not wait forever.
+///
+/// To send the last reply with parameters instead, call `set_continues(false)` before
+/// replying, the stream then sends nothing on drop. The same holds for an error reply, which
+/// ends the call without `continues`.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// use varlink::BeginMore;
+///
+/// fn test_more(&self, call: &mut Call_TestMore, n: i64) -> varlink::Result<()> {
+///     let mut stream = call.begin_more();
+///     for i in 0..n {
+///         stream.reply(State { progress: Some(i), ..Default::default() })?;
+///     }
+///     stream.finish()
+/// }
+/// ```
+pub struct MoreReplies<'a, C: ?Sized + CallTrait + 'a> {
+    call: &'a mut C,
+    finished: bool,
+}
+
+impl<'a, C: ?Sized + CallTrait + 'a> MoreReplies<'a, C> {
+    /// Set `continues` for the replies sent through `call`.
+    pub fn new(call: &'a mut C) -> Self {
+        call.set_continues(true);
+        MoreReplies {
+            call,
+            finished: false,
+        }
+    }
+
+    /// Send the last reply with `continues: false` and no parameters.
+    ///
+    /// Sends nothing, if the call has ended already with an error or a last reply.
+    pub fn finish(mut self) -> Result<()> {
+        self.finished = true;
+        self.call.set_continues(false);
+        if self.call.is_replied() {
+            return Ok(());
+        }
+        self.call.reply_struct(Reply::parameters(None))
+    }
+}
+
+impl<'a, C: ?Sized + CallTrait + 'a> Deref for MoreReplies<'a, C> {
+    type Target = C;
+
+    fn deref(&self) -> &C {
+        &*self.call
+    }
+}
+
+impl<'a, C: ?Sized + CallTrait + 'a> DerefMut for MoreReplies<'a, C> {
+    fn deref_mut(&mut self) -> &mut C {
+        &mut *self.call
+    }
+}
+
+impl<'a, C: ?Sized + CallTrait + 'a> Drop for MoreReplies<'a, C> {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.call.set_continues(false);
+            if !self.call.is_replied() {
+                let _ = self.call.reply_struct(Reply::parameters(None));
+            }
+        }
+    }
+}
+
+/// Start a [stream of replies](struct.MoreReplies.html) on a call, implemented for every
+/// [CallTrait](trait.CallTrait.html), including the generated `Call_<Method>` traits.
+pub trait BeginMore: CallTrait {
+    /// Start sending more than one reply, see [MoreReplies](struct.MoreReplies.html).
+    fn begin_more(&mut self) -> MoreReplies<Self>;
+}
+
+impl<C: ?Sized + CallTrait> BeginMore for C {
+    fn begin_more(&mut self) -> MoreReplies<Self> {
+        MoreReplies::new(self)
+    }
+}
+
 /// A client connection builder to a varlink service.
 #[derive(Default)]
 pub struct Connection {
@@ -797,15 +1166,180 @@ pub struct Connection {
     pub stream: Option<client::VarlinkStream>,
     pub child: Option<Child>,
     pub tempdir: Option<TempDir>,
+    handshake_pending: bool,
+    read_buffer_capacity: Option<usize>,
+    /// The buffer the replies are read into, reused for all replies, see `read_message()`.
+    read_buffer: Vec<u8>,
+    oneway_buffer: Option<Vec<u8>>,
+    latency_observer: Option<Arc<LatencyObserver>>,
+    stderr_tail: Option<StderrTail>,
+    keepalive_error: Option<ErrorKind>,
+    max_nesting_depth: usize,
+    read_timeout: Option<Duration>,
+    retry: Option<RetryPolicy>,
+    connection_lost: bool,
+    /// The calls, which timed out with `MethodCall::timeout()`, whose replies are still to be
+    /// read and dropped, see `skip_abandoned_replies()`.
+    abandoned_calls: usize,
+    /// The part of an abandoned reply read before the timeout.
+    abandoned_partial: Vec<u8>,
+}
+
+/// A callback for the latency of replies, see
+/// [Connection::set_latency_observer](struct.Connection.html#method.set_latency_observer).
+pub type LatencyObserver = Box<dyn Fn(&CallLatency) + Send + Sync>;
+
+/// The latency of a reply, as passed to the observer set with
+/// [Connection::set_latency_observer](struct.Connection.html#method.set_latency_observer).
+#[derive(Debug, Clone, PartialEq)]
+pub struct CallLatency {
+    /// The called method.
+    pub method: String,
+    /// The number of the reply, `0` for the first reply of a call.
+    pub reply: usize,
+    /// The time from sending the request to receiving this reply.
+    pub elapsed: Duration,
+    /// The time from receiving the previous reply to receiving this reply. For the first
+    /// reply, this is the same as `elapsed`.
+    pub since_previous: Duration,
+}
+
+/// How a connection created with
+/// [Connection::new_with_retry](struct.Connection.html#method.new_with_retry) re-dials
+/// the service.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    /// The number of connection attempts, before giving up.
+    pub max_attempts: usize,
+    /// The wait after the first failed attempt, doubled after every further failed attempt.
+    pub backoff: Duration,
+    /// The longest wait between two attempts.
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 5,
+            backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(5),
+        }
+    }
+}
+
+impl RetryPolicy {
+    fn connect(&self, address: &str) -> Result<(client::VarlinkStream, String)> {
+        let mut backoff = self.backoff;
+        let mut attempt = 1;
+        loop {
+            match client::VarlinkStream::connect(address) {
+                Ok(r) => return Ok(r),
+                Err(e) => {
+                    if attempt >= self.max_attempts {
+                        return Err(e);
+                    }
+                }
+            }
+            thread::sleep(backoff);
+            backoff = ::std::cmp::min(backoff * 2, self.max_backoff);
+            attempt += 1;
+        }
+    }
+}
+
+/// Conversion into a varlink address URI, as used by [Connection::new](struct.Connection.html#method.new).
+///
+/// Strings are used as the address URI. A `SocketAddr` is converted to a `tcp:` address and
+/// a `PathBuf` to a `unix:` socket path address.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::net::SocketAddr;
+/// use std::path::PathBuf;
+/// use varlink::IntoVarlinkAddress;
+///
+/// let addr: SocketAddr = "127.0.0.1:12345".parse().unwrap();
+/// assert_eq!(addr.into_varlink_address(), "tcp:127.0.0.1:12345");
+/// assert_eq!(
+///     PathBuf::from("/run/org.example.ftl").into_varlink_address(),
+///     "unix:/run/org.example.ftl"
+/// );
+/// ```
+pub trait IntoVarlinkAddress {
+    fn into_varlink_address(self) -> String;
+}
+
+impl<S: ?Sized + AsRef<str>> IntoVarlinkAddress for &S {
+    fn into_varlink_address(self) -> String {
+        self.as_ref().to_string()
+    }
+}
+
+impl IntoVarlinkAddress for String {
+    fn into_varlink_address(self) -> String {
+        self
+    }
+}
+
+impl IntoVarlinkAddress for SocketAddr {
+    fn into_varlink_address(self) -> String {
+        format!("tcp:{}", self)
+    }
+}
+
+impl IntoVarlinkAddress for PathBuf {
+    fn into_varlink_address(self) -> String {
+        format!("unix:{}", self.to_string_lossy())
+    }
 }
 
 impl Connection {
-    /// Create a connection with a varlink URI
+    /// A connection reading from `reader` and writing to `writer`, with the default settings
+    /// and without a started service.
+    fn from_parts(
+        reader: BufReader<Box<Read + Send + Sync>>,
+        writer: Box<Write + Send + Sync>,
+        address: String,
+        stream: Option<client::VarlinkStream>,
+    ) -> Self {
+        Connection {
+            reader: Some(reader),
+            writer: Some(writer),
+            address,
+            stream,
+            child: None,
+            tempdir: None,
+            handshake_pending: false,
+            read_buffer_capacity: None,
+            read_buffer: Vec::new(),
+            oneway_buffer: None,
+            latency_observer: None,
+            stderr_tail: None,
+            keepalive_error: None,
+            max_nesting_depth: DEFAULT_MAX_NESTING_DEPTH,
+            read_timeout: None,
+            retry: None,
+            connection_lost: false,
+            abandoned_calls: 0,
+            abandoned_partial: Vec::new(),
+        }
+    }
+
+    /// Create a connection with a varlink URI, a socket address or a unix socket path
+    ///
+    /// see [with_address](#method.with_address) and [IntoVarlinkAddress](trait.IntoVarlinkAddress.html)
+    ///
+    /// # Examples
     ///
-    /// see [with_address](#method.with_address)
+    /// ```rust,ignore
+    /// let connection = Connection::new("unix:/tmp/org.example.myservice")?;
+    /// let connection = Connection::new(PathBuf::from("/tmp/org.example.myservice"))?;
+    /// let connection = Connection::new("127.0.0.1:12345".parse::<SocketAddr>()?)?;
+    /// ```
     #[allow(clippy::new_ret_no_self)]
-    pub fn new<S: ?Sized + AsRef<str>>(address: &S) -> Result<Arc<RwLock<Self>>> {
-        Self::with_address(address)
+    pub fn new<A: IntoVarlinkAddress>(address: A) -> Result<Arc<RwLock<Self>>> {
+        Self::with_address(&address.into_varlink_address())
     }
 
     /// Create a connection with a varlink URI
@@ -816,8 +1350,11 @@ impl Connection {
     /// Currently supported address URIs are:
     ///
     /// - TCP `tcp:127.0.0.1:12345` hostname/IP address and port
+    /// - TLS over TCP `tcps:varlink.example.org:12345` hostname/IP address and port
+    ///   (with the `tls` feature), verified with the root certificates of the system
     /// - UNIX socket `unix:/run/org.example.ftl`
-    /// - UNIX abstract namespace socket `unix:@org.example.ftl` (on Linux only)
+    /// - UNIX abstract namespace socket `unix:@org.example.ftl` (on Linux and Android only,
+    ///   other platforms fail with "abstract sockets not supported on this platform")
     ///
     /// # Examples
     ///
@@ -828,15 +1365,81 @@ impl Connection {
     pub fn with_address<S: ?Sized + AsRef<str>>(address: &S) -> Result<Arc<RwLock<Self>>> {
         let (mut stream, address) = client::VarlinkStream::connect(address)?;
         let (r, w) = stream.split()?;
-        let bufreader = BufReader::new(r);
-        Ok(Arc::new(RwLock::new(Connection {
-            reader: Some(bufreader),
-            writer: Some(w),
+        Ok(Arc::new(RwLock::new(Connection::from_parts(
+            BufReader::new(r),
+            w,
             address,
-            stream: Some(stream),
-            child: None,
-            tempdir: None,
-        })))
+            Some(stream),
+        ))))
+    }
+
+    /// Create a connection to a `tcps:` address, verifying the service with `config`, e.g.
+    /// with a pinned CA.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,ignore
+    /// let tls = TlsClientConfig::with_ca_pem(&fs::read("ca.pem")?)?;
+    /// let connection = Connection::with_address_tls("tcps:varlink.example.org:12345", &tls)?;
+    /// ```
+    #[cfg(feature = "tls")]
+    pub fn with_address_tls<S: ?Sized + AsRef<str>>(
+        address: &S,
+        config: &TlsClientConfig,
+    ) -> Result<Arc<RwLock<Self>>> {
+        let (stream, address) = client::VarlinkStream::connect_tls(address, config)?;
+        Self::with_stream(stream, &address, Vec::new())
+    }
+
+    /// Like [with_address](#method.with_address), but fail with
+    /// `ErrorKind::Io(io::ErrorKind::TimedOut)`, if the connection is not established
+    /// within `timeout`.
+    ///
+    /// See [VarlinkStream::connect_timeout](struct.VarlinkStream.html#method.connect_timeout).
+    ///
+    /// # Examples
+    ///
+    /// ```rust,ignore
+    /// let connection =
+    ///     Connection::with_address_timeout("tcp:192.0.2.1:12345", Duration::from_secs(2))?;
+    /// ```
+    pub fn with_address_timeout<S: ?Sized + AsRef<str>>(
+        address: &S,
+        timeout: Duration,
+    ) -> Result<Arc<RwLock<Self>>> {
+        let (stream, address) = client::VarlinkStream::connect_timeout(address, timeout)?;
+        Self::with_stream(stream, &address, Vec::new())
+    }
+
+    /// Create a connection, which re-dials `address` following `policy`, when the
+    /// connection to the service is lost, e.g. because the service restarted.
+    ///
+    /// A `call()` failing, because the connection was reset or closed, is sent again once
+    /// on the new connection, so the methods called this way should be idempotent.
+    /// `oneway()`, `more()` and `upgrade()` calls are never sent again, because the service
+    /// might have received them already. They fail with the error of the lost connection and
+    /// the connection is re-dialed for the next call. Buffered oneway calls, see
+    /// [set_buffer_oneway](#method.set_buffer_oneway), which were not sent, are lost.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,ignore
+    /// let connection = Connection::new_with_retry(
+    ///     "unix:/run/org.example.ftl",
+    ///     RetryPolicy {
+    ///         max_attempts: 10,
+    ///         ..Default::default()
+    ///     },
+    /// )?;
+    /// ```
+    pub fn new_with_retry<A: IntoVarlinkAddress>(
+        address: A,
+        policy: RetryPolicy,
+    ) -> Result<Arc<RwLock<Self>>> {
+        let (stream, address) = policy.connect(&address.into_varlink_address())?;
+        let connection = Self::with_stream(stream, &address, Vec::new())?;
+        connection.write().unwrap().retry = Some(policy);
+        Ok(connection)
     }
 
     /// Create a connection to a service, which is executed in the background.
@@ -846,24 +1449,83 @@ impl Connection {
     /// `VARLINK_ADDRESS`. Additionally the socket activation variables `LISTEN_FDS=1`,
     /// `LISTEN_FDNAMES=varlink` and `LISTEN_PID` are set.
     ///
+    /// If the connection fails, because the service exited, e.g. because `command` was not
+    /// found, the error is `ErrorKind::ServiceFailed` with the exit status of the service.
+    ///
     /// # Examples
     ///
     /// ```rust,ignore
     /// let connection = Connection::with_activate("myservice --varlink=$VARLINK_ADDRESS");
     /// ```
     pub fn with_activate<S: ?Sized + AsRef<str>>(command: &S) -> Result<Arc<RwLock<Self>>> {
-        let (child, unix_address, temp_dir) = varlink_exec(command)?;
-        let (mut stream, address) = client::VarlinkStream::connect(&unix_address)?;
+        Self::activate(command, false)
+    }
+
+    /// Like [with_activate](#method.with_activate), but capture the stderr output of the service.
+    ///
+    /// If the connection fails, e.g. because the service crashed, the error is
+    /// `ErrorKind::ServiceFailed` with the last lines the service wrote to stderr, instead of
+    /// the exit status. The captured output is not passed on to the stderr of the calling
+    /// process. The exit status is available with [exit_status](#method.exit_status).
+    ///
+    /// # Examples
+    ///
+    /// ```rust,ignore
+    /// let connection =
+    ///     Connection::with_activate_capture_stderr("myservice --varlink=$VARLINK_ADDRESS")?;
+    /// ```
+    pub fn with_activate_capture_stderr<S: ?Sized + AsRef<str>>(
+        command: &S,
+    ) -> Result<Arc<RwLock<Self>>> {
+        Self::activate(command, true)
+    }
+
+    fn activate<S: ?Sized + AsRef<str>>(
+        command: &S,
+        capture_stderr: bool,
+    ) -> Result<Arc<RwLock<Self>>> {
+        let (mut child, unix_address, temp_dir) = varlink_exec(command, capture_stderr)?;
+        let stderr_tail = child.stderr.take().map(StderrTail::capture);
+        let (mut stream, address) = match client::VarlinkStream::connect(&unix_address) {
+            Ok(r) => r,
+            Err(e) => {
+                let status = wait_exit(&mut child, Duration::from_millis(100));
+                if status.is_none() {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                }
+                return Err(match (stderr_tail, status) {
+                    (Some(tail), _) => into_cherr!(ErrorKind::ServiceFailed(
+                        tail.last_lines(Duration::from_secs(1))
+                    )),
+                    (None, Some(status)) => cherr!(e, ErrorKind::ServiceFailed(status.to_string())),
+                    (None, None) => e,
+                });
+            }
+        };
         let (reader, writer) = stream.split()?;
-        let bufreader = BufReader::new(reader);
-        Ok(Arc::new(RwLock::new(Connection {
-            reader: Some(bufreader),
-            writer: Some(writer),
-            address,
-            stream: Some(stream),
-            child: Some(child),
-            tempdir: temp_dir,
-        })))
+        let mut conn =
+            Connection::from_parts(BufReader::new(reader), writer, address, Some(stream));
+        conn.child = Some(child);
+        conn.tempdir = temp_dir;
+        conn.stderr_tail = stderr_tail;
+        Ok(Arc::new(RwLock::new(conn)))
+    }
+
+    /// The exit status of the service started with [with_activate](#method.with_activate)
+    /// or [with_bridge](#method.with_bridge), if it exited.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,ignore
+    /// if let Err(e) = iface.ping("Test".into()).call() {
+    ///     if let Some(status) = connection.write().unwrap().exit_status() {
+    ///         eprintln!("service exited with {}", status);
+    ///     }
+    /// }
+    /// ```
+    pub fn exit_status(&mut self) -> Option<ExitStatus> {
+        self.child.as_mut()?.try_wait().ok()?
     }
 
     /// Create a connection to a service via stdin/stdout of a specified command.
@@ -884,46 +1546,682 @@ impl Connection {
     pub fn with_bridge<S: ?Sized + AsRef<str>>(command: &S) -> Result<Arc<RwLock<Self>>> {
         let (child, mut stream) = varlink_bridge(command)?;
         let (r, w) = stream.split()?;
-        let bufreader = BufReader::new(r);
-        Ok(Arc::new(RwLock::new(Connection {
-            reader: Some(bufreader),
-            writer: Some(w),
-            address: "bridge".into(),
-            stream: Some(stream),
-            child: Some(child),
-            tempdir: None,
-        })))
+        let mut conn = Connection::from_parts(BufReader::new(r), w, "bridge".into(), Some(stream));
+        conn.child = Some(child);
+        Ok(Arc::new(RwLock::new(conn)))
+    }
+
+    /// Create a connection on an already connected `stream`, e.g. one handed over by another
+    /// process or taken from another connection.
+    ///
+    /// `pending` are the bytes already read from the stream, but not yet parsed, as returned
+    /// by [pending_bytes](#method.pending_bytes) of the previous owner. They are parsed before
+    /// anything read from the stream. `address` is only used by [address](#method.address)
+    /// and [try_clone](#method.try_clone).
+    ///
+    /// # Examples
+    ///
+    /// ```rust,ignore
+    /// let (stream, pending) = {
+    ///     let mut conn = connection.write().unwrap();
+    ///     (conn.stream.take().unwrap(), conn.pending_bytes()?)
+    /// };
+    /// let connection = Connection::with_stream(stream, &address, pending)?;
+    /// ```
+    pub fn with_stream<S: ?Sized + AsRef<str>>(
+        mut stream: client::VarlinkStream,
+        address: &S,
+        pending: Vec<u8>,
+    ) -> Result<Arc<RwLock<Self>>> {
+        let (r, w) = stream.split()?;
+        let r: Box<Read + Send + Sync> = Box::new(io::Cursor::new(pending).chain(r));
+        Ok(Arc::new(RwLock::new(Connection::from_parts(
+            BufReader::new(r),
+            w,
+            address.as_ref().into(),
+            Some(stream),
+        ))))
+    }
+
+    /// Return the bytes read from the stream, but not yet parsed.
+    ///
+    /// Together with the file descriptor of the [stream](#structfield.stream), these are
+    /// needed to hand the connection over to a new owner, see
+    /// [with_stream](#method.with_stream). This includes the beginning of a reply, which is
+    /// not completely received yet.
+    ///
+    /// If a call is in progress, `ErrorKind::ConnectionBusy` is returned.
+    pub fn pending_bytes(&self) -> Result<Vec<u8>> {
+        match self.reader {
+            Some(ref r) => Ok(r.buffer().to_vec()),
+            None => Err(into_cherr!(ErrorKind::ConnectionBusy)),
+        }
     }
 
     /// Return the `address` used by the connection.
     ///
-    /// Only useful, if you want to clone a connection built
-    /// [with_activate](#method.with_activate) or [with_address](#method.with_address)
+    /// For a connection built [with_activate](#method.with_activate), this is the address
+    /// of the socket passed to the started service, e.g. `unix:@abc123`, so it can be logged
+    /// or used to clone the connection.
     pub fn address(&self) -> String {
         self.address.clone()
     }
-}
 
-impl Drop for Connection {
-    fn drop(&mut self) {
-        if let Some(ref mut stream) = self.stream {
-            let _r = stream.shutdown();
-        }
-        if let Some(ref mut child) = self.child {
-            let _res = child.kill();
-            let _res = child.wait();
-        }
-        if self.tempdir.is_some() {
-            if let Some(dir) = self.tempdir.take() {
-                use std::fs;
-                let _r = fs::remove_dir_all(dir);
+    /// Return the transport of the connection, e.g. to only ask for the credentials of the
+    /// peer on a unix socket.
+    ///
+    /// If the [stream](#structfield.stream) was taken, the transport is derived from the
+    /// [address](#method.address).
+    ///
+    /// # Examples
+    ///
+    /// ```rust,ignore
+    /// let connection = Connection::with_activate("myservice --varlink=$VARLINK_ADDRESS")?;
+    /// assert_eq!(connection.read().unwrap().transport(), Transport::Unix);
+    /// ```
+    pub fn transport(&self) -> Transport {
+        match self.stream {
+            Some(ref stream) => stream.transport(),
+            None if self.address.starts_with("tcp:") || self.address.starts_with("tcps:") => {
+                Transport::Tcp
             }
+            None => Transport::Unix,
         }
     }
-}
 
-pub struct MethodCall<MRequest, MReply, MError>
-where
+    /// Create a new, independent connection to the same service.
+    ///
+    /// The returned connection uses its own socket, so calls on it are not serialized with
+    /// the calls on this connection and can be issued from another thread in parallel.
+    /// Because it is a separate connection, any state the service keeps per connection
+    /// (like an upgraded protocol) is not shared.
+    ///
+    /// For a connection built [with_activate](#method.with_activate), the already running
+    /// service is connected and not started again. The service is still owned by the original
+    /// connection and is terminated, when the original connection is dropped.
+    ///
+    /// Connections built [with_bridge](#method.with_bridge) cannot be cloned and return
+    /// `ErrorKind::InvalidAddress`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,ignore
+    /// let connection = Connection::with_address("unix:/tmp/org.example.myservice")?;
+    /// let connection2 = connection.read().unwrap().try_clone()?;
+    /// ```
+    pub fn try_clone(&self) -> Result<Arc<RwLock<Self>>> {
+        Self::with_address(&self.address)
+    }
+
+    /// Set the initial capacity of the buffers used to read the replies.
+    ///
+    /// With a capacity larger than the typical reply, replies are read without growing the
+    /// buffers. Without a capacity set, the buffers start small and grow as needed.
+    ///
+    /// The read buffer is replaced, so this has to be called before the first call on this
+    /// connection. If a call is in progress, `ErrorKind::ConnectionBusy` is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,ignore
+    /// let connection = Connection::with_address("unix:/tmp/org.example.myservice")?;
+    /// connection.write().unwrap().set_read_buffer_capacity(1024 * 1024)?;
+    /// ```
+    pub fn set_read_buffer_capacity(&mut self, capacity: usize) -> Result<()> {
+        let reader = match self.reader.take() {
+            Some(r) => r,
+            None => return Err(into_cherr!(ErrorKind::ConnectionBusy)),
+        };
+        self.reader = Some(BufReader::with_capacity(capacity, reader.into_inner()));
+        self.read_buffer_capacity = Some(capacity);
+        Ok(())
+    }
+
+    /// Set a timeout for the first reply on this connection.
+    ///
+    /// If the service accepts the connection, but does not reply to the first call within
+    /// `timeout`, the call fails with `ErrorKind::Timeout`. Once the first reply is
+    /// received, the timeout is removed and later calls can take as long as they need.
+    ///
+    /// This has to be set before the first call is made.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,ignore
+    /// let connection = Connection::with_address("tcp:127.0.0.1:12345")?;
+    /// connection
+    ///     .write()
+    ///     .unwrap()
+    ///     .set_handshake_timeout(Duration::from_secs(5))?;
+    /// ```
+    pub fn set_handshake_timeout(&mut self, timeout: Duration) -> Result<()> {
+        if let Some(ref stream) = self.stream {
+            stream.set_read_timeout(Some(timeout))?;
+            self.handshake_pending = true;
+        }
+        Ok(())
+    }
+
+    /// Set a timeout for reading each reply on this connection.
+    ///
+    /// If a reply does not arrive within `timeout`, the call fails with an `ErrorKind::Io`
+    /// error of kind `WouldBlock` or `TimedOut`, depending on the platform. `None` waits
+    /// forever, which is the default.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,ignore
+    /// let connection = Connection::with_address("tcp:127.0.0.1:12345")?;
+    /// connection
+    ///     .write()
+    ///     .unwrap()
+    ///     .set_read_timeout(Some(Duration::from_secs(5)))?;
+    /// ```
+    pub fn set_read_timeout(&mut self, timeout: Option<Duration>) -> Result<()> {
+        if let Some(ref stream) = self.stream {
+            // A pending handshake timeout is replaced by this one on the first reply.
+            if !self.handshake_pending {
+                stream.set_read_timeout(timeout)?;
+            }
+        }
+        self.read_timeout = timeout;
+        Ok(())
+    }
+
+    /// Set a timeout for sending each call on this connection.
+    ///
+    /// If the service does not read the call within `timeout`, the call fails with an
+    /// `ErrorKind::Io` error of kind `WouldBlock` or `TimedOut`. `None` waits forever,
+    /// which is the default.
+    pub fn set_write_timeout(&mut self, timeout: Option<Duration>) -> Result<()> {
+        if let Some(ref stream) = self.stream {
+            stream.set_write_timeout(timeout)?;
+        }
+        Ok(())
+    }
+
+    /// Set `SO_LINGER` on this connection, see
+    /// [VarlinkStream::set_linger](struct.VarlinkStream.html#method.set_linger).
+    ///
+    /// # Examples
+    ///
+    /// ```rust,ignore
+    /// let connection = Connection::with_address("tcp:127.0.0.1:12345")?;
+    /// connection
+    ///     .write()
+    ///     .unwrap()
+    ///     .set_linger(Some(Duration::from_secs(5)))?;
+    /// ```
+    pub fn set_linger(&mut self, linger: Option<Duration>) -> Result<()> {
+        if let Some(ref stream) = self.stream {
+            stream.set_linger(linger)?;
+        }
+        Ok(())
+    }
+
+    /// Buffer `oneway()` calls until [flush](#method.flush) is called.
+    ///
+    /// With buffering enabled, a burst of oneway calls is sent with a single write.
+    /// Calls expecting a reply, like `call()` and `more()`, are always sent immediately,
+    /// together with any buffered oneway calls before them.
+    ///
+    /// Disabling the buffering flushes the buffered calls.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,ignore
+    /// connection.write().unwrap().set_buffer_oneway(true)?;
+    /// for event in events {
+    ///     iface.notify(event).oneway()?;
+    /// }
+    /// connection.write().unwrap().flush()?;
+    /// ```
+    pub fn set_buffer_oneway(&mut self, buffer: bool) -> Result<()> {
+        if buffer {
+            if self.oneway_buffer.is_none() {
+                self.oneway_buffer = Some(Vec::new());
+            }
+            Ok(())
+        } else {
+            self.flush()?;
+            self.oneway_buffer = None;
+            Ok(())
+        }
+    }
+
+    /// Set the maximum nesting depth of the JSON objects and arrays in a reply.
+    ///
+    /// A deeper reply is rejected with `ErrorKind::NestingTooDeep`, before it is parsed. The
+    /// default is [DEFAULT_MAX_NESTING_DEPTH](constant.DEFAULT_MAX_NESTING_DEPTH.html).
+    pub fn set_max_nesting_depth(&mut self, depth: usize) {
+        self.max_nesting_depth = depth;
+    }
+
+    /// Call `observer` with the latency of every reply received on this connection.
+    ///
+    /// For a `more()` call, the observer gets the time to the first reply and the gaps
+    /// between the following replies. Oneway calls have no reply and are not observed.
+    /// Without an observer, the calls are not timed at all. The observer is called after the
+    /// connection is unlocked again, so it may use the connection itself.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,ignore
+    /// connection
+    ///     .write()
+    ///     .unwrap()
+    ///     .set_latency_observer(Some(Box::new(|latency: &CallLatency| {
+    ///         histogram.record(&latency.method, latency.since_previous)
+    ///     })));
+    /// ```
+    pub fn set_latency_observer(&mut self, observer: Option<LatencyObserver>) {
+        self.latency_observer = observer.map(Arc::new);
+    }
+
+    /// Call `org.varlink.service.GetInfo` every `interval`, while the connection is idle.
+    ///
+    /// The pings keep the state of NAT gateways and firewalls for an idle connection and
+    /// detect a dead service early. A ping is only sent, if no call is in flight and no other
+    /// thread holds the lock of the connection, else it is skipped for this `interval`. The
+    /// reply has to arrive within `interval`. As varlink replies to the calls in order,
+    /// there are no pings between the replies of a `more()` call.
+    ///
+    /// If a ping fails, the pings stop and all following calls fail with the error of the
+    /// ping, see [keepalive_error](#method.keepalive_error). The pings also stop, when the
+    /// connection is dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,ignore
+    /// let connection = Connection::with_address("tcp:127.0.0.1:12345")?;
+    /// Connection::start_keepalive(&connection, Duration::from_secs(30));
+    /// ```
+    pub fn start_keepalive(connection: &Arc<RwLock<Self>>, interval: Duration) {
+        let connection = Arc::downgrade(connection);
+        thread::spawn(move || loop {
+            thread::sleep(interval);
+            let connection = match connection.upgrade() {
+                Some(c) => c,
+                None => break,
+            };
+            // Skip the ping, while another thread uses the connection.
+            let mut conn = match connection.try_write() {
+                Ok(conn) => conn,
+                Err(TryLockError::WouldBlock) => continue,
+                Err(TryLockError::Poisoned(_)) => break,
+            };
+            if let Err(e) = conn.keepalive_ping(interval) {
+                conn.keepalive_error = Some(e);
+                break;
+            }
+        });
+    }
+
+    /// The error of the failed ping, if the keepalive started with
+    /// [start_keepalive](#method.start_keepalive) detected a dead service.
+    pub fn keepalive_error(&self) -> Option<&ErrorKind> {
+        self.keepalive_error.as_ref()
+    }
+
+    fn keepalive_ping(&mut self, timeout: Duration) -> ::std::result::Result<(), ErrorKind> {
+        if self.handshake_pending {
+            return Ok(());
+        }
+        let read_timeout = self.read_timeout;
+        let (stream, reader, writer) = match (&self.stream, &mut self.reader, &mut self.writer) {
+            (Some(stream), Some(reader), Some(writer)) => (stream, reader, writer),
+            _ => return Ok(()),
+        };
+
+        let io_error = |e: io::Error| match e.kind() {
+            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => ErrorKind::Timeout,
+            _ => {
+                let e: Error = into_cherr!(e);
+                e.kind().clone()
+            }
+        };
+
+        writer
+            .write_all(b"{\"method\":\"org.varlink.service.GetInfo\"}\0")
+            .and_then(|_| writer.flush())
+            .map_err(io_error)?;
+
+        stream
+            .set_read_timeout(Some(timeout))
+            .map_err(|e| e.kind().clone())?;
+        let mut buf = Vec::new();
+        let reply = skip_abandoned_replies(
+            reader,
+            &mut self.abandoned_calls,
+            &mut self.abandoned_partial,
+        )
+        .and_then(|_| read_message(reader, &mut buf, None));
+        let _ = stream.set_read_timeout(read_timeout);
+        reply.map_err(io_error)?;
+
+        if buf.is_empty() {
+            return Err(ErrorKind::ConnectionClosed);
+        }
+        Ok(())
+    }
+
+    /// Send `request` unchanged and return its replies, e.g. in a proxy, which forwards
+    /// requests without decoding their parameters.
+    ///
+    /// A `oneway` request returns no reply, a `more` request all replies up to the one
+    /// without `continues`. The parameters are passed on as they are, including fields not
+    /// known to the proxy. An error reply of the service is returned like any other reply.
+    /// See [forward_streaming](#method.forward_streaming) to forward the replies of a
+    /// `more` request as they arrive.
+    ///
+    /// If a call is in progress, `ErrorKind::ConnectionBusy` is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,ignore
+    /// let request: Request = serde_json::from_slice(&buf)?;
+    /// for reply in connection.write().unwrap().forward(&request)? {
+    ///     call.reply_struct(reply)?;
+    /// }
+    /// ```
+    pub fn forward(&mut self, request: &Request) -> Result<Vec<Reply>> {
+        let mut replies = Vec::new();
+        self.forward_streaming(request, |reply| {
+            replies.push(reply);
+            Ok(())
+        })?;
+        Ok(replies)
+    }
+
+    /// Like [forward](#method.forward), but pass each reply to `f`, as soon as it is
+    /// received.
+    ///
+    /// If `f` returns an error, the remaining replies of the request are read and dropped,
+    /// so the connection can be used for the next request, and the error is returned.
+    pub fn forward_streaming<F>(&mut self, request: &Request, mut f: F) -> Result<()>
+    where
+        F: FnMut(Reply) -> Result<()>,
+    {
+        if let Some(ref kind) = self.keepalive_error {
+            return Err(into_cherr!(kind.clone()));
+        }
+
+        if self.reader.is_none() || self.writer.is_none() {
+            return Err(into_cherr!(ErrorKind::ConnectionBusy));
+        }
+
+        let oneway = request.oneway == Some(true);
+        let mut msg = serde_json::to_vec(request).map_err(minto_cherr!())?;
+        msg.push(0);
+
+        match self.oneway_buffer {
+            Some(ref mut buf) if oneway => {
+                buf.extend_from_slice(&msg);
+                return Ok(());
+            }
+            Some(ref mut buf) if !buf.is_empty() => {
+                buf.extend_from_slice(&msg);
+                msg = buf.split_off(0);
+            }
+            _ => {}
+        }
+
+        let r = {
+            let w = self.writer.as_mut().unwrap();
+            w.write_all(&msg).and_then(|_| w.flush())
+        };
+        r.map_err(|e| self.connection_error(e))?;
+
+        if oneway {
+            return Ok(());
+        }
+
+        let mut result = Ok(());
+        loop {
+            let reply = self.forward_recv()?;
+            let continues = reply.continues == Some(true);
+            if result.is_ok() {
+                result = f(reply);
+            }
+            if !continues {
+                return result;
+            }
+        }
+    }
+
+    fn forward_recv(&mut self) -> Result<Reply> {
+        let handshake_pending = self.handshake_pending;
+        let capacity = self.read_buffer_capacity;
+        let mut buf = ::std::mem::replace(&mut self.read_buffer, Vec::new());
+        let reader = self.reader.as_mut().unwrap();
+        let r = skip_abandoned_replies(
+            reader,
+            &mut self.abandoned_calls,
+            &mut self.abandoned_partial,
+        )
+        .and_then(|_| read_message(reader, &mut buf, capacity));
+        match r {
+            Err(ref e)
+                if handshake_pending
+                    && (e.kind() == io::ErrorKind::WouldBlock
+                        || e.kind() == io::ErrorKind::TimedOut) =>
+            {
+                return Err(into_cherr!(ErrorKind::Timeout));
+            }
+            r => r.map_err(|e| self.connection_error(e))?,
+        };
+        if buf.is_empty() {
+            return Err(self.connection_error(ErrorKind::ConnectionClosed));
+        }
+        if handshake_pending {
+            self.handshake_pending = false;
+            if let Some(ref stream) = self.stream {
+                let _ = stream.set_read_timeout(self.read_timeout);
+            }
+        }
+        buf.pop();
+        if nesting_too_deep(&buf, self.max_nesting_depth) {
+            return Err(into_cherr!(ErrorKind::NestingTooDeep(
+                self.max_nesting_depth
+            )));
+        }
+        let reply = serde_json::from_slice(&buf).map_err(minto_cherr!());
+        self.read_buffer = buf;
+        reply
+    }
+
+    /// Send the `oneway()` calls buffered with [set_buffer_oneway](#method.set_buffer_oneway).
+    ///
+    /// If a call is in progress, `ErrorKind::ConnectionBusy` is returned.
+    pub fn flush(&mut self) -> Result<()> {
+        match self.oneway_buffer {
+            Some(ref mut buf) if !buf.is_empty() => {
+                let w = match self.writer {
+                    Some(ref mut w) => w,
+                    None => return Err(into_cherr!(ErrorKind::ConnectionBusy)),
+                };
+                let r = w.write_all(buf).and_then(|_| w.flush());
+                buf.clear();
+                r.map_err(minto_cherr!())?;
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Connection {
+    /// Replace the lost connection with a new one to the same address, see
+    /// [new_with_retry](#method.new_with_retry).
+    fn reconnect(&mut self) -> Result<()> {
+        let policy = match self.retry {
+            Some(ref policy) => policy.clone(),
+            None => return Err(into_cherr!(ErrorKind::ConnectionClosed)),
+        };
+        let (mut stream, _) = policy.connect(&self.address)?;
+        let (r, w) = stream.split()?;
+        stream.set_read_timeout(self.read_timeout)?;
+        self.reader = Some(match self.read_buffer_capacity {
+            Some(capacity) => BufReader::with_capacity(capacity, r),
+            None => BufReader::new(r),
+        });
+        self.writer = Some(w);
+        self.stream = Some(stream);
+        self.handshake_pending = false;
+        self.connection_lost = false;
+        self.abandoned_calls = 0;
+        self.abandoned_partial.clear();
+        Ok(())
+    }
+
+    /// The error for a failed connection to a service started
+    /// [with_activate](#method.with_activate) or [with_bridge](#method.with_bridge).
+    ///
+    /// With the stderr of the service captured, this has the last lines of stderr, otherwise
+    /// the exit status of the service, if it exited.
+    fn service_failed(&mut self) -> Option<ErrorKind> {
+        if let Some(ref tail) = self.stderr_tail {
+            return Some(ErrorKind::ServiceFailed(
+                tail.last_lines(Duration::from_secs(1)),
+            ));
+        }
+        let child = self.child.as_mut()?;
+        wait_exit(child, Duration::from_secs(1))
+            .map(|status| ErrorKind::ServiceFailed(status.to_string()))
+    }
+
+    /// The error for a failed read or write, see `MethodCall::connection_error()`.
+    fn connection_error<E>(&mut self, e: E) -> Error
+    where
+        ErrorKind: ChainErrorFrom<E>,
+    {
+        match self.service_failed() {
+            Some(kind) => into_cherr!(kind),
+            None => into_cherr!(e),
+        }
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        let _r = self.flush();
+        if let Some(ref mut stream) = self.stream {
+            let _r = stream.shutdown();
+        }
+        if let Some(ref mut child) = self.child {
+            let _res = child.kill();
+            let _res = child.wait();
+        }
+        if self.tempdir.is_some() {
+            if let Some(dir) = self.tempdir.take() {
+                use std::fs;
+                let _r = fs::remove_dir_all(dir);
+            }
+        }
+    }
+}
+
+/// The timing of the replies of a call, if a latency observer is set.
+struct LatencyTimer {
+    method: String,
+    replies: usize,
+    sent: Instant,
+    previous: Instant,
+}
+
+/// Wait up to `timeout` for `child` to exit.
+fn wait_exit(child: &mut Child, timeout: Duration) -> Option<ExitStatus> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) => return Some(status),
+            Ok(None) if Instant::now() < deadline => thread::sleep(Duration::from_millis(10)),
+            _ => return None,
+        }
+    }
+}
+
+/// Whether `e` means, that the service closed or reset the connection.
+fn is_connection_lost(e: &io::Error) -> bool {
+    match e.kind() {
+        io::ErrorKind::BrokenPipe
+        | io::ErrorKind::ConnectionReset
+        | io::ErrorKind::ConnectionAborted
+        | io::ErrorKind::NotConnected
+        | io::ErrorKind::UnexpectedEof => true,
+        _ => false,
+    }
+}
+
+/// Read and drop the replies of the `abandoned` calls, which timed out with
+/// [MethodCall::timeout](struct.MethodCall.html#method.timeout), up to the last reply of each.
+///
+/// The part of a reply read before another timeout is kept in `partial` and completed on the
+/// next attempt. At the end of the stream, this returns, leaving the error to the read of
+/// the next reply.
+fn skip_abandoned_replies(
+    reader: &mut BufRead,
+    abandoned: &mut usize,
+    partial: &mut Vec<u8>,
+) -> io::Result<()> {
+    while *abandoned > 0 {
+        reader.read_until(0, partial)?;
+        if partial.last() != Some(&0) {
+            return Ok(());
+        }
+        partial.pop();
+        let reply = serde_json::from_slice::<Reply>(partial);
+        partial.clear();
+        let reply = reply.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        if reply.continues != Some(true) {
+            *abandoned -= 1;
+        }
+    }
+    Ok(())
+}
+
+/// The default maximum nesting depth of the JSON objects and arrays of a message, see
+/// [VarlinkService::set_max_nesting_depth](struct.VarlinkService.html#method.set_max_nesting_depth)
+/// and [Connection::set_max_nesting_depth](struct.Connection.html#method.set_max_nesting_depth).
+pub const DEFAULT_MAX_NESTING_DEPTH: usize = 64;
+
+/// Whether the JSON objects and arrays of the message `buf` are nested more than
+/// `max_depth` levels deep.
+///
+/// This only scans the bytes for brackets outside of strings, so a malicious message is
+/// rejected, before the recursive parser sees it.
+fn nesting_too_deep(buf: &[u8], max_depth: usize) -> bool {
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for b in buf {
+        if in_string {
+            match *b {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match *b {
+            b'"' => in_string = true,
+            b'{' | b'[' => {
+                depth += 1;
+                if depth > max_depth {
+                    return true;
+                }
+            }
+            b'}' | b']' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    false
+}
+
+pub struct MethodCall<MRequest, MReply, MError>
+where
     MRequest: Serialize,
     MReply: DeserializeOwned,
     MError: chainerror::ChainErrorFrom<ErrorKind>
@@ -937,6 +2235,11 @@ where
     reader: Option<BufReader<Box<Read + Send + Sync>>>,
     writer: Option<Box<Write + Send + Sync>>,
     continues: bool,
+    latency: Option<LatencyTimer>,
+    /// The latency of the last reply, for the observer to be called without the lock.
+    observed: Option<(Arc<LatencyObserver>, CallLatency)>,
+    sent: Option<String>,
+    timeout: Option<Duration>,
     phantom_reply: PhantomData<MReply>,
     phantom_error: PhantomData<MError>,
 }
@@ -962,14 +2265,99 @@ where
             continues: false,
             reader: None,
             writer: None,
+            latency: None,
+            observed: None,
+            sent: None,
+            timeout: None,
             phantom_reply: PhantomData,
             phantom_error: PhantomData,
         }
     }
 
-    fn send(&mut self, oneway: bool, more: bool, upgrade: bool) -> ChainResult<(), MError> {
+    /// Wait at most `timeout` for each reply of this call.
+    ///
+    /// If a reply does not arrive in time, the call fails with an `ErrorKind::Io` error of
+    /// kind `TimedOut`. The connection stays usable for the next call, which reads and
+    /// drops the late replies first. For a `more()` call, the timeout applies to each reply,
+    /// not to the whole stream.
+    ///
+    /// This overrides the [read timeout](struct.Connection.html#method.set_read_timeout) of
+    /// the connection for this call. It is ignored, while the handshake timeout of the
+    /// connection is pending, and for connections without a socket, e.g. `with_bridge()`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,ignore
+    /// let reply = iface.get_info().timeout(Duration::from_secs(1)).call()?;
+    /// ```
+    pub fn timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Set the [timeout](#method.timeout) of this call on the socket of `conn`, returns
+    /// whether it was set.
+    fn set_call_timeout(&self, conn: &Connection) -> ChainResult<bool, MError> {
+        match (self.timeout, &conn.stream) {
+            (Some(timeout), Some(stream)) if !conn.handshake_pending => {
+                stream
+                    .set_read_timeout(Some(timeout))
+                    .map_err(|e| into_cherr!(e.kind().clone()))?;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Restore the read timeout of `conn` after `set_call_timeout()`.
+    fn reset_call_timeout(conn: &Connection) {
+        if let Some(ref stream) = conn.stream {
+            let _ = stream.set_read_timeout(conn.read_timeout);
+        }
+    }
+
+    /// Give up on the replies of this call after its timeout expired, leaving them to
+    /// `skip_abandoned_replies()`, and hand the connection back for the next call.
+    ///
+    /// `partial` is the part of the reply read before the timeout.
+    fn abandon(
+        &mut self,
+        conn: &mut Connection,
+        reader: BufReader<Box<Read + Send + Sync>>,
+        partial: Vec<u8>,
+    ) -> ChainError<MError> {
+        conn.abandoned_calls += 1;
+        if !partial.is_empty() {
+            conn.abandoned_partial = partial;
+        }
+        self.continues = false;
+        conn.reader = Some(reader);
+        conn.writer = self.writer.take();
+        into_cherr!(ErrorKind::Io(io::ErrorKind::TimedOut))
+    }
+
+    /// The error for a failed read or write of `conn`.
+    ///
+    /// If the service was started with `Connection::with_activate()` and failed, this is
+    /// `ErrorKind::ServiceFailed`.
+    fn connection_error<E>(conn: &mut Connection, e: E) -> ChainError<MError>
+    where
+        MError: ChainErrorFrom<E>,
+    {
+        match conn.service_failed() {
+            Some(kind) => into_cherr!(kind),
+            None => into_cherr!(e),
+        }
+    }
+
+    fn send(
+        &mut self,
+        conn: &mut Connection,
+        oneway: bool,
+        more: bool,
+        upgrade: bool,
+    ) -> ChainResult<(), MError> {
         {
-            let mut conn = self.connection.write().unwrap();
             let mut req = match (self.method.take(), self.request.take()) {
                 (Some(method), Some(request)) => Request::create(
                     method,
@@ -980,6 +2368,15 @@ where
                 }
             };
 
+            if let Some(ref kind) = conn.keepalive_error {
+                return Err(into_cherr!(kind.clone()));
+            }
+
+            if conn.connection_lost && conn.retry.is_some() {
+                conn.reconnect()
+                    .map_err(|e| into_cherr!(e.kind().clone()))?;
+            }
+
             if conn.reader.is_none() || conn.writer.is_none() {
                 return Err(into_cherr!(ErrorKind::ConnectionBusy));
             }
@@ -998,61 +2395,326 @@ where
                 req.upgrade = Some(true);
             }
 
-            let mut w = conn.writer.take().unwrap();
-
             let b = serde_json::to_string(&req).map_err(minto_cherr!())? + "\0";
 
-            w.write_all(b.as_bytes()).map_err(minto_cherr!())?;
-            w.flush().map_err(minto_cherr!())?;
+            if oneway {
+                if let Some(ref mut buf) = conn.oneway_buffer {
+                    buf.extend_from_slice(b.as_bytes());
+                    return Ok(());
+                }
+            }
+
+            if conn.retry.is_some() && !oneway && !more && !upgrade {
+                self.sent = Some(b.clone());
+            }
+
+            let mut w = conn.writer.take().unwrap();
+
+            match conn.oneway_buffer {
+                Some(ref mut buf) if !buf.is_empty() => {
+                    buf.extend_from_slice(b.as_bytes());
+                    let r = w.write_all(buf);
+                    buf.clear();
+                    r
+                }
+                _ => w.write_all(b.as_bytes()),
+            }
+            .and_then(|_| w.flush())
+            .map_err(|e| {
+                conn.connection_lost = is_connection_lost(&e);
+                Self::connection_error(conn, e)
+            })?;
             if oneway {
                 conn.writer = Some(w);
             } else {
+                if conn.latency_observer.is_some() {
+                    let now = Instant::now();
+                    self.latency = Some(LatencyTimer {
+                        method: req.method.into_owned(),
+                        replies: 0,
+                        sent: now,
+                        previous: now,
+                    });
+                }
                 self.writer = Some(w);
             }
         }
         Ok(())
     }
 
+    /// Send the request of a `call()` again on a new connection, after the connection was
+    /// lost, see [Connection::new_with_retry](struct.Connection.html#method.new_with_retry).
+    fn resend(&mut self, conn: &mut Connection) -> ChainResult<(), MError> {
+        let request = match self.sent.take() {
+            Some(request) => request,
+            None => return Err(into_cherr!(ErrorKind::ConnectionClosed)),
+        };
+        conn.reconnect()
+            .map_err(|e| into_cherr!(e.kind().clone()))?;
+        self.reader = conn.reader.take();
+        let mut w = conn.writer.take().unwrap();
+        w.write_all(request.as_bytes())
+            .and_then(|_| w.flush())
+            .map_err(|e| {
+                conn.connection_lost = is_connection_lost(&e);
+                Self::connection_error(conn, e)
+            })?;
+        self.writer = Some(w);
+        Ok(())
+    }
+
+    /// Send the request and receive the reply.
+    ///
+    /// The connection is locked for the whole call, so calls of clients sharing the
+    /// connection from different threads are serialized.
     pub fn call(&mut self) -> ChainResult<MReply, MError> {
-        self.send(false, false, false)?;
-        self.recv()
+        self.locked(|call, conn| {
+            let r = call
+                .send(conn, false, false, false)
+                .and_then(|_| call.recv_locked(conn));
+            if r.is_err() && conn.connection_lost && call.sent.is_some() {
+                call.resend(conn)?;
+                return call.recv_locked(conn);
+            }
+            call.sent = None;
+            r
+        })
     }
 
     pub fn upgrade(&mut self) -> ChainResult<MReply, MError> {
-        self.send(false, false, true)?;
-        self.recv()
+        self.locked(|call, conn| {
+            call.send(conn, false, false, true)?;
+            call.recv_locked(conn)
+        })
     }
 
     pub fn oneway(&mut self) -> ChainResult<(), MError> {
-        self.send(true, false, false)
+        let connection = self.connection.clone();
+        let mut conn = connection.write().unwrap();
+        self.send(&mut conn, true, false, false)
     }
 
     pub fn more(&mut self) -> ChainResult<&mut Self, MError> {
         self.continues = true;
-        self.send(false, true, false)?;
+        let connection = self.connection.clone();
+        let mut conn = connection.write().unwrap();
+        self.send(&mut conn, false, true, false)?;
         Ok(self)
     }
 
     pub fn recv(&mut self) -> ChainResult<MReply, MError> {
+        self.locked(|call, conn| call.recv_locked(conn))
+    }
+
+    /// Run `f` on the locked connection and pass the latency of a reply received by `f` to
+    /// the observer, after the connection is unlocked.
+    fn locked<R, F>(&mut self, f: F) -> R
+    where
+        F: FnOnce(&mut Self, &mut Connection) -> R,
+    {
+        let connection = self.connection.clone();
+        let r = f(self, &mut connection.write().unwrap());
+        if let Some((observer, latency)) = self.observed.take() {
+            observer(&latency);
+        }
+        r
+    }
+
+    fn recv_locked(&mut self, conn: &mut Connection) -> ChainResult<MReply, MError> {
         if self.reader.is_none() || self.writer.is_none() {
             return Err(into_cherr!(ErrorKind::IteratorOldReply));
         }
 
-        let mut buf = Vec::new();
+        let handshake_pending = conn.handshake_pending;
+        let read_buffer_capacity = conn.read_buffer_capacity;
+        let call_timeout = self.set_call_timeout(conn)?;
 
         let mut reader = self.reader.take().unwrap();
-        reader.read_until(0, &mut buf).map_err(minto_cherr!())?;
+        let mut buf = ::std::mem::replace(&mut conn.read_buffer, Vec::new());
+        let r = skip_abandoned_replies(
+            &mut reader,
+            &mut conn.abandoned_calls,
+            &mut conn.abandoned_partial,
+        )
+        .and_then(|_| read_message(&mut reader, &mut buf, read_buffer_capacity));
+        if call_timeout {
+            Self::reset_call_timeout(conn);
+        }
+        match r {
+            Err(ref e)
+                if handshake_pending
+                    && (e.kind() == io::ErrorKind::WouldBlock
+                        || e.kind() == io::ErrorKind::TimedOut) =>
+            {
+                return Err(into_cherr!(ErrorKind::Timeout));
+            }
+            Err(ref e)
+                if call_timeout
+                    && (e.kind() == io::ErrorKind::WouldBlock
+                        || e.kind() == io::ErrorKind::TimedOut) =>
+            {
+                return Err(self.abandon(conn, reader, buf));
+            }
+            r => r.map_err(|e| {
+                conn.connection_lost = is_connection_lost(&e);
+                Self::connection_error(conn, e)
+            })?,
+        };
         self.reader = Some(reader);
         if buf.is_empty() {
-            return Err(into_cherr!(ErrorKind::ConnectionClosed));
+            conn.connection_lost = true;
+            return Err(Self::connection_error(conn, ErrorKind::ConnectionClosed));
+        }
+        self.observe_latency(conn);
+        if handshake_pending {
+            Self::handshake_done(conn);
         }
         buf.pop();
+        if nesting_too_deep(&buf, conn.max_nesting_depth) {
+            return Err(into_cherr!(ErrorKind::NestingTooDeep(
+                conn.max_nesting_depth
+            )));
+        }
         let reply: Reply = serde_json::from_slice(&buf).map_err(minto_cherr!())?;
+        conn.read_buffer = buf;
+        self.reply_parameters(conn, reply)
+    }
+
+    /// Like `call()`, but passes the elements of the array parameter `field` of the reply to
+    /// `f` one at a time, as they are parsed from the connection.
+    ///
+    /// The elements are never collected, so arbitrarily large arrays can be processed with
+    /// bounded memory. In the returned reply, `field` is left empty.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,ignore
+    /// let mut count = 0;
+    /// iface
+    ///     .list_files(path)
+    ///     .call_streaming("files", |file: String| count += file.len())?;
+    /// ```
+    pub fn call_streaming<T, F>(&mut self, field: &str, f: F) -> ChainResult<MReply, MError>
+    where
+        T: DeserializeOwned,
+        F: FnMut(T),
+    {
+        self.locked(|call, conn| {
+            call.send(conn, false, false, false)?;
+            call.recv_streaming_locked(conn, field, f)
+        })
+    }
+
+    /// Like `recv()`, but passes the elements of the array parameter `field` of the reply to
+    /// `f` one at a time, as they are parsed from the connection.
+    ///
+    /// The elements are never collected, so arbitrarily large arrays can be processed with
+    /// bounded memory. In the returned reply, `field` is left empty.
+    pub fn recv_streaming<T, F>(&mut self, field: &str, f: F) -> ChainResult<MReply, MError>
+    where
+        T: DeserializeOwned,
+        F: FnMut(T),
+    {
+        self.locked(|call, conn| call.recv_streaming_locked(conn, field, f))
+    }
+
+    fn recv_streaming_locked<T, F>(
+        &mut self,
+        conn: &mut Connection,
+        field: &str,
+        f: F,
+    ) -> ChainResult<MReply, MError>
+    where
+        T: DeserializeOwned,
+        F: FnMut(T),
+    {
+        if self.reader.is_none() || self.writer.is_none() {
+            return Err(into_cherr!(ErrorKind::IteratorOldReply));
+        }
+
+        let handshake_pending = conn.handshake_pending;
+        let call_timeout = self.set_call_timeout(conn)?;
+
+        let mut reader = self.reader.take().unwrap();
+        let r = skip_abandoned_replies(
+            &mut reader,
+            &mut conn.abandoned_calls,
+            &mut conn.abandoned_partial,
+        )
+        .and_then(|_| reader.fill_buf().map(|b| b.is_empty()));
+        if call_timeout {
+            Self::reset_call_timeout(conn);
+        }
+        let closed = match r {
+            Err(ref e)
+                if handshake_pending
+                    && (e.kind() == io::ErrorKind::WouldBlock
+                        || e.kind() == io::ErrorKind::TimedOut) =>
+            {
+                return Err(into_cherr!(ErrorKind::Timeout));
+            }
+            Err(ref e)
+                if call_timeout
+                    && (e.kind() == io::ErrorKind::WouldBlock
+                        || e.kind() == io::ErrorKind::TimedOut) =>
+            {
+                return Err(self.abandon(conn, reader, Vec::new()));
+            }
+            r => r.map_err(|e| {
+                conn.connection_lost = is_connection_lost(&e);
+                Self::connection_error(conn, e)
+            })?,
+        };
+        if closed {
+            self.reader = Some(reader);
+            conn.connection_lost = true;
+            return Err(Self::connection_error(conn, ErrorKind::ConnectionClosed));
+        }
+        if handshake_pending {
+            Self::handshake_done(conn);
+        }
+        let reply = stream::read_streaming_reply(&mut reader, field, f);
+        self.reader = Some(reader);
+        self.observe_latency(conn);
+        let reply = reply.map_err(minto_cherr!())?;
+        self.reply_parameters(conn, reply)
+    }
+
+    fn observe_latency(&mut self, conn: &Connection) {
+        if let (Some(ref mut timer), Some(ref observer)) =
+            (&mut self.latency, &conn.latency_observer)
+        {
+            let now = Instant::now();
+            self.observed = Some((
+                observer.clone(),
+                CallLatency {
+                    method: timer.method.clone(),
+                    reply: timer.replies,
+                    elapsed: now.duration_since(timer.sent),
+                    since_previous: now.duration_since(timer.previous),
+                },
+            ));
+            timer.replies += 1;
+            timer.previous = now;
+        }
+    }
+
+    fn handshake_done(conn: &mut Connection) {
+        conn.handshake_pending = false;
+        if let Some(ref stream) = conn.stream {
+            let _ = stream.set_read_timeout(conn.read_timeout);
+        }
+    }
+
+    fn reply_parameters(
+        &mut self,
+        conn: &mut Connection,
+        reply: Reply,
+    ) -> ChainResult<MReply, MError> {
         match reply.continues {
             Some(true) => self.continues = true,
             _ => {
                 self.continues = false;
-                let mut conn = self.connection.write().unwrap();
                 conn.reader = self.reader.take();
                 conn.writer = self.writer.take();
             }
@@ -1100,6 +2762,202 @@ where
     }
 }
 
+impl<MRequest, MReply, MError> MethodCall<MRequest, MReply, MError>
+where
+    MRequest: Serialize,
+    MReply: DeserializeOwned + VarlinkReplyValues,
+    MError: chainerror::ChainErrorFrom<ErrorKind>
+        + chainerror::ChainErrorFrom<Reply>
+        + chainerror::ChainErrorFrom<serde_json::error::Error>
+        + chainerror::ChainErrorFrom<::std::io::Error>,
+{
+    /// Iterate over the values of the replies instead of the reply structs.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,ignore
+    /// let strings = iface
+    ///     .test10(client_id, mytype)
+    ///     .more()?
+    ///     .values()
+    ///     .collect::<Result<Vec<String>>>()?;
+    /// ```
+    pub fn values(&mut self) -> impl Iterator<Item = ChainResult<MReply::Values, MError>> + '_ {
+        self.map(|reply| reply.map(VarlinkReplyValues::into_values))
+    }
+}
+
+impl<MRequest, MReply, MError> MethodCall<MRequest, MReply, MError>
+where
+    MRequest: Serialize,
+    MReply: DeserializeOwned,
+    MError: chainerror::ChainErrorFrom<ErrorKind>
+        + chainerror::ChainErrorFrom<Reply>
+        + chainerror::ChainErrorFrom<serde_json::error::Error>
+        + chainerror::ChainErrorFrom<::std::io::Error>
+        + VarlinkErrorReply
+        + ::std::fmt::Display
+        + ::std::fmt::Debug
+        + 'static,
+{
+    /// Pass the replies of this `more()` call on to `call`, each mapped by `f`.
+    ///
+    /// This is for a proxy, which streams the replies of the called service to its own caller.
+    /// The `continues` flag of each reply to `call` follows the one of the received reply, so
+    /// `call` has to be a `more` call, too. If the called service replies with an error, also
+    /// in the middle of the stream, the error reply is passed on as the last reply to `call`.
+    /// Other errors, like a closed connection, are returned without a reply to `call`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,ignore
+    /// fn test10(&self, call: &mut Call_Test10, client_id: String, mytype: MyType) -> varlink::Result<()> {
+    ///     let mut upstream = self.upstream.lock().unwrap();
+    ///     upstream
+    ///         .test10(client_id, mytype)
+    ///         .more()
+    ///         .map_err(|e| cherr!(e, varlink::ErrorKind::Server))?
+    ///         .pump(call, |reply| Test10_Reply { string: reply.string.to_uppercase() })
+    /// }
+    /// ```
+    pub fn pump<C, R, F>(&mut self, call: &mut C, mut f: F) -> Result<()>
+    where
+        C: CallTrait + ?Sized,
+        R: Into<Reply>,
+        F: FnMut(MReply) -> R,
+    {
+        while self.continues {
+            match self.recv() {
+                Ok(reply) => {
+                    call.set_continues(self.continues);
+                    call.reply_struct(f(reply).into())?;
+                }
+                Err(e) => {
+                    call.set_continues(false);
+                    return match MError::into_reply(&e) {
+                        Some(reply) => call.reply_struct(reply),
+                        None => Err(cherr!(e, ErrorKind::Server)),
+                    };
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<MRequest, MReply, MError> MethodCall<MRequest, MReply, MError>
+where
+    MRequest: Serialize,
+    MReply: DeserializeOwned,
+    MError: chainerror::ChainErrorFrom<ErrorKind>
+        + chainerror::ChainErrorFrom<Reply>
+        + chainerror::ChainErrorFrom<serde_json::error::Error>
+        + chainerror::ChainErrorFrom<::std::io::Error>,
+{
+    /// Map the errors of the call to the errors of the calling service.
+    ///
+    /// `f` gets the error kind of the called service and returns the error kind of the caller.
+    /// The original error is kept as the cause of the mapped error, so it can still be found
+    /// with `find_chain_cause()`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,ignore
+    /// let user = users
+    ///     .lookup(name)
+    ///     .map_error(|e| match e {
+    ///         org_example_users::ErrorKind::NotFound(_) => ErrorKind::UnknownOwner(None),
+    ///         _ => ErrorKind::BackendFailed(None),
+    ///     })
+    ///     .call()?;
+    /// ```
+    pub fn map_error<E, F>(self, f: F) -> MapError<MRequest, MReply, MError, F>
+    where
+        F: Fn(&MError) -> E,
+    {
+        MapError { call: self, f }
+    }
+}
+
+/// A [MethodCall](struct.MethodCall.html) with its errors mapped by
+/// [map_error](struct.MethodCall.html#method.map_error).
+pub struct MapError<MRequest, MReply, MError, F>
+where
+    MRequest: Serialize,
+    MReply: DeserializeOwned,
+    MError: chainerror::ChainErrorFrom<ErrorKind>
+        + chainerror::ChainErrorFrom<Reply>
+        + chainerror::ChainErrorFrom<serde_json::error::Error>
+        + chainerror::ChainErrorFrom<::std::io::Error>,
+{
+    call: MethodCall<MRequest, MReply, MError>,
+    f: F,
+}
+
+impl<MRequest, MReply, MError, E, F> MapError<MRequest, MReply, MError, F>
+where
+    MRequest: Serialize,
+    MReply: DeserializeOwned,
+    MError: chainerror::ChainErrorFrom<ErrorKind>
+        + chainerror::ChainErrorFrom<Reply>
+        + chainerror::ChainErrorFrom<serde_json::error::Error>
+        + chainerror::ChainErrorFrom<::std::io::Error>
+        + ::std::fmt::Display
+        + ::std::fmt::Debug
+        + 'static,
+    E: ::std::fmt::Display + ::std::fmt::Debug + 'static,
+    F: Fn(&MError) -> E,
+{
+    fn map_err_kind(&self, e: ChainError<MError>) -> ChainError<E> {
+        let kind = (self.f)(e.kind());
+        cherr!(e, kind)
+    }
+
+    pub fn call(&mut self) -> ChainResult<MReply, E> {
+        self.call.call().map_err(|e| self.map_err_kind(e))
+    }
+
+    pub fn upgrade(&mut self) -> ChainResult<MReply, E> {
+        self.call.upgrade().map_err(|e| self.map_err_kind(e))
+    }
+
+    pub fn oneway(&mut self) -> ChainResult<(), E> {
+        self.call.oneway().map_err(|e| self.map_err_kind(e))
+    }
+
+    pub fn more(&mut self) -> ChainResult<&mut Self, E> {
+        match self.call.more() {
+            Ok(_) => Ok(self),
+            Err(e) => Err(self.map_err_kind(e)),
+        }
+    }
+
+    pub fn recv(&mut self) -> ChainResult<MReply, E> {
+        self.call.recv().map_err(|e| self.map_err_kind(e))
+    }
+}
+
+impl<MRequest, MReply, MError, E, F> Iterator for MapError<MRequest, MReply, MError, F>
+where
+    MRequest: Serialize,
+    MReply: DeserializeOwned,
+    MError: chainerror::ChainErrorFrom<ErrorKind>
+        + chainerror::ChainErrorFrom<Reply>
+        + chainerror::ChainErrorFrom<serde_json::error::Error>
+        + chainerror::ChainErrorFrom<::std::io::Error>
+        + ::std::fmt::Display
+        + ::std::fmt::Debug
+        + 'static,
+    E: ::std::fmt::Display + ::std::fmt::Debug + 'static,
+    F: Fn(&MError) -> E,
+{
+    type Item = ChainResult<MReply, E>;
+    fn next(&mut self) -> Option<ChainResult<MReply, E>> {
+        let reply = self.call.next()?;
+        Some(reply.map_err(|e| self.map_err_kind(e)))
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq, Default, Clone)]
 pub struct GetInterfaceDescriptionArgs<'a> {
     pub interface: Cow<'a, str>,
@@ -1171,6 +3029,7 @@ impl OrgVarlinkServiceInterface for OrgVarlinkServiceClient {
 pub struct VarlinkService {
     info: ServiceInfo,
     ifaces: HashMap<Cow<'static, str>, Box<Interface + Send + Sync>>,
+    max_nesting_depth: usize,
 }
 
 impl Interface for VarlinkService {
@@ -1204,6 +3063,12 @@ error MethodNotImplemented (method: string)
 
 # One of the passed parameters is invalid.
 error InvalidParameter (parameter: string)
+
+# Permission is denied.
+error PermissionDenied ()
+
+# Method is expected to be called with 'more' set to true, but wasn't
+error ExpectedMore ()
 "#
     }
 
@@ -1318,9 +3183,26 @@ impl VarlinkService {
                 interfaces: ifnames,
             },
             ifaces: ifhashmap,
+            max_nesting_depth: DEFAULT_MAX_NESTING_DEPTH,
         }
     }
 
+    /// Set the maximum nesting depth of the JSON objects and arrays in a request.
+    ///
+    /// A deeper request is rejected with `ErrorKind::NestingTooDeep`, before it is parsed,
+    /// and the connection is closed. The default is
+    /// [DEFAULT_MAX_NESTING_DEPTH](constant.DEFAULT_MAX_NESTING_DEPTH.html).
+    ///
+    /// # Examples
+    ///
+    /// ```rust,ignore
+    /// let mut service = varlink::VarlinkService::new(/* ... */);
+    /// service.set_max_nesting_depth(16);
+    /// ```
+    pub fn set_max_nesting_depth(&mut self, depth: usize) {
+        self.max_nesting_depth = depth;
+    }
+
     fn call(&self, iface: &str, call: &mut Call) -> Result<()> {
         match iface {
             "org.varlink.service" => self::Interface::call(self, call),
@@ -1361,6 +3243,19 @@ pub trait ConnectionHandler {
         writer: &mut Write,
         upgraded_iface: Option<String>,
     ) -> Result<(Vec<u8>, Option<String>)>;
+
+    /// Like `handle()`, but passes the connection `context` on to the called methods.
+    ///
+    /// The receive time of every request is filled in by the handler.
+    fn handle_with_context(
+        &self,
+        bufreader: &mut BufRead,
+        writer: &mut Write,
+        upgraded_iface: Option<String>,
+        _context: &CallContext,
+    ) -> Result<(Vec<u8>, Option<String>)> {
+        self.handle(bufreader, writer, upgraded_iface)
+    }
 }
 
 impl ConnectionHandler for VarlinkService {
@@ -1374,6 +3269,9 @@ impl ConnectionHandler for VarlinkService {
     /// Returns Ok(true), if the connection is ```upgraded```. For ```upgraded``` connections
     /// messages are in legacy format and
     ///
+    /// Returns an `ErrorKind::ConnectionClosed` error, if a method requested the connection to
+    /// be closed with `request_connection_close()`.
+    ///
     /// # Examples
     //
     /// ```rust
@@ -1400,16 +3298,38 @@ impl ConnectionHandler for VarlinkService {
         bufreader: &mut BufRead,
         writer: &mut Write,
         upgraded_last_interface: Option<String>,
+    ) -> Result<(Vec<u8>, Option<String>)> {
+        self.handle_with_context(
+            bufreader,
+            writer,
+            upgraded_last_interface,
+            &CallContext::default(),
+        )
+    }
+
+    fn handle_with_context(
+        &self,
+        bufreader: &mut BufRead,
+        writer: &mut Write,
+        upgraded_last_interface: Option<String>,
+        context: &CallContext,
     ) -> Result<(Vec<u8>, Option<String>)> {
         let mut upgraded_iface = upgraded_last_interface.clone();
+        // reused for all requests, so it only grows for a request longer than all before
+        let mut buf = Vec::new();
         loop {
             if let Some(iface) = upgraded_iface {
                 let mut call = Call::new_upgraded(writer);
+                call.context = CallContext {
+                    received: Some(SystemTime::now()),
+                    upgraded_interface: Some(iface.clone()),
+                    ..context.clone()
+                };
                 let unread = self.call_upgraded(&iface, &mut call, bufreader)?;
                 return Ok((unread, Some(iface)));
             }
 
-            let mut buf = Vec::new();
+            buf.clear();
             let len = bufreader
                 .read_until(b'\0', &mut buf)
                 .map_err(minto_cherr!())?;
@@ -1419,6 +3339,8 @@ impl ConnectionHandler for VarlinkService {
                 return Ok((buf, None));
             }
 
+            let received = SystemTime::now();
+
             if buf.get(len - 1).unwrap_or(&b'x') != &b'\0' {
                 // Incomplete message
                 return Ok((buf, None));
@@ -1427,6 +3349,12 @@ impl ConnectionHandler for VarlinkService {
             // pop the last zero byte
             buf.pop();
 
+            if nesting_too_deep(&buf, self.max_nesting_depth) {
+                return Err(into_cherr!(ErrorKind::NestingTooDeep(
+                    self.max_nesting_depth
+                )));
+            }
+
             let req: Request = serde_json::from_slice(&buf).map_err(|e| {
                 cherr!(
                     e,
@@ -1438,6 +3366,10 @@ impl ConnectionHandler for VarlinkService {
                 None => {
                     let method: String = String::from(req.method.as_ref());
                     let mut call = Call::new(writer, &req);
+                    call.context = CallContext {
+                        received: Some(received),
+                        ..context.clone()
+                    };
                     call.reply_interface_not_found(Some(method))?;
                     return Ok((Vec::new(), None));
                 }
@@ -1447,8 +3379,16 @@ impl ConnectionHandler for VarlinkService {
             let iface = String::from(&req.method[..n]);
 
             let mut call = Call::new(writer, &req);
+            call.context = CallContext {
+                received: Some(received),
+                ..context.clone()
+            };
             self.call(&iface, &mut call)?;
 
+            if call.close {
+                return Err(into_cherr!(ErrorKind::ConnectionClosed));
+            }
+
             if call.upgraded {
                 upgraded_iface = Some(iface);
                 break;
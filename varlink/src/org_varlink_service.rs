@@ -0,0 +1,53 @@
+//! Client helpers for the `org.varlink.service` interface, which every varlink service
+//! implements
+//!
+//! A tool can discover the interfaces of a service and their descriptions without
+//! generating bindings.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use varlink::{org_varlink_service, Connection};
+//!
+//! let connection = Connection::with_address("unix:/run/org.example.ftl")?;
+//! let info = org_varlink_service::get_info(&connection)?;
+//! for interface in info.interfaces {
+//!     let description = org_varlink_service::get_interface_description(&connection, &interface)?;
+//!     println!("{}", description);
+//! }
+//! # Ok::<(), varlink::Error>(())
+//! ```
+
+use std::sync::{Arc, RwLock};
+
+use chainerror::*;
+
+use crate::{Connection, ErrorKind, Result};
+pub use crate::{
+    GetInterfaceDescriptionReply, OrgVarlinkServiceClient, OrgVarlinkServiceInterface, ServiceInfo,
+};
+
+/// Call `org.varlink.service.GetInfo`, returning the vendor, product, version and url of the
+/// service and the interfaces it implements.
+pub fn get_info(connection: &Arc<RwLock<Connection>>) -> Result<ServiceInfo> {
+    OrgVarlinkServiceClient::new(connection.clone()).get_info()
+}
+
+/// Call `org.varlink.service.GetInterfaceDescription`, returning the description of
+/// `interface` in the varlink interface definition language.
+///
+/// If the service does not implement `interface`, it replies with
+/// `ErrorKind::InvalidParameter("interface")`.
+pub fn get_interface_description(
+    connection: &Arc<RwLock<Connection>>,
+    interface: &str,
+) -> Result<String> {
+    let reply = OrgVarlinkServiceClient::new(connection.clone())
+        .get_interface_description(interface.to_string())?;
+    match reply.description {
+        Some(description) => Ok(description),
+        None => Err(into_cherr!(ErrorKind::SerdeJsonDe(
+            "missing field `description`".into()
+        ))),
+    }
+}
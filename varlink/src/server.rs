@@ -6,10 +6,14 @@ use chainerror::*;
 
 //#![feature(getpid)]
 //use std::process;
-use std::io::{BufRead, BufReader, Read, Write};
-use std::net::{Shutdown, TcpListener, TcpStream};
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{Ipv4Addr, Ipv6Addr, Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::path::Path;
 use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{mpsc, Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
 use std::{env, fs, thread};
 
 #[cfg(unix)]
@@ -24,6 +28,9 @@ use std::os::windows::io::{AsRawSocket, FromRawSocket, IntoRawSocket, RawSocket}
 
 use std::mem;
 
+#[cfg(feature = "tls")]
+use crate::tls::{TlsServerConfig, TlsStream};
+
 #[derive(Debug)]
 pub enum Listener {
     TCP(Option<TcpListener>, bool),
@@ -34,6 +41,64 @@ pub enum Listener {
 pub enum Stream {
     TCP(TcpStream),
     UNIX(UnixStream),
+    /// A TLS session, accepted on a `tcps:` address.
+    #[cfg(feature = "tls")]
+    TLS(TlsStream),
+}
+
+#[cfg(target_os = "linux")]
+fn get_peer_credentials(fd: RawFd) -> Option<crate::PeerCredentials> {
+    let mut ucred = libc::ucred {
+        pid: 0,
+        uid: 0,
+        gid: 0,
+    };
+    let mut len = mem::size_of::<libc::ucred>() as libc::socklen_t;
+
+    let ret = unsafe {
+        libc::getsockopt(
+            fd,
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            &mut ucred as *mut libc::ucred as *mut libc::c_void,
+            &mut len,
+        )
+    };
+
+    if ret != 0 {
+        return None;
+    }
+
+    Some(crate::PeerCredentials {
+        pid: ucred.pid,
+        uid: ucred.uid,
+        gid: ucred.gid,
+    })
+}
+
+/// Enable `SO_PASSCRED` on the unix socket `fd`.
+///
+/// With the option set, the kernel attaches the credentials of the sender to the received
+/// messages, so the credentials are available independent of the kernel version and
+/// the socket type of the peer.
+#[cfg(target_os = "linux")]
+fn set_pass_credentials(fd: RawFd) -> io::Result<()> {
+    let one: libc::c_int = 1;
+
+    let ret = unsafe {
+        libc::setsockopt(
+            fd,
+            libc::SOL_SOCKET,
+            libc::SO_PASSCRED,
+            &one as *const libc::c_int as *const libc::c_void,
+            mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+
+    if ret != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
 }
 
 impl<'a> Stream {
@@ -48,12 +113,33 @@ impl<'a> Stream {
                 Box::new(s.try_clone().map_err(minto_cherr!())?),
                 Box::new(s.try_clone().map_err(minto_cherr!())?),
             )),
+            #[cfg(feature = "tls")]
+            Stream::TLS(ref mut s) => Ok((
+                Box::new(s.try_clone().map_err(minto_cherr!())?),
+                Box::new(s.try_clone().map_err(minto_cherr!())?),
+            )),
         }
     }
     pub fn shutdown(&mut self) -> Result<()> {
         match *self {
             Stream::TCP(ref mut s) => s.shutdown(Shutdown::Both).map_err(minto_cherr!())?,
             Stream::UNIX(ref mut s) => s.shutdown(Shutdown::Both).map_err(minto_cherr!())?,
+            #[cfg(feature = "tls")]
+            Stream::TLS(ref mut s) => s.shutdown().map_err(minto_cherr!())?,
+        }
+        Ok(())
+    }
+
+    /// Stop receiving on the connection, replies can still be sent.
+    fn shutdown_read(&mut self) -> Result<()> {
+        match *self {
+            Stream::TCP(ref mut s) => s.shutdown(Shutdown::Read).map_err(minto_cherr!())?,
+            Stream::UNIX(ref mut s) => s.shutdown(Shutdown::Read).map_err(minto_cherr!())?,
+            #[cfg(feature = "tls")]
+            Stream::TLS(ref mut s) => s
+                .socket()
+                .shutdown(Shutdown::Read)
+                .map_err(minto_cherr!())?,
         }
         Ok(())
     }
@@ -62,6 +148,8 @@ impl<'a> Stream {
         match *self {
             Stream::TCP(ref mut s) => Ok(Stream::TCP(s.try_clone()?)),
             Stream::UNIX(ref mut s) => Ok(Stream::UNIX(s.try_clone()?)),
+            #[cfg(feature = "tls")]
+            Stream::TLS(ref mut s) => Ok(Stream::TLS(s.try_clone()?)),
         }
     }
 
@@ -75,14 +163,60 @@ impl<'a> Stream {
                 s.set_nonblocking(b).map_err(minto_cherr!())?;
                 Ok(())
             }
+            #[cfg(feature = "tls")]
+            Stream::TLS(ref mut s) => {
+                s.socket().set_nonblocking(b).map_err(minto_cherr!())?;
+                Ok(())
+            }
         }
     }
 
+    /// Set `SO_LINGER`, see
+    /// [VarlinkStream::set_linger](struct.VarlinkStream.html#method.set_linger).
+    #[cfg(unix)]
+    pub fn set_linger(&mut self, linger: Option<Duration>) -> Result<()> {
+        crate::client::set_linger(self.as_raw_fd(), linger)
+    }
+
+    #[cfg(windows)]
+    pub fn set_linger(&mut self, _linger: Option<Duration>) -> Result<()> {
+        Err(into_cherr!(ErrorKind::MethodNotImplemented(
+            "set_linger".into()
+        )))
+    }
+
+    /// Run the TLS handshake on an accepted TCP connection with `config`.
+    #[cfg(feature = "tls")]
+    pub fn accept_tls(self, config: &TlsServerConfig) -> Result<Stream> {
+        match self {
+            Stream::TCP(s) => Ok(Stream::TLS(config.accept(s)?)),
+            _ => Err(into_cherr!(ErrorKind::InvalidAddress)),
+        }
+    }
+
+    /// The credentials of the peer process, if this is a unix socket connection.
+    #[cfg(target_os = "linux")]
+    pub fn peer_credentials(&self) -> Option<crate::PeerCredentials> {
+        match *self {
+            Stream::TCP(_) => None,
+            Stream::UNIX(ref s) => get_peer_credentials(s.as_raw_fd()),
+            #[cfg(feature = "tls")]
+            Stream::TLS(_) => None,
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn peer_credentials(&self) -> Option<crate::PeerCredentials> {
+        None
+    }
+
     #[cfg(unix)]
     pub fn as_raw_fd(&mut self) -> RawFd {
         match *self {
             Stream::TCP(ref mut s) => s.as_raw_fd(),
             Stream::UNIX(ref mut s) => s.as_raw_fd(),
+            #[cfg(feature = "tls")]
+            Stream::TLS(ref mut s) => s.socket().as_raw_fd(),
         }
     }
 
@@ -91,6 +225,8 @@ impl<'a> Stream {
         match self {
             Stream::TCP(ref mut s) => s.as_raw_socket(),
             Stream::UNIX(ref mut s) => s.as_raw_socket(),
+            #[cfg(feature = "tls")]
+            Stream::TLS(ref mut s) => s.socket().as_raw_socket(),
         }
     }
 }
@@ -100,6 +236,8 @@ impl ::std::io::Write for Stream {
         match *self {
             Stream::TCP(ref mut s) => s.write(buf),
             Stream::UNIX(ref mut s) => s.write(buf),
+            #[cfg(feature = "tls")]
+            Stream::TLS(ref mut s) => s.write(buf),
         }
     }
 
@@ -107,6 +245,8 @@ impl ::std::io::Write for Stream {
         match *self {
             Stream::TCP(ref mut s) => s.flush(),
             Stream::UNIX(ref mut s) => s.flush(),
+            #[cfg(feature = "tls")]
+            Stream::TLS(ref mut s) => s.flush(),
         }
     }
 
@@ -114,6 +254,8 @@ impl ::std::io::Write for Stream {
         match *self {
             Stream::TCP(ref mut s) => s.write_all(buf),
             Stream::UNIX(ref mut s) => s.write_all(buf),
+            #[cfg(feature = "tls")]
+            Stream::TLS(ref mut s) => s.write_all(buf),
         }
     }
 
@@ -121,6 +263,8 @@ impl ::std::io::Write for Stream {
         match *self {
             Stream::TCP(ref mut s) => s.write_fmt(fmt),
             Stream::UNIX(ref mut s) => s.write_fmt(fmt),
+            #[cfg(feature = "tls")]
+            Stream::TLS(ref mut s) => s.write_fmt(fmt),
         }
     }
 }
@@ -130,6 +274,8 @@ impl ::std::io::Read for Stream {
         match *self {
             Stream::TCP(ref mut s) => s.read(buf),
             Stream::UNIX(ref mut s) => s.read(buf),
+            #[cfg(feature = "tls")]
+            Stream::TLS(ref mut s) => s.read(buf),
         }
     }
 
@@ -137,6 +283,8 @@ impl ::std::io::Read for Stream {
         match *self {
             Stream::TCP(ref mut s) => s.read_to_end(buf),
             Stream::UNIX(ref mut s) => s.read_to_end(buf),
+            #[cfg(feature = "tls")]
+            Stream::TLS(ref mut s) => s.read_to_end(buf),
         }
     }
 
@@ -144,6 +292,8 @@ impl ::std::io::Read for Stream {
         match *self {
             Stream::TCP(ref mut s) => s.read_to_string(buf),
             Stream::UNIX(ref mut s) => s.read_to_string(buf),
+            #[cfg(feature = "tls")]
+            Stream::TLS(ref mut s) => s.read_to_string(buf),
         }
     }
 
@@ -151,8 +301,24 @@ impl ::std::io::Read for Stream {
         match *self {
             Stream::TCP(ref mut s) => s.read_exact(buf),
             Stream::UNIX(ref mut s) => s.read_exact(buf),
+            #[cfg(feature = "tls")]
+            Stream::TLS(ref mut s) => s.read_exact(buf),
+        }
+    }
+}
+
+/// The `host:port` part of a `tcp:` address, or of a `tcps:` address with the `tls` feature.
+fn tcp_address(address: &str) -> Option<&str> {
+    if address.starts_with("tcp:") {
+        return Some(&address[4..]);
+    }
+    #[cfg(feature = "tls")]
+    {
+        if address.starts_with("tcps:") {
+            return Some(&address[5..]);
         }
     }
+    None
 }
 
 fn activation_listener() -> Result<Option<usize>> {
@@ -211,17 +377,45 @@ fn get_abstract_unixlistener(addr: &str) -> Result<UnixListener> {
 
 #[cfg(not(any(target_os = "linux", target_os = "android")))]
 fn get_abstract_unixlistener(_addr: &str) -> Result<UnixListener> {
-    Err(into_cherr!(ErrorKind::InvalidAddress))
+    Err(crate::client::abstract_socket_unsupported())
+}
+
+/// Create the directory `dir` of a socket and its missing parents with the permission
+/// bits `mode`.
+fn create_socket_dir(dir: &Path, mode: u32) -> Result<()> {
+    let mut builder = fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::DirBuilderExt;
+        builder.mode(mode);
+    }
+    #[cfg(windows)]
+    let _ = mode;
+    builder.create(dir).map_err(minto_cherr!())
 }
 
 impl Listener {
     #[allow(clippy::new_ret_no_self)]
     pub fn new<S: ?Sized + AsRef<str>>(address: &S) -> Result<Self> {
+        Listener::bind(address, None, false)
+    }
+
+    /// Like `new()`, but create the missing parent directories of a `unix:` socket path with
+    /// the permission bits `create_dirs`, see
+    /// [ListenConfig::create_dirs](struct.ListenConfig.html#structfield.create_dirs), and
+    /// only replace a stale socket file with `replace_stale`, see
+    /// [ListenConfig::replace_stale](struct.ListenConfig.html#structfield.replace_stale).
+    pub fn bind<S: ?Sized + AsRef<str>>(
+        address: &S,
+        create_dirs: Option<u32>,
+        replace_stale: bool,
+    ) -> Result<Self> {
         let address = address.as_ref();
         if let Some(l) = activation_listener()? {
             #[cfg(windows)]
             {
-                if address.starts_with("tcp:") {
+                if tcp_address(address).is_some() {
                     unsafe {
                         return Ok(Listener::TCP(
                             Some(TcpListener::from_raw_socket(l as u64)),
@@ -241,7 +435,7 @@ impl Listener {
             }
             #[cfg(unix)]
             {
-                if address.starts_with("tcp:") {
+                if tcp_address(address).is_some() {
                     unsafe {
                         return Ok(Listener::TCP(
                             Some(TcpListener::from_raw_fd(l as i32)),
@@ -261,9 +455,10 @@ impl Listener {
             }
         }
 
-        if address.starts_with("tcp:") {
+        if let Some(addr) = tcp_address(address) {
+            let host_port = crate::client::tcp_host_port(addr)?;
             Ok(Listener::TCP(
-                Some(TcpListener::bind(&address[4..]).map_err(minto_cherr!())?),
+                Some(TcpListener::bind(host_port).map_err(minto_cherr!())?),
                 false,
             ))
         } else if address.starts_with("unix:") {
@@ -273,6 +468,23 @@ impl Listener {
                 return get_abstract_unixlistener(&addr)
                     .and_then(|v| Ok(Listener::UNIX(Some(v), false)));
             }
+            if let Some(dir) = Path::new(&addr).parent() {
+                if !dir.as_os_str().is_empty() && !dir.exists() {
+                    match create_dirs {
+                        Some(mode) => create_socket_dir(dir, mode)?,
+                        None => {
+                            return Err(into_cherr!(ErrorKind::SocketDirMissing(
+                                dir.display().to_string()
+                            )));
+                        }
+                    }
+                }
+            }
+            // A socket file left over by a crashed service can be replaced,
+            // but with `replace_stale` don't take the socket away from a running service.
+            if replace_stale && UnixStream::connect(&*addr).is_ok() {
+                return Err(into_cherr!(ErrorKind::Io(io::ErrorKind::AddrInUse)));
+            }
             // ignore error on non-existant file
             let _ = fs::remove_file(&*addr);
             Ok(Listener::UNIX(
@@ -382,6 +594,10 @@ impl Listener {
             }
             Listener::UNIX(Some(ref l), _) => {
                 let (s, _addr) = l.accept().map_err(minto_cherr!())?;
+                // Failing to enable SO_PASSCRED only affects this connection, which is still
+                // served with the credentials from SO_PEERCRED alone.
+                #[cfg(target_os = "linux")]
+                let _ = set_pass_credentials(s.as_raw_fd());
                 Ok(Stream::UNIX(s))
             }
             _ => Err(into_cherr!(ErrorKind::ConnectionClosed)),
@@ -461,10 +677,12 @@ enum Message {
     Terminate,
 }
 
-struct ThreadPool {
+pub(crate) struct ThreadPool {
     max_workers: usize,
     workers: Vec<Worker>,
     num_busy: Arc<RwLock<usize>>,
+    num_workers: Arc<RwLock<usize>>,
+    idle: Option<(usize, Duration)>,
     sender: mpsc::Sender<Message>,
     receiver: Arc<Mutex<mpsc::Receiver<Message>>>,
 }
@@ -484,12 +702,18 @@ type Job = Box<FnBox + Send + 'static>;
 impl ThreadPool {
     /// Create a new ThreadPool.
     ///
-    /// The initial_worker is the number of threads in the pool.
+    /// The initial_worker is the number of threads in the pool. With an `idle_timeout`,
+    /// the threads started additionally under load exit again, after they were idle for
+    /// `idle_timeout`.
     ///
     /// # Panics
     ///
     /// The `new` function will panic if the initial_worker is zero.
-    pub fn new(initial_worker: usize, max_workers: usize) -> ThreadPool {
+    pub fn new(
+        initial_worker: usize,
+        max_workers: usize,
+        idle_timeout: Option<Duration>,
+    ) -> ThreadPool {
         assert!(initial_worker > 0);
 
         let (sender, receiver) = mpsc::channel();
@@ -499,9 +723,16 @@ impl ThreadPool {
         let mut workers = Vec::with_capacity(initial_worker);
 
         let num_busy = Arc::new(RwLock::new(0 as usize));
+        let num_workers = Arc::new(RwLock::new(initial_worker));
+        let idle = idle_timeout.map(|timeout| (initial_worker, timeout));
 
         for _ in 0..initial_worker {
-            workers.push(Worker::new(Arc::clone(&receiver), Arc::clone(&num_busy)));
+            workers.push(Worker::new(
+                Arc::clone(&receiver),
+                Arc::clone(&num_busy),
+                Arc::clone(&num_workers),
+                idle,
+            ));
         }
 
         ThreadPool {
@@ -510,6 +741,8 @@ impl ThreadPool {
             sender,
             receiver,
             num_busy,
+            num_workers,
+            idle,
         }
     }
 
@@ -519,11 +752,15 @@ impl ThreadPool {
     {
         let job = Box::new(f);
         self.sender.send(Message::NewJob(job)).unwrap();
-        if ((self.num_busy() + 1) >= self.workers.len()) && (self.workers.len() <= self.max_workers)
-        {
+        self.workers.retain(|worker| !worker.retired());
+        let mut num_workers = self.num_workers.write().unwrap();
+        if ((self.num_busy() + 1) >= *num_workers) && (*num_workers <= self.max_workers) {
+            *num_workers += 1;
             self.workers.push(Worker::new(
                 Arc::clone(&self.receiver),
                 Arc::clone(&self.num_busy),
+                Arc::clone(&self.num_workers),
+                self.idle,
             ));
         }
     }
@@ -532,6 +769,11 @@ impl ThreadPool {
         let num_busy = self.num_busy.read().unwrap();
         *num_busy
     }
+
+    /// The number of running worker threads.
+    pub fn num_workers(&self) -> usize {
+        *self.num_workers.read().unwrap()
+    }
 }
 
 impl Drop for ThreadPool {
@@ -550,12 +792,43 @@ impl Drop for ThreadPool {
 
 struct Worker {
     thread: Option<thread::JoinHandle<()>>,
+    retired: Arc<AtomicBool>,
 }
 
 impl Worker {
-    fn new(receiver: Arc<Mutex<mpsc::Receiver<Message>>>, num_busy: Arc<RwLock<usize>>) -> Worker {
+    /// Start a worker thread.
+    ///
+    /// With `idle` set to `(min_workers, timeout)`, the thread exits after waiting `timeout`
+    /// for a job, as long as more than `min_workers` threads are running.
+    fn new(
+        receiver: Arc<Mutex<mpsc::Receiver<Message>>>,
+        num_busy: Arc<RwLock<usize>>,
+        num_workers: Arc<RwLock<usize>>,
+        idle: Option<(usize, Duration)>,
+    ) -> Worker {
+        let retired = Arc::new(AtomicBool::new(false));
+        let thread_retired = retired.clone();
+
         let thread = thread::spawn(move || loop {
-            let message = receiver.lock().unwrap().recv().unwrap();
+            let message = {
+                let receiver = receiver.lock().unwrap();
+                match idle {
+                    Some((min_workers, timeout)) => match receiver.recv_timeout(timeout) {
+                        Ok(message) => message,
+                        Err(mpsc::RecvTimeoutError::Timeout) => {
+                            let mut num_workers = num_workers.write().unwrap();
+                            if *num_workers > min_workers {
+                                *num_workers -= 1;
+                                thread_retired.store(true, Ordering::SeqCst);
+                                break;
+                            }
+                            continue;
+                        }
+                        Err(mpsc::RecvTimeoutError::Disconnected) => break,
+                    },
+                    None => receiver.recv().unwrap(),
+                }
+            };
 
             match message {
                 Message::NewJob(job) => {
@@ -577,8 +850,13 @@ impl Worker {
 
         Worker {
             thread: Some(thread),
+            retired,
         }
     }
+
+    fn retired(&self) -> bool {
+        self.retired.load(Ordering::SeqCst)
+    }
 }
 
 /// `listen` creates a server, with `num_worker` threads listening on `varlink_uri`.
@@ -587,6 +865,10 @@ impl Worker {
 /// amount of seconds, if no new connection is made in that time frame. It still waits for
 /// all pending connections to finish.
 ///
+/// An existing unix socket file, e.g. the one left behind by a crashed service, is replaced.
+/// See [ListenConfig::replace_stale](struct.ListenConfig.html#structfield.replace_stale) to
+/// keep the socket of a service still running on it.
+///
 ///# Examples
 ///
 ///```
@@ -615,18 +897,287 @@ pub fn listen<S: ?Sized + AsRef<str>, H: crate::ConnectionHandler + Send + Sync
     initial_worker_threads: usize,
     max_worker_threads: usize,
     idle_timeout: u64,
+) -> Result<()> {
+    listen_with_config(
+        handler,
+        address,
+        &ListenConfig {
+            initial_worker_threads,
+            max_worker_threads,
+            idle_timeout,
+            ..Default::default()
+        },
+    )
+}
+
+/// Configuration of the server started by [listen_with_config](fn.listen_with_config.html).
+#[derive(Debug, Clone)]
+pub struct ListenConfig {
+    /// Number of worker threads started initially.
+    pub initial_worker_threads: usize,
+    /// Maximum number of worker threads.
+    pub max_worker_threads: usize,
+    /// The time after which a worker thread, which was started additionally to the
+    /// `initial_worker_threads` under load, exits again, if it got no new connection.
+    ///
+    /// Idle threads exit one at a time. `None` keeps all started threads running.
+    pub idle_thread_timeout: Option<Duration>,
+    /// Seconds without a new connection, after which the server returns. `0` waits forever.
+    pub idle_timeout: u64,
+    /// Seconds after the start, after which the server returns with `ErrorKind::Timeout`,
+    /// even if it is busy. The open connections are handled like on a
+    /// [shutdown](struct.ShutdownHandle.html#method.shutdown). `0` serves forever.
+    pub max_lifetime: u64,
+    /// Initial capacity of the read buffer of every connection.
+    ///
+    /// `None` uses the default capacity of `BufReader`.
+    pub read_buffer_capacity: Option<usize>,
+    /// Create the missing parent directories of a `unix:` socket path with these permission
+    /// bits, e.g. `Some(0o755)`.
+    ///
+    /// With `None`, listening on a path in a missing directory fails with
+    /// `ErrorKind::SocketDirMissing`. The mode is ignored on Windows.
+    pub create_dirs: Option<u32>,
+    /// `SO_LINGER` of the accepted connections, see
+    /// [VarlinkStream::set_linger](struct.VarlinkStream.html#method.set_linger).
+    ///
+    /// `None` keeps the default of the system.
+    pub linger: Option<Duration>,
+    /// Replace an existing socket file of a `unix:` path only, if no service accepts
+    /// connections on it, e.g. the file left behind by a crashed service.
+    ///
+    /// The file is probed with a connect, removed and then the new socket is bound. This is
+    /// not atomic: a service starting at the same time on the same path can lose its socket
+    /// file between the probe and the removal. If a service accepts connections on the file,
+    /// `ErrorKind::Io(AddrInUse)` is returned. With `false`, an existing file is removed
+    /// without the probe, even if a running service still accepts connections on it.
+    pub replace_stale: bool,
+    /// The certificate and key for a `tcps:` address, which is required to listen on one.
+    #[cfg(feature = "tls")]
+    pub tls: Option<TlsServerConfig>,
+    /// A handle to stop the server, see [ShutdownHandle](struct.ShutdownHandle.html).
+    pub shutdown: Option<ShutdownHandle>,
+}
+
+/// A handle to stop a server started by [listen_with_config](fn.listen_with_config.html),
+/// e.g. from a signal handler thread.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// let handle = varlink::ShutdownHandle::new();
+/// let config = varlink::ListenConfig {
+///     shutdown: Some(handle.clone()),
+///     ..Default::default()
+/// };
+/// let server = thread::spawn(move || varlink::listen_with_config(service, &address, &config));
+/// // ...
+/// handle.shutdown();
+/// server.join().unwrap()?;
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ShutdownHandle {
+    state: Arc<ShutdownState>,
+}
+
+#[derive(Debug, Default)]
+struct ShutdownState {
+    requested: AtomicBool,
+    wake_address: Mutex<Option<String>>,
+}
+
+impl ShutdownHandle {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Stop the server.
+    ///
+    /// The server stops accepting new connections and stops reading new calls from the
+    /// open connections. `listen_with_config` returns `Ok(())`, after the calls in progress
+    /// are replied.
+    pub fn shutdown(&self) {
+        self.state.requested.store(true, Ordering::SeqCst);
+        // Wake up the server waiting for a new connection.
+        if let Some(ref address) = *self.state.wake_address.lock().unwrap() {
+            let _ = crate::client::VarlinkStream::connect(address);
+        }
+    }
+
+    /// Whether [shutdown](#method.shutdown) was called.
+    pub fn is_shutdown(&self) -> bool {
+        self.state.requested.load(Ordering::SeqCst)
+    }
+}
+
+/// The address to connect to, to wake up the `accept()` of `listener` bound to `address`.
+fn wake_address(listener: &Listener, address: &str) -> Option<String> {
+    match *listener {
+        Listener::TCP(Some(ref l), _) => {
+            let mut addr = l.local_addr().ok()?;
+            if addr.ip().is_unspecified() {
+                addr.set_ip(match addr {
+                    SocketAddr::V4(_) => Ipv4Addr::LOCALHOST.into(),
+                    SocketAddr::V6(_) => Ipv6Addr::LOCALHOST.into(),
+                });
+            }
+            Some(format!("tcp:{}", addr))
+        }
+        Listener::UNIX(Some(_), _) => Some(address.into()),
+        _ => None,
+    }
+}
+
+/// Stop accepting new connections, stop reading new calls from the open `connections` and
+/// wait for the workers to finish the calls in progress.
+fn stop_server(listener: Listener, connections: &Mutex<HashMap<u64, Stream>>, pool: ThreadPool) {
+    drop(listener);
+    for stream in connections.lock().unwrap().values_mut() {
+        let _ = stream.shutdown_read();
+    }
+    drop(pool);
+}
+
+/// An open connection of the server, which is stopped by
+/// [ShutdownHandle::shutdown](struct.ShutdownHandle.html#method.shutdown).
+struct OpenConnection {
+    connections: Arc<Mutex<HashMap<u64, Stream>>>,
+    id: u64,
+}
+
+impl Drop for OpenConnection {
+    fn drop(&mut self) {
+        self.connections.lock().unwrap().remove(&self.id);
+    }
+}
+
+impl Default for ListenConfig {
+    fn default() -> Self {
+        ListenConfig {
+            initial_worker_threads: 1,
+            max_worker_threads: 100,
+            idle_thread_timeout: None,
+            idle_timeout: 0,
+            max_lifetime: 0,
+            read_buffer_capacity: None,
+            create_dirs: None,
+            linger: None,
+            replace_stale: false,
+            #[cfg(feature = "tls")]
+            tls: None,
+            shutdown: None,
+        }
+    }
+}
+
+/// `listen_with_config` creates a server listening on `varlink_uri`, configured by `config`.
+///
+/// See [listen](fn.listen.html) for details.
+///
+///# Examples
+///
+///```
+/// extern crate varlink;
+///
+/// let service = varlink::VarlinkService::new(
+///     "org.varlink",
+///     "test service",
+///     "0.1",
+///     "http://varlink.org",
+///     vec![/* Your varlink interfaces go here */],
+/// );
+///
+/// let config = varlink::ListenConfig {
+///     idle_timeout: 1,
+///     read_buffer_capacity: Some(64 * 1024),
+///     ..Default::default()
+/// };
+///
+/// if let Err(e) = varlink::listen_with_config(service, "unix:test_listen_with_config", &config) {
+///     if *e.kind() != varlink::ErrorKind::Timeout {
+///         panic!("Error listen: {:?}", e);
+///     }
+/// }
+///```
+pub fn listen_with_config<
+    S: ?Sized + AsRef<str>,
+    H: crate::ConnectionHandler + Send + Sync + 'static,
+>(
+    handler: H,
+    address: &S,
+    config: &ListenConfig,
 ) -> Result<()> {
     let handler = Arc::new(handler);
-    let listener = Listener::new(address)?;
+
+    #[cfg(feature = "tls")]
+    let tls = match (address.as_ref().starts_with("tcps:"), &config.tls) {
+        (true, Some(tls)) => Some(tls.clone()),
+        (true, None) => {
+            return Err(into_cherr!(ErrorKind::Tls(
+                "no certificate configured for the tcps: address".into()
+            )));
+        }
+        (false, _) => None,
+    };
+
+    let listener = Listener::bind(address, config.create_dirs, config.replace_stale)?;
 
     listener.set_nonblocking(false)?;
 
-    let mut pool = ThreadPool::new(initial_worker_threads, max_worker_threads);
+    let connections: Arc<Mutex<HashMap<u64, Stream>>> = Default::default();
+    if let Some(ref handle) = config.shutdown {
+        *handle.state.wake_address.lock().unwrap() = wake_address(&listener, address.as_ref());
+    }
+
+    let idle_timeout = config.idle_timeout;
+    let read_buffer_capacity = config.read_buffer_capacity;
+    let mut pool = ThreadPool::new(
+        config.initial_worker_threads,
+        config.max_worker_threads,
+        config.idle_thread_timeout,
+    );
+    let mut connection_id: u64 = 0;
+
+    let deadline = match config.max_lifetime {
+        0 => None,
+        secs => Some(Instant::now() + Duration::from_secs(secs)),
+    };
+    let expired = |now: Instant| deadline.map_or(false, |deadline| now >= deadline);
 
     loop {
-        let mut stream = match listener.accept(idle_timeout) {
+        if config
+            .shutdown
+            .as_ref()
+            .map_or(false, ShutdownHandle::is_shutdown)
+        {
+            stop_server(listener, &connections, pool);
+            return Ok(());
+        }
+        let now = Instant::now();
+        if expired(now) {
+            stop_server(listener, &connections, pool);
+            return Err(into_cherr!(ErrorKind::Timeout));
+        }
+
+        // Wake up for the end of the lifetime, rounded up to whole seconds.
+        let timeout = match deadline {
+            Some(deadline) => {
+                let remaining = deadline - now;
+                let remaining = remaining.as_secs() + u64::from(remaining.subsec_nanos() > 0);
+                match idle_timeout {
+                    0 => remaining,
+                    idle_timeout => ::std::cmp::min(idle_timeout, remaining),
+                }
+            }
+            None => idle_timeout,
+        };
+
+        let mut stream = match listener.accept(timeout) {
             Err(e) => match e.kind() {
                 ErrorKind::Timeout => {
+                    if expired(Instant::now()) {
+                        continue;
+                    }
                     if pool.num_busy() == 0 {
                         return Err(e);
                     }
@@ -638,14 +1189,63 @@ pub fn listen<S: ?Sized + AsRef<str>, H: crate::ConnectionHandler + Send + Sync
             },
             r => r?,
         };
+        if config
+            .shutdown
+            .as_ref()
+            .map_or(false, ShutdownHandle::is_shutdown)
+        {
+            continue;
+        }
+        if config.linger.is_some() {
+            stream.set_linger(config.linger)?;
+        }
         let handler = handler.clone();
 
+        connection_id += 1;
+        let context = crate::CallContext {
+            peer_credentials: stream.peer_credentials(),
+            connection_id,
+            #[cfg(unix)]
+            unix_socket: match stream {
+                Stream::UNIX(ref s) => Some(s.as_raw_fd()),
+                _ => None,
+            },
+            ..Default::default()
+        };
+
+        #[cfg(feature = "tls")]
+        let tls = tls.clone();
+
+        let open_connection = if config.shutdown.is_some() || deadline.is_some() {
+            let clone = stream.try_clone().map_err(minto_cherr!())?;
+            connections.lock().unwrap().insert(connection_id, clone);
+            Some(OpenConnection {
+                connections: connections.clone(),
+                id: connection_id,
+            })
+        } else {
+            None
+        };
+
         pool.execute(move || {
+            let _open_connection = open_connection;
+            #[cfg(feature = "tls")]
+            let mut stream = match tls {
+                Some(ref tls) => match stream.accept_tls(tls) {
+                    Ok(stream) => stream,
+                    // A failed handshake only drops this connection.
+                    Err(_) => return,
+                },
+                None => stream,
+            };
             let (r, mut w) = stream.split().unwrap();
-            let mut br = BufReader::new(r);
+            let mut br = match read_buffer_capacity {
+                Some(capacity) => BufReader::with_capacity(capacity, r),
+                None => BufReader::new(r),
+            };
             let mut iface: Option<String> = None;
             loop {
-                match handler.handle(&mut br, &mut w, iface.clone()) {
+                match handler.handle_with_context(&mut br, &mut w, iface.clone(), &context) {
                     Ok((_, i)) => {
                         iface = i;
                         match br.fill_buf() {
@@ -656,7 +1256,9 @@ pub fn listen<S: ?Sized + AsRef<str>, H: crate::ConnectionHandler + Send + Sync
                     }
                     Err(err) => {
                         match err.kind() {
-                            ErrorKind::ConnectionClosed | ErrorKind::SerdeJsonDe(_) => {}
+                            ErrorKind::ConnectionClosed
+                            | ErrorKind::SerdeJsonDe(_)
+                            | ErrorKind::NestingTooDeep(_) => {}
                             _ => {
                                 eprintln!("Worker error: {:?}", err);
                             }
@@ -0,0 +1,251 @@
+//! Incremental parsing of replies with large array fields
+
+use std::borrow::Cow;
+use std::fmt;
+use std::io::{self, BufRead, Read};
+use std::marker::PhantomData;
+
+use serde::de::{self, DeserializeOwned, DeserializeSeed, IgnoredAny};
+use serde_json::{Map, Value};
+
+use crate::Reply;
+
+/// Reads a single null terminated message from `reader`, consuming the null byte.
+struct MessageBody<'a> {
+    reader: &'a mut dyn BufRead,
+    done: bool,
+}
+
+impl<'a> Read for MessageBody<'a> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        if self.done {
+            return Ok(0);
+        }
+
+        let (n, end) = {
+            let buf = self.reader.fill_buf()?;
+            let buf = &buf[..buf.len().min(out.len() + 1)];
+            let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+            let n = len.min(out.len());
+            out[..n].copy_from_slice(&buf[..n]);
+            (n, n == len && len < buf.len())
+        };
+
+        if end {
+            self.reader.consume(n + 1);
+            self.done = true;
+        } else {
+            self.reader.consume(n);
+        }
+
+        Ok(n)
+    }
+}
+
+/// Deserializes a `Reply`, passing the elements of the array `field` of its parameters to `f`.
+struct ReplySeed<'a, T, F> {
+    field: &'a str,
+    f: F,
+    phantom: PhantomData<fn(T)>,
+}
+
+impl<'de, 'a, T, F> DeserializeSeed<'de> for ReplySeed<'a, T, F>
+where
+    T: DeserializeOwned,
+    F: FnMut(T),
+{
+    type Value = Reply;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, 'a, T, F> de::Visitor<'de> for ReplySeed<'a, T, F>
+where
+    T: DeserializeOwned,
+    F: FnMut(T),
+{
+    type Value = Reply;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a varlink reply")
+    }
+
+    fn visit_map<V>(mut self, mut map: V) -> Result<Self::Value, V::Error>
+    where
+        V: de::MapAccess<'de>,
+    {
+        let mut reply = Reply::parameters(None);
+
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_ref() {
+                "continues" => reply.continues = map.next_value()?,
+                "error" => reply.error = map.next_value::<Option<String>>()?.map(Cow::from),
+                "parameters" => {
+                    reply.parameters = Some(map.next_value_seed(ParametersSeed {
+                        field: self.field,
+                        f: &mut self.f,
+                        phantom: PhantomData,
+                    })?)
+                }
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+
+        Ok(reply)
+    }
+}
+
+/// Deserializes the reply parameters, leaving an empty array in place of `field`.
+struct ParametersSeed<'a, 'f, T, F> {
+    field: &'a str,
+    f: &'f mut F,
+    phantom: PhantomData<fn(T)>,
+}
+
+impl<'de, 'a, 'f, T, F> DeserializeSeed<'de> for ParametersSeed<'a, 'f, T, F>
+where
+    T: DeserializeOwned,
+    F: FnMut(T),
+{
+    type Value = Value;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, 'a, 'f, T, F> de::Visitor<'de> for ParametersSeed<'a, 'f, T, F>
+where
+    T: DeserializeOwned,
+    F: FnMut(T),
+{
+    type Value = Value;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("the reply parameters")
+    }
+
+    fn visit_map<V>(self, mut map: V) -> Result<Self::Value, V::Error>
+    where
+        V: de::MapAccess<'de>,
+    {
+        let mut parameters = Map::new();
+
+        while let Some(key) = map.next_key::<String>()? {
+            let value = if key == self.field {
+                let present = map.next_value_seed(ElementsSeed {
+                    f: &mut *self.f,
+                    phantom: PhantomData,
+                })?;
+                if present {
+                    Value::Array(Vec::new())
+                } else {
+                    Value::Null
+                }
+            } else {
+                map.next_value()?
+            };
+            parameters.insert(key, value);
+        }
+
+        Ok(Value::Object(parameters))
+    }
+}
+
+/// Passes the elements of an array to `f`, one at a time as they are parsed.
+///
+/// Deserializes to `false`, if the array is `null`.
+struct ElementsSeed<'f, T, F> {
+    f: &'f mut F,
+    phantom: PhantomData<fn(T)>,
+}
+
+impl<'de, 'f, T, F> DeserializeSeed<'de> for ElementsSeed<'f, T, F>
+where
+    T: DeserializeOwned,
+    F: FnMut(T),
+{
+    type Value = bool;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        deserializer.deserialize_option(self)
+    }
+}
+
+impl<'de, 'f, T, F> de::Visitor<'de> for ElementsSeed<'f, T, F>
+where
+    T: DeserializeOwned,
+    F: FnMut(T),
+{
+    type Value = bool;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an array")
+    }
+
+    fn visit_none<E>(self) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(false)
+    }
+
+    fn visit_some<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        deserializer.deserialize_seq(self)
+    }
+
+    fn visit_seq<V>(self, mut seq: V) -> Result<Self::Value, V::Error>
+    where
+        V: de::SeqAccess<'de>,
+    {
+        while let Some(element) = seq.next_element::<T>()? {
+            (self.f)(element);
+        }
+        Ok(true)
+    }
+}
+
+/// Read a null terminated reply from `reader` and pass the elements of the array parameter
+/// `field` to `f` while parsing, without collecting them.
+///
+/// In the returned `Reply`, `field` is left as an empty array (or `null`).
+pub(crate) fn read_streaming_reply<T, F>(
+    reader: &mut dyn BufRead,
+    field: &str,
+    f: F,
+) -> serde_json::Result<Reply>
+where
+    T: DeserializeOwned,
+    F: FnMut(T),
+{
+    let mut deserializer = serde_json::Deserializer::from_reader(MessageBody {
+        reader,
+        done: false,
+    });
+
+    let reply = ReplySeed {
+        field,
+        f,
+        phantom: PhantomData,
+    }
+    .deserialize(&mut deserializer)?;
+
+    deserializer.end()?;
+    Ok(reply)
+}
@@ -110,6 +110,12 @@ error MethodNotImplemented (method: string)
 
 # One of the passed parameters is invalid.
 error InvalidParameter (parameter: string)
+
+# Permission is denied.
+error PermissionDenied ()
+
+# Method is expected to be called with 'more' set to true, but wasn't
+error ExpectedMore ()
 "#
         );
 
@@ -201,3 +207,2367 @@ fn test_handle() -> Result<()> {
     );
     Ok(())
 }
+
+#[test]
+fn test_try_clone() -> Result<()> {
+    let address = "unix:test_try_clone";
+
+    let child = thread::spawn(move || {
+        let service = VarlinkService::new(
+            "org.varlink",
+            "test service",
+            "0.1",
+            "http://varlink.org",
+            vec![],
+        );
+
+        if let Err(e) = listen(service, &address, 4, 10, 3) {
+            if *e.kind() != ErrorKind::Timeout {
+                panic!("Error listen: {:#?}", e);
+            }
+        }
+    });
+
+    // give server time to start
+    thread::sleep(time::Duration::from_secs(1));
+
+    let conn = Connection::with_address(address)?;
+
+    let clients = (0..4)
+        .map(|_| {
+            let conn = conn.read().unwrap().try_clone()?;
+            Ok(thread::spawn(move || {
+                let mut call = OrgVarlinkServiceClient::new(conn);
+                for _ in 0..10 {
+                    assert_eq!(&call.get_info().unwrap().product, "test service");
+                }
+            }))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    for client in clients {
+        assert!(client.join().is_ok());
+    }
+
+    assert_eq!(
+        &OrgVarlinkServiceClient::new(conn).get_info()?.product,
+        "test service"
+    );
+
+    assert!(child.join().is_ok());
+
+    Ok(())
+}
+
+#[test]
+fn test_request_connection_close() -> Result<()> {
+    struct CloseInterface;
+
+    impl Interface for CloseInterface {
+        fn get_description(&self) -> &'static str {
+            "interface org.example.close\nmethod Close() -> ()\n"
+        }
+
+        fn get_name(&self) -> &'static str {
+            "org.example.close"
+        }
+
+        fn call_upgraded(&self, _call: &mut Call, _bufreader: &mut dyn BufRead) -> Result<Vec<u8>> {
+            Ok(Vec::new())
+        }
+
+        fn call(&self, call: &mut Call) -> Result<()> {
+            call.request_connection_close();
+            call.reply_struct(Reply::parameters(None))
+        }
+    }
+
+    let address = "unix:test_request_connection_close";
+
+    let child = thread::spawn(move || {
+        let service = VarlinkService::new(
+            "org.varlink",
+            "test service",
+            "0.1",
+            "http://varlink.org",
+            vec![Box::new(CloseInterface)],
+        );
+
+        if let Err(e) = listen(service, &address, 1, 10, 3) {
+            if *e.kind() != ErrorKind::Timeout {
+                panic!("Error listen: {:#?}", e);
+            }
+        }
+    });
+
+    // give server time to start
+    thread::sleep(time::Duration::from_secs(1));
+
+    let conn = Connection::with_address(address)?;
+
+    MethodCall::<GetInfoArgs, Value, ErrorKind>::new(
+        conn.clone(),
+        "org.example.close.Close",
+        GetInfoArgs {},
+    )
+    .call()?;
+
+    let e = OrgVarlinkServiceClient::new(conn).get_info();
+    assert_eq!(e.err().unwrap().kind(), &ErrorKind::ConnectionClosed);
+
+    assert!(child.join().is_ok());
+
+    Ok(())
+}
+
+#[test]
+fn test_call_context() -> Result<()> {
+    struct ContextInterface;
+
+    impl Interface for ContextInterface {
+        fn get_description(&self) -> &'static str {
+            "interface org.example.context\nmethod Context() -> (uid: int, pid: int, received: bool, id: int)\n"
+        }
+
+        fn get_name(&self) -> &'static str {
+            "org.example.context"
+        }
+
+        fn call_upgraded(&self, _call: &mut Call, _bufreader: &mut dyn BufRead) -> Result<Vec<u8>> {
+            Ok(Vec::new())
+        }
+
+        fn call(&self, call: &mut Call) -> Result<()> {
+            let context = call.context().clone();
+            call.reply_struct(Reply::parameters(Some(serde_json::json!({
+                "uid": context.peer_credentials.map(|c| c.uid),
+                "pid": call.peer_credentials().map(|c| c.pid),
+                "received": context.received.is_some(),
+                "id": context.connection_id,
+            }))))
+        }
+    }
+
+    let address = "unix:test_call_context";
+
+    let child = thread::spawn(move || {
+        let service = VarlinkService::new(
+            "org.varlink",
+            "test service",
+            "0.1",
+            "http://varlink.org",
+            vec![Box::new(ContextInterface)],
+        );
+
+        if let Err(e) = listen(service, &address, 1, 10, 3) {
+            if *e.kind() != ErrorKind::Timeout {
+                panic!("Error listen: {:#?}", e);
+            }
+        }
+    });
+
+    // give server time to start
+    thread::sleep(time::Duration::from_secs(1));
+
+    let conn = Connection::with_address(address)?;
+
+    let reply = MethodCall::<GetInfoArgs, Value, ErrorKind>::new(
+        conn,
+        "org.example.context.Context",
+        GetInfoArgs {},
+    )
+    .call()?;
+
+    assert_eq!(reply["uid"], serde_json::json!(unsafe { libc::getuid() }));
+    assert_eq!(reply["pid"], serde_json::json!(unsafe { libc::getpid() }));
+    assert_eq!(reply["received"], serde_json::json!(true));
+    assert_ne!(reply["id"], serde_json::json!(0));
+
+    assert!(child.join().is_ok());
+
+    Ok(())
+}
+
+#[test]
+fn test_read_buffer_capacity() -> Result<()> {
+    const CAPACITY: usize = 1024 * 1024;
+
+    struct LargeInterface;
+
+    impl Interface for LargeInterface {
+        fn get_description(&self) -> &'static str {
+            "interface org.example.large\nmethod Large() -> (data: string)\n"
+        }
+
+        fn get_name(&self) -> &'static str {
+            "org.example.large"
+        }
+
+        fn call_upgraded(&self, _call: &mut Call, _bufreader: &mut dyn BufRead) -> Result<Vec<u8>> {
+            Ok(Vec::new())
+        }
+
+        fn call(&self, call: &mut Call) -> Result<()> {
+            call.reply_struct(Reply::parameters(Some(serde_json::json!({
+                "data": "x".repeat(CAPACITY / 2)
+            }))))
+        }
+    }
+
+    // A message smaller than the capacity is read without growing the buffer.
+    let mut message = serde_json::to_vec(&serde_json::json!({ "data": "x".repeat(CAPACITY / 2) }))
+        .map_err(minto_cherr!())?;
+    message.push(0);
+    let mut reader = BufReader::with_capacity(CAPACITY, &message[..]);
+    let mut buf = Vec::new();
+    read_message(&mut reader, &mut buf, Some(CAPACITY)).map_err(minto_cherr!())?;
+    assert_eq!(buf, message);
+    assert_eq!(buf.capacity(), CAPACITY);
+
+    let address = "unix:test_read_buffer_capacity";
+
+    let child = thread::spawn(move || {
+        let service = VarlinkService::new(
+            "org.varlink",
+            "test service",
+            "0.1",
+            "http://varlink.org",
+            vec![Box::new(LargeInterface)],
+        );
+
+        let config = ListenConfig {
+            idle_timeout: 3,
+            read_buffer_capacity: Some(CAPACITY),
+            ..Default::default()
+        };
+
+        if let Err(e) = listen_with_config(service, &address, &config) {
+            if *e.kind() != ErrorKind::Timeout {
+                panic!("Error listen: {:#?}", e);
+            }
+        }
+    });
+
+    // give server time to start
+    thread::sleep(time::Duration::from_secs(1));
+
+    let conn = Connection::with_address(address)?;
+    conn.write().unwrap().set_read_buffer_capacity(CAPACITY)?;
+
+    let reply = MethodCall::<GetInfoArgs, Value, ErrorKind>::new(
+        conn.clone(),
+        "org.example.large.Large",
+        GetInfoArgs {},
+    )
+    .call()?;
+
+    assert_eq!(reply["data"].as_str().map(str::len), Some(CAPACITY / 2));
+    assert_eq!(
+        conn.read()
+            .unwrap()
+            .reader
+            .as_ref()
+            .map(BufReader::capacity),
+        Some(CAPACITY)
+    );
+
+    drop(conn);
+    assert!(child.join().is_ok());
+
+    Ok(())
+}
+
+#[test]
+fn test_into_varlink_address() -> Result<()> {
+    use std::net::SocketAddr;
+    use std::path::PathBuf;
+
+    let servers = ["tcp:127.0.0.1:23459", "unix:test_into_varlink_address"]
+        .iter()
+        .map(|address| {
+            thread::spawn(move || {
+                let service = VarlinkService::new(
+                    "org.varlink",
+                    "test service",
+                    "0.1",
+                    "http://varlink.org",
+                    vec![],
+                );
+
+                if let Err(e) = listen(service, address, 1, 10, 3) {
+                    if *e.kind() != ErrorKind::Timeout {
+                        panic!("Error listen: {:#?}", e);
+                    }
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+
+    // give the servers time to start
+    thread::sleep(time::Duration::from_secs(1));
+
+    let addr: SocketAddr = "127.0.0.1:23459".parse().unwrap();
+    let path = PathBuf::from("test_into_varlink_address");
+
+    for conn in [Connection::new(addr)?, Connection::new(path)?]
+        .iter()
+        .cloned()
+    {
+        assert_eq!(
+            &OrgVarlinkServiceClient::new(conn).get_info()?.product,
+            "test service"
+        );
+    }
+
+    for server in servers {
+        assert!(server.join().is_ok());
+    }
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_handshake_timeout() -> Result<()> {
+    use std::os::unix::net::UnixListener;
+    use std::time::{Duration, Instant};
+
+    let path = "test_handshake_timeout";
+    let _ = std::fs::remove_file(path);
+    let listener = UnixListener::bind(path).map_err(minto_cherr!())?;
+
+    // accept the connection, but never reply
+    let child = thread::spawn(move || {
+        let (_stream, _) = listener.accept().unwrap();
+        thread::sleep(time::Duration::from_secs(3));
+    });
+
+    let conn = Connection::with_address(&format!("unix:{}", path))?;
+    conn.write()
+        .unwrap()
+        .set_handshake_timeout(Duration::from_millis(500))?;
+
+    let start = Instant::now();
+    let e = OrgVarlinkServiceClient::new(conn).get_info();
+    assert_eq!(e.err().unwrap().kind(), &ErrorKind::Timeout);
+    assert!(start.elapsed() < Duration::from_secs(2));
+
+    assert!(child.join().is_ok());
+    let _ = std::fs::remove_file(path);
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_replace_stale_socket() -> Result<()> {
+    let path = "test_replace_stale_socket";
+    let address = format!("unix:{}", path);
+    let _ = std::fs::remove_file(path);
+
+    // leave a stale socket file behind
+    drop(std::os::unix::net::UnixListener::bind(path).map_err(minto_cherr!())?);
+    assert!(std::path::Path::new(path).exists());
+
+    let listener = Listener::bind(&address, None, true)?;
+
+    // a running service is not replaced
+    match Listener::bind(&address, None, true) {
+        Err(e) => assert_eq!(e.kind(), &ErrorKind::Io(std::io::ErrorKind::AddrInUse)),
+        Ok(_) => panic!("replaced the socket of a running service"),
+    }
+
+    drop(listener);
+    let _ = std::fs::remove_file(path);
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_listen_stale_socket() -> Result<()> {
+    let path = "test_listen_stale_socket";
+    let address = "unix:test_listen_stale_socket";
+    let _ = std::fs::remove_file(path);
+
+    // a crashed service left its socket file behind
+    drop(std::os::unix::net::UnixListener::bind(path).map_err(minto_cherr!())?);
+    assert!(std::path::Path::new(path).exists());
+
+    let child = thread::spawn(move || {
+        let service = VarlinkService::new(
+            "org.varlink",
+            "test service",
+            "0.1",
+            "http://varlink.org",
+            vec![],
+        );
+
+        if let Err(e) = listen(service, &address, 1, 10, 3) {
+            if *e.kind() != ErrorKind::Timeout {
+                panic!("Error listen: {:#?}", e);
+            }
+        }
+    });
+
+    // give server time to start
+    thread::sleep(time::Duration::from_secs(1));
+
+    let conn = Connection::with_address(address)?;
+    assert_eq!(
+        &OrgVarlinkServiceClient::new(conn).get_info()?.product,
+        "test service"
+    );
+
+    assert!(child.join().is_ok());
+    Ok(())
+}
+
+#[test]
+fn test_call_streaming() -> Result<()> {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    const COUNT: usize = 1_000_000;
+
+    /// Generates a reply with a large `strings` array on the fly.
+    struct LargeReply {
+        next: usize,
+        pending: Vec<u8>,
+        produced: Rc<Cell<usize>>,
+    }
+
+    impl Read for LargeReply {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.pending.is_empty() {
+                self.pending = match self.next {
+                    0 => b"{\"parameters\":{\"strings\":[".to_vec(),
+                    1 => b"\"s1\"".to_vec(),
+                    n if n <= COUNT => format!(",\"s{}\"", n).into_bytes(),
+                    n if n == COUNT + 1 => format!("],\"count\":{}}}}}\0", COUNT).into_bytes(),
+                    _ => return Ok(0),
+                };
+                self.next += 1;
+            }
+            let n = buf.len().min(self.pending.len());
+            buf[..n].copy_from_slice(&self.pending[..n]);
+            self.pending.drain(..n);
+            self.produced.set(self.produced.get() + n);
+            Ok(n)
+        }
+    }
+
+    let produced = Rc::new(Cell::new(0));
+    let mut reader = BufReader::new(LargeReply {
+        next: 0,
+        pending: Vec::new(),
+        produced: produced.clone(),
+    });
+
+    // every element is processed, while only a buffer's worth of the reply has been read ahead
+    let mut parsed = "{\"parameters\":{\"strings\":[".len();
+    let mut count = 0;
+    let reply = stream::read_streaming_reply(&mut reader, "strings", |s: String| {
+        count += 1;
+        assert_eq!(s, format!("s{}", count));
+        parsed += s.len() + 3;
+        assert!(produced.get() < parsed + 16 * 1024);
+    })
+    .map_err(minto_cherr!())?;
+
+    assert_eq!(count, COUNT);
+    assert_eq!(
+        reply.parameters,
+        Some(json!({"strings": [], "count": COUNT}))
+    );
+    assert_eq!(reader.fill_buf().map_err(minto_cherr!())?.len(), 0);
+
+    struct ListInterface;
+
+    impl Interface for ListInterface {
+        fn get_description(&self) -> &'static str {
+            "interface org.example.list\nmethod List() -> (count: int, strings: []string)\n"
+        }
+
+        fn get_name(&self) -> &'static str {
+            "org.example.list"
+        }
+
+        fn call_upgraded(&self, _call: &mut Call, _bufreader: &mut dyn BufRead) -> Result<Vec<u8>> {
+            Ok(Vec::new())
+        }
+
+        fn call(&self, call: &mut Call) -> Result<()> {
+            let strings: Vec<String> = (0..10_000).map(|i| format!("s{}", i)).collect();
+            call.reply_struct(Reply::parameters(Some(json!({
+                "strings": strings,
+                "count": strings.len(),
+            }))))
+        }
+    }
+
+    let address = "unix:test_call_streaming";
+
+    let child = thread::spawn(move || {
+        let service = VarlinkService::new(
+            "org.varlink",
+            "test service",
+            "0.1",
+            "http://varlink.org",
+            vec![Box::new(ListInterface)],
+        );
+
+        if let Err(e) = listen(service, &address, 1, 10, 3) {
+            if *e.kind() != ErrorKind::Timeout {
+                panic!("Error listen: {:#?}", e);
+            }
+        }
+    });
+
+    // give server time to start
+    thread::sleep(time::Duration::from_secs(1));
+
+    let conn = Connection::with_address(address)?;
+
+    let mut strings = 0;
+    let reply = MethodCall::<GetInfoArgs, Value, ErrorKind>::new(
+        conn.clone(),
+        "org.example.list.List",
+        GetInfoArgs {},
+    )
+    .call_streaming("strings", |s: String| {
+        assert_eq!(s, format!("s{}", strings));
+        strings += 1;
+    })?;
+
+    assert_eq!(strings, 10_000);
+    assert_eq!(reply, json!({"strings": [], "count": 10_000}));
+
+    // the connection is usable after a streamed reply
+    assert_eq!(
+        &OrgVarlinkServiceClient::new(conn.clone())
+            .get_info()?
+            .product,
+        "test service"
+    );
+
+    drop(conn);
+    assert!(child.join().is_ok());
+
+    Ok(())
+}
+
+#[test]
+fn test_buffer_oneway() -> Result<()> {
+    use std::sync::Mutex;
+
+    /// Records every write call.
+    struct CountingWriter(Arc<Mutex<Vec<Vec<u8>>>>);
+
+    impl Write for CountingWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().push(buf.to_vec());
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let writes = Arc::new(Mutex::new(Vec::new()));
+    let reply = b"{\"parameters\":{}}\0".to_vec();
+
+    let conn = Arc::new(RwLock::new(Connection::from_parts(
+        BufReader::new(Box::new(io::Cursor::new(reply))),
+        Box::new(CountingWriter(writes.clone())),
+        "test".into(),
+        None,
+    )));
+    conn.write().unwrap().set_buffer_oneway(true)?;
+
+    let messages = |buf: &[u8]| -> Vec<Value> {
+        assert_eq!(buf.last(), Some(&0));
+        buf[..buf.len() - 1]
+            .split(|&b| b == 0)
+            .map(|m| from_slice(m).unwrap())
+            .collect()
+    };
+
+    let ping = |method: &'static str| {
+        MethodCall::<GetInfoArgs, Value, ErrorKind>::new(conn.clone(), method, GetInfoArgs {})
+    };
+
+    for _ in 0..3 {
+        ping("org.example.ping.Ping").oneway()?;
+    }
+    assert!(writes.lock().unwrap().is_empty());
+
+    conn.write().unwrap().flush()?;
+    {
+        let writes = writes.lock().unwrap();
+        assert_eq!(writes.len(), 1);
+        let messages = messages(&writes[0]);
+        assert_eq!(messages.len(), 3);
+        for m in messages {
+            assert_eq!(m["method"], "org.example.ping.Ping");
+            assert_eq!(m["oneway"], true);
+        }
+    }
+
+    // a call is sent immediately, together with the buffered oneway calls
+    writes.lock().unwrap().clear();
+    ping("org.example.ping.Ping").oneway()?;
+    ping("org.example.ping.Ping").call()?;
+    {
+        let writes = writes.lock().unwrap();
+        assert_eq!(writes.len(), 1);
+        let messages = messages(&writes[0]);
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0]["oneway"], true);
+        assert_eq!(messages[1].get("oneway"), None);
+    }
+
+    // nothing is buffered without the buffer mode
+    writes.lock().unwrap().clear();
+    conn.write().unwrap().set_buffer_oneway(false)?;
+    ping("org.example.ping.Ping").oneway()?;
+    assert_eq!(writes.lock().unwrap().len(), 1);
+
+    Ok(())
+}
+
+#[test]
+fn test_latency_observer() -> Result<()> {
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    struct TickInterface;
+
+    impl Interface for TickInterface {
+        fn get_description(&self) -> &'static str {
+            "interface org.example.tick\nmethod Tick() -> (n: int)\n"
+        }
+
+        fn get_name(&self) -> &'static str {
+            "org.example.tick"
+        }
+
+        fn call_upgraded(&self, _call: &mut Call, _bufreader: &mut dyn BufRead) -> Result<Vec<u8>> {
+            Ok(Vec::new())
+        }
+
+        fn call(&self, call: &mut Call) -> Result<()> {
+            for n in 0..3 {
+                thread::sleep(Duration::from_millis(50));
+                call.set_continues(n < 2);
+                call.reply_struct(Reply::parameters(Some(json!({ "n": n }))))?;
+            }
+            Ok(())
+        }
+    }
+
+    let address = "unix:test_latency_observer";
+
+    let child = thread::spawn(move || {
+        let service = VarlinkService::new(
+            "org.varlink",
+            "test service",
+            "0.1",
+            "http://varlink.org",
+            vec![Box::new(TickInterface)],
+        );
+
+        if let Err(e) = listen(service, &address, 1, 10, 3) {
+            if *e.kind() != ErrorKind::Timeout {
+                panic!("Error listen: {:#?}", e);
+            }
+        }
+    });
+
+    // give server time to start
+    thread::sleep(time::Duration::from_secs(1));
+
+    let conn = Connection::with_address(address)?;
+    let latencies = Arc::new(Mutex::new(Vec::new()));
+    {
+        let latencies = latencies.clone();
+        conn.write()
+            .unwrap()
+            .set_latency_observer(Some(Box::new(move |latency: &CallLatency| {
+                latencies.lock().unwrap().push(latency.clone())
+            })));
+    }
+
+    OrgVarlinkServiceClient::new(conn.clone()).get_info()?;
+    {
+        let latencies = latencies.lock().unwrap();
+        assert_eq!(latencies.len(), 1);
+        assert_eq!(latencies[0].method, "org.varlink.service.GetInfo");
+        assert_eq!(latencies[0].reply, 0);
+        assert!(latencies[0].elapsed > Duration::from_secs(0));
+        assert!(latencies[0].elapsed < Duration::from_secs(1));
+        assert_eq!(latencies[0].since_previous, latencies[0].elapsed);
+    }
+    latencies.lock().unwrap().clear();
+
+    let mut call = MethodCall::<GetInfoArgs, Value, ErrorKind>::new(
+        conn.clone(),
+        "org.example.tick.Tick",
+        GetInfoArgs {},
+    );
+    assert_eq!(call.more()?.count(), 3);
+    {
+        let latencies = latencies.lock().unwrap();
+        assert_eq!(
+            latencies.iter().map(|l| l.reply).collect::<Vec<_>>(),
+            vec![0, 1, 2]
+        );
+        for l in latencies.iter() {
+            assert_eq!(l.method, "org.example.tick.Tick");
+            assert!(l.since_previous >= Duration::from_millis(40));
+            assert!(l.since_previous < Duration::from_secs(1));
+        }
+        assert!(latencies[2].elapsed >= latencies[1].elapsed + latencies[2].since_previous);
+    }
+    drop(call);
+
+    // the observer is called without the lock of the connection
+    {
+        let weak = Arc::downgrade(&conn);
+        let latencies = latencies.clone();
+        conn.write()
+            .unwrap()
+            .set_latency_observer(Some(Box::new(move |latency: &CallLatency| {
+                if let Some(conn) = weak.upgrade() {
+                    assert_eq!(conn.read().unwrap().address(), address);
+                }
+                latencies.lock().unwrap().push(latency.clone())
+            })));
+    }
+    latencies.lock().unwrap().clear();
+    OrgVarlinkServiceClient::new(conn.clone()).get_info()?;
+    assert_eq!(latencies.lock().unwrap().len(), 1);
+
+    drop(conn);
+    assert!(child.join().is_ok());
+
+    Ok(())
+}
+
+#[test]
+fn test_activate_capture_stderr() -> Result<()> {
+    let e = Connection::with_activate_capture_stderr(
+        "sh -c \"echo 'starting' >&2; echo 'fatal: no config' >&2; exit 1\"",
+    )
+    .and_then(|conn| OrgVarlinkServiceClient::new(conn).get_info())
+    .err()
+    .unwrap();
+
+    match e.kind() {
+        ErrorKind::ServiceFailed(stderr) => assert_eq!(stderr, "starting\nfatal: no config"),
+        kind => panic!("unexpected error: {:?}", kind),
+    }
+    assert!(e.to_string().contains("fatal: no config"));
+
+    Ok(())
+}
+
+#[test]
+fn test_activate_exit_status() -> Result<()> {
+    // the service exits either before or after the connection is established
+    let e = match Connection::with_activate("sh -c \"exit 3\"") {
+        Err(e) => e,
+        Ok(conn) => {
+            let e = OrgVarlinkServiceClient::new(conn.clone())
+                .get_info()
+                .err()
+                .unwrap();
+            let status = conn.write().unwrap().exit_status().unwrap();
+            assert_eq!(status.code(), Some(3));
+            e
+        }
+    };
+
+    match e.kind() {
+        // the wording of `ExitStatus` differs between rust versions
+        ErrorKind::ServiceFailed(status) => assert!(status.ends_with(": 3"), "{}", status),
+        kind => panic!("unexpected error: {:?}", kind),
+    }
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_keepalive() -> Result<()> {
+    use std::os::unix::net::UnixListener;
+    use std::time::{Duration, Instant};
+
+    let path = "test_keepalive";
+    let _ = std::fs::remove_file(path);
+    let listener = UnixListener::bind(path).map_err(minto_cherr!())?;
+
+    // reply to three pings, then die
+    let child = thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut writer = stream;
+        let mut methods = Vec::new();
+        for _ in 0..3 {
+            let mut buf = Vec::new();
+            reader.read_until(0, &mut buf).unwrap();
+            buf.pop();
+            let req: Request = from_slice(&buf).unwrap();
+            methods.push(req.method.to_string());
+            writer.write_all(b"{\"parameters\":{}}\0").unwrap();
+        }
+        methods
+    });
+
+    let conn = Connection::with_address(&format!("unix:{}", path))?;
+    Connection::start_keepalive(&conn, Duration::from_millis(100));
+
+    let start = Instant::now();
+    while conn.read().unwrap().keepalive_error().is_none() {
+        assert!(start.elapsed() < Duration::from_secs(5));
+        thread::sleep(time::Duration::from_millis(50));
+    }
+    assert_eq!(
+        conn.read().unwrap().keepalive_error(),
+        Some(&ErrorKind::ConnectionClosed)
+    );
+
+    let e = OrgVarlinkServiceClient::new(conn).get_info();
+    assert_eq!(e.err().unwrap().kind(), &ErrorKind::ConnectionClosed);
+
+    assert_eq!(
+        child.join().unwrap(),
+        vec!["org.varlink.service.GetInfo"; 3]
+    );
+    let _ = std::fs::remove_file(path);
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_pending_bytes() -> Result<()> {
+    use std::os::unix::net::UnixStream;
+
+    fn reply(vendor: &str) -> String {
+        format!(
+            "{{\"parameters\":{{\"vendor\":\"{}\",\"product\":\"p\",\"version\":\"1\",\
+             \"url\":\"u\",\"interfaces\":[]}}}}\0",
+            vendor
+        )
+    }
+
+    let (stream, mut peer) = UnixStream::pair().map_err(minto_cherr!())?;
+
+    // the third reply is only partially sent
+    let third = reply("third");
+    let (head, tail) = third.split_at(20);
+    peer.write_all(format!("{}{}{}", reply("first"), reply("second"), head).as_bytes())
+        .map_err(minto_cherr!())?;
+
+    let conn = Connection::with_stream(VarlinkStream::UNIX(stream), "unix:pair", Vec::new())?;
+    assert_eq!(
+        &OrgVarlinkServiceClient::new(conn.clone())
+            .get_info()?
+            .vendor,
+        "first"
+    );
+
+    let (stream, pending) = {
+        let mut conn = conn.write().unwrap();
+        (conn.stream.take().unwrap(), conn.pending_bytes()?)
+    };
+    drop(conn);
+    assert_eq!(pending, format!("{}{}", reply("second"), head).into_bytes());
+
+    let conn = Connection::with_stream(stream, "unix:pair", pending)?;
+    assert_eq!(conn.read().unwrap().address(), "unix:pair");
+    peer.write_all(tail.as_bytes()).map_err(minto_cherr!())?;
+
+    let mut call = OrgVarlinkServiceClient::new(conn);
+    assert_eq!(&call.get_info()?.vendor, "second");
+    assert_eq!(&call.get_info()?.vendor, "third");
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_connection_transport() -> Result<()> {
+    use std::net::TcpListener;
+    use std::os::unix::net::UnixStream;
+
+    let listener = TcpListener::bind("127.0.0.1:0").map_err(minto_cherr!())?;
+    let address = format!("tcp:{}", listener.local_addr().map_err(minto_cherr!())?);
+    let conn = Connection::with_address(&address)?;
+    assert_eq!(conn.read().unwrap().address(), address);
+    assert_eq!(conn.read().unwrap().transport(), Transport::Tcp);
+
+    let (stream, _peer) = UnixStream::pair().map_err(minto_cherr!())?;
+    let conn = Connection::with_stream(VarlinkStream::UNIX(stream), "unix:pair", Vec::new())?;
+    assert_eq!(conn.read().unwrap().transport(), Transport::Unix);
+
+    // without the stream, the transport follows the address
+    let (stream, _peer) = UnixStream::pair().map_err(minto_cherr!())?;
+    let conn = Connection::with_stream(VarlinkStream::UNIX(stream), &address, Vec::new())?;
+    let mut conn = conn.write().unwrap();
+    assert_eq!(conn.stream.take().unwrap().transport(), Transport::Unix);
+    assert_eq!(conn.transport(), Transport::Tcp);
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_pass_fds() -> Result<()> {
+    use std::fs::File;
+    use std::io::{Seek, SeekFrom};
+    use std::os::unix::io::{AsRawFd, FromRawFd};
+    use std::os::unix::net::UnixStream;
+
+    fn read_fd(fd: RawFd) -> String {
+        let mut file = unsafe { File::from_raw_fd(fd) };
+        let mut content = String::new();
+        file.seek(SeekFrom::Start(0)).unwrap();
+        file.read_to_string(&mut content).unwrap();
+        content
+    }
+
+    let mut file = tempfile::tempfile().map_err(minto_cherr!())?;
+    file.write_all(b"passed").map_err(minto_cherr!())?;
+
+    let (client, server) = UnixStream::pair().map_err(minto_cherr!())?;
+    let client = VarlinkStream::UNIX(client);
+    let mut writer = Vec::new();
+    let mut call = Call::new_upgraded(&mut writer);
+    call.context.unix_socket = Some(server.as_raw_fd());
+
+    client.send_fds(&[file.as_raw_fd()])?;
+    let fds = call.recv_fds(2)?;
+    assert_eq!(fds.len(), 1);
+    assert_ne!(
+        unsafe { libc::fcntl(fds[0], libc::F_GETFD) } & libc::FD_CLOEXEC,
+        0
+    );
+    assert_eq!(read_fd(fds[0]), "passed");
+
+    call.send_fds(&[file.as_raw_fd(), file.as_raw_fd()])?;
+    let fds = client.recv_fds(2)?;
+    assert_eq!(fds.len(), 2);
+    assert_eq!(read_fd(fds[1]), "passed");
+    unsafe { libc::close(fds[0]) };
+
+    // more file descriptors than expected
+    client.send_fds(&[file.as_raw_fd(), file.as_raw_fd()])?;
+    assert_eq!(
+        *call.recv_fds(1).err().unwrap().kind(),
+        ErrorKind::Io(io::ErrorKind::InvalidData)
+    );
+
+    // only upgraded unix socket connections
+    let request = Request::create("org.example.ping.Ping", None);
+    let mut writer = Vec::new();
+    let mut call = Call::new(&mut writer, &request);
+    call.context.unix_socket = Some(server.as_raw_fd());
+    assert_eq!(
+        *call.send_fds(&[]).err().unwrap().kind(),
+        ErrorKind::MethodNotImplemented("send_fds".into())
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_forward() -> Result<()> {
+    let address = "unix:test_forward";
+
+    let child = thread::spawn(move || {
+        let service = VarlinkService::new(
+            "org.varlink",
+            "test service",
+            "0.1",
+            "http://varlink.org",
+            vec![],
+        );
+
+        if let Err(e) = listen(service, &address, 1, 10, 1) {
+            if *e.kind() != ErrorKind::Timeout {
+                panic!("Error listen: {:#?}", e);
+            }
+        }
+    });
+
+    // give server time to start
+    thread::sleep(time::Duration::from_secs(1));
+
+    {
+        // the proxy side: requests are forwarded without knowing their types
+        let conn = Connection::with_address(address)?;
+        let mut conn = conn.write().unwrap();
+
+        let request: Request =
+            from_slice(br#"{"method":"org.varlink.service.GetInfo","parameters":{}}"#).unwrap();
+        let replies = conn.forward(&request)?;
+        assert_eq!(replies.len(), 1);
+        let parameters = replies[0].parameters.as_ref().unwrap();
+        assert_eq!(parameters["product"], "test service");
+        assert_eq!(parameters["interfaces"][0], "org.varlink.service");
+
+        let request = Request::create("org.example.Unknown", None);
+        let replies = conn.forward(&request)?;
+        assert_eq!(
+            replies[0].error,
+            Some("org.varlink.service.InterfaceNotFound".into())
+        );
+
+        let mut request = Request::create("org.varlink.service.GetInfo", None);
+        request.more = Some(true);
+        let mut count = 0;
+        conn.forward_streaming(&request, |reply| {
+            assert_eq!(reply.continues, None);
+            count += 1;
+            Ok(())
+        })?;
+        assert_eq!(count, 1);
+
+        let mut request = Request::create("org.varlink.service.GetInfo", None);
+        request.oneway = Some(true);
+        assert!(conn.forward(&request)?.is_empty());
+    }
+
+    assert!(child.join().is_ok());
+
+    Ok(())
+}
+
+#[test]
+fn test_string_hash_set_from_str() {
+    let set: StringHashSet = serde_json::from_str(r#"{"a":{},"b":{}}"#).unwrap();
+    assert!(set.contains("a") && set.contains("b"));
+
+    // the value of an entry is skipped, whatever it is
+    let other: StringHashSet = serde_json::from_str(r#"{"a":{"x":1},"b":null}"#).unwrap();
+    assert_eq!(other, set);
+
+    // the input following a set is left intact
+    let (inner, n): (StringHashSet, i64) = serde_json::from_str(r#"[{"a":{},"b":{}},1]"#).unwrap();
+    assert_eq!((inner, n), (set.clone(), 1));
+    assert_eq!(
+        serde_json::from_str::<StringHashSet>(&serde_json::to_string(&set).unwrap()).unwrap(),
+        set
+    );
+}
+
+#[test]
+fn test_map_error() -> Result<()> {
+    #[derive(Debug, PartialEq)]
+    enum LocalError {
+        InvalidArgument(String),
+        BackendFailed,
+    }
+
+    impl ::std::fmt::Display for LocalError {
+        fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+            write!(f, "{:?}", self)
+        }
+    }
+
+    let address = "unix:test_map_error";
+
+    let child = thread::spawn(move || {
+        let service = VarlinkService::new(
+            "org.varlink",
+            "test service",
+            "0.1",
+            "http://varlink.org",
+            vec![],
+        );
+
+        if let Err(e) = listen(service, &address, 1, 10, 1) {
+            if *e.kind() != ErrorKind::Timeout {
+                panic!("Error listen: {:#?}", e);
+            }
+        }
+    });
+
+    // give server time to start
+    thread::sleep(time::Duration::from_secs(1));
+
+    {
+        let conn = Connection::with_address(address)?;
+        let e = MethodCall::<GetInterfaceDescriptionArgs, GetInterfaceDescriptionReply, ErrorKind>::new(
+            conn.clone(),
+            "org.varlink.service.GetInterfaceDescription",
+            GetInterfaceDescriptionArgs {
+                interface: "org.example.backend".into(),
+            },
+        )
+        .map_error(|e| match e {
+            ErrorKind::InvalidParameter(p) => LocalError::InvalidArgument(p.clone()),
+            _ => LocalError::BackendFailed,
+        })
+        .call()
+        .unwrap_err();
+
+        assert_eq!(e.kind(), &LocalError::InvalidArgument("interface".into()));
+        assert_eq!(
+            e.find_chain_cause::<ErrorKind>().map(|e| e.kind()),
+            Some(&ErrorKind::InvalidParameter("interface".into()))
+        );
+
+        // successful replies pass unchanged
+        let reply = MethodCall::<
+            GetInterfaceDescriptionArgs,
+            GetInterfaceDescriptionReply,
+            ErrorKind,
+        >::new(
+            conn,
+            "org.varlink.service.GetInterfaceDescription",
+            GetInterfaceDescriptionArgs {
+                interface: "org.varlink.service".into(),
+            },
+        )
+        .map_error(|_| LocalError::BackendFailed)
+        .call()
+        .unwrap();
+        assert!(reply.description.is_some());
+    }
+
+    assert!(child.join().is_ok());
+
+    Ok(())
+}
+
+#[test]
+fn test_nesting_depth() -> Result<()> {
+    let deep = |depth: usize| format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+
+    let mut service = VarlinkService::new(
+        "org.varlink",
+        "test service",
+        "0.1",
+        "http://varlink.org",
+        vec![],
+    );
+
+    // a deeply nested request is rejected without parsing it
+    let request = format!(
+        "{{\"method\":\"org.varlink.service.GetInfo\",\"parameters\":{{\"a\":{}}}}}\0",
+        deep(10_000)
+    );
+    let mut out: Vec<u8> = Vec::new();
+    let e = service
+        .handle(&mut io::BufReader::new(request.as_bytes()), &mut out, None)
+        .unwrap_err();
+    assert_eq!(
+        *e.kind(),
+        ErrorKind::NestingTooDeep(DEFAULT_MAX_NESTING_DEPTH)
+    );
+    assert!(out.is_empty());
+
+    // brackets in strings do not count
+    let request = format!(
+        "{{\"method\":\"org.varlink.service.GetInfo\",\"parameters\":{{\"a\":\"{}\\\"\"}}}}\0",
+        deep(10_000)
+    );
+    service.handle(&mut io::BufReader::new(request.as_bytes()), &mut out, None)?;
+    assert!(!out.is_empty());
+
+    service.set_max_nesting_depth(2);
+    let request = "{\"method\":\"org.varlink.service.GetInfo\",\"parameters\":{\"a\":[]}}\0";
+    let e = service
+        .handle(&mut io::BufReader::new(request.as_bytes()), &mut out, None)
+        .unwrap_err();
+    assert_eq!(*e.kind(), ErrorKind::NestingTooDeep(2));
+
+    // the same for a reply
+    let reply = format!("{{\"parameters\":{{\"a\":{}}}}}\0", deep(10_000));
+    let conn = Arc::new(RwLock::new(Connection::from_parts(
+        BufReader::new(Box::new(io::Cursor::new(reply.into_bytes()))),
+        Box::new(io::sink()),
+        "test".into(),
+        None,
+    )));
+    let e = MethodCall::<GetInfoArgs, Value, ErrorKind>::new(
+        conn,
+        "org.varlink.service.GetInfo",
+        GetInfoArgs {},
+    )
+    .call()
+    .unwrap_err();
+    assert_eq!(
+        *e.kind(),
+        ErrorKind::NestingTooDeep(DEFAULT_MAX_NESTING_DEPTH)
+    );
+
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_pass_credentials() -> Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    let listener = Listener::new("unix:test_pass_credentials")?;
+    let _client =
+        std::os::unix::net::UnixStream::connect("test_pass_credentials").map_err(minto_cherr!())?;
+    let mut stream = listener.accept(1)?;
+
+    let mut passcred: libc::c_int = 0;
+    let mut len = std::mem::size_of::<libc::c_int>() as libc::socklen_t;
+    let ret = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PASSCRED,
+            &mut passcred as *mut libc::c_int as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    assert_eq!(ret, 0);
+    assert_eq!(passcred, 1);
+
+    let credentials = stream.peer_credentials().unwrap();
+    assert_eq!(credentials.uid, unsafe { libc::getuid() });
+    assert_eq!(credentials.pid, unsafe { libc::getpid() });
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_exec_tempdir() -> Result<()> {
+    let e = crate::client::exec_tempdir("server", &["/nonexistent/tmp", "/nonexistent/run"])
+        .err()
+        .unwrap();
+    assert_eq!(*e.kind(), ErrorKind::ExecTempDir("server".into()));
+    assert!(e
+        .to_string()
+        .contains("could not create temp socket dir for exec: server"));
+
+    let dir = crate::client::exec_tempdir("server", &["/nonexistent/tmp", "."])?;
+    assert!(dir.path().is_dir());
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_listen_create_dirs() -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = tempfile::tempdir().map_err(minto_cherr!())?;
+    let socket_dir = dir.path().join("run").join("myservice");
+    let address = format!("unix:{}", socket_dir.join("test.sock").display());
+
+    let e = Listener::new(&address).err().unwrap();
+    assert_eq!(
+        *e.kind(),
+        ErrorKind::SocketDirMissing(socket_dir.display().to_string())
+    );
+
+    let service = VarlinkService::new(
+        "org.varlink",
+        "test service",
+        "0.1",
+        "http://varlink.org",
+        vec![],
+    );
+    let config = ListenConfig {
+        idle_timeout: 1,
+        create_dirs: Some(0o700),
+        ..Default::default()
+    };
+    let e = listen_with_config(service, &address, &config)
+        .err()
+        .unwrap();
+    assert_eq!(*e.kind(), ErrorKind::Timeout);
+
+    let mode = std::fs::metadata(&socket_dir)
+        .map_err(minto_cherr!())?
+        .permissions()
+        .mode();
+    assert_eq!(mode & 0o777, 0o700);
+    // the listener removes its socket, when it is dropped
+    assert!(!socket_dir.join("test.sock").exists());
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_linger() -> Result<()> {
+    use std::os::unix::io::AsRawFd;
+    use std::time::Duration;
+
+    let listener = Listener::new("tcp:127.0.0.1:23456")?;
+    let (mut client, _) = VarlinkStream::connect("tcp:127.0.0.1:23456")?;
+    let mut stream = listener.accept(1)?;
+
+    let get_linger = |fd| {
+        let mut linger = libc::linger {
+            l_onoff: 0,
+            l_linger: 0,
+        };
+        let mut len = std::mem::size_of::<libc::linger>() as libc::socklen_t;
+        let ret = unsafe {
+            libc::getsockopt(
+                fd,
+                libc::SOL_SOCKET,
+                libc::SO_LINGER,
+                &mut linger as *mut libc::linger as *mut libc::c_void,
+                &mut len,
+            )
+        };
+        assert_eq!(ret, 0);
+        (linger.l_onoff, linger.l_linger)
+    };
+
+    client.set_linger(Some(Duration::from_secs(5)))?;
+    assert_eq!(get_linger(client.as_raw_fd()), (1, 5));
+    // a fraction of a second does not turn into a reset on close
+    client.set_linger(Some(Duration::from_millis(500)))?;
+    assert_eq!(get_linger(client.as_raw_fd()), (1, 1));
+    client.set_linger(Some(Duration::from_millis(1500)))?;
+    assert_eq!(get_linger(client.as_raw_fd()), (1, 2));
+    client.set_linger(Some(Duration::from_secs(0)))?;
+    assert_eq!(get_linger(client.as_raw_fd()), (1, 0));
+    client.set_linger(None)?;
+    assert_eq!(get_linger(client.as_raw_fd()), (0, 0));
+
+    // queued data is still delivered when the lingering side closes
+    let data = vec![b'x'; 4 * 1024 * 1024];
+    let len = data.len();
+    let child = thread::spawn(move || {
+        stream.set_linger(Some(Duration::from_secs(5))).unwrap();
+        stream.write_all(&data).unwrap();
+    });
+
+    let (mut reader, _) = client.split()?;
+    let mut received = Vec::new();
+    reader.read_to_end(&mut received).map_err(minto_cherr!())?;
+    assert!(child.join().is_ok());
+    assert_eq!(received.len(), len);
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_connect_timeout() -> Result<()> {
+    use std::time::{Duration, Instant};
+
+    let path = "test_connect_timeout";
+    let _ = std::fs::remove_file(path);
+    let address = format!("unix:{}", path);
+
+    let start = Instant::now();
+    let e = VarlinkStream::connect_timeout(&address, Duration::from_millis(200))
+        .err()
+        .unwrap();
+    assert_eq!(*e.kind(), ErrorKind::Io(io::ErrorKind::TimedOut));
+    assert!(start.elapsed() >= Duration::from_millis(200));
+
+    // the service starts listening after the client started connecting
+    let server_address = address.clone();
+    let child = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(300));
+        let listener = Listener::new(&server_address).unwrap();
+        listener.accept(5).unwrap();
+    });
+
+    let (_stream, connected) = VarlinkStream::connect_timeout(&address, Duration::from_secs(5))?;
+    assert_eq!(connected, address);
+    assert!(child.join().is_ok());
+    let _ = std::fs::remove_file(path);
+
+    // the start of a service cannot be bounded
+    let start = Instant::now();
+    let e = VarlinkStream::connect_timeout("exec:sleep 10", Duration::from_millis(200))
+        .err()
+        .unwrap();
+    assert_eq!(*e.kind(), ErrorKind::InvalidAddress);
+    assert!(start.elapsed() < Duration::from_secs(5));
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_read_timeout() -> Result<()> {
+    use std::os::unix::net::UnixListener;
+    use std::time::{Duration, Instant};
+
+    let path = "test_read_timeout";
+    let _ = std::fs::remove_file(path);
+    let listener = UnixListener::bind(path).map_err(minto_cherr!())?;
+
+    // accept the connection, but never reply
+    let child = thread::spawn(move || {
+        let (_stream, _) = listener.accept().unwrap();
+        thread::sleep(time::Duration::from_secs(3));
+    });
+
+    let conn = Connection::with_address(&format!("unix:{}", path))?;
+    {
+        let mut conn = conn.write().unwrap();
+        conn.set_read_timeout(Some(Duration::from_millis(500)))?;
+        conn.set_write_timeout(Some(Duration::from_millis(500)))?;
+    }
+
+    let start = Instant::now();
+    let e = OrgVarlinkServiceClient::new(conn).get_info();
+    match e.err().unwrap().kind() {
+        ErrorKind::Io(std::io::ErrorKind::WouldBlock)
+        | ErrorKind::Io(std::io::ErrorKind::TimedOut) => {}
+        kind => panic!("unexpected error: {:?}", kind),
+    }
+    assert!(start.elapsed() < Duration::from_secs(2));
+
+    assert!(child.join().is_ok());
+    let _ = std::fs::remove_file(path);
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_call_timeout() -> Result<()> {
+    use std::os::unix::net::UnixListener;
+    use std::time::Duration;
+
+    let path = "test_call_timeout";
+    let _ = std::fs::remove_file(path);
+    let listener = UnixListener::bind(path).map_err(minto_cherr!())?;
+
+    let child = thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut writer = stream.try_clone().unwrap();
+        let mut reader = MessageReader::new(stream);
+        while let Some(request) = reader.read_message().unwrap() {
+            let replies: &[&[u8]] = match request["method"].as_str().unwrap() {
+                "org.example.test.Slow" => {
+                    thread::sleep(Duration::from_millis(500));
+                    &[
+                        b"{\"continues\":true,\"parameters\":{\"n\":0}}\0",
+                        b"{\"parameters\":{\"n\":1}}\0",
+                    ]
+                }
+                "org.example.test.Fast" => &[b"{\"parameters\":{\"n\":2}}\0"],
+                _ => &[
+                    b"{\"continues\":true,\"parameters\":{\"n\":3}}\0",
+                    b"{\"continues\":true,\"parameters\":{\"n\":4}}\0",
+                    b"{\"parameters\":{\"n\":5}}\0",
+                ],
+            };
+            for reply in replies {
+                if request["more"] == true {
+                    thread::sleep(Duration::from_millis(150));
+                }
+                writer.write_all(reply).unwrap();
+            }
+        }
+    });
+
+    {
+        let conn = Connection::with_address(&format!("unix:{}", path))?;
+        let call = |method: &'static str| {
+            MethodCall::<Value, Value, ErrorKind>::new(conn.clone(), method, json!({}))
+        };
+
+        let e = call("org.example.test.Slow")
+            .timeout(Duration::from_millis(100))
+            .call()
+            .unwrap_err();
+        assert_eq!(*e.kind(), ErrorKind::Io(io::ErrorKind::TimedOut));
+
+        // the late replies of the timed out call are dropped
+        let reply = call("org.example.test.Fast").call()?;
+        assert_eq!(reply["n"], 2);
+
+        // the timeout applies to each reply of a `more` call
+        let replies = call("org.example.test.More")
+            .timeout(Duration::from_millis(400))
+            .more()?
+            .map(|r| r.map(|r| r["n"].as_u64().unwrap()))
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(replies, vec![3, 4, 5]);
+    }
+
+    assert!(child.join().is_ok());
+    let _ = std::fs::remove_file(path);
+
+    Ok(())
+}
+
+#[cfg(feature = "tls")]
+#[test]
+fn test_tls() -> Result<()> {
+    let address = "tcps:localhost:23460";
+
+    let child = thread::spawn(move || {
+        let service = VarlinkService::new(
+            "org.varlink",
+            "test service",
+            "0.1",
+            "http://varlink.org",
+            vec![],
+        );
+
+        let config = ListenConfig {
+            idle_timeout: 3,
+            tls: Some(
+                TlsServerConfig::from_pem(
+                    include_bytes!("../tests/tls/localhost.pem"),
+                    include_bytes!("../tests/tls/localhost.key"),
+                )
+                .unwrap(),
+            ),
+            ..Default::default()
+        };
+
+        if let Err(e) = listen_with_config(service, &address, &config) {
+            if *e.kind() != ErrorKind::Timeout {
+                panic!("Error listen: {:#?}", e);
+            }
+        }
+    });
+
+    // give server time to start
+    thread::sleep(time::Duration::from_secs(1));
+
+    // the test CA is not one of the root certificates of the system
+    match Connection::with_address(address) {
+        Err(e) => match e.kind() {
+            ErrorKind::Tls(_) => {}
+            kind => panic!("unexpected error: {:?}", kind),
+        },
+        Ok(_) => panic!("connected to a service with an unknown CA"),
+    }
+
+    let tls = TlsClientConfig::with_ca_pem(include_bytes!("../tests/tls/ca.pem"))?;
+    let conn = Connection::with_address_tls(address, &tls)?;
+    assert_eq!(
+        &OrgVarlinkServiceClient::new(conn).get_info()?.product,
+        "test service"
+    );
+
+    assert!(child.join().is_ok());
+
+    Ok(())
+}
+
+#[cfg(feature = "tls")]
+#[test]
+fn test_tls_connect_timeout() -> Result<()> {
+    use std::net::TcpListener;
+    use std::time::{Duration, Instant};
+
+    // the service accepts the TCP connection, but never answers the handshake
+    let listener = TcpListener::bind("127.0.0.1:0").map_err(minto_cherr!())?;
+    let address = format!("tcps:{}", listener.local_addr().map_err(minto_cherr!())?);
+    let child = thread::spawn(move || listener.accept().map(|(stream, _)| stream));
+
+    let start = Instant::now();
+    let e = VarlinkStream::connect_timeout(&address, Duration::from_millis(200))
+        .err()
+        .unwrap();
+    assert_eq!(*e.kind(), ErrorKind::Io(io::ErrorKind::TimedOut));
+    assert!(start.elapsed() < Duration::from_secs(5));
+
+    drop(child.join().unwrap());
+    Ok(())
+}
+
+#[test]
+fn test_tcp_ipv6() -> Result<()> {
+    use crate::client::tcp_host_port;
+
+    assert_eq!(tcp_host_port("127.0.0.1:8080")?, ("127.0.0.1", 8080));
+    assert_eq!(tcp_host_port("localhost:8080")?, ("localhost", 8080));
+    assert_eq!(tcp_host_port("[::1]:8080")?, ("::1", 8080));
+    assert_eq!(tcp_host_port("[fe80::1]:0")?, ("fe80::1", 0));
+    for invalid in &[
+        "::1:8080",
+        "[::1]",
+        "[::1]:",
+        "[::1]:http",
+        "8080",
+        ":8080",
+        "[]:8080",
+    ] {
+        assert_eq!(
+            tcp_host_port(invalid).err().unwrap().kind(),
+            &ErrorKind::InvalidAddress,
+            "{}",
+            invalid
+        );
+    }
+
+    let listener = Listener::new("tcp:[::1]:23461")?;
+    let (mut client, address) = VarlinkStream::connect("tcp:[::1]:23461")?;
+    assert_eq!(address, "tcp:[::1]:23461");
+    let mut stream = listener.accept(1)?;
+
+    stream.write_all(b"ping").map_err(minto_cherr!())?;
+    let (mut reader, _) = client.split()?;
+    let mut buf = [0u8; 4];
+    reader.read_exact(&mut buf).map_err(minto_cherr!())?;
+    assert_eq!(&buf, b"ping");
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_read_buffer_reused() -> Result<()> {
+    use std::os::unix::net::UnixStream;
+
+    // replies of the same size
+    let mut replies = Vec::new();
+    for i in 0..100 {
+        replies.extend_from_slice(
+            format!(
+                "{{\"continues\":true,\"parameters\":{{\"n\":\"{:03}\"}}}}\0",
+                i
+            )
+            .as_bytes(),
+        );
+    }
+
+    let mut reader = BufReader::new(&replies[..]);
+    let mut buf = Vec::new();
+    read_message(&mut reader, &mut buf, None).map_err(minto_cherr!())?;
+    let ptr = buf.as_ptr();
+    for _ in 1..100 {
+        read_message(&mut reader, &mut buf, None).map_err(minto_cherr!())?;
+        assert_eq!(buf.as_ptr(), ptr);
+    }
+    read_message(&mut reader, &mut buf, None).map_err(minto_cherr!())?;
+    assert!(buf.is_empty());
+
+    // the replies of a call are read into the buffer of the connection
+    let (stream, mut peer) = UnixStream::pair().map_err(minto_cherr!())?;
+    peer.write_all(&replies).map_err(minto_cherr!())?;
+    let conn = Connection::with_stream(VarlinkStream::UNIX(stream), "unix:pair", Vec::new())?;
+    let mut call = MethodCall::<Value, Value, ErrorKind>::new(
+        conn.clone(),
+        "org.example.more.TestMore",
+        serde_json::json!({}),
+    );
+    call.more()?;
+    assert_eq!(call.recv()?["n"], "000");
+    let ptr = conn.read().unwrap().read_buffer.as_ptr();
+    for _ in 1..100 {
+        call.recv()?;
+        assert_eq!(conn.read().unwrap().read_buffer.as_ptr(), ptr);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_retry() -> Result<()> {
+    let address = "unix:test_retry";
+    let listener = Listener::new(address)?;
+
+    // close the first connection after receiving the call, like a restarting service,
+    // and reply on the second one
+    let child = thread::spawn(move || {
+        let mut requests = Vec::new();
+        for reply in &[None, Some("test service")] {
+            let mut stream = listener.accept(5).unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut request = Vec::new();
+            read_message(&mut reader, &mut request, None).unwrap();
+            requests.push(request);
+            if let Some(product) = reply {
+                let reply = serde_json::json!({"parameters": {
+                    "vendor": "org.varlink",
+                    "product": product,
+                    "version": "0.1",
+                    "url": "http://varlink.org",
+                    "interfaces": ["org.varlink.service"],
+                }});
+                stream.write_all(format!("{}\0", reply).as_bytes()).unwrap();
+            }
+        }
+        requests
+    });
+
+    let conn = Connection::new_with_retry(address, RetryPolicy::default())?;
+    assert_eq!(
+        &OrgVarlinkServiceClient::new(conn).get_info()?.product,
+        "test service"
+    );
+
+    let requests = child.join().unwrap();
+    assert_eq!(requests[0], requests[1]);
+
+    Ok(())
+}
+
+#[test]
+fn test_shutdown() -> Result<()> {
+    let address = "unix:test_shutdown";
+    let handle = ShutdownHandle::new();
+
+    let server_handle = handle.clone();
+    let child = thread::spawn(move || {
+        let service = VarlinkService::new(
+            "org.varlink",
+            "test service",
+            "0.1",
+            "http://varlink.org",
+            vec![],
+        );
+
+        let config = ListenConfig {
+            shutdown: Some(server_handle),
+            ..Default::default()
+        };
+
+        if let Err(e) = listen_with_config(service, &address, &config) {
+            panic!("Error listen: {:#?}", e);
+        }
+    });
+
+    let conn = Connection::with_address_timeout(address, time::Duration::from_secs(5))?;
+    let mut client = OrgVarlinkServiceClient::new(conn);
+    assert_eq!(&client.get_info()?.product, "test service");
+
+    // the open connection does not keep the server running
+    handle.shutdown();
+    assert!(handle.is_shutdown());
+    assert!(child.join().is_ok());
+
+    assert!(client.get_info().is_err());
+    assert!(!std::path::Path::new("test_shutdown").exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_max_lifetime() -> Result<()> {
+    use std::time::{Duration, Instant};
+
+    let address = "unix:test_max_lifetime";
+    let start = Instant::now();
+
+    let child = thread::spawn(move || {
+        let service = VarlinkService::new(
+            "org.varlink",
+            "test service",
+            "0.1",
+            "http://varlink.org",
+            vec![],
+        );
+
+        let config = ListenConfig {
+            idle_timeout: 10,
+            max_lifetime: 2,
+            ..Default::default()
+        };
+
+        listen_with_config(service, &address, &config).map_err(|e| e.kind().clone())
+    });
+
+    // a server with an open connection returns at the end of its lifetime, too
+    let conn = Connection::with_address_timeout(address, Duration::from_secs(5))?;
+    let mut client = OrgVarlinkServiceClient::new(conn);
+    assert_eq!(&client.get_info()?.product, "test service");
+
+    assert_eq!(child.join().unwrap(), Err(ErrorKind::Timeout));
+    let elapsed = start.elapsed();
+    assert!(elapsed >= Duration::from_secs(2) && elapsed < Duration::from_secs(5));
+    assert!(client.get_info().is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_idle_worker_threads() {
+    use crate::server::ThreadPool;
+    use std::time::{Duration, Instant};
+
+    let mut pool = ThreadPool::new(1, 10, Some(Duration::from_millis(100)));
+    for _ in 0..4 {
+        pool.execute(|| thread::sleep(Duration::from_millis(300)));
+    }
+    assert!(pool.num_workers() > 1);
+
+    // the additional threads exit one at a time, after they are idle
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while pool.num_workers() > 1 && Instant::now() < deadline {
+        thread::sleep(Duration::from_millis(50));
+    }
+    assert_eq!(pool.num_workers(), 1);
+    assert_eq!(pool.num_busy(), 0);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_bridge() -> Result<()> {
+    use std::io::{self, BufReader, Cursor, Read, Write};
+    use std::os::unix::net::UnixStream;
+
+    /// Fails every read and write.
+    struct Broken;
+
+    impl Read for Broken {
+        fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+            Err(io::Error::from(io::ErrorKind::ConnectionReset))
+        }
+    }
+
+    impl Write for Broken {
+        fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+            Err(io::Error::from(io::ErrorKind::BrokenPipe))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let address = "unix:test_bridge";
+    let handle = ShutdownHandle::new();
+
+    let server_handle = handle.clone();
+    let child = thread::spawn(move || {
+        let service = VarlinkService::new(
+            "org.varlink",
+            "test service",
+            "0.1",
+            "http://varlink.org",
+            vec![],
+        );
+
+        let config = ListenConfig {
+            shutdown: Some(server_handle),
+            ..Default::default()
+        };
+
+        if let Err(e) = listen_with_config(service, &address, &config) {
+            panic!("Error listen: {:#?}", e);
+        }
+    });
+
+    let conn = Connection::with_address_timeout(address, time::Duration::from_secs(5))?;
+
+    // errors of the client are returned and leave the connection usable
+    assert!(bridge_connection(conn.clone(), BufReader::new(Broken), Vec::new()).is_err());
+    let get_info = b"{\"method\":\"org.varlink.service.GetInfo\"}\0".to_vec();
+    assert!(bridge_connection(conn.clone(), Cursor::new(get_info), Broken).is_err());
+
+    let requests = b"{\"method\":\"org.varlink.service.GetInfo\"}\0\
+                     {\"method\":\"org.varlink.service.GetInterfaceDescription\",\
+                     \"parameters\":{\"interface\":\"org.varlink.service\"}}\0"
+        .to_vec();
+    let (client_writer, mut replies) = UnixStream::pair().map_err(minto_cherr!())?;
+
+    bridge_connection(conn, Cursor::new(requests), client_writer)?;
+
+    let mut buf = Vec::new();
+    replies.read_to_end(&mut buf).map_err(minto_cherr!())?;
+    let replies: Vec<&[u8]> = buf.split(|c| *c == 0).filter(|r| !r.is_empty()).collect();
+    assert_eq!(replies.len(), 2);
+
+    let reply: Reply = from_slice(replies[0]).map_err(minto_cherr!())?;
+    let info: ServiceInfo = from_value(reply.parameters.unwrap()).map_err(minto_cherr!())?;
+    assert_eq!(&info.product, "test service");
+
+    let reply: Reply = from_slice(replies[1]).map_err(minto_cherr!())?;
+    assert!(reply.error.is_none());
+
+    handle.shutdown();
+    assert!(child.join().is_ok());
+
+    Ok(())
+}
+
+#[test]
+fn test_framing() -> Result<()> {
+    use std::io::{self, Read};
+
+    /// Returns at most `chunk` bytes per `read`.
+    struct Chunked<'a> {
+        data: &'a [u8],
+        chunk: usize,
+    }
+
+    impl<'a> Read for Chunked<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = self.chunk.min(buf.len()).min(self.data.len());
+            buf[..n].copy_from_slice(&self.data[..n]);
+            self.data = &self.data[n..];
+            Ok(n)
+        }
+    }
+
+    let mut writer = MessageWriter::new(Vec::new());
+    writer
+        .write_message(&json!({"method": "org.example.Ping", "parameters": {"ping": "a"}}))
+        .map_err(minto_cherr!())?;
+    writer
+        .write_message(&json!({"method": "org.example.Ping", "parameters": {"ping": "\u{0}"}}))
+        .map_err(minto_cherr!())?;
+    assert!(writer.write_frame(b"{}\0{}").is_err());
+    let data = writer.into_inner();
+    assert_eq!(data.iter().filter(|&&b| b == 0).count(), 2);
+
+    // every message and null byte split across reads
+    for chunk in 1..data.len() {
+        let mut reader = MessageReader::new(Chunked { data: &data, chunk });
+        let m = reader.read_message().map_err(minto_cherr!())?.unwrap();
+        assert_eq!(m["parameters"]["ping"], "a");
+        let m = reader.read_message().map_err(minto_cherr!())?.unwrap();
+        assert_eq!(m["parameters"]["ping"], "\u{0}");
+        assert!(reader.read_message().map_err(minto_cherr!())?.is_none());
+    }
+
+    let mut reader = MessageReader::new(&data[..data.len() - 1]);
+    assert!(reader.read_message().map_err(minto_cherr!())?.is_some());
+    let e = reader.read_message().err().unwrap();
+    assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof);
+
+    let mut reader = MessageReader::new(&b"{\"method\":\0"[..]);
+    let e = reader.read_message().err().unwrap();
+    assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+
+    Ok(())
+}
+
+#[cfg(all(unix, not(any(target_os = "linux", target_os = "android"))))]
+#[test]
+fn test_abstract_socket_unsupported() -> Result<()> {
+    let e = Connection::with_address("unix:@org.example.test_abstract")
+        .err()
+        .unwrap();
+    assert_eq!(e.kind(), &ErrorKind::Io(std::io::ErrorKind::Other));
+    assert!(format!("{:?}", e).contains("abstract sockets not supported on this platform"));
+
+    let service = VarlinkService::new(
+        "org.varlink",
+        "test service",
+        "0.1",
+        "http://varlink.org",
+        vec![],
+    );
+    let e = listen(service, "unix:@org.example.test_abstract", 1, 10, 1)
+        .err()
+        .unwrap();
+    assert_eq!(e.kind(), &ErrorKind::Io(std::io::ErrorKind::Other));
+
+    Ok(())
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn test_async_connection() -> Result<()> {
+    let address = "unix:test_async_connection";
+    let handle = ShutdownHandle::new();
+
+    let server_handle = handle.clone();
+    let child = thread::spawn(move || {
+        let service = VarlinkService::new(
+            "org.varlink",
+            "test service",
+            "0.1",
+            "http://varlink.org",
+            vec![],
+        );
+
+        let config = ListenConfig {
+            shutdown: Some(server_handle),
+            ..Default::default()
+        };
+
+        if let Err(e) = listen_with_config(service, &address, &config) {
+            panic!("Error listen: {:#?}", e);
+        }
+    });
+
+    // wait for the service
+    Connection::with_address_timeout(address, time::Duration::from_secs(5))?;
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_io()
+        .build()
+        .map_err(minto_cherr!())?;
+
+    runtime.block_on(async {
+        let conn = AsyncConnection::with_address(address).await?;
+
+        let info: ServiceInfo = conn.call("org.varlink.service.GetInfo", json!({})).await?;
+        assert_eq!(&info.product, "test service");
+
+        let r: Result<ServiceInfo> = conn.call("org.varlink.service.Unknown", json!({})).await;
+        assert_eq!(
+            r.err().unwrap().kind(),
+            &ErrorKind::MethodNotFound("org.varlink.service.Unknown".into())
+        );
+
+        // the connection is still usable after an error reply
+        let info: ServiceInfo = conn.call("org.varlink.service.GetInfo", json!({})).await?;
+        assert_eq!(&info.vendor, "org.varlink");
+        Ok::<(), Error>(())
+    })?;
+
+    handle.shutdown();
+    assert!(child.join().is_ok());
+
+    Ok(())
+}
+
+#[test]
+fn test_org_varlink_service_helpers() -> Result<()> {
+    let address = "unix:test_org_varlink_service_helpers";
+    let handle = ShutdownHandle::new();
+
+    let server_handle = handle.clone();
+    let child = thread::spawn(move || {
+        let service = VarlinkService::new(
+            "org.varlink",
+            "test service",
+            "0.1",
+            "http://varlink.org",
+            vec![],
+        );
+
+        let config = ListenConfig {
+            shutdown: Some(server_handle),
+            ..Default::default()
+        };
+
+        if let Err(e) = listen_with_config(service, &address, &config) {
+            panic!("Error listen: {:#?}", e);
+        }
+    });
+
+    let conn = Connection::with_address_timeout(address, time::Duration::from_secs(5))?;
+
+    let info = org_varlink_service::get_info(&conn)?;
+    assert_eq!(info.vendor, "org.varlink");
+    assert_eq!(info.product, "test service");
+    assert_eq!(info.interfaces, vec!["org.varlink.service".to_string()]);
+
+    let description = org_varlink_service::get_interface_description(&conn, "org.varlink.service")?;
+    assert!(description.starts_with("# The Varlink Service Interface"));
+
+    match org_varlink_service::get_interface_description(&conn, "org.example.unknown") {
+        Err(e) => assert_eq!(*e.kind(), ErrorKind::InvalidParameter("interface".into())),
+        Ok(_) => panic!("unknown interface has a description"),
+    }
+
+    handle.shutdown();
+    assert!(child.join().is_ok());
+
+    Ok(())
+}
+
+#[cfg(feature = "validate")]
+#[test]
+fn test_validate_interfaces() -> Result<()> {
+    let address = "unix:test_validate_interfaces";
+    let handle = ShutdownHandle::new();
+
+    let server_handle = handle.clone();
+    let child = thread::spawn(move || {
+        let service = VarlinkService::new(
+            "org.varlink",
+            "test service",
+            "0.1",
+            "http://varlink.org",
+            vec![],
+        );
+
+        let config = ListenConfig {
+            shutdown: Some(server_handle),
+            ..Default::default()
+        };
+
+        if let Err(e) = listen_with_config(service, &address, &config) {
+            panic!("Error listen: {:#?}", e);
+        }
+    });
+
+    let conn = Connection::with_address_timeout(address, time::Duration::from_secs(5))?;
+
+    // an older version of org.varlink.service without `url`
+    let service = "
+interface org.varlink.service
+method GetInfo() -> (vendor: string, product: string, version: string, interfaces: []string)
+method GetInterfaceDescription(interface: string) -> (description: string)
+error InterfaceNotFound (interface: string)
+error MethodNotFound (method: string)
+error MethodNotImplemented (method: string)
+error InvalidParameter (parameter: string)
+";
+    let ping = "interface org.example.ping\nmethod Ping(ping: string) -> (pong: string)";
+
+    assert_eq!(
+        validate_interfaces(&conn, &[service, ping])?,
+        vec![
+            Mismatch::AddedField {
+                interface: "org.varlink.service".into(),
+                member: "method GetInfo output".into(),
+                field: "url".into(),
+                vtype: "string".into(),
+            },
+            Mismatch::MissingInterface {
+                interface: "org.example.ping".into(),
+            },
+        ]
+    );
+
+    match validate_interfaces(&conn, &["interface org.example.broken"]) {
+        Err(e) => match e.kind() {
+            ErrorKind::InvalidInterfaceDescription(_) => {}
+            kind => panic!("wrong error: {:?}", kind),
+        },
+        Ok(_) => panic!("invalid description accepted"),
+    }
+
+    handle.shutdown();
+    assert!(child.join().is_ok());
+
+    Ok(())
+}
+
+#[test]
+fn test_call_request_flags() {
+    let mut writer = Vec::new();
+
+    let request = Request::create("org.example.ping.Ping", None);
+    let call = Call::new(&mut writer, &request);
+    assert!(!call.wants_more());
+    assert!(!call.is_oneway());
+    assert!(!call.is_upgrade());
+
+    let request = Request {
+        more: Some(true),
+        ..Request::create("org.example.ping.Ping", None)
+    };
+    assert!(Call::new(&mut writer, &request).wants_more());
+
+    let request = Request {
+        oneway: Some(true),
+        ..Request::create("org.example.ping.Ping", None)
+    };
+    assert!(Call::new(&mut writer, &request).is_oneway());
+
+    let request = Request {
+        upgrade: Some(true),
+        ..Request::create("org.example.ping.Upgrade", None)
+    };
+    let call = Call::new(&mut writer, &request);
+    assert!(call.is_upgrade());
+    assert!(!call.wants_more());
+}
+
+#[test]
+fn test_request_parameters_as() {
+    let request = Request::create("org.example.ping.Ping", Some(json!({"ping": "pong"})));
+    let args: StringHashMap<String> = request.parameters_as().unwrap().unwrap();
+    assert_eq!(args["ping"], "pong");
+
+    let args: Option<serde_json::Result<StringHashMap<i64>>> = request.parameters_as();
+    assert!(args.unwrap().is_err());
+
+    let request = Request::create("org.example.ping.Ping", None);
+    assert!(request.parameters_as::<StringHashMap<String>>().is_none());
+}
+
+#[test]
+fn test_call_replied_already() -> Result<()> {
+    let mut writer = Vec::new();
+    {
+        let request = Request::create("org.example.ping.Ping", None);
+        let mut call = Call::new(&mut writer, &request);
+        call.reply_struct(Reply::parameters(None))?;
+        match call.reply_struct(Reply::parameters(None)) {
+            Err(e) => assert_eq!(*e.kind(), ErrorKind::CallRepliedAlready),
+            Ok(_) => panic!("second reply sent"),
+        }
+    }
+    assert_eq!(writer.iter().filter(|&&b| b == 0).count(), 1);
+
+    let mut writer = Vec::new();
+    {
+        let request = Request {
+            more: Some(true),
+            ..Request::create("org.example.more.TestMore", None)
+        };
+        let mut call = Call::new(&mut writer, &request);
+        call.set_continues(true);
+        call.reply_struct(Reply::parameters(None))?;
+        call.reply_struct(Reply::parameters(None))?;
+        call.set_continues(false);
+        call.reply_struct(Reply::parameters(None))?;
+        assert!(call.reply_struct(Reply::parameters(None)).is_err());
+    }
+    assert_eq!(writer.iter().filter(|&&b| b == 0).count(), 3);
+
+    Ok(())
+}
+
+#[test]
+fn test_more_replies() -> Result<()> {
+    let request = Request {
+        more: Some(true),
+        ..Request::create("org.example.more.TestMore", None)
+    };
+    let replies = |writer: &[u8]| -> Vec<Reply> {
+        writer
+            .split(|&b| b == 0)
+            .filter(|r| !r.is_empty())
+            .map(|r| from_slice(r).unwrap())
+            .collect()
+    };
+
+    let mut writer = Vec::new();
+    {
+        let mut call = Call::new(&mut writer, &request);
+        let mut stream = call.begin_more();
+        stream.reply_struct(Reply::parameters(Some(serde_json::json!({ "n": 1 }))))?;
+        stream.reply_struct(Reply::parameters(Some(serde_json::json!({ "n": 2 }))))?;
+        stream.finish()?;
+    }
+    let r = replies(&writer);
+    assert_eq!(r.len(), 3);
+    assert_eq!(r[1].continues, Some(true));
+    assert_eq!(r[2], Reply::parameters(None));
+
+    // an early return sends the last reply on drop
+    let mut writer = Vec::new();
+    {
+        let mut call = Call::new(&mut writer, &request);
+        let stream = call.begin_more();
+        let send = |mut stream: MoreReplies<Call>| -> Result<()> {
+            stream.reply_struct(Reply::parameters(None))?;
+            Err(into_cherr!(ErrorKind::InvalidParameter("n".into())))
+        };
+        assert!(send(stream).is_err());
+    }
+    let r = replies(&writer);
+    assert_eq!(r.len(), 2);
+    assert_eq!(r[0].continues, Some(true));
+    assert_eq!(r[1].continues, None);
+
+    // nothing is sent on drop after the last reply
+    let mut writer = Vec::new();
+    {
+        let mut call = Call::new(&mut writer, &request);
+        let mut stream = call.begin_more();
+        stream.set_continues(false);
+        stream.reply_struct(Reply::parameters(Some(serde_json::json!({ "n": 1 }))))?;
+    }
+    assert_eq!(replies(&writer).len(), 1);
+
+    // an error reply after some replies ends the call, with or without `finish()`
+    for &finish in &[false, true] {
+        let mut writer = Vec::new();
+        {
+            let mut call = Call::new(&mut writer, &request);
+            let mut stream = call.begin_more();
+            stream.reply_struct(Reply::parameters(Some(serde_json::json!({ "n": 1 }))))?;
+            stream.reply_invalid_parameter("n".into())?;
+            assert!(stream.is_replied());
+            if finish {
+                stream.finish()?;
+            }
+        }
+        let r = replies(&writer);
+        assert_eq!(r.len(), 2);
+        assert_eq!(r[0].continues, Some(true));
+        assert_eq!(r[1].continues, None);
+        assert_eq!(
+            r[1].error,
+            Some("org.varlink.service.InvalidParameter".into())
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_reply_standard_errors() -> Result<()> {
+    let request = Request::create("org.example.ping.Ping", None);
+
+    let mut writer = Vec::new();
+    Call::new(&mut writer, &request).reply_permission_denied()?;
+    Call::new(&mut writer, &request).reply_expected_more()?;
+
+    let mut replies = writer.split(|&b| b == 0);
+    let reply: Reply = from_slice(replies.next().unwrap()).map_err(minto_cherr!())?;
+    assert!(ErrorKind::is_error(&reply));
+    assert_eq!(ErrorKind::from(reply), ErrorKind::PermissionDenied);
+    let reply: Reply = from_slice(replies.next().unwrap()).map_err(minto_cherr!())?;
+    assert_eq!(ErrorKind::from(reply), ErrorKind::ExpectedMore);
+
+    let e: Error = into_cherr!(ErrorKind::PermissionDenied);
+    assert_eq!(
+        ErrorKind::into_reply(&e).unwrap().error.unwrap(),
+        "org.varlink.service.PermissionDenied"
+    );
+
+    Ok(())
+}
+
+#[cfg(windows)]
+#[test]
+fn test_pipe_busy() -> Result<()> {
+    use std::ffi::OsStr;
+    use std::os::windows::ffi::OsStrExt;
+    use winapi::um::handleapi::{CloseHandle, INVALID_HANDLE_VALUE};
+    use winapi::um::winbase::{
+        CreateNamedPipeW, PIPE_ACCESS_DUPLEX, PIPE_TYPE_BYTE, PIPE_UNLIMITED_INSTANCES,
+    };
+
+    // A new instance of the pipe, the handle as `usize` to return it from a thread.
+    fn create_instance(path: &str) -> usize {
+        let wide: Vec<u16> = OsStr::new(path).encode_wide().chain(Some(0)).collect();
+        let handle = unsafe {
+            CreateNamedPipeW(
+                wide.as_ptr(),
+                PIPE_ACCESS_DUPLEX,
+                PIPE_TYPE_BYTE,
+                PIPE_UNLIMITED_INSTANCES,
+                4096,
+                4096,
+                0,
+                ::std::ptr::null_mut(),
+            )
+        };
+        assert_ne!(handle, INVALID_HANDLE_VALUE);
+        handle as usize
+    }
+
+    let path = r"\\.\pipe\varlink_test_pipe_busy";
+    let address = format!("unix:{}", path);
+
+    // the first client takes the only instance
+    let first = create_instance(path);
+    let (_busy, _) = VarlinkStream::connect(&address)?;
+
+    // the second client waits for the instance created later
+    let server = thread::spawn(move || {
+        thread::sleep(time::Duration::from_millis(200));
+        create_instance(path)
+    });
+    let (stream, _) = VarlinkStream::connect(&address)?;
+    assert_eq!(stream.transport(), Transport::Unix);
+    let second = server.join().unwrap();
+
+    unsafe {
+        CloseHandle(first as _);
+        CloseHandle(second as _);
+    }
+    Ok(())
+}
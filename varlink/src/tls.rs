@@ -0,0 +1,191 @@
+//! TLS for `tcps:` addresses, enabled with the `tls` feature
+
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, TcpStream};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use native_tls::{Certificate, HandshakeError, Identity, TlsAcceptor, TlsConnector};
+
+use crate::client::tcp_host_port;
+use crate::error::*;
+use chainerror::*;
+
+fn tls_error<E: ::std::error::Error + Send + Sync + 'static>(e: E) -> Error {
+    let msg = e.to_string();
+    cherr!(e, ErrorKind::Tls(msg))
+}
+
+fn handshake_error(e: HandshakeError<TcpStream>) -> Error {
+    match e {
+        HandshakeError::Failure(e) => tls_error(e),
+        // The socket is blocking, unless a read or write timeout interrupted the handshake.
+        HandshakeError::WouldBlock(_) => into_cherr!(io::Error::from(io::ErrorKind::WouldBlock)),
+    }
+}
+
+/// The client side TLS settings of a `tcps:` connection.
+///
+/// The certificate of the service is verified against the root certificates of the system,
+/// unless a CA is pinned with [with_ca_pem](#method.with_ca_pem).
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// let tls = TlsClientConfig::with_ca_pem(&fs::read("ca.pem")?)?;
+/// let connection = Connection::with_address_tls("tcps:varlink.example.org:12345", &tls)?;
+/// ```
+#[derive(Clone)]
+pub struct TlsClientConfig {
+    connector: TlsConnector,
+}
+
+impl TlsClientConfig {
+    /// Verify the service with the root certificates of the system.
+    pub fn new() -> Result<Self> {
+        Ok(TlsClientConfig {
+            connector: TlsConnector::new().map_err(tls_error)?,
+        })
+    }
+
+    /// Verify the service only with the PEM encoded CA certificate `pem`.
+    pub fn with_ca_pem(pem: &[u8]) -> Result<Self> {
+        let ca = Certificate::from_pem(pem).map_err(tls_error)?;
+        Ok(TlsClientConfig {
+            connector: TlsConnector::builder()
+                .add_root_certificate(ca)
+                .disable_built_in_roots(true)
+                .build()
+                .map_err(tls_error)?,
+        })
+    }
+
+    /// Use a `native_tls::TlsConnector` configured by the caller, e.g. with a client
+    /// certificate.
+    pub fn from_connector(connector: TlsConnector) -> Self {
+        TlsClientConfig { connector }
+    }
+
+    /// Start a TLS session on `tcp`, connected to `address`, which is the `host:port` part of
+    /// the `tcps:` address.
+    pub(crate) fn connect(&self, address: &str, tcp: TcpStream) -> Result<TlsStream> {
+        let (host, _) = tcp_host_port(address)?;
+        let socket = tcp.try_clone().map_err(minto_cherr!())?;
+        let session = self.connector.connect(host, tcp).map_err(handshake_error)?;
+        Ok(TlsStream::new(session, socket))
+    }
+}
+
+impl fmt::Debug for TlsClientConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("TlsClientConfig { .. }")
+    }
+}
+
+/// The certificate and key, with which a service accepts connections on a `tcps:` address,
+/// see [ListenConfig::tls](struct.ListenConfig.html#structfield.tls).
+#[derive(Clone)]
+pub struct TlsServerConfig {
+    acceptor: TlsAcceptor,
+}
+
+impl TlsServerConfig {
+    /// Use the PEM encoded certificate chain `cert` and the PEM encoded PKCS #8 private `key`.
+    pub fn from_pem(cert: &[u8], key: &[u8]) -> Result<Self> {
+        let identity = Identity::from_pkcs8(cert, key).map_err(tls_error)?;
+        Self::from_identity(identity)
+    }
+
+    /// Use the certificate and key of the DER encoded PKCS #12 archive `der`.
+    pub fn from_pkcs12(der: &[u8], password: &str) -> Result<Self> {
+        let identity = Identity::from_pkcs12(der, password).map_err(tls_error)?;
+        Self::from_identity(identity)
+    }
+
+    /// Use a `native_tls::TlsAcceptor` configured by the caller.
+    pub fn from_acceptor(acceptor: TlsAcceptor) -> Self {
+        TlsServerConfig { acceptor }
+    }
+
+    fn from_identity(identity: Identity) -> Result<Self> {
+        Ok(TlsServerConfig {
+            acceptor: TlsAcceptor::new(identity).map_err(tls_error)?,
+        })
+    }
+
+    /// Run the server side of the TLS handshake on the accepted `tcp` connection.
+    pub(crate) fn accept(&self, tcp: TcpStream) -> Result<TlsStream> {
+        let socket = tcp.try_clone().map_err(minto_cherr!())?;
+        let session = self.acceptor.accept(tcp).map_err(handshake_error)?;
+        Ok(TlsStream::new(session, socket))
+    }
+}
+
+impl fmt::Debug for TlsServerConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("TlsServerConfig { .. }")
+    }
+}
+
+/// A TLS session over TCP.
+///
+/// The reading and the writing half returned by `split()` share the session, so a read
+/// blocking for the next message holds up a write from another thread until data arrives
+/// or the read times out.
+#[derive(Debug)]
+pub struct TlsStream {
+    session: Arc<Mutex<native_tls::TlsStream<TcpStream>>>,
+    socket: TcpStream,
+}
+
+impl TlsStream {
+    fn new(session: native_tls::TlsStream<TcpStream>, socket: TcpStream) -> Self {
+        TlsStream {
+            session: Arc::new(Mutex::new(session)),
+            socket,
+        }
+    }
+
+    fn lock(&self) -> io::Result<MutexGuard<native_tls::TlsStream<TcpStream>>> {
+        self.session
+            .lock()
+            .map_err(|_| io::Error::new(io::ErrorKind::Other, "TLS session poisoned"))
+    }
+
+    /// The underlying TCP socket, e.g. to set socket options.
+    pub fn socket(&self) -> &TcpStream {
+        &self.socket
+    }
+
+    pub fn try_clone(&self) -> io::Result<TlsStream> {
+        Ok(TlsStream {
+            session: self.session.clone(),
+            socket: self.socket.try_clone()?,
+        })
+    }
+
+    /// Send the TLS `close_notify` alert, if no other thread is using the session, and shut
+    /// down the socket.
+    pub fn shutdown(&self) -> io::Result<()> {
+        if let Ok(mut session) = self.session.try_lock() {
+            let _ = session.shutdown();
+        }
+        self.socket.shutdown(Shutdown::Both)
+    }
+}
+
+impl Read for TlsStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.lock()?.read(buf)
+    }
+}
+
+impl Write for TlsStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.lock()?.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.lock()?.flush()
+    }
+}
@@ -0,0 +1,63 @@
+//! Detect drift between the interfaces of a service and the local bindings, enabled with the
+//! `validate` feature
+
+use std::sync::{Arc, RwLock};
+
+use chainerror::*;
+pub use varlink_parser::Mismatch;
+use varlink_parser::{compare_interfaces, IDL};
+
+use crate::error::*;
+use crate::{org_varlink_service, Connection};
+
+fn invalid_description(e: ChainError<varlink_parser::Error>) -> Error {
+    let msg = e.to_string();
+    cherr!(e, ErrorKind::InvalidInterfaceDescription(msg))
+}
+
+/// Compare the interfaces of the service on `connection` with the `local` interface
+/// descriptions, from which the client bindings were generated.
+///
+/// For each interface, which the service advertises with `GetInfo` and which is defined in
+/// `local`, the remote description is fetched with `GetInterfaceDescription` and compared with
+/// [varlink_parser::compare_interfaces](../varlink_parser/fn.compare_interfaces.html). A local
+/// interface, which the service does not advertise, is a `Mismatch::MissingInterface`.
+/// Interfaces only advertised by the service are ignored.
+///
+/// A description, which does not parse, returns `ErrorKind::InvalidInterfaceDescription`.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// let connection = Connection::with_address("unix:/run/org.example.ping")?;
+/// let mismatches = varlink::validate_interfaces(
+///     &connection,
+///     &[include_str!("org.example.ping.varlink")],
+/// )?;
+/// for mismatch in mismatches {
+///     eprintln!("{}", mismatch);
+/// }
+/// ```
+pub fn validate_interfaces(
+    connection: &Arc<RwLock<Connection>>,
+    local: &[&str],
+) -> Result<Vec<Mismatch>> {
+    let info = org_varlink_service::get_info(connection)?;
+    let mut mismatches = Vec::new();
+
+    for description in local {
+        let local = IDL::from_string(description).map_err(invalid_description)?;
+        if !info.interfaces.iter().any(|i| i == local.name) {
+            mismatches.push(Mismatch::MissingInterface {
+                interface: local.name.into(),
+            });
+            continue;
+        }
+
+        let description = org_varlink_service::get_interface_description(connection, local.name)?;
+        let remote = IDL::from_string(&description).map_err(invalid_description)?;
+        mismatches.extend(compare_interfaces(&local, &remote));
+    }
+
+    Ok(mismatches)
+}
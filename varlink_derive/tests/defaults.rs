@@ -0,0 +1,43 @@
+varlink_derive::varlink!(
+    org_example_defaults,
+    r#"
+interface org.example.defaults
+
+type Settings (
+  verbose: bool = true,
+  retries: int = 3,
+  ratio: float = 0.5,
+  name: string = "example",
+  limit: ?int = -1,
+  comment: ?string,
+  tags: []string
+)
+
+method Configure(settings: Settings, dry_run: bool = false, level: int = 2) -> (applied: int = 0)
+"#
+);
+
+use crate::org_example_defaults::*;
+
+#[test]
+fn test_declared_defaults() {
+    assert_eq!(
+        Settings::default(),
+        Settings {
+            verbose: true,
+            retries: 3,
+            ratio: 0.5,
+            name: "example".into(),
+            limit: Some(-1),
+            comment: None,
+            tags: vec![],
+        }
+    );
+
+    let args = Configure_Args::default();
+    assert_eq!(args.settings, Settings::default());
+    assert!(!args.dry_run);
+    assert_eq!(args.level, 2);
+
+    assert_eq!(Configure_Reply::default().applied, 0);
+}
@@ -0,0 +1,119 @@
+use serde_json::json;
+
+varlink_derive::varlink!(
+    org_example_keywords,
+    r#"
+interface org.example.keywords
+
+type Self (self, super, crate, dyn, async, await, try, type)
+
+type Verbs (type, as, move, match)
+
+type Keywords (
+  self: bool,
+  super: bool,
+  crate: bool,
+  dyn: bool,
+  async: bool,
+  await: bool,
+  try: bool,
+  type: Self
+)
+
+method Type(self: Keywords, dyn: ?string) -> (super: Self, async: []Self)
+
+method Self(crate: int) -> ()
+
+method Async() -> (await: bool)
+
+error Dyn (try: bool)
+"#
+);
+
+use crate::org_example_keywords::*;
+
+#[test]
+fn test_keyword_fields() {
+    let keywords = Keywords {
+        self_: true,
+        super_: false,
+        crate_: true,
+        r#dyn: false,
+        r#async: true,
+        r#await: false,
+        r#try: true,
+        r#type: Self_::self_,
+    };
+
+    let value = serde_json::to_value(&keywords).unwrap();
+    assert_eq!(
+        value,
+        json!({
+            "self": true,
+            "super": false,
+            "crate": true,
+            "dyn": false,
+            "async": true,
+            "await": false,
+            "try": true,
+            "type": "self"
+        })
+    );
+    assert_eq!(serde_json::from_value::<Keywords>(value).unwrap(), keywords);
+}
+
+#[test]
+fn test_keyword_variants() {
+    for (variant, name) in &[
+        (Self_::self_, "self"),
+        (Self_::super_, "super"),
+        (Self_::crate_, "crate"),
+        (Self_::r#dyn, "dyn"),
+        (Self_::r#async, "async"),
+        (Self_::r#await, "await"),
+        (Self_::r#try, "try"),
+        (Self_::r#type, "type"),
+    ] {
+        let value = serde_json::to_value(variant).unwrap();
+        assert_eq!(value, json!(name));
+        assert_eq!(&serde_json::from_value::<Self_>(value).unwrap(), variant);
+    }
+}
+
+#[test]
+fn test_keyword_args() {
+    let args = Type_Args {
+        self_: Keywords {
+            self_: true,
+            super_: true,
+            crate_: true,
+            r#dyn: true,
+            r#async: true,
+            r#await: true,
+            r#try: true,
+            r#type: Self_::r#async,
+        },
+        r#dyn: None,
+    };
+    let value = serde_json::to_value(&args).unwrap();
+    assert!(value.get("self").is_some());
+    assert!(value.get("dyn").is_none());
+
+    let value = serde_json::to_value(&Self_Args { crate_: 1 }).unwrap();
+    assert_eq!(value, json!({ "crate": 1 }));
+
+    let value = serde_json::to_value(&Dyn_Args { r#try: true }).unwrap();
+    assert_eq!(value, json!({ "try": true }));
+}
+
+#[test]
+fn test_keyword_variants_round_trip() {
+    let verbs = vec![Verbs::r#type, Verbs::r#as, Verbs::r#move, Verbs::r#match];
+
+    let value = serde_json::to_value(&verbs).unwrap();
+    assert_eq!(value, json!(["type", "as", "move", "match"]));
+    assert_eq!(serde_json::from_value::<Vec<Verbs>>(value).unwrap(), verbs);
+
+    assert!(serde_json::from_value::<Verbs>(json!("type_")).is_err());
+    assert!(serde_json::from_value::<Verbs>(json!("r#type")).is_err());
+}
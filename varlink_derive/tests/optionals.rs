@@ -0,0 +1,55 @@
+use serde_json::json;
+
+varlink_derive::varlink!(
+    org_example_optionals,
+    r#"
+interface org.example.optionals
+
+type Nested (
+  ao: []?string,
+  oa: ?[]string,
+  oao: ?[]?string,
+  aa: [][]string
+)
+"#
+);
+
+use crate::org_example_optionals::*;
+
+#[test]
+fn test_nested_optionals() {
+    let nested = Nested {
+        ao: vec![Some("a".into()), None],
+        oa: Some(vec!["b".into()]),
+        oao: Some(vec![None, Some("c".into())]),
+        aa: vec![vec![], vec!["d".into(), "e".into()]],
+    };
+
+    let value = serde_json::to_value(&nested).unwrap();
+    assert_eq!(
+        value,
+        json!({
+            "ao": ["a", null],
+            "oa": ["b"],
+            "oao": [null, "c"],
+            "aa": [[], ["d", "e"]]
+        })
+    );
+    assert_eq!(serde_json::from_value::<Nested>(value).unwrap(), nested);
+
+    // a missing or null optional array is `None`, a null element of an array of optionals
+    // is `None`, too
+    let nested: Nested = serde_json::from_value(json!({
+        "ao": [null],
+        "oao": null,
+        "aa": []
+    }))
+    .unwrap();
+    assert_eq!(nested.ao, vec![None]);
+    assert_eq!(nested.oa, None);
+    assert_eq!(nested.oao, None);
+
+    // only the optional elements may be null
+    assert!(serde_json::from_value::<Nested>(json!({ "ao": [], "oa": [null], "aa": [] })).is_err());
+    assert!(serde_json::from_value::<Nested>(json!({ "ao": [], "aa": [null] })).is_err());
+}
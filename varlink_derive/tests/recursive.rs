@@ -0,0 +1,59 @@
+use serde_json::json;
+
+varlink_derive::varlink!(
+    org_example_recursive,
+    r#"
+interface org.example.recursive
+
+type Node (value: int, next: ?Node, children: []Node)
+
+type Even (next: ?Odd)
+
+type Odd (next: Even)
+
+method Walk(node: Node) -> (even: Even)
+"#
+);
+
+use crate::org_example_recursive::*;
+
+#[test]
+fn test_recursive_optional_field() {
+    let node = Node {
+        value: 1,
+        next: Some(Box::new(Node {
+            value: 2,
+            next: None,
+            children: vec![],
+        })),
+        children: vec![Node {
+            value: 3,
+            next: None,
+            children: vec![],
+        }],
+    };
+
+    let value = serde_json::to_value(&node).unwrap();
+    assert_eq!(
+        value,
+        json!({
+            "value": 1,
+            "next": { "value": 2, "next": null, "children": [] },
+            "children": [ { "value": 3, "next": null, "children": [] } ]
+        })
+    );
+    assert_eq!(serde_json::from_value::<Node>(value).unwrap(), node);
+}
+
+#[test]
+fn test_mutually_recursive_types() {
+    let even = Even {
+        next: Some(Box::new(Odd {
+            next: Even { next: None },
+        })),
+    };
+
+    let value = serde_json::to_value(&even).unwrap();
+    assert_eq!(value, json!({ "next": { "next": { "next": null } } }));
+    assert_eq!(serde_json::from_value::<Even>(value).unwrap(), even);
+}
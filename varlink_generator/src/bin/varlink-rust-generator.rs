@@ -10,6 +10,9 @@
 //! If <varlink_file> is omitted, input is expected to come from stdin.
 //!
 //! Output is sent to stdout.
+//!
+//! With `--changelog <old_varlink_file>` a summary of the changes of the generated API from
+//! the old to the new interface version is printed instead of the code.
 
 extern crate varlink_generator;
 
@@ -20,20 +23,26 @@ use std::io::{Read, Write};
 use std::path::Path;
 
 use chainerror::*;
-use varlink_generator::generate;
+use varlink_generator::{generate, generate_changelog};
 
 fn print_usage(program: &str, opts: &getopts::Options) {
     let brief = format!("Usage: {} [VARLINK FILE]", program);
     print!("{}", opts.usage(&brief));
 }
 
-fn main() -> std::result::Result<(), Box<std::error::Error>> {
+fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
     let args: Vec<_> = env::args().collect();
     let program = args[0].clone();
 
     let mut opts = getopts::Options::new();
     opts.optflag("h", "help", "print this help menu");
     opts.optflag("", "nosource", "don't print doc header and allow");
+    opts.optopt(
+        "",
+        "changelog",
+        "print the API changes from an older version of the varlink file",
+        "<OLD VARLINK FILE>",
+    );
 
     let matches = match opts.parse(&args[1..]) {
         Ok(m) => m,
@@ -51,7 +60,7 @@ fn main() -> std::result::Result<(), Box<std::error::Error>> {
 
     let tosource = !matches.opt_present("nosource");
 
-    let mut reader: Box<Read> = match matches.free.len() {
+    let mut reader: Box<dyn Read> = match matches.free.len() {
         0 => Box::new(io::stdin()),
         _ => {
             if matches.free[0] == "-" {
@@ -64,6 +73,13 @@ fn main() -> std::result::Result<(), Box<std::error::Error>> {
             }
         }
     };
-    let writer: &mut Write = &mut io::stdout();
+    let writer: &mut dyn Write = &mut io::stdout();
+
+    if let Some(old) = matches.opt_str("changelog") {
+        let mut old_reader =
+            File::open(Path::new(&old)).map_err(mstrerr!("Failed to open '{}'", &old))?;
+        return generate_changelog(&mut old_reader, &mut reader, writer).map_err(|e| e.into());
+    }
+
     generate(&mut reader, writer, tosource).map_err(|e| e.into())
 }
@@ -0,0 +1,136 @@
+//! Summarize the changes of the generated rust API between two interface versions
+
+use std::collections::BTreeMap;
+use std::io::Write;
+
+use chainerror::*;
+use varlink_parser::{VStructOrEnum, IDL};
+
+use crate::{to_rust_name, to_snake_case, Error, Result};
+
+/// A method, type or error of an interface and the rust items generated for it.
+struct ApiItem {
+    declaration: String,
+    signature: String,
+    rust_items: Vec<String>,
+}
+
+/// Collect the API items of `idl`, keyed by kind and varlink name.
+fn api_items(idl: &IDL) -> BTreeMap<(&'static str, String), ApiItem> {
+    let mut items = BTreeMap::new();
+
+    for t in idl.typedefs.values() {
+        let rust_kind = match t.elt {
+            VStructOrEnum::VStruct(_) => "struct",
+            VStructOrEnum::VEnum(_) => "enum",
+        };
+        items.insert(
+            ("type", t.name.to_string()),
+            ApiItem {
+                declaration: format!("type {} {}", t.name, t.elt),
+                signature: t.elt.to_string(),
+                rust_items: vec![format!("{} {}", rust_kind, to_rust_name(t.name))],
+            },
+        );
+    }
+
+    for m in idl.methods.values() {
+        items.insert(
+            ("method", m.name.to_string()),
+            ApiItem {
+                declaration: format!("method {}{} -> {}", m.name, m.input, m.output),
+                signature: format!("{} -> {}", m.input, m.output),
+                rust_items: vec![
+                    format!("fn {}", to_snake_case(m.name)),
+                    format!("struct {}_Args", m.name),
+                    format!("struct {}_Reply", m.name),
+                ],
+            },
+        );
+    }
+
+    for e in idl.errors.values() {
+        items.insert(
+            ("error", e.name.to_string()),
+            ApiItem {
+                declaration: format!("error {} {}", e.name, e.parm),
+                signature: e.parm.to_string(),
+                rust_items: vec![
+                    format!("ErrorKind::{}", to_rust_name(e.name)),
+                    format!("struct {}_Args", e.name),
+                ],
+            },
+        );
+    }
+
+    items
+}
+
+/// Write a summary of the API changes from the interface `old` to `new` to `writer`.
+pub(crate) fn write_changelog(old: &IDL, new: &IDL, writer: &mut dyn Write) -> Result<()> {
+    let old_items = api_items(old);
+    let new_items = api_items(new);
+
+    let mut added = Vec::new();
+    let mut removed = Vec::new();
+    let mut changed = Vec::new();
+
+    for (key, item) in &new_items {
+        match old_items.get(key) {
+            None => added.push(format!(
+                "  {}  [{}]",
+                item.declaration,
+                item.rust_items.join(", ")
+            )),
+            Some(old_item) if old_item.signature != item.signature => changed.push(format!(
+                "  {} {}\n    - {}\n    + {}",
+                key.0, key.1, old_item.signature, item.signature
+            )),
+            _ => {}
+        }
+    }
+
+    for (key, item) in &old_items {
+        if !new_items.contains_key(key) {
+            removed.push(format!(
+                "  {}  [{}]",
+                item.declaration,
+                item.rust_items.join(", ")
+            ));
+        }
+    }
+
+    let mut out = String::new();
+
+    if old.name == new.name {
+        out += &format!("interface {}", new.name);
+    } else {
+        out += &format!("interface {} renamed to {}", old.name, new.name);
+    }
+    out += &format!(
+        ": {} added, {} removed, {} changed\n",
+        added.len(),
+        removed.len(),
+        changed.len()
+    );
+
+    for (title, lines) in &[
+        ("Added", &added),
+        ("Removed", &removed),
+        ("Changed", &changed),
+    ] {
+        if lines.is_empty() {
+            continue;
+        }
+        out += &format!("\n{}:\n", title);
+        for line in lines.iter() {
+            out += line;
+            out += "\n";
+        }
+    }
+
+    writer
+        .write_all(out.as_bytes())
+        .map_err(mstrerr!(Error, "Failed to write to buffer"))?;
+    Ok(())
+}
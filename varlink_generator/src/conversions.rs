@@ -0,0 +1,243 @@
+//! `From` conversions between the types of structurally compatible interfaces
+
+use std::collections::HashSet;
+
+use proc_macro2::{Ident, Span, TokenStream};
+use quote::quote;
+use varlink_parser::{VStruct, VStructOrEnum, VType, VTypeExt, IDL};
+
+use crate::{to_rust_ident, GeneratorContext};
+
+/// Whether the type `name` has the same structure in `a` and `b`.
+///
+/// Types in `assumed` are taken as matching, which ends the comparison of recursive types.
+fn same_typedef<'a>(name: &'a str, a: &IDL<'a>, b: &IDL, assumed: &mut HashSet<&'a str>) -> bool {
+    if assumed.contains(name) {
+        return true;
+    }
+
+    match (a.typedefs.get(name), b.typedefs.get(name)) {
+        (Some(ta), Some(tb)) => {
+            assumed.insert(name);
+            match (&ta.elt, &tb.elt) {
+                (VStructOrEnum::VStruct(sa), VStructOrEnum::VStruct(sb)) => {
+                    same_struct(sa, sb, a, b, assumed)
+                }
+                (VStructOrEnum::VEnum(ea), VStructOrEnum::VEnum(eb)) => ea.elts == eb.elts,
+                _ => false,
+            }
+        }
+        _ => false,
+    }
+}
+
+fn same_struct<'a>(
+    sa: &VStruct<'a>,
+    sb: &VStruct,
+    a: &IDL<'a>,
+    b: &IDL,
+    assumed: &mut HashSet<&'a str>,
+) -> bool {
+    sa.elts.len() == sb.elts.len()
+        && sa
+            .elts
+            .iter()
+            .zip(sb.elts.iter())
+            .all(|(ea, eb)| ea.name == eb.name && same_type(&ea.vtype, &eb.vtype, a, b, assumed))
+}
+
+fn same_type<'a>(
+    ta: &VTypeExt<'a>,
+    tb: &VTypeExt,
+    a: &IDL<'a>,
+    b: &IDL,
+    assumed: &mut HashSet<&'a str>,
+) -> bool {
+    match (ta, tb) {
+        (VTypeExt::Array(va), VTypeExt::Array(vb))
+        | (VTypeExt::Dict(va), VTypeExt::Dict(vb))
+        | (VTypeExt::Option(va), VTypeExt::Option(vb)) => same_type(va, vb, a, b, assumed),
+        (VTypeExt::Plain(va), VTypeExt::Plain(vb)) => match (va, vb) {
+            (VType::Bool, VType::Bool)
+            | (VType::Int, VType::Int)
+            | (VType::Float, VType::Float)
+            | (VType::String, VType::String)
+            | (VType::Object, VType::Object) => true,
+            (VType::Typename(na), VType::Typename(nb)) => {
+                na == nb && same_typedef(na, a, b, assumed)
+            }
+            (VType::Struct(sa), VType::Struct(sb)) => same_struct(sa, sb, a, b, assumed),
+            (VType::Enum(ea), VType::Enum(eb)) => ea.elts == eb.elts,
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// The expression converting `expr` of the other interface to the field type `vtype`.
+///
+/// Returns `None`, if both types are the same rust type. The strings of a field with a
+/// `newtype` are converted, because each module has its own newtype.
+fn convert_field(
+    vtype: &VTypeExt,
+    expr: TokenStream,
+    path: &str,
+    newtype: bool,
+    options: &GeneratorContext,
+) -> Option<TokenStream> {
+    match *vtype {
+        VTypeExt::Plain(VType::Typename(_))
+        | VTypeExt::Plain(VType::Struct(_))
+        | VTypeExt::Plain(VType::Enum(_)) => Some(quote!(#expr.into())),
+        VTypeExt::Plain(VType::String) if newtype => Some(quote!(#expr.into())),
+        VTypeExt::Plain(_) => None,
+        VTypeExt::Array(ref v) => convert_field(v, quote!(v), path, newtype, options)
+            .map(|c| quote!(#expr.into_iter().map(|v| #c).collect())),
+        VTypeExt::Dict(ref v) => convert_field(v, quote!(v), path, newtype, options)
+            .map(|c| quote!(#expr.into_iter().map(|(k, v)| (k, #c)).collect())),
+        VTypeExt::Option(ref v) if options.boxed_fields.contains(path) => {
+            convert_field(v, quote!((*v)), path, newtype, options)
+                .map(|c| quote!(#expr.map(|v| Box::new(#c))))
+        }
+        VTypeExt::Option(ref v) => {
+            convert_field(v, quote!(v), path, newtype, options).map(|c| quote!(#expr.map(|v| #c)))
+        }
+    }
+}
+
+/// Generate the conversion from the struct `name` of the module `other`, and the
+/// conversions of its anonymous member types.
+fn generate_struct_conversion(
+    name: &str,
+    vstruct: &VStruct,
+    other: &TokenStream,
+    options: &GeneratorContext,
+    ts: &mut TokenStream,
+) {
+    let tname = to_rust_ident(name);
+    let mut fields = Vec::new();
+    let mut values = Vec::new();
+
+    for e in &vstruct.elts {
+        let path = format!("{}_{}", name, e.name);
+        let field = to_rust_ident(e.name);
+        let newtype = options.newtype(e.name).is_some();
+        values.push(
+            convert_field(&e.vtype, quote!(v.#field), &path, newtype, options)
+                .unwrap_or_else(|| quote!(v.#field)),
+        );
+        fields.push(field);
+        generate_member_conversions(&e.vtype, &path, other, options, ts);
+    }
+
+    let allow_deprecated = &options.allow_deprecated;
+    ts.extend(quote!(
+        #allow_deprecated
+        impl From<#other::#tname> for #tname {
+            #[allow(unused_variables)]
+            fn from(v: #other::#tname) -> Self {
+                #tname {
+                    #(#fields: #values,)*
+                }
+            }
+        }
+    ));
+}
+
+/// Generate the conversion from the enum `name` of the module `other`.
+fn generate_enum_conversion(
+    name: &str,
+    variants: &[&str],
+    other: &TokenStream,
+    options: &GeneratorContext,
+    ts: &mut TokenStream,
+) {
+    let tname = to_rust_ident(name);
+    let arms = variants.iter().map(|v| {
+        let variant = to_rust_ident(v);
+        quote!(#other::#tname::#variant => #tname::#variant,)
+    });
+
+    let allow_deprecated = &options.allow_deprecated;
+    ts.extend(quote!(
+        #allow_deprecated
+        impl From<#other::#tname> for #tname {
+            fn from(v: #other::#tname) -> Self {
+                match v {
+                    #(#arms)*
+                }
+            }
+        }
+    ));
+}
+
+/// Generate the conversions of the anonymous structs and enums in `vtype`.
+fn generate_member_conversions(
+    vtype: &VTypeExt,
+    path: &str,
+    other: &TokenStream,
+    options: &GeneratorContext,
+    ts: &mut TokenStream,
+) {
+    match *vtype {
+        VTypeExt::Array(ref v) | VTypeExt::Dict(ref v) | VTypeExt::Option(ref v) => {
+            generate_member_conversions(v, path, other, options, ts)
+        }
+        VTypeExt::Plain(VType::Struct(ref s)) => {
+            generate_struct_conversion(path, s, other, options, ts)
+        }
+        VTypeExt::Plain(VType::Enum(ref e)) => {
+            generate_enum_conversion(path, &e.elts, other, options, ts)
+        }
+        VTypeExt::Plain(_) => {}
+    }
+}
+
+/// Generate `From` conversions from the types of the interfaces `others` to the types of
+/// `idl` with the same name and structure.
+///
+/// The modules of the other interfaces are expected next to the module of `idl`, named
+/// after the interface with `.` replaced by `_`.
+pub(crate) fn generate_conversions(
+    idl: &IDL,
+    others: &[IDL],
+    options: &GeneratorContext,
+) -> TokenStream {
+    let mut ts = TokenStream::new();
+
+    for other in others.iter().filter(|o| o.name != idl.name) {
+        let module = Ident::new(&other.name.replace(".", "_"), Span::call_site());
+        let module = quote!(super::#module);
+
+        let mut newtypes = HashSet::new();
+        for (_, newtype) in options.newtype_fields.iter().flatten() {
+            if newtypes.insert(newtype) {
+                let tname = Ident::new(newtype, Span::call_site());
+                ts.extend(quote!(
+                    impl From<#module::#tname> for #tname {
+                        fn from(v: #module::#tname) -> Self {
+                            #tname(v.0)
+                        }
+                    }
+                ));
+            }
+        }
+
+        for name in &idl.typedef_keys {
+            if !same_typedef(name, idl, other, &mut HashSet::new()) {
+                continue;
+            }
+
+            match idl.typedefs[name].elt {
+                VStructOrEnum::VStruct(ref s) => {
+                    generate_struct_conversion(name, s, &module, options, &mut ts)
+                }
+                VStructOrEnum::VEnum(ref e) => {
+                    generate_enum_conversion(name, &e.elts, &module, options, &mut ts)
+                }
+            }
+        }
+    }
+
+    ts
+}
@@ -0,0 +1,94 @@
+//! Self-check of the generated code against the embedded interface description
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use varlink_parser::IDL;
+
+use crate::{to_rust_ident, GeneratorContext};
+
+/// Generate the module `generated_description_tests` with `check_description()` and a test
+/// calling it with the embedded description.
+///
+/// The names are taken from the generated code, the types from their `VARLINK_NAME`, the
+/// errors from the conversion of an error reply and the methods from the dispatch table of
+/// `VarlinkInterfaceProxy`. `stub_methods` are the methods of a `VarlinkInterface`
+/// implementation for the proxy, which only replies `MethodNotImplemented`.
+pub(crate) fn generate_description_test(
+    idl: &IDL,
+    stub_methods: TokenStream,
+    options: &GeneratorContext,
+) -> TokenStream {
+    let module = options.prefixed_snake("generated_description_tests");
+    let interface_trait = options.prefixed("VarlinkInterface");
+    let error_kind = options.prefixed("ErrorKind");
+    let proxy_struct = options.prefixed("VarlinkInterfaceProxy");
+    let new_fn = options.prefixed_snake("new");
+    let typedefs = idl.typedefs.keys().map(|name| to_rust_ident(name));
+    let allow_deprecated = &options.allow_deprecated;
+
+    quote!(
+        #[cfg(test)]
+        #[allow(non_snake_case)]
+        #allow_deprecated
+        pub(crate) mod #module {
+            use super::*;
+
+            struct DescriptionStub;
+
+            #[allow(unused_variables)]
+            impl #interface_trait for DescriptionStub {
+                #stub_methods
+            }
+
+            /// Check, that the interface `description` matches the generated code.
+            ///
+            /// The interface name, the type names, the errors and the methods have to be the
+            /// same. The errors are checked with the conversion of an error reply to
+            /// `ErrorKind`, the methods with the dispatch table of `VarlinkInterfaceProxy`.
+            pub fn check_description(description: &str) -> ::std::result::Result<(), String> {
+                let idl = varlink_parser::IDL::from_string(description)
+                    .map_err(|e| e.to_string())?;
+                let proxy = #new_fn(Box::new(DescriptionStub));
+
+                let name = varlink::Interface::get_name(&proxy);
+                if idl.name != name {
+                    return Err(format!("interface {} is generated as {}", idl.name, name));
+                }
+
+                let generated: &[&str] = &[#(#typedefs::VARLINK_NAME),*];
+                let typedefs: Vec<&str> = idl.typedefs.keys().cloned().collect();
+                if typedefs.as_slice() != generated {
+                    return Err(format!("types {:?} are generated as {:?}", typedefs, generated));
+                }
+
+                for error in idl.errors.keys() {
+                    let reply = varlink::Reply::error(format!("{}.{}", idl.name, error), None);
+                    let e = <#error_kind as ChainErrorFrom<varlink::Reply>>::chain_error_from(reply, None);
+                    if let #error_kind::VarlinkReply_Error(_) = e.kind() {
+                        return Err(format!("error {} is not generated", error));
+                    }
+                }
+
+                let generated: Vec<&str> = #proxy_struct::METHODS
+                    .iter()
+                    .map(|&(method, _)| method.rsplit('.').next().unwrap_or(method))
+                    .collect();
+                let methods: Vec<&str> = idl.methods.keys().cloned().collect();
+                if methods != generated {
+                    return Err(format!("methods {:?} are generated as {:?}", methods, generated));
+                }
+
+                Ok(())
+            }
+
+            #[test]
+            fn description_matches_generated_code() {
+                let proxy = #new_fn(Box::new(DescriptionStub));
+                let description = varlink::Interface::get_description(&proxy);
+                if let Err(e) = check_description(description) {
+                    panic!("{}", e);
+                }
+            }
+        }
+    )
+}
@@ -24,6 +24,18 @@
 //! }
 //! ```
 //! [`varlink_generator::cargo_build_tosource`]: fn.cargo_build_tosource.html
+//!
+//! The comments of the varlink file become the doc comments of the generated code. A method
+//! with the comment line `# @deprecated <note>` is called with client methods marked
+//! `#[deprecated(note = "<note>")]`, so code still calling it gets a warning:
+//!
+//! ```varlink
+//! # @deprecated use Pong instead
+//! method Ping(ping: string) -> (pong: string)
+//! ```
+//!
+//! The `_Args` and `_Reply` structs of the method are marked, too, and so is a type with this
+//! comment line. The generated code itself uses them with `#[allow(deprecated)]`.
 
 #![recursion_limit = "512"]
 #![doc(
@@ -32,18 +44,35 @@
 )]
 
 use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
 use std::env;
 use std::fs::File;
 use std::io::{Read, Write};
+use std::ops::Deref;
 use std::path::{Path, PathBuf};
-use std::process::{exit, Command};
+use std::process::{exit, Command, Stdio};
 use std::str::FromStr;
 
 use chainerror::*;
-use proc_macro2::{Ident, Span, TokenStream};
+use proc_macro2::{Ident, Literal, Span, TokenStream};
 use quote::quote;
 
-use varlink_parser::{Typedef, VEnum, VError, VStruct, VStructOrEnum, VType, VTypeExt, IDL};
+use varlink_parser::{
+    Argument, Typedef, VEnum, VError, VStruct, VStructOrEnum, VType, VTypeExt, IDL,
+};
+
+mod changelog;
+mod conversions;
+mod description_test;
+#[cfg(feature = "resolver")]
+mod resolver;
+mod round_trip;
+
+#[cfg(feature = "resolver")]
+pub use crate::resolver::{
+    cargo_build_from_resolver, cargo_build_from_resolver_options, description_from_resolver,
+    resolver_address, DEFAULT_RESOLVER,
+};
 
 derive_str_cherr!(Error);
 pub type Result<T> = ChainResult<T, Error>;
@@ -53,7 +82,7 @@ trait ToRustString<'short, 'long: 'short> {
         &'long self,
         name: &str,
         tokenstream: &mut TokenStream,
-        options: &'long GeneratorOptions,
+        options: &'long GeneratorContext<'long>,
     ) -> Cow<'long, str>;
 }
 
@@ -62,7 +91,7 @@ trait ToTokenStream<'short, 'long: 'short> {
         &'long self,
         name: &str,
         tokenstream: &mut TokenStream,
-        options: &'long GeneratorOptions,
+        options: &'long GeneratorContext<'long>,
     );
 }
 
@@ -73,6 +102,396 @@ pub struct GeneratorOptions {
     pub float_type: Option<&'static str>,
     pub string_type: Option<&'static str>,
     pub preamble: Option<TokenStream>,
+    /// Names of the methods, which may be called with `more`.
+    ///
+    /// If set, the generated interface replies with `org.varlink.service.InvalidParameter`
+    /// (parameter `more`) to a `more` call of any other method. A name, which is not a
+    /// method of the interface, is an error.
+    ///
+    /// For each of these methods, the client gets the named iterator `<Method>_MoreReply` over
+    /// the replies, which `VarlinkClient::<method>_more()` returns.
+    pub more_methods: Option<Vec<&'static str>>,
+    /// Names of the methods, which may be called with `oneway`.
+    ///
+    /// If set, the generated interface replies with `org.varlink.service.InvalidParameter`
+    /// (parameter `oneway`) to a `oneway` call of any other method, instead of calling it.
+    /// A name, which is not a method of the interface, is an error.
+    pub oneway_methods: Option<Vec<&'static str>>,
+    /// Generate `http_status()`, mapping an `Error` to a HTTP status code.
+    ///
+    /// Interface errors listed here by name map to the given status code, all other
+    /// interface errors map to `500`. Standard varlink errors map to `400` for
+    /// `InvalidParameter`, `404` for `InterfaceNotFound` and `MethodNotFound` and `501` for
+    /// `MethodNotImplemented`.
+    pub http_status: Option<Vec<(&'static str, u16)>>,
+    /// Generate `object` fields as `Box<serde_json::value::RawValue>` instead of
+    /// `serde_json::Value`.
+    ///
+    /// A handler, which only passes an object on, gets it as the JSON text and does not
+    /// have to build its own `serde_json::Value` tree for it. The varlink message itself is
+    /// still parsed by `VarlinkService`, so see the `raw_object` benchmark of
+    /// `varlink-certification` for the actual gain. The crate using the generated code has to
+    /// enable the `raw_value` feature of `serde_json`. As `RawValue` cannot be compared, the
+    /// generated types do not derive `PartialEq` with this option.
+    pub raw_object: bool,
+    /// Implement `TryFrom<serde_json::Value>` for the `_Reply` structs of the methods.
+    ///
+    /// Code handling the replies generically, e.g. a bridge or a logger, can interpret a
+    /// `serde_json::Value` as the reply of a specific method. A value, which does not match
+    /// the reply, gives the generated `Error`.
+    pub reply_try_from: bool,
+    /// Add `<method>_sync()` to `VarlinkClientInterface`, which performs the call and returns
+    /// the reply, e.g. `iface.ping_sync(x)?` instead of `iface.ping(x).call()?`.
+    pub sync_methods: bool,
+    /// Generate `VarlinkDirectClient`, whose methods perform the call and return the reply.
+    ///
+    /// Besides `iface.ping(x)` returning the reply, the `VarlinkClientDirectInterface` trait
+    /// has `iface.ping_more(x)` returning the `MethodCall` to iterate over the replies and
+    /// `iface.ping_oneway(x)`. The builder API of `VarlinkClient` is still generated.
+    pub direct_client: bool,
+    /// Generate `VarlinkDynClient`, whose trait `VarlinkClientDynInterface` does not expose
+    /// the `MethodCall` types.
+    ///
+    /// The methods are like the ones of `VarlinkClientDirectInterface`, but `iface.ping_more(x)`
+    /// returns a `Box<dyn Iterator>` over the replies, so the client can be held as a
+    /// `Box<dyn VarlinkClientDynInterface + Send + Sync>`, e.g. by plugins, which only know
+    /// the trait. `VarlinkDynClient::boxed(connection)` returns such a box.
+    pub dyn_client: bool,
+    /// Generate `AsyncVarlinkClient`, whose async methods perform the call on a
+    /// `varlink::AsyncConnection`.
+    ///
+    /// `client.ping(x).await` returns the reply. `more`, `oneway` and `upgrade` calls are not
+    /// supported by the async client. The crate using the generated code has to enable the
+    /// `async` feature of `varlink`, which runs the connection on tokio.
+    pub async_client: bool,
+    /// Generate `From` conversions between the types of the interfaces generated together with
+    /// [generate_many_with_options](fn.generate_many_with_options.html) or
+    /// [cargo_build_options_many](fn.cargo_build_options_many.html).
+    ///
+    /// A type gets a conversion from each type of the same name and structure in the other
+    /// interfaces. The generated modules have to be siblings, named after the interface with
+    /// `.` replaced by `_`, e.g. `mod org_example_v1`.
+    pub structural_conversions: bool,
+    /// Generate the module `borrowed` with variants of the types, which borrow their
+    /// `string` fields from the deserialized input.
+    ///
+    /// The module contains the types, the `_Args` and `_Reply` structs of the methods and
+    /// the `_Args` structs of the errors. Their `string` fields are `Cow<'a, str>` and the
+    /// structs containing strings have a lifetime parameter `'a`. Deserializing e.g. a
+    /// `borrowed::List_Reply` with `serde_json::from_slice()` does not allocate the strings,
+    /// unless they contain escape sequences. Serde only borrows for `string` fields of a
+    /// struct, strings in arrays, dicts and optionals are always allocated. The client and
+    /// the server API use the owned types.
+    pub borrowed_types: bool,
+    /// Generate newtypes for opaque identifier fields.
+    ///
+    /// Each entry maps a field name to the name of a newtype, e.g.
+    /// `("client_id", "ClientId")`. The `string` fields of that name are generated as the
+    /// newtype, also in arrays, dicts and optionals, so an identifier cannot be passed where
+    /// another string is expected. The field is matched by its name alone, across the whole
+    /// interface: every `client_id` of a type, an anonymous struct, the parameters and the
+    /// reply of a method and the parameters of an error becomes a `ClientId`. The newtype
+    /// `pub struct ClientId(pub String)` serializes as a plain string and has `From<String>`,
+    /// `From<&str>` and `Deref<Target = String>`. The types of the `borrowed` module keep
+    /// their strings.
+    ///
+    /// A `String` is not accepted for the newtype, e.g. for the generated
+    /// `fn test01(&mut self, client_id: ClientId)` of the client:
+    ///
+    /// ```rust,compile_fail
+    /// # #[derive(Debug, PartialEq, Eq, Hash, Clone, Default)]
+    /// # pub struct ClientId(pub String);
+    /// # impl From<String> for ClientId {
+    /// #     fn from(v: String) -> Self {
+    /// #         ClientId(v)
+    /// #     }
+    /// # }
+    /// # fn test01(client_id: ClientId) {}
+    /// let client_id: String = "abc".into();
+    /// test01(client_id);
+    /// ```
+    ///
+    /// It has to be converted explicitly:
+    ///
+    /// ```rust
+    /// # #[derive(Debug, PartialEq, Eq, Hash, Clone, Default)]
+    /// # pub struct ClientId(pub String);
+    /// # impl From<String> for ClientId {
+    /// #     fn from(v: String) -> Self {
+    /// #         ClientId(v)
+    /// #     }
+    /// # }
+    /// # fn test01(client_id: ClientId) {}
+    /// let client_id: String = "abc".into();
+    /// test01(client_id.into());
+    /// ```
+    pub newtype_fields: Option<Vec<(&'static str, &'static str)>>,
+    /// Generate a `#[cfg(test)]` module `generated_tests` with a serialization round-trip
+    /// test for each type, for the `_Args` and `_Reply` structs of the methods and for the
+    /// `_Args` structs of the errors.
+    ///
+    /// The tests serialize a sample value of the type to JSON, deserialize it and check, that
+    /// the result is equal to the sample. This catches e.g. a `#[serde(rename)]` of a keyword
+    /// field, which does not round-trip. The tests run with `cargo test` of the crate
+    /// including the generated code, which needs `serde_json` as a dependency.
+    pub emit_tests: bool,
+    /// Generate the enums with `#[repr(i64)]` and explicit integer discriminants.
+    ///
+    /// The discriminants follow the declaration order starting at `0`, unless set in
+    /// `enum_discriminants`. An enum value converts to its discriminant with `as i64` and
+    /// back with the generated `from_i64()`, e.g. to pass it to C code. On the wire, the
+    /// enums are still serialized with their string names.
+    pub integer_enums: bool,
+    /// Explicit discriminants for `integer_enums`, keyed by the enum name and the variant,
+    /// e.g. `("MyType_enum.one", 1)` for the anonymous enum of the field `enum` of `MyType`.
+    ///
+    /// Variants without an entry continue from the discriminant of the previous variant.
+    pub enum_discriminants: Option<Vec<(&'static str, i64)>>,
+    /// Optional fields of the method arguments, method replies and error arguments, which
+    /// serialize `None` as an explicit `null`, keyed by the struct name and the field, e.g.
+    /// `"Ping_Reply.pong"`.
+    ///
+    /// By default, such a field is left out of the message, if it is `None`. Some peers
+    /// distinguish a missing key from a key with the value `null`, so the listed fields
+    /// are always sent. Both forms deserialize to `None`.
+    pub null_fields: Option<Vec<&'static str>>,
+    /// Implement `varlink::VarlinkErrorReply` for `ErrorKind`, converting an error back into
+    /// its error reply.
+    ///
+    /// With this, `MethodCall::pump()` can pass the replies of a `more` call on to the `Call`
+    /// of a proxy, including an error reply of the called service.
+    pub reply_bridge: bool,
+    /// Generate the `[string]` dictionaries as `varlink::StringBTreeMap` instead of
+    /// `varlink::StringHashMap`.
+    ///
+    /// The maps iterate and serialize in the order of their keys, so code walking the
+    /// generated types produces the same output every time. Sets `[string]()` stay
+    /// `varlink::StringHashSet`.
+    pub ordered_maps: bool,
+    /// Add the field `extra` to the `_Args` and `_Reply` structs of the methods, which keeps
+    /// the parameters not known to the interface.
+    ///
+    /// The field is a `#[serde(flatten)] varlink::StringHashMap<serde_json::Value>`, so e.g.
+    /// a proxy, which deserializes a reply to inspect a field, passes the parameters added by
+    /// a newer version of the service on unchanged. The generated `reply()` and client methods
+    /// leave `extra` empty.
+    pub preserve_unknown_fields: bool,
+    /// Generate a `#[cfg(test)]` module `generated_description_tests`, which checks, that the
+    /// embedded interface description matches the generated code.
+    ///
+    /// The test parses the description returned by `get_description()` and compares its types,
+    /// errors and methods with the generated ones, so a manual edit of the generated code,
+    /// which lets it drift from the description, fails `cargo test`. The types get a
+    /// `#[cfg(test)]` constant `VARLINK_NAME` with their name for the test. The crate
+    /// including the generated code needs `varlink_parser` and `serde_json` as
+    /// dev-dependencies.
+    pub emit_description_test: bool,
+    /// Attributes for the structs and enums of the types, e.g. `quote!(#[derive(Eq, Hash)])`
+    /// or a `#[cfg_attr(...)]`.
+    ///
+    /// They follow the generated `#[derive(...)]`. The anonymous structs and enums nested in
+    /// the fields of the types, methods and errors are types, too.
+    pub type_attributes: Option<TokenStream>,
+    /// Attributes for the `_Args` and `_Reply` structs of the methods, see `type_attributes`.
+    pub method_attributes: Option<TokenStream>,
+    /// Attributes for the `_Args` structs of the errors, see `type_attributes`.
+    pub error_attributes: Option<TokenStream>,
+    /// Implement `Default` for the `_Args` structs of the methods, also without declared
+    /// default values, e.g. to write `Get_Args { filter: Some(f), ..Default::default() }`.
+    ///
+    /// Fields without a declared default get their natural `Default`, like `None`, `0` or
+    /// an empty string. An `_Args` struct with a field of a type, struct or enum without a
+    /// declared default gets no implementation.
+    pub default_args: bool,
+    /// Mark the generated `ErrorKind` as `#[non_exhaustive]`.
+    ///
+    /// Crates matching on the `ErrorKind` of re-exported generated code then need a wildcard
+    /// arm, so adding an error to the interface is not a breaking change for them. Inside the
+    /// crate containing the generated code, matches are still checked for exhaustiveness.
+    /// Needs rust 1.40 or later.
+    pub non_exhaustive_errors: bool,
+    /// Deserialize a missing field of the types, method arguments, method replies and error
+    /// arguments to its `Default` with `#[serde(default)]`.
+    ///
+    /// Client and service bindings generated from different versions of an interface then
+    /// still understand each other, if one side leaves out a field, which the other one
+    /// requires. Missing optional fields deserialize to `None` also without this option.
+    /// Fields with a declared default value and fields of a type, struct or enum stay
+    /// required.
+    pub default_fields: bool,
+    /// A prefix for the items, which are generated with the same name for every interface,
+    /// so the code of several interfaces can be included into the same module.
+    ///
+    /// With `Some("Ping".into())`, the types and traits are prefixed as is, e.g.
+    /// `PingVarlinkClient`, `PingVarlinkInterface`, `PingVarlinkInterfaceProxy`,
+    /// `PingErrorKind`, `PingError` and `PingResult`, the functions and modules in snake case,
+    /// e.g. `ping_new()` and `ping_borrowed`. The imports of the generated code are made
+    /// through the module `ping_imports`, so they do not collide either. The names of the
+    /// types, errors and methods of the interfaces, and with them e.g. `Call_Ping` and
+    /// `Ping_Args`, still have to be distinct.
+    pub ident_prefix: Option<String>,
+    /// Generate a builder for each struct type, e.g. `MyTypeBuilder` for `MyType`, which is
+    /// returned by `MyType::builder()`.
+    ///
+    /// The builder has a setter for each field, taking the value of an optional field without
+    /// the `Option`. `build()` returns `Err(varlink::MissingField("name"))` for the first
+    /// required field, which was not set. Unset optional fields are `None`, unset fields with a
+    /// declared default get the default value.
+    pub builders: bool,
+}
+
+impl GeneratorOptions {
+    /// The type or trait `name`, which is generated for every interface, prefixed with
+    /// [GeneratorOptions::ident_prefix](struct.GeneratorOptions.html#structfield.ident_prefix).
+    fn prefixed(&self, name: &str) -> Ident {
+        match self.ident_prefix {
+            Some(ref prefix) => Ident::new(&format!("{}{}", prefix, name), Span::call_site()),
+            None => Ident::new(name, Span::call_site()),
+        }
+    }
+
+    /// The function or module `name`, which is generated for every interface, prefixed with
+    /// the snake case
+    /// [GeneratorOptions::ident_prefix](struct.GeneratorOptions.html#structfield.ident_prefix).
+    fn prefixed_snake(&self, name: &str) -> Ident {
+        match self.ident_prefix {
+            Some(ref prefix) => Ident::new(
+                &format!("{}_{}", to_snake_case(prefix), name),
+                Span::call_site(),
+            ),
+            None => Ident::new(name, Span::call_site()),
+        }
+    }
+}
+
+/// The options and the information about the whole interface, which the generation of
+/// single types needs.
+struct GeneratorContext<'a> {
+    options: &'a GeneratorOptions,
+    /// The paths of the optional fields, which have to be boxed to break a type recursion.
+    boxed_fields: HashSet<String>,
+    /// For the types of the `borrowed` module, the names of the types borrowing strings.
+    borrowing_types: Option<HashSet<String>>,
+    /// The types with a declared default value, which get a `Default` implementation.
+    default_types: HashSet<String>,
+    /// `#[allow(deprecated)]` for the generated items, if the interface has deprecated types
+    /// or methods, which the generated code refers to, else nothing.
+    allow_deprecated: TokenStream,
+}
+
+impl<'a> GeneratorContext<'a> {
+    /// The newtype for the `string` fields named `field`, see
+    /// [GeneratorOptions::newtype_fields](struct.GeneratorOptions.html#structfield.newtype_fields).
+    fn newtype(&self, field: &str) -> Option<&'static str> {
+        if self.borrowing_types.is_some() {
+            return None;
+        }
+        self.newtype_fields
+            .as_ref()?
+            .iter()
+            .find(|(f, _)| *f == field)
+            .map(|(_, t)| *t)
+    }
+
+    /// The rust type for the `[string]` dictionaries, see
+    /// [GeneratorOptions::ordered_maps](struct.GeneratorOptions.html#structfield.ordered_maps).
+    fn map_type(&self) -> &'static str {
+        if self.ordered_maps {
+            "varlink::StringBTreeMap"
+        } else {
+            "varlink::StringHashMap"
+        }
+    }
+
+    /// The serde attribute of the optional field `field` of the struct `name`, which leaves
+    /// the field out of the message if it is `None`, unless it is listed in
+    /// [GeneratorOptions::null_fields](struct.GeneratorOptions.html#structfield.null_fields).
+    fn skip_none(&self, name: &str, field: &str) -> TokenStream {
+        let key = format!("{}.{}", name, field);
+        if self.null_fields.iter().flatten().any(|f| *f == key) {
+            quote!()
+        } else {
+            quote!(#[serde(skip_serializing_if = "Option::is_none")])
+        }
+    }
+
+    /// The serde attribute of the field `e`, which deserializes it to its `Default`, if it is
+    /// missing, see
+    /// [GeneratorOptions::default_fields](struct.GeneratorOptions.html#structfield.default_fields).
+    fn serde_default(&self, e: &Argument) -> TokenStream {
+        if self.default_fields
+            && e.default.is_none()
+            && has_default(&e.vtype, &self.default_types, self.options)
+        {
+            quote!(#[serde(default)])
+        } else {
+            quote!()
+        }
+    }
+
+    /// The lifetime parameter of a struct, if its strings are borrowed.
+    fn lifetime_generics(&self, vstruct: &VStruct) -> TokenStream {
+        match self.borrowing_types {
+            Some(ref types) if struct_borrows(vstruct, types) => quote!(<'a>),
+            _ => TokenStream::new(),
+        }
+    }
+}
+
+/// Whether `vstruct` has strings, directly or in the types in `borrowing_types`.
+fn struct_borrows(vstruct: &VStruct, borrowing_types: &HashSet<String>) -> bool {
+    vstruct
+        .elts
+        .iter()
+        .any(|e| type_borrows(&e.vtype, borrowing_types))
+}
+
+fn type_borrows(vtype: &VTypeExt, borrowing_types: &HashSet<String>) -> bool {
+    match *vtype {
+        VTypeExt::Plain(VType::String) => true,
+        VTypeExt::Plain(VType::Typename(t)) => borrowing_types.contains(t),
+        VTypeExt::Plain(VType::Struct(ref s)) => struct_borrows(s, borrowing_types),
+        VTypeExt::Plain(_) => false,
+        VTypeExt::Array(ref v) | VTypeExt::Dict(ref v) | VTypeExt::Option(ref v) => {
+            type_borrows(v, borrowing_types)
+        }
+    }
+}
+
+/// The names of the types of `idl`, which contain strings.
+fn borrowing_types(idl: &IDL) -> HashSet<String> {
+    let mut types = HashSet::new();
+    loop {
+        let mut changed = false;
+        for t in idl.typedefs.values() {
+            if let VStructOrEnum::VStruct(ref s) = t.elt {
+                if !types.contains(t.name) && struct_borrows(s, &types) {
+                    types.insert(t.name.to_string());
+                    changed = true;
+                }
+            }
+        }
+        if !changed {
+            return types;
+        }
+    }
+}
+
+/// The serde attribute to borrow a field from the input, if its rust type has a lifetime.
+fn serde_borrow(rust_type: &str) -> TokenStream {
+    if rust_type.contains("'a") {
+        quote!(#[serde(borrow)])
+    } else {
+        quote!()
+    }
+}
+
+impl<'a> Deref for GeneratorContext<'a> {
+    type Target = GeneratorOptions;
+
+    fn deref(&self) -> &GeneratorOptions {
+        self.options
+    }
 }
 
 impl<'short, 'long: 'short> ToRustString<'short, 'long> for VType<'long> {
@@ -80,22 +499,27 @@ impl<'short, 'long: 'short> ToRustString<'short, 'long> for VType<'long> {
         &'long self,
         name: &str,
         tokenstream: &mut TokenStream,
-        options: &'long GeneratorOptions,
+        options: &'long GeneratorContext<'long>,
     ) -> Cow<'long, str> {
         match *self {
             VType::Bool => options.bool_type.unwrap_or("bool").into(),
             VType::Int => options.int_type.unwrap_or("i64").into(),
             VType::Float => options.float_type.unwrap_or("f64").into(),
+            VType::String if options.borrowing_types.is_some() => "Cow<'a, str>".into(),
             VType::String => options.string_type.unwrap_or("String").into(),
+            VType::Object if options.raw_object => "Box<serde_json::value::RawValue>".into(),
             VType::Object => "serde_json::Value".into(),
-            VType::Typename(v) => v.into(),
+            VType::Typename(v) => match options.borrowing_types {
+                Some(ref types) if types.contains(v) => format!("{}<'a>", to_rust_name(v)).into(),
+                _ => to_rust_name(v),
+            },
             VType::Enum(ref v) => {
                 v.to_tokenstream(name, tokenstream, options);
                 Cow::Owned(name.to_string())
             }
             VType::Struct(ref v) => {
                 v.to_tokenstream(name, tokenstream, options);
-                Cow::Owned(name.to_string())
+                Cow::Owned(format!("{}{}", name, options.lifetime_generics(v)))
             }
         }
     }
@@ -106,23 +530,31 @@ impl<'short, 'long: 'short> ToRustString<'short, 'long> for VTypeExt<'long> {
         &'long self,
         name: &str,
         tokenstream: &mut TokenStream,
-        options: &'long GeneratorOptions,
+        options: &'long GeneratorContext<'long>,
     ) -> Cow<'long, str> {
         match *self {
             VTypeExt::Plain(ref vtype) => vtype.to_rust_string(name, tokenstream, options),
             VTypeExt::Array(ref v) => {
                 format!("Vec<{}>", v.to_rust_string(name, tokenstream, options)).into()
             }
+            // Only a dictionary of the empty struct `[string]()` is a set, the values of
+            // other dictionaries, also of nested ones like `[string][string]()`, recurse.
             VTypeExt::Dict(ref v) => match *v.as_ref() {
                 VTypeExt::Plain(VType::Struct(ref s)) if s.elts.is_empty() => {
                     "varlink::StringHashSet".into()
                 }
                 _ => format!(
-                    "varlink::StringHashMap<{}>",
+                    "{}<{}>",
+                    options.map_type(),
                     v.to_rust_string(name, tokenstream, options)
                 )
                 .into(),
             },
+            VTypeExt::Option(ref v) if options.boxed_fields.contains(name) => format!(
+                "Option<Box<{}>>",
+                v.to_rust_string(name, tokenstream, options)
+            )
+            .into(),
             VTypeExt::Option(ref v) => {
                 format!("Option<{}>", v.to_rust_string(name, tokenstream, options)).into()
             }
@@ -130,6 +562,39 @@ impl<'short, 'long: 'short> ToRustString<'short, 'long> for VTypeExt<'long> {
     }
 }
 
+/// The rust type of the struct field `e`, using the newtype for its strings, if any.
+fn field_rust_string<'long>(
+    e: &'long Argument<'long>,
+    name: &str,
+    tokenstream: &mut TokenStream,
+    options: &'long GeneratorContext<'long>,
+) -> Cow<'long, str> {
+    match options
+        .newtype(e.name)
+        .and_then(|newtype| newtype_rust_string(&e.vtype, newtype, options.map_type()))
+    {
+        Some(t) => t.into(),
+        None => e.vtype.to_rust_string(name, tokenstream, options),
+    }
+}
+
+/// The rust type of `vtype` with `newtype` in place of `String`, if `vtype` is a string.
+fn newtype_rust_string(vtype: &VTypeExt, newtype: &str, map_type: &str) -> Option<String> {
+    match *vtype {
+        VTypeExt::Plain(VType::String) => Some(newtype.into()),
+        VTypeExt::Plain(_) => None,
+        VTypeExt::Array(ref v) => {
+            newtype_rust_string(v, newtype, map_type).map(|t| format!("Vec<{}>", t))
+        }
+        VTypeExt::Dict(ref v) => {
+            newtype_rust_string(v, newtype, map_type).map(|t| format!("{}<{}>", map_type, t))
+        }
+        VTypeExt::Option(ref v) => {
+            newtype_rust_string(v, newtype, map_type).map(|t| format!("Option<{}>", t))
+        }
+    }
+}
+
 fn to_snake_case(mut str: &str) -> String {
     let mut words = vec![];
     // Preserve leading underscores
@@ -147,8 +612,16 @@ fn to_snake_case(mut str: &str) -> String {
         if s.is_empty() {
             continue;
         }
-        for ch in s.chars() {
-            if !buf.is_empty() && buf != "'" && ch.is_uppercase() && !last_upper {
+        let mut chars = s.chars().peekable();
+        while let Some(ch) = chars.next() {
+            // A word starts with an uppercase letter after a lowercase letter or a digit,
+            // or with the last letter of an acronym, which is followed by a lowercase letter:
+            // `HTTPSProxy` is `https_proxy`.
+            if !buf.is_empty()
+                && buf != "'"
+                && ch.is_uppercase()
+                && (!last_upper || chars.peek().map_or(false, |c| c.is_lowercase()))
+            {
                 words.push(buf);
                 buf = String::new();
             }
@@ -160,39 +633,270 @@ fn to_snake_case(mut str: &str) -> String {
     words.join("_")
 }
 
+/// The derive attribute for the generated structs and enums, followed by the configured
+/// `attributes`.
+fn type_derives(options: &GeneratorOptions, attributes: &Option<TokenStream>) -> TokenStream {
+    let derives = if options.raw_object {
+        quote!(#[derive(Serialize, Deserialize, Debug, Clone)])
+    } else {
+        quote!(#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)])
+    };
+    quote!(#derives #attributes)
+}
+
+/// Rust keywords, which cannot be used as raw identifiers.
+const NON_RAW_KEYWORDS: &[&str] = &["crate", "self", "Self", "super"];
+
+/// Strict and reserved Rust keywords of the 2018 edition.
+const RUST_KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "crate",
+    "do", "dyn", "else", "enum", "extern", "false", "final", "fn", "for", "if", "impl", "in",
+    "let", "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref",
+    "return", "self", "Self", "static", "struct", "super", "trait", "true", "try", "type",
+    "typeof", "unsafe", "unsized", "use", "virtual", "where", "while", "yield",
+];
+
+/// The rust name for a varlink name, with `_` appended to keywords, which cannot be raw
+/// identifiers.
+fn to_rust_name(name: &str) -> Cow<'_, str> {
+    if NON_RAW_KEYWORDS.contains(&name) {
+        Cow::Owned(format!("{}_", name))
+    } else {
+        Cow::Borrowed(name)
+    }
+}
+
+/// The identifier for a varlink member or type name, always using the raw form,
+/// if possible.
+fn to_rust_ident(name: &str) -> Ident {
+    if NON_RAW_KEYWORDS.contains(&name) {
+        Ident::new(&to_rust_name(name), Span::call_site())
+    } else {
+        syn::parse_str(&(String::from("r#") + name)).unwrap()
+    }
+}
+
+/// The identifier of the `ErrorKind` variant for the varlink error `name`.
+fn to_error_variant(name: &str) -> Ident {
+    Ident::new(&to_rust_name(name), Span::call_site())
+}
+
+/// The identifier for a method name, using the raw form only for keywords.
+fn to_method_ident(name: &str) -> Ident {
+    if RUST_KEYWORDS.contains(&name) {
+        to_rust_ident(name)
+    } else {
+        Ident::new(name, Span::call_site())
+    }
+}
+
+/// The serde attribute to keep the varlink name, if `to_rust_ident()` had to change it.
+fn serde_rename(name: &str) -> TokenStream {
+    if NON_RAW_KEYWORDS.contains(&name) {
+        quote!(#[serde(rename = #name)])
+    } else {
+        quote!()
+    }
+}
+
+/// The `///` doc comments for the `#` comment lines `doc` of a varlink member.
+///
+/// Empty lines between the comment lines become empty doc lines, separating paragraphs.
+fn doc_attrs(doc: &str) -> TokenStream {
+    let lines = doc.lines().map(str::trim).filter_map(|line| {
+        if line.starts_with('#') {
+            Some(line[1..].trim_end())
+        } else if line.is_empty() {
+            Some("")
+        } else {
+            None
+        }
+    });
+    quote!(#(#[doc = #lines])*)
+}
+
+/// The `#[deprecated]` attribute for a varlink comment with a line `# @deprecated <note>`,
+/// e.g. `# @deprecated use Pong instead`, else nothing.
+fn deprecated_attr(doc: &str) -> TokenStream {
+    for line in doc.lines().map(str::trim) {
+        if !line.starts_with('#') {
+            continue;
+        }
+        let line = line[1..].trim();
+        if !line.starts_with("@deprecated") {
+            continue;
+        }
+        let note = &line["@deprecated".len()..];
+        if note.is_empty() {
+            return quote!(#[deprecated]);
+        }
+        if note.starts_with(char::is_whitespace) {
+            let note = note.trim();
+            return quote!(#[deprecated(note = #note)]);
+        }
+    }
+    TokenStream::new()
+}
+
 impl<'short, 'long: 'short> ToTokenStream<'short, 'long> for VStruct<'long> {
     fn to_tokenstream(
         &'long self,
         name: &str,
         tokenstream: &mut TokenStream,
-        options: &'long GeneratorOptions,
+        options: &'long GeneratorContext<'long>,
     ) {
-        let tname: Ident = syn::parse_str(&(String::from("r#") + name)).unwrap();
+        generate_struct(self, name, "", false, tokenstream, options);
+    }
+}
 
-        let mut enames = vec![];
-        let mut etypes = vec![];
-        for e in &self.elts {
-            let ename_ident: Ident = syn::parse_str(&(String::from("r#") + e.name)).unwrap();
-            enames.push(ename_ident);
-            etypes.push(
-                TokenStream::from_str(
-                    e.vtype
-                        .to_rust_string(
-                            format!("{}_{}", name, e.name).as_ref(),
-                            tokenstream,
-                            options,
-                        )
-                        .as_ref(),
-                )
-                .unwrap(),
-            );
+/// The struct `name` for `vstruct`, documented with the varlink comment `doc`.
+///
+/// With `builder`, the builder of the struct is generated, if the `builders` option is set.
+fn generate_struct<'long>(
+    vstruct: &'long VStruct<'long>,
+    name: &str,
+    doc: &str,
+    builder: bool,
+    tokenstream: &mut TokenStream,
+    options: &'long GeneratorContext<'long>,
+) {
+    let tname = to_rust_ident(name);
+
+    let mut enames = vec![];
+    let mut etypes = vec![];
+    let mut anot = vec![];
+    let mut builder_fields = Builder::default();
+    for e in &vstruct.elts {
+        let etype = field_rust_string(
+            e,
+            format!("{}_{}", name, e.name).as_ref(),
+            tokenstream,
+            options,
+        );
+        let rename = serde_rename(e.name);
+        let borrow = serde_borrow(&etype);
+        let default = options.serde_default(e);
+        anot.push(quote!(#rename #borrow #default));
+        enames.push(to_rust_ident(e.name));
+        builder_fields.push(e, &etype);
+        etypes.push(TokenStream::from_str(etype.as_ref()).unwrap());
+    }
+    let deprecated = deprecated_attr(doc);
+    let doc = doc_attrs(doc);
+    let allow_deprecated = &options.allow_deprecated;
+    let derives = type_derives(options, &options.type_attributes);
+    let generics = options.lifetime_generics(vstruct);
+    let default_impl = generate_default_impl(
+        &tname,
+        &generics,
+        &vstruct.elts,
+        &TokenStream::new(),
+        false,
+        allow_deprecated,
+    );
+    tokenstream.extend(quote!(
+        #doc
+        #deprecated
+        #allow_deprecated
+        #derives
+        pub struct #tname #generics {
+            #(#anot pub #enames: #etypes,)*
         }
-        tokenstream.extend(quote!(
-            #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
-            pub struct #tname {
-                #(pub #enames: #etypes,)*
+
+        #default_impl
+    ));
+
+    // The borrowed types are only deserialized.
+    if builder && options.builders && options.borrowing_types.is_none() {
+        tokenstream.extend(builder_fields.generate(name, allow_deprecated));
+    }
+}
+
+/// The fields of the builder of a struct, see
+/// [GeneratorOptions::builders](struct.GeneratorOptions.html#structfield.builders).
+#[derive(Default)]
+struct Builder {
+    /// The fields of the builder, all `Option`s.
+    fields: Vec<TokenStream>,
+    setters: Vec<TokenStream>,
+    /// The initialization of the fields of the struct in `build()`.
+    values: Vec<TokenStream>,
+}
+
+impl Builder {
+    /// Add the field `e` of the rust type `etype`.
+    fn push(&mut self, e: &Argument, etype: &str) {
+        let ename = to_rust_ident(e.name);
+        let field_name = e.name;
+        let default = e.default.and_then(|value| default_value(&e.vtype, value));
+        let (field, value, vtype) = match e.vtype {
+            // `Option<T>`, the setter takes the `T`
+            VTypeExt::Option(_) => {
+                let value = match default {
+                    Some(default) => quote!(self.#ename.or_else(|| #default)),
+                    None => quote!(self.#ename),
+                };
+                let field = TokenStream::from_str(etype).unwrap();
+                (field, value, &etype["Option<".len()..etype.len() - 1])
+            }
+            _ => {
+                let value = match default {
+                    Some(default) => quote!(self.#ename.unwrap_or_else(|| #default)),
+                    None => quote!(self.#ename.ok_or(varlink::MissingField(#field_name))?),
+                };
+                let field = TokenStream::from_str(&format!("Option<{}>", etype)).unwrap();
+                (field, value, etype)
+            }
+        };
+        let vtype = TokenStream::from_str(vtype).unwrap();
+
+        self.fields.push(quote!(#ename: #field));
+        self.setters.push(quote!(
+            pub fn #ename(mut self, #ename: #vtype) -> Self {
+                self.#ename = Some(#ename);
+                self
             }
         ));
+        self.values.push(quote!(#ename: #value));
+    }
+
+    /// The builder `<name>Builder` of the struct `name` and `<name>::builder()`.
+    fn generate(&self, name: &str, allow_deprecated: &TokenStream) -> TokenStream {
+        let tname = to_rust_ident(name);
+        let builder_name = to_rust_ident(&format!("{}Builder", name));
+        let doc = format!(" The builder of `{}`, see `{}::builder()`.", name, name);
+        let fields = &self.fields;
+        let setters = &self.setters;
+        let values = &self.values;
+
+        quote!(
+            #[doc = #doc]
+            #allow_deprecated
+            #[derive(Debug, Clone, Default)]
+            pub struct #builder_name {
+                #(#fields,)*
+            }
+
+            #allow_deprecated
+            impl #builder_name {
+                #(#setters)*
+
+                /// Build the struct, failing for the first required field, which was not set.
+                pub fn build(self) -> ::std::result::Result<#tname, varlink::MissingField> {
+                    Ok(#tname {
+                        #(#values,)*
+                    })
+                }
+            }
+
+            #allow_deprecated
+            impl #tname {
+                /// A builder with all fields unset.
+                pub fn builder() -> #builder_name {
+                    Default::default()
+                }
+            }
+        )
     }
 }
 
@@ -201,20 +905,66 @@ impl<'short, 'long: 'short> ToTokenStream<'short, 'long> for VEnum<'long> {
         &'long self,
         name: &str,
         tokenstream: &mut TokenStream,
-        _options: &'long GeneratorOptions,
+        options: &'long GeneratorContext<'long>,
     ) {
-        let tname: Ident = syn::parse_str(&(String::from("r#") + name)).unwrap();
+        let tname = to_rust_ident(name);
 
         let mut enames = vec![];
+        let mut anot = vec![];
+
+        for elt in &self.elts {
+            anot.push(serde_rename(elt));
+            enames.push(to_rust_ident(elt));
+        }
+        let derives = type_derives(options, &options.type_attributes);
+
+        if !options.integer_enums {
+            tokenstream.extend(quote!(
+                #derives
+                pub enum #tname {
+                    #(#anot #enames, )*
+                }
+            ));
+            return;
+        }
 
+        let mut discriminants = vec![];
+        let mut arms = vec![];
+        let mut next = 0;
         for elt in &self.elts {
-            let ename_ident: Ident = syn::parse_str(&(String::from("r#") + elt)).unwrap();
-            enames.push(ename_ident);
+            let key = format!("{}.{}", name, elt);
+            let value = options
+                .enum_discriminants
+                .iter()
+                .flatten()
+                .find(|(k, _)| *k == key)
+                .map(|(_, v)| *v);
+            discriminants.push(match value {
+                Some(v) => quote!(= #v),
+                None => TokenStream::new(),
+            });
+            let value = value.unwrap_or(next);
+            let ename = to_rust_ident(elt);
+            arms.push(quote!(#value => Some(#tname::#ename),));
+            next = value + 1;
         }
+        let allow_deprecated = &options.allow_deprecated;
         tokenstream.extend(quote!(
-            #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+            #derives
+            #[repr(i64)]
             pub enum #tname {
-                #(#enames, )*
+                #(#anot #enames #discriminants, )*
+            }
+
+            #allow_deprecated
+            impl #tname {
+                /// The variant with the integer discriminant `v`.
+                pub fn from_i64(v: i64) -> Option<Self> {
+                    match v {
+                        #(#arms)*
+                        _ => None,
+                    }
+                }
             }
         ));
     }
@@ -225,11 +975,33 @@ impl<'short, 'long: 'short> ToTokenStream<'short, 'long> for Typedef<'long> {
         &'long self,
         _name: &str,
         tokenstream: &mut TokenStream,
-        options: &'long GeneratorOptions,
+        options: &'long GeneratorContext<'long>,
     ) {
         match self.elt {
-            VStructOrEnum::VStruct(ref v) => v.to_tokenstream(self.name, tokenstream, options),
-            VStructOrEnum::VEnum(ref v) => v.to_tokenstream(self.name, tokenstream, options),
+            VStructOrEnum::VStruct(ref v) => {
+                generate_struct(v, self.name, self.doc, true, tokenstream, options)
+            }
+            VStructOrEnum::VEnum(ref v) => {
+                // the enum is the first item emitted, it has no nested types
+                tokenstream.extend(doc_attrs(self.doc));
+                tokenstream.extend(deprecated_attr(self.doc));
+                v.to_tokenstream(self.name, tokenstream, options)
+            }
+        }
+
+        // The name checked by `check_description()` of the description test.
+        if options.emit_description_test && options.borrowing_types.is_none() {
+            let tname = to_rust_ident(self.name);
+            let name = self.name;
+            let allow_deprecated = &options.allow_deprecated;
+            tokenstream.extend(quote!(
+                #[cfg(test)]
+                #allow_deprecated
+                impl #tname {
+                    /// The name of the type in the interface description.
+                    pub(crate) const VARLINK_NAME: &'static str = #name;
+                }
+            ));
         }
     }
 }
@@ -239,7 +1011,7 @@ impl<'short, 'long: 'short> ToTokenStream<'short, 'long> for VError<'long> {
         &'long self,
         _name: &str,
         tokenstream: &mut TokenStream,
-        options: &'long GeneratorOptions,
+        options: &'long GeneratorContext<'long>,
     ) {
         let args_name = Ident::new(&format!("{}_Args", self.name), Span::call_site());
         let mut args_enames = vec![];
@@ -247,36 +1019,499 @@ impl<'short, 'long: 'short> ToTokenStream<'short, 'long> for VError<'long> {
         let mut args_anot = vec![];
 
         for e in &self.parm.elts {
+            let etype = field_rust_string(
+                e,
+                format!("{}_Args_{}", self.name, e.name).as_ref(),
+                tokenstream,
+                options,
+            );
+            let rename = serde_rename(e.name);
+            let borrow = serde_borrow(&etype);
+            let default = options.serde_default(e);
             args_anot.push(if let VTypeExt::Option(_) = e.vtype {
-                quote!(#[serde(skip_serializing_if = "Option::is_none")])
+                let skip = options.skip_none(&format!("{}_Args", self.name), e.name);
+                quote!(#rename #borrow #default #skip)
             } else {
-                quote!()
+                quote!(#rename #borrow #default)
             });
-            let ename_ident: Ident = syn::parse_str(&(String::from("r#") + e.name)).unwrap();
-            args_enames.push(ename_ident);
-            args_etypes.push(
-                TokenStream::from_str(
-                    e.vtype
-                        .to_rust_string(
-                            format!("{}_Args_{}", self.name, e.name).as_ref(),
-                            tokenstream,
-                            options,
-                        )
-                        .as_ref(),
-                )
-                .unwrap(),
-            );
+            args_enames.push(to_rust_ident(e.name));
+            args_etypes.push(TokenStream::from_str(etype.as_ref()).unwrap());
         }
+        let derives = type_derives(options, &options.error_attributes);
+        let generics = options.lifetime_generics(&self.parm);
+        let allow_deprecated = &options.allow_deprecated;
+        let default_impl = generate_default_impl(
+            &args_name,
+            &generics,
+            &self.parm.elts,
+            &TokenStream::new(),
+            false,
+            allow_deprecated,
+        );
         tokenstream.extend(quote!(
-            #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
-            pub struct #args_name {
+            #allow_deprecated
+            #derives
+            pub struct #args_name #generics {
                             #(#args_anot pub #args_enames: #args_etypes,)*
             }
+
+            #default_impl
         ));
     }
 }
 
-fn varlink_to_rust(idl: &IDL, options: &GeneratorOptions, tosource: bool) -> Result<TokenStream> {
+/// A by-value reference of a type to another type.
+struct TypeRef<'a> {
+    to: &'a str,
+    /// The path of the innermost optional field on the way, which can be boxed.
+    optional: Option<String>,
+}
+
+fn collect_type_refs<'a>(
+    vtype: &VTypeExt<'a>,
+    path: &str,
+    optional: Option<&str>,
+    refs: &mut Vec<TypeRef<'a>>,
+) {
+    match *vtype {
+        VTypeExt::Plain(VType::Typename(t)) => refs.push(TypeRef {
+            to: t,
+            optional: optional.map(String::from),
+        }),
+        VTypeExt::Plain(VType::Struct(ref s)) => {
+            for e in &s.elts {
+                collect_type_refs(&e.vtype, &format!("{}_{}", path, e.name), optional, refs);
+            }
+        }
+        VTypeExt::Option(ref v) => collect_type_refs(v, path, Some(path), refs),
+        // Arrays and dicts store their values on the heap.
+        _ => {}
+    }
+}
+
+/// Find a path of references from `from` to `to`, which takes at least one step.
+fn find_type_path<'a>(
+    refs: &HashMap<&'a str, Vec<TypeRef<'a>>>,
+    from: &'a str,
+    to: &str,
+    required_only: bool,
+) -> Option<Vec<&'a str>> {
+    let mut visited = HashSet::new();
+    let mut paths = vec![vec![from]];
+
+    while !paths.is_empty() {
+        let mut next_paths = Vec::new();
+        for path in paths {
+            let last = path[path.len() - 1];
+            for r in refs.get(last).into_iter().flatten() {
+                if required_only && r.optional.is_some() {
+                    continue;
+                }
+                let mut next = path.clone();
+                next.push(r.to);
+                if r.to == to {
+                    return Some(next);
+                }
+                if visited.insert(r.to) {
+                    next_paths.push(next);
+                }
+            }
+        }
+        paths = next_paths;
+    }
+    None
+}
+
+/// Check the types for recursions and return the paths of the optional fields, which have
+/// to be boxed to break them.
+///
+/// A recursion without an optional field would be infinitely large and is an error.
+fn recursive_fields(idl: &IDL) -> Result<HashSet<String>> {
+    let mut refs = HashMap::new();
+
+    for t in idl.typedefs.values() {
+        let mut type_refs = Vec::new();
+        if let VStructOrEnum::VStruct(ref s) = t.elt {
+            for e in &s.elts {
+                collect_type_refs(
+                    &e.vtype,
+                    &format!("{}_{}", t.name, e.name),
+                    None,
+                    &mut type_refs,
+                );
+            }
+        }
+        refs.insert(t.name, type_refs);
+    }
+
+    for name in &idl.typedef_keys {
+        if let Some(cycle) = find_type_path(&refs, name, name, true) {
+            return Err(strerr!(
+                Error,
+                "Type recursion without an optional field in '{}': {}",
+                idl.name,
+                cycle.join(" -> ")
+            ));
+        }
+    }
+
+    let mut boxed_fields = HashSet::new();
+    for (from, type_refs) in &refs {
+        for r in type_refs {
+            if let Some(ref path) = r.optional {
+                if r.to == *from || find_type_path(&refs, r.to, from, false).is_some() {
+                    boxed_fields.insert(path.clone());
+                }
+            }
+        }
+    }
+    Ok(boxed_fields)
+}
+
+/// The rust expression for the default `value` declared for a field of type `vtype`.
+///
+/// Returns `None`, if the value does not match the type.
+fn default_value(vtype: &VTypeExt, value: &str) -> Option<TokenStream> {
+    match *vtype {
+        VTypeExt::Option(ref v) => default_value(v, value).map(|v| quote!(Some(#v))),
+        VTypeExt::Plain(VType::Bool) if value == "true" || value == "false" => {
+            TokenStream::from_str(value).ok()
+        }
+        VTypeExt::Plain(VType::Int) => value.parse::<i64>().ok().map(|v| {
+            let v = Literal::i64_unsuffixed(v);
+            quote!(#v)
+        }),
+        VTypeExt::Plain(VType::Float) => value.parse::<f64>().ok().map(|v| {
+            let v = Literal::f64_unsuffixed(v);
+            quote!(#v)
+        }),
+        VTypeExt::Plain(VType::String) if value.len() >= 2 && value.starts_with('"') => {
+            let v = Literal::string(&value[1..value.len() - 1]);
+            Some(quote!(#v.into()))
+        }
+        _ => None,
+    }
+}
+
+/// Check, that the default values of the fields of `vstruct` and of its anonymous structs
+/// match the field types, and that the other fields of a struct with a declared default
+/// have a `Default`.
+fn check_defaults(
+    name: &str,
+    vstruct: &VStruct,
+    default_types: &HashSet<String>,
+    options: &GeneratorOptions,
+) -> Result<()> {
+    let any_default = vstruct.elts.iter().any(|e| e.default.is_some());
+    for e in &vstruct.elts {
+        if let Some(value) = e.default {
+            if default_value(&e.vtype, value).is_none() {
+                return Err(strerr!(
+                    Error,
+                    "Default value `{}` does not match the type of field '{}.{}'",
+                    value,
+                    name,
+                    e.name
+                ));
+            }
+        } else if any_default && !has_default(&e.vtype, default_types, options) {
+            return Err(strerr!(
+                Error,
+                "Field '{}.{}' needs a default value, because its type has no `Default`",
+                name,
+                e.name
+            ));
+        }
+
+        let mut vtype = &e.vtype;
+        loop {
+            match *vtype {
+                VTypeExt::Array(ref v) | VTypeExt::Dict(ref v) | VTypeExt::Option(ref v) => {
+                    vtype = v
+                }
+                VTypeExt::Plain(VType::Struct(ref s)) => {
+                    check_defaults(&format!("{}.{}", name, e.name), s, default_types, options)?;
+                    break;
+                }
+                _ => break,
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The names of the anonymous structs and enums generated for the fields of `vstruct`, which
+/// is generated as `name`.
+fn anon_type_names(name: &str, vstruct: &VStruct, names: &mut Vec<String>) {
+    for e in &vstruct.elts {
+        let anon_name = format!("{}_{}", name, e.name);
+        let mut vtype = &e.vtype;
+        loop {
+            match *vtype {
+                // `[string]()` is a set
+                VTypeExt::Dict(ref v) => match **v {
+                    VTypeExt::Plain(VType::Struct(ref s)) if s.elts.is_empty() => break,
+                    _ => vtype = v,
+                },
+                VTypeExt::Array(ref v) | VTypeExt::Option(ref v) => vtype = v,
+                VTypeExt::Plain(VType::Struct(ref s)) => {
+                    anon_type_names(&anon_name, s, names);
+                    names.push(anon_name);
+                    break;
+                }
+                VTypeExt::Plain(VType::Enum(_)) => {
+                    names.push(anon_name);
+                    break;
+                }
+                _ => break,
+            }
+        }
+    }
+}
+
+/// Check, that no two parts of the interface are generated with the same name.
+///
+/// E.g. the anonymous struct of the field `Reply` of the type `Ping` would be a second
+/// `Ping_Reply` next to the reply of the method `Ping`, and a type named `Error` would
+/// collide with the generated `Error`. The functions of the client traits and of
+/// `VarlinkCallError` are checked separately, so the `ping_sync` of a method `PingSync`
+/// does not clash with the one generated for `Ping`.
+fn check_name_collisions(idl: &IDL, options: &GeneratorOptions) -> Result<()> {
+    let mut generated: Vec<(String, String)> = [
+        "Error",
+        "ErrorKind",
+        "Result",
+        "VarlinkCallError",
+        "VarlinkInterface",
+        "VarlinkInterfaceProxy",
+        "VarlinkClientInterface",
+        "VarlinkClient",
+        "VarlinkClientDirectInterface",
+        "VarlinkDirectClient",
+        "VarlinkClientDynInterface",
+        "VarlinkDynClient",
+        "AsyncVarlinkClient",
+    ]
+    .iter()
+    .map(|n| {
+        let n = options.prefixed(n);
+        (n.to_string(), format!("the generated `{}`", n))
+    })
+    .collect();
+
+    for t in idl.typedefs.values() {
+        let origin = format!("the type `{}`", t.name);
+        let mut names = vec![t.name.to_string()];
+        if let VStructOrEnum::VStruct(ref s) = t.elt {
+            anon_type_names(t.name, s, &mut names);
+            if options.builders {
+                names.push(format!("{}Builder", t.name));
+            }
+        }
+        generated.extend(names.into_iter().map(|n| (n, origin.clone())));
+    }
+    for m in idl.methods.values() {
+        let origin = format!("the method `{}`", m.name);
+        let mut names = vec![
+            format!("{}_Args", m.name),
+            format!("{}_Reply", m.name),
+            format!("Call_{}", m.name),
+        ];
+        if is_more_method(m.name, options) {
+            names.push(format!("{}_MoreReply", m.name));
+        }
+        anon_type_names(&format!("{}_Args", m.name), &m.input, &mut names);
+        anon_type_names(&format!("{}_Reply", m.name), &m.output, &mut names);
+        generated.extend(names.into_iter().map(|n| (n, origin.clone())));
+    }
+    for e in idl.errors.values() {
+        let origin = format!("the error `{}`", e.name);
+        let mut names = vec![format!("{}_Args", e.name)];
+        anon_type_names(&format!("{}_Args", e.name), &e.parm, &mut names);
+        generated.extend(names.into_iter().map(|n| (n, origin.clone())));
+    }
+    check_duplicates(&generated, "name")?;
+
+    let mut functions: Vec<(String, String)> = Vec::new();
+    for m in idl.methods.values() {
+        let origin = format!("the method `{}`", m.name);
+        let name = to_snake_case(m.name);
+        let mut names = vec![format!("{}_oneway", name)];
+        if options.sync_methods {
+            names.push(format!("{}_sync", name));
+        }
+        if options.direct_client || options.dyn_client || is_more_method(m.name, options) {
+            names.push(format!("{}_more", name));
+        }
+        names.push(name);
+        functions.extend(names.into_iter().map(|n| (n, origin.clone())));
+    }
+    check_duplicates(&functions, "client function")?;
+
+    let mut functions: Vec<(String, String)> = Vec::new();
+    for e in idl.errors.values() {
+        let origin = format!("the error `{}`", e.name);
+        let name = to_snake_case(e.name);
+        if !e.parm.elts.is_empty() {
+            functions.push((format!("reply_{}_struct", name), origin.clone()));
+        }
+        functions.push((format!("reply_{}", name), origin));
+    }
+    check_duplicates(&functions, "function")
+}
+
+fn check_duplicates(generated: &[(String, String)], what: &str) -> Result<()> {
+    let mut seen: HashMap<&str, &str> = HashMap::new();
+    for (name, origin) in generated {
+        if let Some(other) = seen.insert(name, origin) {
+            return Err(strerr!(
+                Error,
+                "The {} `{}` is generated for {} and for {}",
+                what,
+                name,
+                other,
+                origin
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// The interface description as returned by `get_description()`.
+///
+/// A byte order mark, carriage returns and trailing whitespace are removed, so the
+/// description has `\n` line endings and ends with a single newline.
+fn normalize_description(description: &str) -> String {
+    let description = description.trim_start_matches('\u{feff}');
+    let mut normalized = String::with_capacity(description.len());
+    for line in description.split('\n') {
+        normalized.push_str(line.trim_end());
+        normalized.push('\n');
+    }
+    let len = normalized.trim_end().len();
+    normalized.truncate(len);
+    normalized.push('\n');
+    normalized
+}
+
+/// The names of the struct types of `idl` with a declared default value, the only types
+/// with a generated `Default` implementation.
+fn default_types(idl: &IDL) -> HashSet<String> {
+    idl.typedefs
+        .values()
+        .filter(|t| match t.elt {
+            VStructOrEnum::VStruct(ref s) => s.elts.iter().any(|e| e.default.is_some()),
+            VStructOrEnum::VEnum(_) => false,
+        })
+        .map(|t| t.name.to_string())
+        .collect()
+}
+
+/// Whether the rust type generated for `vtype` implements `Default`.
+fn has_default(
+    vtype: &VTypeExt,
+    default_types: &HashSet<String>,
+    options: &GeneratorOptions,
+) -> bool {
+    match *vtype {
+        VTypeExt::Array(_) | VTypeExt::Dict(_) | VTypeExt::Option(_) => true,
+        VTypeExt::Plain(VType::Object) => !options.raw_object,
+        VTypeExt::Plain(VType::Typename(name)) => default_types.contains(name),
+        VTypeExt::Plain(VType::Struct(_)) | VTypeExt::Plain(VType::Enum(_)) => false,
+        VTypeExt::Plain(_) => true,
+    }
+}
+
+/// A `Default` implementation using the default values declared in the interface.
+///
+/// Fields without a declared default use their `Default`. Without any declared default,
+/// no implementation is generated, unless `always` is set.
+fn generate_default_impl(
+    tname: &Ident,
+    generics: &TokenStream,
+    elts: &[Argument],
+    extra_init: &TokenStream,
+    always: bool,
+    allow_deprecated: &TokenStream,
+) -> TokenStream {
+    if !always && elts.iter().all(|e| e.default.is_none()) {
+        return TokenStream::new();
+    }
+
+    let mut enames = vec![];
+    let mut evalues = vec![];
+    for e in elts {
+        enames.push(to_rust_ident(e.name));
+        evalues.push(
+            e.default
+                .and_then(|value| default_value(&e.vtype, value))
+                .unwrap_or_else(|| quote!(Default::default())),
+        );
+    }
+
+    quote!(
+        #allow_deprecated
+        impl #generics Default for #tname #generics {
+            fn default() -> Self {
+                #tname {
+                    #(#enames: #evalues,)*
+                    #extra_init
+                }
+            }
+        }
+    )
+}
+
+fn varlink_to_rust(
+    idl: &IDL,
+    others: &[IDL],
+    options: &GeneratorOptions,
+    tosource: bool,
+) -> Result<TokenStream> {
+    check_name_collisions(idl, options)?;
+
+    let default_types = default_types(idl);
+    for t in idl.typedefs.values() {
+        if let VStructOrEnum::VStruct(ref s) = t.elt {
+            check_defaults(t.name, s, &default_types, options)?;
+        }
+    }
+    for e in idl.errors.values() {
+        check_defaults(e.name, &e.parm, &default_types, options)?;
+    }
+    for m in idl.methods.values() {
+        check_defaults(
+            &format!("{}_Args", m.name),
+            &m.input,
+            &default_types,
+            options,
+        )?;
+        check_defaults(
+            &format!("{}_Reply", m.name),
+            &m.output,
+            &default_types,
+            options,
+        )?;
+    }
+
+    let deprecations = idl
+        .typedefs
+        .values()
+        .map(|t| t.doc)
+        .chain(idl.methods.values().map(|m| m.doc))
+        .any(|doc| !deprecated_attr(doc).is_empty());
+    let options = &GeneratorContext {
+        options,
+        boxed_fields: recursive_fields(idl)?,
+        borrowing_types: None,
+        default_types,
+        allow_deprecated: if deprecations {
+            quote!(#[allow(deprecated)])
+        } else {
+            TokenStream::new()
+        },
+    };
+
     let mut ts = TokenStream::new();
 
     if tosource {
@@ -284,22 +1519,40 @@ fn varlink_to_rust(idl: &IDL, options: &GeneratorOptions, tosource: bool) -> Res
             #![doc = "This file was automatically generated by the varlink rust generator" ]
             #![allow(non_camel_case_types)]
             #![allow(non_snake_case)]
+            #![allow(dead_code)]
         ));
     }
 
-    ts.extend(quote!(
-        use chainerror::*;
-        use serde_derive::{{Deserialize, Serialize}};
-        use serde_json;
-        use std::io::BufRead;
-        use std::sync::{{Arc, RwLock}};
-        use varlink::{{self, CallTrait}};
-    ));
+    if options.ident_prefix.is_some() {
+        // Imported through a glob, the same names of several interfaces do not collide.
+        let imports = options.prefixed_snake("imports");
+        ts.extend(quote!(
+            use chainerror::*;
+            use self::#imports::*;
+            #[allow(unused_imports)]
+            mod #imports {
+                pub use serde_derive::{Deserialize, Serialize};
+                pub use std::io::BufRead;
+                pub use std::sync::{Arc, RwLock};
+                pub use varlink::CallTrait;
+            }
+        ));
+    } else {
+        ts.extend(quote!(
+            use chainerror::*;
+            use serde_derive::{{Deserialize, Serialize}};
+            use std::io::BufRead;
+            use std::sync::{{Arc, RwLock}};
+            use varlink::{{self, CallTrait}};
+        ));
+    }
 
     if let Some(ref v) = options.preamble {
         ts.extend(v.clone());
     }
 
+    ts.extend(generate_newtypes(options));
+
     for t in idl.typedefs.values() {
         t.to_tokenstream("", &mut ts, options);
     }
@@ -313,9 +1566,25 @@ fn varlink_to_rust(idl: &IDL, options: &GeneratorOptions, tosource: bool) -> Res
     let mut server_method_decls = TokenStream::new();
     let mut client_method_decls = TokenStream::new();
     let mut server_method_impls = TokenStream::new();
+    let mut server_dispatch_entries = Vec::new();
     let mut client_method_impls = TokenStream::new();
+    let mut direct_method_decls = TokenStream::new();
+    let mut direct_method_impls = TokenStream::new();
+    let mut dyn_method_decls = TokenStream::new();
+    let mut dyn_method_impls = TokenStream::new();
+    let mut async_method_impls = TokenStream::new();
+    let mut client_more_impls = TokenStream::new();
+    let mut stub_method_impls = TokenStream::new();
     let iname = idl.name;
-    let description = idl.description;
+    let description = normalize_description(idl.description);
+    let error_type = options.prefixed("Error");
+    let result_type = options.prefixed("Result");
+    let call_error_trait = options.prefixed("VarlinkCallError");
+    let interface_trait = options.prefixed("VarlinkInterface");
+    let client_interface_trait = options.prefixed("VarlinkClientInterface");
+    let client_struct = options.prefixed("VarlinkClient");
+    let proxy_struct = options.prefixed("VarlinkInterfaceProxy");
+    let new_fn = options.prefixed_snake("new");
 
     for t in idl.methods.values() {
         let mut in_field_types = Vec::new();
@@ -329,8 +1598,13 @@ fn varlink_to_rust(idl: &IDL, options: &GeneratorOptions, tosource: bool) -> Res
         let mut out_anot: Vec<TokenStream> = Vec::new();
 
         let call_name = Ident::new(&format!("Call_{}", t.name), Span::call_site());
-        let method_name = Ident::new(&to_snake_case(t.name), Span::call_site());
+        let method_name = to_method_ident(&to_snake_case(t.name));
         let varlink_method_name = format!("{}.{}", idl.name, t.name);
+        let doc = doc_attrs(t.doc);
+        // The client methods and the `_Args` and `_Reply` structs of a method marked
+        // `@deprecated`.
+        let deprecated = deprecated_attr(t.doc);
+        let allow_deprecated = &options.allow_deprecated;
 
         generate_anon_struct(
             &format!("{}_{}", t.name, "Args"),
@@ -352,24 +1626,96 @@ fn varlink_to_rust(idl: &IDL, options: &GeneratorOptions, tosource: bool) -> Res
             &mut out_anot,
         );
 
+        let (extra_field, extra_init) = if options.preserve_unknown_fields {
+            (
+                quote!(#[serde(flatten)] pub extra: varlink::StringHashMap<serde_json::Value>,),
+                quote!(extra: Default::default(),),
+            )
+        } else {
+            (TokenStream::new(), TokenStream::new())
+        };
+        // After the comma separated fields of a struct expression
+        let extra_after = |fields: &[Ident]| {
+            if fields.is_empty() || extra_init.is_empty() {
+                extra_init.clone()
+            } else {
+                quote!(, #extra_init)
+            }
+        };
+        let in_extra_init = extra_after(&in_field_names[..]);
+        let out_extra_init = extra_after(&out_field_names[..]);
+
         {
+            let reply_values =
+                generate_reply_values(&out_struct_name, &out_field_names, &out_field_types);
             let out_field_names = out_field_names.iter();
             let out_field_types = out_field_types.iter();
             let in_field_names = in_field_names.iter();
             let in_field_types = in_field_types.iter();
 
+            let derives = type_derives(options, &options.method_attributes);
+            let no_generics = TokenStream::new();
+            let out_default_impl = generate_default_impl(
+                &out_struct_name,
+                &no_generics,
+                &t.output.elts,
+                &extra_init,
+                false,
+                allow_deprecated,
+            );
+            let in_default_impl = generate_default_impl(
+                &in_struct_name,
+                &no_generics,
+                &t.input.elts,
+                &extra_init,
+                options.default_args
+                    && t.input.elts.iter().all(|e| {
+                        e.default.is_some()
+                            || has_default(&e.vtype, &options.default_types, options)
+                    }),
+                allow_deprecated,
+            );
+            let reply_try_from = if options.reply_try_from {
+                quote!(
+                    #allow_deprecated
+                    impl ::std::convert::TryFrom<serde_json::Value> for #out_struct_name {
+                        type Error = ChainError<#error_type>;
+                        fn try_from(v: serde_json::Value) -> #result_type<Self> {
+                            serde_json::from_value(v).map_err(minto_cherr!())
+                        }
+                    }
+                )
+            } else {
+                TokenStream::new()
+            };
             ts.extend(quote!(
-                #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+                #deprecated
+                #allow_deprecated
+                #derives
                 pub struct #out_struct_name {
                                 #(#out_anot pub #out_field_names: #out_field_types,)*
+                                #extra_field
                 }
 
+                #out_default_impl
+
+                #allow_deprecated
                 impl varlink::VarlinkReply for #out_struct_name {}
 
-                #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+                #allow_deprecated
+                #reply_values
+
+                #reply_try_from
+
+                #deprecated
+                #allow_deprecated
+                #derives
                 pub struct #in_struct_name {
                                 #(#in_anot pub #in_field_names: #in_field_types,)*
+                                #extra_field
                 }
+
+                #in_default_impl
             ));
         }
 
@@ -379,15 +1725,17 @@ fn varlink_to_rust(idl: &IDL, options: &GeneratorOptions, tosource: bool) -> Res
             let field_types_1 = out_field_types.iter();
             if !t.output.elts.is_empty() {
                 ts.extend(quote!(
-                pub trait #call_name: VarlinkCallError {
+                #allow_deprecated
+                pub trait #call_name: #call_error_trait {
                     fn reply(&mut self, #(#field_names_1: #field_types_1),*) -> varlink::Result<()> {
-                        self.reply_struct(#out_struct_name { #(#field_names_2),* }.into())
+                        self.reply_struct(#out_struct_name { #(#field_names_2),* #out_extra_init }.into())
                     }
                 }
             ));
             } else {
                 ts.extend(quote!(
-                    pub trait #call_name: VarlinkCallError {
+                    #allow_deprecated
+                    pub trait #call_name: #call_error_trait {
                         fn reply(&mut self) -> varlink::Result<()> {
                             self.reply_struct(varlink::Reply::parameters(None))
                         }
@@ -400,12 +1748,65 @@ fn varlink_to_rust(idl: &IDL, options: &GeneratorOptions, tosource: bool) -> Res
             impl<'a> #call_name for varlink::Call<'a> {}
         ));
 
+        // The named iterator over the replies of a `more` call
+        if is_more_method(t.name, options) {
+            let more_reply_name = Ident::new(&format!("{}_MoreReply", t.name), Span::call_site());
+            let method_more = to_method_ident(&format!("{}_more", to_snake_case(t.name)));
+            let more_doc = format!(" The replies of a `more` call of `{}`.", t.name);
+            let more_method_doc = format!(
+                " Call `{}` with `more` and return the iterator over the replies.",
+                t.name
+            );
+            let in_args = {
+                let in_field_names = in_field_names.iter();
+                let in_field_types = in_field_types.iter();
+                quote!(#(#in_field_names: #in_field_types),*)
+            };
+            let in_names = in_field_names.iter();
+
+            ts.extend(quote!(
+                #[doc = #more_doc]
+                #allow_deprecated
+                pub struct #more_reply_name(varlink::MethodCall<#in_struct_name, #out_struct_name, #error_type>);
+
+                #allow_deprecated
+                impl Iterator for #more_reply_name {
+                    type Item = #result_type<#out_struct_name>;
+                    fn next(&mut self) -> Option<Self::Item> {
+                        self.0.next()
+                    }
+                }
+            ));
+
+            client_more_impls.extend(quote!(
+                #[doc = #more_method_doc]
+                #deprecated
+                pub fn #method_more(&mut self, #in_args) -> #result_type<#more_reply_name> {
+                    let mut call = self.#method_name(#(#in_names),*);
+                    call.more()?;
+                    Ok(#more_reply_name(call))
+                }
+            ));
+        }
+
+        if options.emit_description_test {
+            let in_field_names = in_field_names.iter();
+            let in_field_types = in_field_types.iter();
+            stub_method_impls.extend(quote!(
+                fn #method_name (&self, call: &mut dyn #call_name, #(#in_field_names: #in_field_types),*) ->
+                varlink::Result<()> {
+                    call.reply_method_not_implemented(#varlink_method_name.into())
+                }
+            ));
+        }
+
         // #server_method_decls
         {
             let in_field_names = in_field_names.iter();
             let in_field_types = in_field_types.iter();
             server_method_decls.extend(quote!(
-                fn #method_name (&self, call: &mut #call_name, #(#in_field_names: #in_field_types),*) ->
+                #doc
+                fn #method_name (&self, call: &mut dyn #call_name, #(#in_field_names: #in_field_types),*) ->
                 varlink::Result<()>;
             ));
         }
@@ -415,8 +1816,26 @@ fn varlink_to_rust(idl: &IDL, options: &GeneratorOptions, tosource: bool) -> Res
             let in_field_names = in_field_names.iter();
             let in_field_types = in_field_types.iter();
             client_method_decls.extend(quote!(
+                #doc
+                #deprecated
                 fn #method_name(&mut self, #(#in_field_names: #in_field_types),*) ->
-                varlink::MethodCall<#in_struct_name, #out_struct_name, Error>;
+                varlink::MethodCall<#in_struct_name, #out_struct_name, #error_type>;
+            ));
+        }
+
+        // the blocking call without the `MethodCall` builder in #client_method_decls
+        if options.sync_methods {
+            let method_sync = to_method_ident(&format!("{}_sync", to_snake_case(t.name)));
+            let sync_doc = format!(" Call `{}` and return its reply.", t.name);
+            let in_names = in_field_names.iter();
+            let in_field_names = in_field_names.iter();
+            let in_field_types = in_field_types.iter();
+            client_method_decls.extend(quote!(
+                #[doc = #sync_doc]
+                #deprecated
+                fn #method_sync(&mut self, #(#in_field_names: #in_field_types),*) -> #result_type<#out_struct_name> {
+                    self.#method_name(#(#in_names),*).call()
+                }
             ));
         }
 
@@ -428,86 +1847,323 @@ fn varlink_to_rust(idl: &IDL, options: &GeneratorOptions, tosource: bool) -> Res
 
             client_method_impls.extend(quote!(
             fn #method_name(&mut self, #(#in_field_names: #in_field_types),*) -> varlink::MethodCall<#in_struct_name, #out_struct_name,
-            Error> {
-             varlink::MethodCall::<#in_struct_name, #out_struct_name, Error>::new(
+            #error_type> {
+             varlink::MethodCall::<#in_struct_name, #out_struct_name, #error_type>::new(
                 self.connection.clone(),
                 #varlink_method_name,
-                #in_struct_name {#(#in_field_names_2),*})
+                #in_struct_name {#(#in_field_names_2),* #in_extra_init})
              }
             ));
         }
 
+        // #direct_method_decls and #direct_method_impls
+        if options.direct_client {
+            let method_more = to_method_ident(&format!("{}_more", to_snake_case(t.name)));
+            let method_oneway = to_method_ident(&format!("{}_oneway", to_snake_case(t.name)));
+            let in_args = {
+                let in_field_names = in_field_names.iter();
+                let in_field_types = in_field_types.iter();
+                quote!(#(#in_field_names: #in_field_types),*)
+            };
+            let in_names = {
+                let in_field_names = in_field_names.iter();
+                quote!(#(#in_field_names),*)
+            };
+
+            direct_method_decls.extend(quote!(
+                #doc
+                #deprecated
+                fn #method_name(&mut self, #in_args) -> #result_type<#out_struct_name>;
+                #deprecated
+                fn #method_more(&mut self, #in_args) ->
+                #result_type<varlink::MethodCall<#in_struct_name, #out_struct_name, #error_type>>;
+                #deprecated
+                fn #method_oneway(&mut self, #in_args) -> #result_type<()>;
+            ));
+
+            direct_method_impls.extend(quote!(
+                fn #method_name(&mut self, #in_args) -> #result_type<#out_struct_name> {
+                    self.client.#method_name(#in_names).call()
+                }
+                fn #method_more(&mut self, #in_args) ->
+                #result_type<varlink::MethodCall<#in_struct_name, #out_struct_name, #error_type>> {
+                    let mut call = self.client.#method_name(#in_names);
+                    call.more()?;
+                    Ok(call)
+                }
+                fn #method_oneway(&mut self, #in_args) -> #result_type<()> {
+                    self.client.#method_name(#in_names).oneway()
+                }
+            ));
+        }
+
+        // #dyn_method_decls and #dyn_method_impls
+        if options.dyn_client {
+            let method_more = to_method_ident(&format!("{}_more", to_snake_case(t.name)));
+            let method_oneway = to_method_ident(&format!("{}_oneway", to_snake_case(t.name)));
+            let in_args = {
+                let in_field_names = in_field_names.iter();
+                let in_field_types = in_field_types.iter();
+                quote!(#(#in_field_names: #in_field_types),*)
+            };
+            let in_names = {
+                let in_field_names = in_field_names.iter();
+                quote!(#(#in_field_names),*)
+            };
+
+            dyn_method_decls.extend(quote!(
+                #doc
+                #deprecated
+                fn #method_name(&mut self, #in_args) -> #result_type<#out_struct_name>;
+                #deprecated
+                fn #method_more(&mut self, #in_args) ->
+                #result_type<Box<dyn Iterator<Item = #result_type<#out_struct_name>> + Send + Sync>>;
+                #deprecated
+                fn #method_oneway(&mut self, #in_args) -> #result_type<()>;
+            ));
+
+            dyn_method_impls.extend(quote!(
+                fn #method_name(&mut self, #in_args) -> #result_type<#out_struct_name> {
+                    self.client.#method_name(#in_names).call()
+                }
+                fn #method_more(&mut self, #in_args) ->
+                #result_type<Box<dyn Iterator<Item = #result_type<#out_struct_name>> + Send + Sync>> {
+                    let mut call = self.client.#method_name(#in_names);
+                    call.more()?;
+                    Ok(Box::new(call))
+                }
+                fn #method_oneway(&mut self, #in_args) -> #result_type<()> {
+                    self.client.#method_name(#in_names).oneway()
+                }
+            ));
+        }
+
+        // #async_method_impls
+        if options.async_client {
+            let in_args = {
+                let in_field_names = in_field_names.iter();
+                let in_field_types = in_field_types.iter();
+                quote!(#(#in_field_names: #in_field_types),*)
+            };
+            let in_names = in_field_names.iter();
+
+            async_method_impls.extend(quote!(
+                #doc
+                #deprecated
+                pub async fn #method_name(&self, #in_args) -> #result_type<#out_struct_name> {
+                    self.connection
+                        .call(#varlink_method_name, #in_struct_name {#(#in_names),* #in_extra_init})
+                        .await
+                }
+            ));
+        }
+
         // #server_method_impls
         {
             let in_field_names = in_field_names.iter();
+            let call_contract = generate_call_contract(t.name, options);
+            let dispatch_fn = Ident::new(
+                &format!("dispatch_{}", to_snake_case(t.name)),
+                Span::call_site(),
+            );
+            server_dispatch_entries
+                .push(quote!((#varlink_method_name, #proxy_struct::#dispatch_fn)));
 
             if !t.input.elts.is_empty() {
                 server_method_impls.extend(quote!(
-                    #varlink_method_name => {
-                        if let Some(args) = req.parameters.clone() {
-                            let args: #in_struct_name = match serde_json::from_value(args) {
-                                Ok(v) => v,
-                                Err(e) => {
-                                    let es = format!("{}", e);
-                                    let _ = call.reply_invalid_parameter(es.clone());
-                                    return Err(into_cherr!(varlink::ErrorKind::SerdeJsonDe(es)));
-                                }
-                            };
-                            self.inner.#method_name(call as &mut #call_name, #(args.#in_field_names),*)
-                        } else {
-                            call.reply_invalid_parameter("parameters".into())
-                        }
-                    },
+                    fn #dispatch_fn(&self, call: &mut varlink::Call) -> varlink::Result<()> {
+                        let req = call.request.unwrap();
+                        #call_contract
+                        let args: #in_struct_name = match req.parameters_as() {
+                            Some(Ok(v)) => v,
+                            Some(Err(e)) => {
+                                let es = format!("{}", e);
+                                let _ = call.reply_invalid_parameter(es.clone());
+                                return Err(into_cherr!(varlink::ErrorKind::SerdeJsonDe(es)));
+                            }
+                            None => return call.reply_invalid_parameter("parameters".into()),
+                        };
+                        self.inner.#method_name(call as &mut dyn #call_name, #(args.#in_field_names),*)
+                    }
                 ));
             } else {
                 server_method_impls.extend(quote!(
-                    #varlink_method_name => self.inner.#method_name(call as &mut #call_name),
+                    fn #dispatch_fn(&self, call: &mut varlink::Call) -> varlink::Result<()> {
+                        #call_contract
+                        self.inner.#method_name(call as &mut dyn #call_name)
+                    }
                 ));
             }
         }
     }
 
+    let allow_deprecated = &options.allow_deprecated;
     ts.extend(quote!(
-        pub trait VarlinkInterface {
+        #allow_deprecated
+        pub trait #interface_trait {
             #server_method_decls
 
-            fn call_upgraded(&self, _call: &mut varlink::Call, _bufreader: &mut BufRead) -> varlink::Result<Vec<u8>> {
+            fn call_upgraded(&self, _call: &mut varlink::Call, _bufreader: &mut dyn BufRead) -> varlink::Result<Vec<u8>> {
                 Ok(Vec::new())
             }
         }
-        pub trait VarlinkClientInterface {
+        #allow_deprecated
+        pub trait #client_interface_trait {
             #client_method_decls
         }
 
+        /// A client of the interface.
+        ///
+        /// Clones share the connection, so they are cheap to hand to other threads.
+        /// Calls of all clones are serialized through the lock of the shared connection,
+        /// while a `more()` call is still receiving replies, other calls fail with
+        /// `ConnectionBusy`.
         #[allow(dead_code)]
-        pub struct VarlinkClient {
+        #[derive(Clone)]
+        pub struct #client_struct {
             connection: Arc<RwLock<varlink::Connection>>,
         }
 
-        impl VarlinkClient {
+        #allow_deprecated
+        impl #client_struct {
             #[allow(dead_code)]
             pub fn new(connection: Arc<RwLock<varlink::Connection>>) -> Self {
-                VarlinkClient {
+                #client_struct {
                     connection,
                 }
             }
+
+            #client_more_impls
         }
 
-        impl VarlinkClientInterface for VarlinkClient {
+        #allow_deprecated
+        impl #client_interface_trait for #client_struct {
             #client_method_impls
         }
+    ));
+
+    if options.direct_client {
+        let direct_interface_trait = options.prefixed("VarlinkClientDirectInterface");
+        let direct_client_struct = options.prefixed("VarlinkDirectClient");
+        ts.extend(quote!(
+            #allow_deprecated
+            pub trait #direct_interface_trait {
+                #direct_method_decls
+            }
+
+            /// A client of the interface, whose methods perform the call directly.
+            #[allow(dead_code)]
+            #[derive(Clone)]
+            pub struct #direct_client_struct {
+                client: #client_struct,
+            }
+
+            impl #direct_client_struct {
+                #[allow(dead_code)]
+                pub fn new(connection: Arc<RwLock<varlink::Connection>>) -> Self {
+                    #direct_client_struct {
+                        client: #client_struct::new(connection),
+                    }
+                }
+            }
+
+            #allow_deprecated
+            impl #direct_interface_trait for #direct_client_struct {
+                #direct_method_impls
+            }
+        ));
+    }
+
+    if options.dyn_client {
+        let dyn_interface_trait = options.prefixed("VarlinkClientDynInterface");
+        let dyn_client_struct = options.prefixed("VarlinkDynClient");
+        ts.extend(quote!(
+            #allow_deprecated
+            pub trait #dyn_interface_trait {
+                #dyn_method_decls
+            }
+
+            /// A client of the interface, which can be used as a
+            /// `Box<dyn VarlinkClientDynInterface + Send + Sync>`.
+            #[allow(dead_code)]
+            #[derive(Clone)]
+            pub struct #dyn_client_struct {
+                client: #client_struct,
+            }
+
+            impl #dyn_client_struct {
+                #[allow(dead_code)]
+                pub fn new(connection: Arc<RwLock<varlink::Connection>>) -> Self {
+                    #dyn_client_struct {
+                        client: #client_struct::new(connection),
+                    }
+                }
+
+                /// The client as a trait object.
+                #[allow(dead_code)]
+                pub fn boxed(
+                    connection: Arc<RwLock<varlink::Connection>>,
+                ) -> Box<dyn #dyn_interface_trait + Send + Sync> {
+                    Box::new(Self::new(connection))
+                }
+            }
+
+            #allow_deprecated
+            impl #dyn_interface_trait for #dyn_client_struct {
+                #dyn_method_impls
+            }
+        ));
+    }
+
+    if options.async_client {
+        let async_client_struct = options.prefixed("AsyncVarlinkClient");
+        ts.extend(quote!(
+            /// An async client of the interface.
+            ///
+            /// Clones share the connection, their calls are serialized through the lock of the
+            /// connection.
+            #[allow(dead_code)]
+            #[derive(Clone)]
+            pub struct #async_client_struct {
+                connection: Arc<varlink::AsyncConnection>,
+            }
+
+            #[allow(dead_code)]
+            #allow_deprecated
+            impl #async_client_struct {
+                pub fn new(connection: Arc<varlink::AsyncConnection>) -> Self {
+                    #async_client_struct { connection }
+                }
+
+                #async_method_impls
+            }
+        ));
+    }
+
+    ts.extend(quote!(
 
         #[allow(dead_code)]
-        pub struct VarlinkInterfaceProxy {
-            inner: Box<VarlinkInterface + Send + Sync>,
+        pub struct #proxy_struct {
+            inner: Box<dyn #interface_trait + Send + Sync>,
         }
 
         #[allow(dead_code)]
-        pub fn new(inner: Box<VarlinkInterface + Send + Sync>) -> VarlinkInterfaceProxy {
-            VarlinkInterfaceProxy { inner }
+        pub fn #new_fn(inner: Box<dyn #interface_trait + Send + Sync>) -> #proxy_struct {
+            #proxy_struct { inner }
+        }
+
+        #allow_deprecated
+        impl #proxy_struct {
+            /// The dispatch functions of the methods, sorted by the method name like
+            /// `idl.methods`, for the binary search.
+            const METHODS: &'static [(&'static str, fn(&#proxy_struct, &mut varlink::Call) -> varlink::Result<()>)] = &[
+                #(#server_dispatch_entries,)*
+            ];
+
+            #server_method_impls
         }
 
-        impl varlink::Interface for VarlinkInterfaceProxy {
+        impl varlink::Interface for #proxy_struct {
             fn get_description(&self) -> &'static str {
                 #description
             }
@@ -516,58 +2172,277 @@ fn varlink_to_rust(idl: &IDL, options: &GeneratorOptions, tosource: bool) -> Res
                 #iname
             }
 
-            fn call_upgraded(&self, call: &mut varlink::Call, bufreader: &mut BufRead) -> varlink::Result<Vec<u8>> {
-                self.inner.call_upgraded(call, bufreader)
-            }
+            fn call_upgraded(&self, call: &mut varlink::Call, bufreader: &mut dyn BufRead) -> varlink::Result<Vec<u8>> {
+                self.inner.call_upgraded(call, bufreader)
+            }
+
+            fn call(&self, call: &mut varlink::Call) -> varlink::Result<()> {
+                let req = call.request.unwrap();
+                match Self::METHODS.binary_search_by(|&(name, _)| name.cmp(&*req.method)) {
+                    Ok(i) => (Self::METHODS[i].1)(self, call),
+                    Err(_) => call.reply_method_not_found(req.method.to_string()),
+                }
+            }
+        }
+    ));
+
+    ts.extend(conversions::generate_conversions(idl, others, options));
+
+    if options.borrowed_types {
+        ts.extend(generate_borrowed_types(idl, options));
+    }
+
+    if options.emit_tests {
+        ts.extend(round_trip::generate_round_trip_tests(idl, options));
+    }
+
+    if options.emit_description_test {
+        ts.extend(description_test::generate_description_test(
+            idl,
+            stub_method_impls,
+            options,
+        ));
+    }
+
+    Ok(ts)
+}
+
+/// The newtypes of [GeneratorOptions::newtype_fields](struct.GeneratorOptions.html#structfield.newtype_fields).
+fn generate_newtypes(options: &GeneratorOptions) -> TokenStream {
+    let mut ts = TokenStream::new();
+    let mut done = HashSet::new();
+
+    for (_, newtype) in options.newtype_fields.iter().flatten() {
+        if !done.insert(newtype) {
+            continue;
+        }
+        let tname = Ident::new(newtype, Span::call_site());
+        ts.extend(quote!(
+            #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Hash, Clone, Default)]
+            #[serde(transparent)]
+            pub struct #tname(pub String);
+
+            impl From<String> for #tname {
+                fn from(v: String) -> Self {
+                    #tname(v)
+                }
+            }
+
+            impl<'a> From<&'a str> for #tname {
+                fn from(v: &'a str) -> Self {
+                    #tname(v.into())
+                }
+            }
+
+            impl From<#tname> for String {
+                fn from(v: #tname) -> Self {
+                    v.0
+                }
+            }
+
+            impl std::ops::Deref for #tname {
+                type Target = String;
+
+                fn deref(&self) -> &String {
+                    &self.0
+                }
+            }
+        ));
+    }
+
+    ts
+}
+
+/// Generate the module `borrowed` with the types borrowing their strings from the input.
+fn generate_borrowed_types(idl: &IDL, options: &GeneratorContext) -> TokenStream {
+    let options = &GeneratorContext {
+        options: options.options,
+        boxed_fields: options.boxed_fields.clone(),
+        borrowing_types: Some(borrowing_types(idl)),
+        default_types: options.default_types.clone(),
+        allow_deprecated: options.allow_deprecated.clone(),
+    };
+    let mut ts = TokenStream::new();
+
+    for t in idl.typedefs.values() {
+        t.to_tokenstream("", &mut ts, options);
+    }
+
+    for t in idl.errors.values() {
+        t.to_tokenstream("", &mut ts, options);
+    }
 
-            fn call(&self, call: &mut varlink::Call) -> varlink::Result<()> {
-                let req = call.request.unwrap();
-                match req.method.as_ref() {
-                    #server_method_impls
-                    m => {
-                        call.reply_method_not_found(String::from(m))
-                    }
+    for t in idl.methods.values() {
+        for (suffix, vstruct) in &[("Args", &t.input), ("Reply", &t.output)] {
+            let name = format!("{}_{}", t.name, suffix);
+            let struct_name = Ident::new(&name, Span::call_site());
+            let mut field_types = Vec::new();
+            let mut field_names = Vec::new();
+            let mut anot = Vec::new();
+
+            generate_anon_struct(
+                &name,
+                vstruct,
+                options,
+                &mut ts,
+                &mut field_types,
+                &mut field_names,
+                &mut anot,
+            );
+
+            let derives = type_derives(options, &options.method_attributes);
+            let generics = options.lifetime_generics(vstruct);
+            let default_impl = generate_default_impl(
+                &struct_name,
+                &generics,
+                &vstruct.elts,
+                &TokenStream::new(),
+                false,
+                &options.allow_deprecated,
+            );
+            let deprecated = deprecated_attr(t.doc);
+            ts.extend(quote!(
+                #deprecated
+                #derives
+                pub struct #struct_name #generics {
+                    #(#anot pub #field_names: #field_types,)*
                 }
-            }
+
+                #default_impl
+            ));
         }
-    ));
+    }
 
-    Ok(ts)
+    let module = options.prefixed_snake("borrowed");
+    let allow_deprecated = &options.allow_deprecated;
+    quote!(
+        /// The types of the interface, which borrow their strings from the deserialized input.
+        #allow_deprecated
+        pub mod #module {
+            #[allow(unused_imports)]
+            use serde_derive::{Deserialize, Serialize};
+            #[allow(unused_imports)]
+            use std::borrow::Cow;
+
+            #ts
+        }
+    )
 }
 
 fn generate_anon_struct(
     name: &str,
     vstruct: &VStruct,
-    options: &GeneratorOptions,
+    options: &GeneratorContext,
     mut ts: &mut TokenStream,
     field_types: &mut Vec<TokenStream>,
     field_names: &mut Vec<Ident>,
     anot: &mut Vec<TokenStream>,
 ) {
     for e in &vstruct.elts {
+        let etype = field_rust_string(e, format!("{}_{}", name, e.name).as_ref(), &mut ts, options);
+        let rename = serde_rename(e.name);
+        let borrow = serde_borrow(&etype);
+        let default = options.serde_default(e);
         anot.push(if let VTypeExt::Option(_) = e.vtype {
-            quote!(#[serde(skip_serializing_if = "Option::is_none")])
+            let skip = options.skip_none(name, e.name);
+            quote!(#rename #borrow #default #skip)
         } else {
-            quote!()
+            quote!(#rename #borrow #default)
         });
-        let ename_ident: Ident = syn::parse_str(&(String::from("r#") + e.name)).unwrap();
-        field_names.push(ename_ident);
-        field_types.push(
-            TokenStream::from_str(
-                e.vtype
-                    .to_rust_string(format!("{}_{}", name, e.name).as_ref(), &mut ts, options)
-                    .as_ref(),
-            )
-            .unwrap(),
-        );
+        field_names.push(to_rust_ident(e.name));
+        field_types.push(TokenStream::from_str(etype.as_ref()).unwrap());
+    }
+}
+
+fn generate_reply_values(
+    struct_name: &Ident,
+    field_names: &[Ident],
+    field_types: &[TokenStream],
+) -> TokenStream {
+    let (values_type, values) = if field_names.is_empty() {
+        (quote!(()), quote!())
+    } else if field_names.len() == 1 {
+        let field_name = &field_names[0];
+        let field_type = &field_types[0];
+        (quote!(#field_type), quote!(self.#field_name))
+    } else {
+        let field_names = field_names.iter();
+        let field_types = field_types.iter();
+        (
+            quote!((#(#field_types),*)),
+            quote!((#(self.#field_names),*)),
+        )
+    };
+
+    quote!(
+        impl varlink::VarlinkReplyValues for #struct_name {
+            type Values = #values_type;
+            fn into_values(self) -> Self::Values {
+                #values
+            }
+        }
+    )
+}
+
+/// Check, that the names in `more_methods` and `oneway_methods` are methods of `idls`, so a
+/// typo does not disable the check of a method.
+fn check_method_options(idls: &[IDL], options: &GeneratorOptions) -> Result<()> {
+    for (option, names) in &[
+        ("more_methods", &options.more_methods),
+        ("oneway_methods", &options.oneway_methods),
+    ] {
+        for name in names.iter().flatten() {
+            if !idls.iter().any(|idl| idl.methods.contains_key(name)) {
+                return Err(strerr!(
+                    Error,
+                    "`{}` of `{}` is not a method of the interface",
+                    name,
+                    option
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Whether `method` is listed in `more_methods` and gets the named iterator over its replies.
+fn is_more_method(method: &str, options: &GeneratorOptions) -> bool {
+    options
+        .more_methods
+        .as_ref()
+        .map_or(false, |m| m.contains(&method))
+}
+
+fn generate_call_contract(method: &str, options: &GeneratorOptions) -> TokenStream {
+    let mut ts = TokenStream::new();
+
+    if let Some(ref more_methods) = options.more_methods {
+        if !more_methods.contains(&method) {
+            ts.extend(quote!(if call.wants_more() {
+                return call.reply_invalid_parameter("more".into());
+            }));
+        }
+    }
+
+    if let Some(ref oneway_methods) = options.oneway_methods {
+        if !oneway_methods.contains(&method) {
+            ts.extend(quote!(if call.is_oneway() {
+                return call.reply_invalid_parameter("oneway".into());
+            }));
+        }
     }
+
+    ts
 }
 
 fn generate_error_code(
-    options: &GeneratorOptions,
+    options: &GeneratorContext,
     idl: &varlink_parser::IDL,
     ts: &mut TokenStream,
 ) {
+    let error_kind = options.prefixed("ErrorKind");
+    let call_error_trait = options.prefixed("VarlinkCallError");
+
     // Errors traits
     {
         let mut error_structs_and_enums = TokenStream::new();
@@ -581,18 +2456,16 @@ fn generate_error_code(
             let args_name = Ident::new(&format!("{}_Args", t.name), Span::call_site());
             if !t.parm.elts.is_empty() {
                 for e in &t.parm.elts {
-                    let ename_ident: Ident =
-                        syn::parse_str(&(String::from("r#") + e.name)).unwrap();
-                    inparms_name.push(ename_ident);
+                    inparms_name.push(to_rust_ident(e.name));
                     inparms_type.push(
                         TokenStream::from_str(
-                            e.vtype
-                                .to_rust_string(
-                                    format!("{}_Args_{}", t.name, e.name).as_ref(),
-                                    &mut error_structs_and_enums,
-                                    options,
-                                )
-                                .as_ref(),
+                            field_rust_string(
+                                e,
+                                format!("{}_Args_{}", t.name, e.name).as_ref(),
+                                &mut error_structs_and_enums,
+                                options,
+                            )
+                            .as_ref(),
                         )
                         .unwrap(),
                     );
@@ -616,39 +2489,63 @@ fn generate_error_code(
                     self.reply_struct(varlink::Reply::error(#errorname, #parms))
                 }
             ));
+
+            if !t.parm.elts.is_empty() {
+                let struct_func_name = Ident::new(
+                    &format!("reply_{}_struct", to_snake_case(t.name)),
+                    Span::call_site(),
+                );
+                funcs.extend(quote!(
+                    fn #struct_func_name(&mut self, args: #args_name) -> varlink::Result<()> {
+                        self.reply_struct(varlink::Reply::error(
+                            #errorname,
+                            Some(serde_json::to_value(args).map_err(minto_cherr!())?),
+                        ))
+                    }
+                ));
+            }
         }
+        let allow_deprecated = &options.allow_deprecated;
         ts.extend(quote!(
             #error_structs_and_enums
-            pub trait VarlinkCallError: varlink::CallTrait {
+            #allow_deprecated
+            pub trait #call_error_trait: varlink::CallTrait {
                 #funcs
             }
         ));
     }
     ts.extend(quote!(
-        impl<'a> VarlinkCallError for varlink::Call<'a> {}
+        impl<'a> #call_error_trait for varlink::Call<'a> {}
     ));
     {
         let mut errors = Vec::new();
         let mut errors_display = Vec::new();
         for t in idl.errors.values() {
-            errors.push(
-                TokenStream::from_str(&format!("{ename}(Option<{ename}_Args>)", ename = t.name,))
-                    .unwrap(),
-            );
-            errors_display.push(
-                TokenStream::from_str(&format!(
-                    "ErrorKind::{ename}(v) => write!(f, \"{iname}.{ename}: {{:#?}}\", v)",
-                    ename = t.name,
-                    iname = idl.name,
-                ))
-                .unwrap(),
-            );
+            let variant = to_error_variant(t.name);
+            let args_name = Ident::new(&format!("{}_Args", t.name), Span::call_site());
+            let doc = doc_attrs(t.doc);
+            errors.push(quote!(#doc #variant(Option<#args_name>)));
+            let format = format!("{}.{}: {{:#?}}", idl.name, t.name);
+            errors_display.push(quote!(#error_kind::#variant(v) => write!(f, #format, v)));
         }
 
+        let error_derives = if options.raw_object {
+            quote!(#[derive(Clone, Debug)])
+        } else {
+            quote!(#[derive(Clone, PartialEq, Debug)])
+        };
+
+        let non_exhaustive = if options.non_exhaustive_errors {
+            quote!(#[non_exhaustive])
+        } else {
+            quote!()
+        };
+
         ts.extend(quote!(
             #[allow(dead_code)]
-            #[derive(Clone, PartialEq, Debug)]
-            pub enum ErrorKind {
+            #error_derives
+            #non_exhaustive
+            pub enum #error_kind {
                 Io_Error(::std::io::ErrorKind),
                 SerdeJson_Error(serde_json::error::Category),
                 Varlink_Error,
@@ -656,54 +2553,56 @@ fn generate_error_code(
                 Generic,
                 #(#errors),*
             }
-            impl ::std::fmt::Display for ErrorKind {
+            impl ::std::fmt::Display for #error_kind {
                 fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
                     match self {
-                        ErrorKind::Io_Error(_) => write!(f, "IO error"),
-                        ErrorKind::SerdeJson_Error(_) => {
+                        #error_kind::Io_Error(_) => write!(f, "IO error"),
+                        #error_kind::SerdeJson_Error(_) => {
                             write!(f, "(De)Serialization Error")
                         }
-                        ErrorKind::Varlink_Error => write!(f, "Varlink Error"),
-                        ErrorKind::VarlinkReply_Error(v) => write!(f, "Unknown error reply: '{:#?}'", v),
-                        ErrorKind::Generic => Ok(()),
+                        #error_kind::Varlink_Error => write!(f, "Varlink Error"),
+                        #error_kind::VarlinkReply_Error(v) => write!(f, "Unknown error reply: '{:#?}'", v),
+                        #error_kind::Generic => Ok(()),
                         #(#errors_display),*
                     }
                 }
             }
-            impl ::std::error::Error for ErrorKind {}
+            impl ::std::error::Error for #error_kind {}
 
         ));
     }
+    let error_type = options.prefixed("Error");
+    let result_type = options.prefixed("Result");
     ts.extend(quote!(
-    impl ChainErrorFrom<std::io::Error> for ErrorKind {
+    impl ChainErrorFrom<std::io::Error> for #error_kind {
         fn chain_error_from(
             e: std::io::Error,
             line_filename: Option<(u32, &'static str)>,
         ) -> ChainError<Self> {
-            ChainError::<_>::new(ErrorKind::Io_Error(e.kind()), Some(Box::from(e)), line_filename)
+            ChainError::<_>::new(#error_kind::Io_Error(e.kind()), Some(Box::from(e)), line_filename)
         }
     }
 
-    impl ChainErrorFrom<serde_json::error::Error> for ErrorKind {
+    impl ChainErrorFrom<serde_json::error::Error> for #error_kind {
         fn chain_error_from(
             e: serde_json::error::Error,
             line_filename: Option<(u32, &'static str)>,
         ) -> ChainError<Self> {
             ChainError::<_>::new(
-                ErrorKind::SerdeJson_Error(e.classify()),
+                #error_kind::SerdeJson_Error(e.classify()),
                 Some(Box::from(e)),
                 line_filename,
             )
         }
     }
 
-    impl ChainErrorFrom<varlink::ErrorKind> for ErrorKind {
+    impl ChainErrorFrom<varlink::ErrorKind> for #error_kind {
         fn chain_error_from(
             e: varlink::ErrorKind,
             line_filename: Option<(u32, &'static str)>,
         ) -> ChainError<Self> {
             ChainError::<_>::new(
-                ErrorKind::Varlink_Error,
+                #error_kind::Varlink_Error,
                 Some(Box::from(
                     ChainError::<_>::new(
                         e,
@@ -717,15 +2616,16 @@ fn generate_error_code(
     }
 
     #[allow(dead_code)]
-    pub type Result<T> = ChainResult<T, ErrorKind>;
+    pub type #result_type<T> = ChainResult<T, #error_kind>;
     #[allow(dead_code)]
-    pub type Error = ErrorKind;
+    pub type #error_type = #error_kind;
     ));
     {
         let mut arms = TokenStream::new();
         for t in idl.errors.values() {
             let error_name = format!("{iname}.{ename}", iname = idl.name, ename = t.name);
-            let ename = TokenStream::from_str(&format!("ErrorKind::{}", t.name)).unwrap();
+            let variant = to_error_variant(t.name);
+            let ename = quote!(#error_kind::#variant);
             arms.extend(quote!(
                 varlink::Reply { error: Some(ref t), .. } if t == #error_name => {
                     match e {
@@ -742,8 +2642,20 @@ fn generate_error_code(
             ));
         }
 
+        let unknown_error = quote!(into_cherr!(#error_kind::VarlinkReply_Error(e)));
+        let reply_error = if idl.errors.is_empty() {
+            unknown_error
+        } else {
+            quote!(
+                match e {
+                #arms
+                _ => #unknown_error,
+                }
+            )
+        };
+
         ts.extend(quote!(
-            impl ChainErrorFrom<varlink::Reply> for ErrorKind {
+            impl ChainErrorFrom<varlink::Reply> for #error_kind {
                 #[allow(unused_variables)]
                 fn chain_error_from(e: varlink::Reply, line_filename: Option<(u32, &'static str)>) -> ChainError<Self> {
                     if varlink::ErrorKind::is_error(&e) {
@@ -751,20 +2663,100 @@ fn generate_error_code(
                         return into_cherr!(e);
                     }
 
-                    match e {
+                    #reply_error
+                }
+            }
+        ));
+    }
+    if let Some(ref http_status) = options.http_status {
+        generate_http_status(http_status, options, idl, ts);
+    }
+    if options.reply_bridge {
+        generate_error_reply(options, idl, ts);
+    }
+}
+
+fn generate_error_reply(
+    options: &GeneratorContext,
+    idl: &varlink_parser::IDL,
+    ts: &mut TokenStream,
+) {
+    let error_kind = options.prefixed("ErrorKind");
+    let mut arms = TokenStream::new();
+    for t in idl.errors.values() {
+        let error_name = format!("{iname}.{ename}", iname = idl.name, ename = t.name);
+        let variant = to_error_variant(t.name);
+        let ename = quote!(#error_kind::#variant);
+        arms.extend(quote!(
+            #ename(v) => Some(varlink::Reply::error(
+                #error_name,
+                v.as_ref().and_then(|v| serde_json::to_value(v).ok()),
+            )),
+        ));
+    }
+
+    ts.extend(quote!(
+        impl varlink::VarlinkErrorReply for #error_kind {
+            fn into_reply(e: &ChainError<Self>) -> Option<varlink::Reply> {
+                match e.kind() {
                     #arms
-                    _ => into_cherr!(ErrorKind::VarlinkReply_Error(e)),
-                    }
+                    #error_kind::VarlinkReply_Error(reply) => Some(reply.clone()),
+                    #error_kind::Varlink_Error => e
+                        .find_chain_cause::<varlink::ErrorKind>()
+                        .and_then(<varlink::ErrorKind as varlink::VarlinkErrorReply>::into_reply),
+                    _ => None,
                 }
             }
+        }
+    ));
+}
+
+fn generate_http_status(
+    http_status: &[(&str, u16)],
+    options: &GeneratorContext,
+    idl: &varlink_parser::IDL,
+    ts: &mut TokenStream,
+) {
+    let error_kind = options.prefixed("ErrorKind");
+    let http_status_fn = options.prefixed_snake("http_status");
+    let mut arms = TokenStream::new();
+    for t in idl.errors.values() {
+        let status = http_status
+            .iter()
+            .find(|(name, _)| *name == t.name)
+            .map(|(_, status)| *status)
+            .unwrap_or(500);
+        let status = Literal::u16_unsuffixed(status);
+        let variant = to_error_variant(t.name);
+        let ename = quote!(#error_kind::#variant);
+        arms.extend(quote!(
+            #ename(_) => #status,
         ));
     }
+
+    ts.extend(quote!(
+        #[allow(dead_code)]
+        pub fn #http_status_fn(e: &ChainError<#error_kind>) -> u16 {
+            match e.kind() {
+                #arms
+                #error_kind::Varlink_Error => match e.find_chain_cause::<varlink::ErrorKind>().map(|e| e.kind()) {
+                    Some(varlink::ErrorKind::InvalidParameter(_)) => 400,
+                    Some(varlink::ErrorKind::InterfaceNotFound(_))
+                    | Some(varlink::ErrorKind::MethodNotFound(_)) => 404,
+                    Some(varlink::ErrorKind::MethodNotImplemented(_)) => 501,
+                    _ => 500,
+                },
+                _ => 500,
+            }
+        }
+    ));
 }
 
 pub fn compile(source: String) -> Result<TokenStream> {
     let idl = IDL::from_string(&source).map_err(mstrerr!(Error, "Failed to parse {}", &source))?;
     varlink_to_rust(
         &idl,
+        &[],
         &GeneratorOptions {
             ..Default::default()
         },
@@ -774,7 +2766,7 @@ pub fn compile(source: String) -> Result<TokenStream> {
 
 /// `generate` reads a varlink interface definition from `reader` and writes
 /// the rust code to `writer`.
-pub fn generate(reader: &mut Read, writer: &mut Write, tosource: bool) -> Result<()> {
+pub fn generate(reader: &mut dyn Read, writer: &mut dyn Write, tosource: bool) -> Result<()> {
     generate_with_options(
         reader,
         writer,
@@ -785,11 +2777,66 @@ pub fn generate(reader: &mut Read, writer: &mut Write, tosource: bool) -> Result
     )
 }
 
+/// `generate_str` parses the varlink interface definition `source` and returns the rust
+/// code as a standalone source file, like `cargo_build_tosource` writes it.
+///
+/// # Examples
+///
+/// ```rust
+/// let code = varlink_generator::generate_str(
+///     "interface org.example.ping\nmethod Ping(ping: string) -> (pong: string)\n",
+/// )
+/// .unwrap();
+/// assert!(code.contains("pub struct Ping_Reply"));
+/// ```
+pub fn generate_str(source: &str) -> Result<String> {
+    let mut out = Vec::new();
+    generate(&mut source.as_bytes(), &mut out, true)?;
+    String::from_utf8(out).map_err(mstrerr!(Error, "Generated code is not UTF-8"))
+}
+
+/// The rust code of the interfaces `idls` of one varlink file.
+///
+/// If there are several, the code of each is put in a module named after the interface,
+/// so their items do not collide.
+fn interfaces_to_rust(
+    idls: &[IDL],
+    others: &[IDL],
+    options: &GeneratorOptions,
+    tosource: bool,
+) -> Result<TokenStream> {
+    if idls.len() == 1 {
+        return varlink_to_rust(&idls[0], others, options, tosource);
+    }
+
+    let mut ts = TokenStream::new();
+    for idl in idls {
+        let module = Ident::new(
+            &idl.name.replace(".", "_").replace("-", "_"),
+            Span::call_site(),
+        );
+        let code = varlink_to_rust(idl, others, options, tosource)?;
+        ts.extend(quote!(
+            pub mod #module {
+                #code
+            }
+        ));
+    }
+    Ok(ts)
+}
+
 /// `generate_with_options` reads a varlink interface definition from `reader`
 /// and writes the rust code to `writer`.
+///
+/// If `reader` contains several interfaces, the code of each is written as a module named
+/// after the interface with `.` and `-` replaced by `_`, e.g. `pub mod org_example_ping`.
+///
+/// The code only depends on the input: the types, errors and methods are emitted ordered by
+/// name, not in the order of their declaration, so checked in code regenerated from the same
+/// interface does not change.
 pub fn generate_with_options(
-    reader: &mut Read,
-    writer: &mut Write,
+    reader: &mut dyn Read,
+    writer: &mut dyn Write,
     options: &GeneratorOptions,
     tosource: bool,
 ) -> Result<()> {
@@ -799,15 +2846,108 @@ pub fn generate_with_options(
         .read_to_string(&mut buffer)
         .map_err(mstrerr!(Error, "Failed to read from buffer"))?;
 
-    let idl = IDL::from_string(&buffer).map_err(mstrerr!(Error, "Failed to parse {}", &buffer))?;
+    let idls =
+        IDL::from_string_many(&buffer).map_err(mstrerr!(Error, "Failed to parse {}", &buffer))?;
 
-    let ts = varlink_to_rust(&idl, options, tosource)?;
+    check_method_options(&idls, options)?;
+    let ts = interfaces_to_rust(&idls, &[], options, tosource)?;
     writer
         .write_all(ts.to_string().as_bytes())
         .map_err(mstrerr!(Error, "Failed to write to buffer"))?;
     Ok(())
 }
 
+/// `generate_many_with_options` reads varlink interface definitions from `readers`
+/// and writes the rust code of each to the corresponding entry of `writers`.
+///
+/// With `options.structural_conversions`, `From` conversions between the matching types of
+/// the interfaces are generated.
+pub fn generate_many_with_options(
+    readers: &mut [&mut dyn Read],
+    writers: &mut [&mut dyn Write],
+    options: &GeneratorOptions,
+    tosource: bool,
+) -> Result<()> {
+    let mut buffers = Vec::new();
+
+    for reader in readers.iter_mut() {
+        let mut buffer = String::new();
+        reader
+            .read_to_string(&mut buffer)
+            .map_err(mstrerr!(Error, "Failed to read from buffer"))?;
+        buffers.push(buffer);
+    }
+
+    let mut idls = Vec::new();
+    let mut counts = Vec::new();
+    for buffer in &buffers {
+        let file_idls =
+            IDL::from_string_many(buffer).map_err(mstrerr!(Error, "Failed to parse {}", buffer))?;
+        counts.push(file_idls.len());
+        idls.extend(file_idls);
+    }
+    check_method_options(&idls, options)?;
+
+    let others: &[IDL] = if options.structural_conversions {
+        &idls
+    } else {
+        &[]
+    };
+
+    let mut start = 0;
+    for (count, writer) in counts.into_iter().zip(writers.iter_mut()) {
+        let ts = interfaces_to_rust(&idls[start..start + count], others, options, tosource)?;
+        start += count;
+        writer
+            .write_all(ts.to_string().as_bytes())
+            .map_err(mstrerr!(Error, "Failed to write to buffer"))?;
+    }
+    Ok(())
+}
+
+/// `generate_changelog` reads two versions of a varlink interface definition from `old`
+/// and `new` and writes a summary of the changes of the generated rust API to `writer`.
+///
+/// Added, removed and changed methods, types and errors are listed with the rust items
+/// generated for them.
+///
+/// # Examples
+///
+/// ```rust
+/// let mut old = "interface org.example.ping\nmethod Ping(ping: string) -> (pong: string)\n".as_bytes();
+/// let mut new = "interface org.example.ping\nmethod Ping(ping: string) -> (pong: string)\n\
+///                method Reset() -> ()\n".as_bytes();
+/// let mut out = Vec::new();
+/// varlink_generator::generate_changelog(&mut old, &mut new, &mut out).unwrap();
+/// assert!(String::from_utf8(out).unwrap().contains("method Reset() -> ()"));
+/// ```
+pub fn generate_changelog(
+    old: &mut dyn Read,
+    new: &mut dyn Read,
+    writer: &mut dyn Write,
+) -> Result<()> {
+    let mut old_buffer = String::new();
+    let mut new_buffer = String::new();
+
+    old.read_to_string(&mut old_buffer)
+        .map_err(mstrerr!(Error, "Failed to read from buffer"))?;
+    new.read_to_string(&mut new_buffer)
+        .map_err(mstrerr!(Error, "Failed to read from buffer"))?;
+
+    let old_idl = IDL::from_string(&old_buffer).map_err(mstrerr!(
+        Error,
+        "Failed to parse {}",
+        &old_buffer
+    ))?;
+    let new_idl = IDL::from_string(&new_buffer).map_err(mstrerr!(
+        Error,
+        "Failed to parse {}",
+        &new_buffer
+    ))?;
+
+    changelog::write_changelog(&old_idl, &new_idl, writer)
+}
+
 /// cargo build helper function
 ///
 /// `cargo_build` is used in a `build.rs` program to build the rust code
@@ -826,12 +2966,7 @@ pub fn generate_with_options(
 /// ```
 ///
 pub fn cargo_build<T: AsRef<Path> + ?Sized>(input_path: &T) {
-    cargo_build_options_many(
-        &[input_path],
-        &GeneratorOptions {
-            ..Default::default()
-        },
-    )
+    exit_on_error(try_cargo_build(input_path))
 }
 
 /// cargo build helper function
@@ -859,12 +2994,12 @@ where
     T: std::marker::Sized,
     T: AsRef<Path>,
 {
-    cargo_build_options_many(
+    exit_on_error(try_cargo_build_options_many(
         input_paths,
         &GeneratorOptions {
             ..Default::default()
         },
-    )
+    ))
 }
 
 /// cargo build helper function
@@ -890,7 +3025,47 @@ where
 /// }
 /// ```
 pub fn cargo_build_options<T: AsRef<Path> + ?Sized>(input_path: &T, options: &GeneratorOptions) {
-    cargo_build_options_many(&[input_path], options)
+    exit_on_error(try_cargo_build_options(input_path, options))
+}
+
+/// Like [cargo_build](fn.cargo_build.html), but return the error instead of terminating the
+/// process, e.g. to try several paths or to emit a `cargo:warning=` of its own.
+///
+/// Returns the path of the rust file written to `OUT_DIR`.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// extern crate varlink_generator;
+///
+/// fn main() {
+///     let generated = ["src/org.example.ping.varlink", "/usr/share/varlink/org.example.ping.varlink"]
+///         .iter()
+///         .find_map(|path| varlink_generator::try_cargo_build(path).ok());
+///     if generated.is_none() {
+///         panic!("org.example.ping.varlink not found");
+///     }
+/// }
+/// ```
+pub fn try_cargo_build<T: AsRef<Path> + ?Sized>(input_path: &T) -> Result<PathBuf> {
+    try_cargo_build_options(
+        input_path,
+        &GeneratorOptions {
+            ..Default::default()
+        },
+    )
+}
+
+/// Like [cargo_build_options](fn.cargo_build_options.html), but return the error instead of
+/// terminating the process.
+///
+/// Returns the path of the rust file written to `OUT_DIR`.
+pub fn try_cargo_build_options<T: AsRef<Path> + ?Sized>(
+    input_path: &T,
+    options: &GeneratorOptions,
+) -> Result<PathBuf> {
+    let mut paths = try_cargo_build_options_many(&[input_path], options)?;
+    Ok(paths.remove(0))
 }
 
 /// cargo build helper function
@@ -923,44 +3098,126 @@ where
     T: std::marker::Sized,
     T: AsRef<Path>,
 {
+    exit_on_error(try_cargo_build_options_many(input_paths, options))
+}
+
+/// Like [cargo_build_options_many](fn.cargo_build_options_many.html), but return the error
+/// instead of terminating the process.
+///
+/// Returns the paths of the rust files written to `OUT_DIR`, in the order of `input_paths`.
+pub fn try_cargo_build_options_many<T>(
+    input_paths: &[T],
+    options: &GeneratorOptions,
+) -> Result<Vec<PathBuf>>
+where
+    T: std::marker::Sized,
+    T: AsRef<Path>,
+{
+    let out_dir: PathBuf = match env::var_os("OUT_DIR") {
+        Some(out_dir) => out_dir.into(),
+        None => {
+            return Err(strerr!(
+                Error,
+                "OUT_DIR is not set, run from a build script"
+            ))
+        }
+    };
+    let mut rust_paths = Vec::new();
+    let mut readers = Vec::new();
+    let mut writers = Vec::new();
+
     for input_path in input_paths {
         let input_path = input_path.as_ref();
 
-        let out_dir: PathBuf = env::var_os("OUT_DIR").unwrap().into();
-        let rust_path = out_dir
-            .join(input_path.file_name().unwrap())
-            .with_extension("rs");
+        let rust_path = match input_path.file_name() {
+            Some(f) => out_dir.join(f).with_extension("rs"),
+            None => {
+                return Err(strerr!(
+                    Error,
+                    "Invalid varlink input file name `{}`",
+                    input_path.display()
+                ))
+            }
+        };
+
+        readers.push(File::open(input_path).map_err(mstrerr!(
+            Error,
+            "Could not read varlink input file `{}`",
+            input_path.display()
+        ))?);
+
+        writers.push(File::create(&rust_path).map_err(mstrerr!(
+            Error,
+            "Could not open varlink output file `{}`",
+            rust_path.display()
+        ))?);
+        rust_paths.push(rust_path);
+    }
 
-        let writer: &mut Write = &mut (File::create(&rust_path).unwrap_or_else(|e| {
-            eprintln!(
-                "Could not open varlink output file `{}`: {}",
-                rust_path.display(),
-                e
-            );
-            exit(1);
-        }));
+    if options.structural_conversions {
+        let mut readers: Vec<&mut dyn Read> =
+            readers.iter_mut().map(|r| r as &mut dyn Read).collect();
+        let mut writers: Vec<&mut dyn Write> =
+            writers.iter_mut().map(|w| w as &mut dyn Write).collect();
+
+        generate_many_with_options(&mut readers, &mut writers, options, false).map_err(
+            mstrerr!(Error, "Could not generate rust code from varlink files"),
+        )?;
+    } else {
+        for ((input_path, reader), writer) in input_paths
+            .iter()
+            .zip(readers.iter_mut())
+            .zip(writers.iter_mut())
+        {
+            generate_with_options(reader, writer, options, false).map_err(mstrerr!(
+                Error,
+                "Could not generate rust code from varlink file `{}`",
+                input_path.as_ref().display()
+            ))?;
+        }
+    }
 
-        let reader: &mut Read = &mut (File::open(input_path).unwrap_or_else(|e| {
-            eprintln!(
-                "Could not read varlink input file `{}`: {}",
-                input_path.display(),
-                e
-            );
-            exit(1);
-        }));
+    for input_path in input_paths {
+        println!("cargo:rerun-if-changed={}", input_path.as_ref().display());
+    }
+    Ok(rust_paths)
+}
 
-        if let Err(e) = generate_with_options(reader, writer, options, false) {
-            eprintln!(
-                "Could not generate rust code from varlink file `{}`: {}",
-                input_path.display(),
-                e
-            );
+/// Print the error of a `try_cargo_build*` function and terminate the process.
+fn exit_on_error<T>(result: Result<T>) {
+    if let Err(e) = result {
+        eprintln!("{:?}", e);
+        exit(1);
+    }
+}
 
-            exit(1);
-        }
+/// Format the rust code `src` with `rustfmt`, passing it through stdin and stdout.
+///
+/// Fails, if `rustfmt` cannot be run. If `rustfmt` exits with an error, e.g. because it
+/// cannot parse the code, `src` is returned unchanged.
+fn rustfmt(src: String) -> Result<String> {
+    let mut child = Command::new("rustfmt")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(mstrerr!(Error, "Failed to start rustfmt"))?;
+
+    // rustfmt reads all of stdin, before it writes the formatted code
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(src.as_bytes())
+        .map_err(mstrerr!(Error, "Failed to write to rustfmt"))?;
 
-        println!("cargo:rerun-if-changed={}", input_path.display());
+    let output = child
+        .wait_with_output()
+        .map_err(mstrerr!(Error, "Failed to read from rustfmt"))?;
+
+    if !output.status.success() {
+        return Ok(src);
     }
+    Ok(String::from_utf8(output.stdout).unwrap_or(src))
 }
 
 /// cargo build helper function
@@ -975,7 +3232,8 @@ where
 ///
 /// Set `rustfmt` to `true`, if you want the generator to run rustfmt on the
 /// generated code. This might be good practice to avoid large changes after a
-/// global `cargo fmt` run.
+/// global `cargo fmt` run. The code is formatted before the file is written. If rustfmt
+/// cannot be run, the unformatted code is written and cargo shows a warning.
 ///
 /// Errors are emitted to stderr and terminate the process.
 ///
@@ -989,13 +3247,7 @@ where
 /// }
 /// ```
 pub fn cargo_build_tosource<T: AsRef<Path> + ?Sized>(input_path: &T, rustfmt: bool) {
-    cargo_build_tosource_options(
-        input_path,
-        rustfmt,
-        &GeneratorOptions {
-            ..Default::default()
-        },
-    )
+    exit_on_error(try_cargo_build_tosource(input_path, rustfmt))
 }
 
 /// cargo build helper function
@@ -1010,7 +3262,8 @@ pub fn cargo_build_tosource<T: AsRef<Path> + ?Sized>(input_path: &T, rustfmt: bo
 ///
 /// Set `rustfmt` to `true`, if you want the generator to run rustfmt on the
 /// generated code. This might be good practice to avoid large changes after a
-/// global `cargo fmt` run.
+/// global `cargo fmt` run. The code is formatted before the file is written. If rustfmt
+/// cannot be run, the unformatted code is written and cargo shows a warning.
 ///
 /// Errors are emitted to stderr and terminate the process.
 ///
@@ -1035,59 +3288,100 @@ pub fn cargo_build_tosource_options<T: AsRef<Path> + ?Sized>(
     rustfmt: bool,
     options: &GeneratorOptions,
 ) {
+    exit_on_error(try_cargo_build_tosource_options(
+        input_path, rustfmt, options,
+    ))
+}
+
+/// Like [cargo_build_tosource](fn.cargo_build_tosource.html), but return the error instead
+/// of terminating the process.
+///
+/// Returns the path of the written rust file.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// extern crate varlink_generator;
+///
+/// fn main() {
+///     if let Err(e) = varlink_generator::try_cargo_build_tosource("src/org.example.ping.varlink", true) {
+///         println!("cargo:warning=Using the checked in bindings: {}", e);
+///     }
+/// }
+/// ```
+pub fn try_cargo_build_tosource<T: AsRef<Path> + ?Sized>(
+    input_path: &T,
+    rustfmt: bool,
+) -> Result<PathBuf> {
+    try_cargo_build_tosource_options(
+        input_path,
+        rustfmt,
+        &GeneratorOptions {
+            ..Default::default()
+        },
+    )
+}
+
+/// Like [cargo_build_tosource_options](fn.cargo_build_tosource_options.html), but return the
+/// error instead of terminating the process.
+///
+/// Returns the path of the written rust file.
+pub fn try_cargo_build_tosource_options<T: AsRef<Path> + ?Sized>(
+    input_path: &T,
+    rustfmt: bool,
+    options: &GeneratorOptions,
+) -> Result<PathBuf> {
     let input_path = input_path.as_ref();
     let noextension = input_path.with_extension("");
-    let newfilename = noextension
-        .file_name()
-        .unwrap()
-        .to_str()
-        .unwrap()
-        .replace(".", "_");
+    let newfilename = match noextension.file_name().and_then(|f| f.to_str()) {
+        Some(f) => f.replace(".", "_"),
+        None => {
+            return Err(strerr!(
+                Error,
+                "Invalid varlink input file name `{}`",
+                input_path.display()
+            ))
+        }
+    };
     let rust_path = input_path
         .parent()
-        .unwrap()
+        .unwrap_or_else(|| Path::new(""))
         .join(Path::new(&newfilename).with_extension("rs"));
 
-    let writer: &mut Write = &mut (File::create(&rust_path).unwrap_or_else(|e| {
-        eprintln!(
-            "Could not open varlink output file `{}`: {}",
-            rust_path.display(),
-            e
-        );
-        exit(1);
-    }));
-
-    let reader: &mut Read = &mut (File::open(input_path).unwrap_or_else(|e| {
-        eprintln!(
-            "Could not read varlink input file `{}`: {}",
-            input_path.display(),
-            e
-        );
-        exit(1);
-    }));
-
-    if let Err(e) = generate_with_options(reader, writer, options, true) {
-        eprintln!(
-            "Could not generate rust code from varlink file `{}`: {}",
-            input_path.display(),
-            e
-        );
-        exit(1);
-    }
+    let mut reader = File::open(input_path).map_err(mstrerr!(
+        Error,
+        "Could not read varlink input file `{}`",
+        input_path.display()
+    ))?;
+
+    let mut out = Vec::new();
+    generate_with_options(&mut reader, &mut out, options, true).map_err(mstrerr!(
+        Error,
+        "Could not generate rust code from varlink file `{}`",
+        input_path.display()
+    ))?;
+    // written from the `String` of the tokens
+    let mut src = String::from_utf8(out).unwrap();
 
     if rustfmt {
-        if let Err(e) = Command::new("rustfmt")
-            .arg(rust_path.to_str().unwrap())
-            .output()
-        {
-            eprintln!(
-                "Could not run rustfmt on file `{}` {}",
+        src = self::rustfmt(src.clone()).unwrap_or_else(|e| {
+            println!(
+                "cargo:warning=Could not run rustfmt on `{}`, keeping the unformatted code: {}",
                 rust_path.display(),
                 e
             );
-            exit(1);
-        }
+            src
+        });
     }
 
+    File::create(&rust_path)
+        .and_then(|mut f| f.write_all(src.as_bytes()))
+        .map_err(mstrerr!(
+            Error,
+            "Could not write varlink output file `{}`",
+            rust_path.display()
+        ))?;
+
     println!("cargo:rerun-if-changed={}", input_path.display());
+    Ok(rust_path)
 }
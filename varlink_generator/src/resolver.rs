@@ -0,0 +1,139 @@
+//! Generate the rust code from the interface description of a running service, enabled with
+//! the `resolver` feature
+
+use std::env;
+use std::fs::File;
+use std::path::PathBuf;
+use std::process::exit;
+
+use chainerror::*;
+use serde_json::{json, Value};
+use varlink::{org_varlink_service, Connection, MethodCall};
+
+use crate::{generate_with_options, Error, GeneratorOptions, Result};
+
+/// The address of the varlink resolver, if `VARLINK_RESOLVER` is not set.
+pub const DEFAULT_RESOLVER: &str = "unix:/run/org.varlink.resolver";
+
+/// The address of the varlink resolver: `$VARLINK_RESOLVER` or
+/// [DEFAULT_RESOLVER](constant.DEFAULT_RESOLVER.html).
+pub fn resolver_address() -> String {
+    env::var("VARLINK_RESOLVER").unwrap_or_else(|_| DEFAULT_RESOLVER.into())
+}
+
+/// Look up the service implementing `interface` with the varlink resolver at `resolver` and
+/// return the description of the interface, as the service returns it with
+/// `GetInterfaceDescription`.
+pub fn description_from_resolver(resolver: &str, interface: &str) -> Result<String> {
+    let connection = Connection::with_address(resolver).map_err(mstrerr!(
+        Error,
+        "Could not connect to the varlink resolver `{}`",
+        resolver
+    ))?;
+
+    let reply: Value = MethodCall::<Value, Value, varlink::ErrorKind>::new(
+        connection,
+        "org.varlink.resolver.Resolve",
+        json!({ "interface": interface }),
+    )
+    .call()
+    .map_err(mstrerr!(
+        Error,
+        "The varlink resolver `{}` could not resolve `{}`",
+        resolver,
+        interface
+    ))?;
+
+    let address = match reply["address"].as_str() {
+        Some(address) => address,
+        None => {
+            return Err(strerr!(
+                Error,
+                "The varlink resolver `{}` returned no address for `{}`",
+                resolver,
+                interface
+            ))
+        }
+    };
+
+    let connection = Connection::with_address(address).map_err(mstrerr!(
+        Error,
+        "Could not connect to `{}` implementing `{}`",
+        address,
+        interface
+    ))?;
+
+    org_varlink_service::get_interface_description(&connection, interface).map_err(mstrerr!(
+        Error,
+        "Could not get the description of `{}` from `{}`",
+        interface,
+        address
+    ))
+}
+
+/// cargo build helper function
+///
+/// `cargo_build_from_resolver` is used in a `build.rs` program to build the rust code from
+/// the description of `interface`, which the service implementing it returns. The service is
+/// looked up with the varlink resolver at [resolver_address()](fn.resolver_address.html).
+///
+/// The rust code is written to `$OUT_DIR/<interface>.rs`.
+///
+/// Errors are emitted to stderr and terminate the process.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// extern crate varlink_generator;
+///
+/// fn main() {
+///     varlink_generator::cargo_build_from_resolver("org.example.ping");
+/// }
+/// ```
+pub fn cargo_build_from_resolver(interface: &str) {
+    cargo_build_from_resolver_options(
+        interface,
+        &GeneratorOptions {
+            ..Default::default()
+        },
+    )
+}
+
+/// cargo build helper function
+///
+/// Like [cargo_build_from_resolver](fn.cargo_build_from_resolver.html) with `options`.
+pub fn cargo_build_from_resolver_options(interface: &str, options: &GeneratorOptions) {
+    let resolver = resolver_address();
+    let description = description_from_resolver(&resolver, interface).unwrap_or_else(|e| {
+        eprintln!(
+            "Could not get the varlink interface `{}`: {}\n\
+             Set VARLINK_RESOLVER to the address of a running varlink resolver or generate the \
+             code from the varlink file with `cargo_build()`.",
+            interface, e
+        );
+        exit(1);
+    });
+
+    let out_dir: PathBuf = env::var_os("OUT_DIR").unwrap().into();
+    let rust_path = out_dir.join(format!("{}.rs", interface));
+
+    let mut writer = File::create(&rust_path).unwrap_or_else(|e| {
+        eprintln!(
+            "Could not open varlink output file `{}`: {}",
+            rust_path.display(),
+            e
+        );
+        exit(1);
+    });
+
+    if let Err(e) = generate_with_options(&mut description.as_bytes(), &mut writer, options, false)
+    {
+        eprintln!(
+            "Could not generate rust code from varlink interface `{}`: {}",
+            interface, e
+        );
+        exit(1);
+    }
+
+    println!("cargo:rerun-if-env-changed=VARLINK_RESOLVER");
+}
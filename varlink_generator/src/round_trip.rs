@@ -0,0 +1,191 @@
+//! Serialization round-trip tests for the generated types
+
+use proc_macro2::{Ident, Span, TokenStream};
+use quote::quote;
+use std::str::FromStr;
+use varlink_parser::{VStruct, VStructOrEnum, VType, VTypeExt, IDL};
+
+use crate::{to_rust_ident, GeneratorContext};
+
+/// The function returning a sample value of the type `name`.
+fn sample_fn(name: &str) -> Ident {
+    Ident::new(&format!("sample_{}", name), Span::call_site())
+}
+
+/// A sample value of `vtype` for the field `path`.
+///
+/// Optionals, arrays and dicts only contain a value, while `depth` is not `0`, which ends
+/// the samples of recursive types.
+fn sample_value(vtype: &VTypeExt, path: &str, options: &GeneratorContext) -> TokenStream {
+    match *vtype {
+        VTypeExt::Plain(VType::Bool) => quote!(true),
+        VTypeExt::Plain(VType::Int) => quote!(1),
+        VTypeExt::Plain(VType::Float) => quote!(1.5),
+        VTypeExt::Plain(VType::String) => quote!("sample".into()),
+        VTypeExt::Plain(VType::Object) if options.raw_object => {
+            quote!(serde_json::value::RawValue::from_string("{\"sample\":1}".into()).unwrap())
+        }
+        VTypeExt::Plain(VType::Object) => quote!(serde_json::json!({ "sample": 1 })),
+        VTypeExt::Plain(VType::Typename(t)) => {
+            let f = sample_fn(t);
+            quote!(#f(depth))
+        }
+        VTypeExt::Plain(VType::Struct(ref s)) => {
+            sample_struct(path, s, &TokenStream::new(), options)
+        }
+        VTypeExt::Plain(VType::Enum(ref e)) => sample_variant(path, &e.elts),
+        VTypeExt::Array(ref v) => {
+            let value = sample_value(v, path, options);
+            quote!(if depth > 0 {
+                let depth = depth - 1;
+                vec![#value]
+            } else {
+                Vec::new()
+            })
+        }
+        VTypeExt::Dict(ref v) => match *v.as_ref() {
+            VTypeExt::Plain(VType::Struct(ref s)) if s.elts.is_empty() => quote!({
+                let mut set = varlink::StringHashSet::new();
+                set.insert("sample".into());
+                set
+            }),
+            _ => {
+                let value = sample_value(v, path, options);
+                let map_type = TokenStream::from_str(options.map_type()).unwrap();
+                quote!({
+                    let mut map = #map_type::new();
+                    if depth > 0 {
+                        let depth = depth - 1;
+                        map.insert("sample".into(), #value);
+                    }
+                    map
+                })
+            }
+        },
+        VTypeExt::Option(ref v) => {
+            let value = sample_value(v, path, options);
+            let value = if options.boxed_fields.contains(path) {
+                quote!(Box::new(#value))
+            } else {
+                value
+            };
+            quote!(if depth > 0 {
+                let depth = depth - 1;
+                Some(#value)
+            } else {
+                None
+            })
+        }
+    }
+}
+
+/// A sample value of the struct `name`, `extra` initializes the fields not declared in the
+/// interface.
+fn sample_struct(
+    name: &str,
+    vstruct: &VStruct,
+    extra: &TokenStream,
+    options: &GeneratorContext,
+) -> TokenStream {
+    let tname = to_rust_ident(name);
+    let mut fields = Vec::new();
+    let mut values = Vec::new();
+
+    for e in &vstruct.elts {
+        fields.push(to_rust_ident(e.name));
+        values.push(sample_value(
+            &e.vtype,
+            &format!("{}_{}", name, e.name),
+            options,
+        ));
+    }
+
+    quote!(#tname { #(#fields: #values,)* #extra })
+}
+
+fn sample_variant(name: &str, variants: &[&str]) -> TokenStream {
+    let tname = to_rust_ident(name);
+    let variant = to_rust_ident(variants[0]);
+    quote!(#tname::#variant)
+}
+
+/// Generate the module `generated_tests` with a test for each type of `idl`, the `_Args` and
+/// `_Reply` structs of its methods and the `_Args` structs of its errors, which serializes a
+/// sample value and checks, that it deserializes to the same value.
+pub(crate) fn generate_round_trip_tests(idl: &IDL, options: &GeneratorContext) -> TokenStream {
+    let no_extra = TokenStream::new();
+    let method_extra = if options.preserve_unknown_fields {
+        quote!(extra: Default::default(),)
+    } else {
+        TokenStream::new()
+    };
+
+    let mut values = Vec::new();
+    for name in &idl.typedef_keys {
+        let value = match idl.typedefs[name].elt {
+            VStructOrEnum::VStruct(ref s) => sample_struct(name, s, &no_extra, options),
+            VStructOrEnum::VEnum(ref e) => sample_variant(name, &e.elts),
+        };
+        values.push((name.to_string(), value));
+    }
+    for name in &idl.method_keys {
+        let m = &idl.methods[name];
+        for (suffix, vstruct) in &[("Args", &m.input), ("Reply", &m.output)] {
+            let sname = format!("{}_{}", name, suffix);
+            let value = sample_struct(&sname, vstruct, &method_extra, options);
+            values.push((sname, value));
+        }
+    }
+    for name in &idl.error_keys {
+        let sname = format!("{}_Args", name);
+        let value = sample_struct(&sname, &idl.errors[name].parm, &no_extra, options);
+        values.push((sname, value));
+    }
+
+    let mut samples = TokenStream::new();
+    let mut tests = TokenStream::new();
+
+    for (name, value) in values {
+        let tname = to_rust_ident(&name);
+        let f = sample_fn(&name);
+        let test_name = Ident::new(&format!("round_trip_{}", name), Span::call_site());
+
+        samples.extend(quote!(
+            #[allow(unused_variables)]
+            fn #f(depth: usize) -> #tname {
+                #value
+            }
+        ));
+
+        // `RawValue` cannot be compared, so only the JSON is compared with `raw_object`.
+        let compare = if options.raw_object {
+            TokenStream::new()
+        } else {
+            quote!(assert_eq!(back, value);)
+        };
+        tests.extend(quote!(
+            #[test]
+            fn #test_name() {
+                let value = #f(2);
+                let json = serde_json::to_string(&value).unwrap();
+                let back: #tname = serde_json::from_str(&json).unwrap();
+                assert_eq!(serde_json::to_string(&back).unwrap(), json);
+                #compare
+            }
+        ));
+    }
+
+    let module = options.prefixed_snake("generated_tests");
+    let allow_deprecated = &options.allow_deprecated;
+    quote!(
+        #[cfg(test)]
+        #[allow(non_snake_case)]
+        #allow_deprecated
+        mod #module {
+            use super::*;
+
+            #samples
+            #tests
+        }
+    )
+}
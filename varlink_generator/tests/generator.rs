@@ -1,5 +1,289 @@
 extern crate varlink_generator;
 
+use quote::ToTokens;
+use varlink_generator::GeneratorOptions;
+
+/// Generate the code of the interface `description` with `options`, as included from `OUT_DIR`.
+fn try_generate(description: &str, options: &GeneratorOptions) -> Result<String, String> {
+    let mut out = Vec::new();
+    varlink_generator::generate_with_options(&mut description.as_bytes(), &mut out, options, false)
+        .map_err(|e| e.to_string())?;
+    Ok(String::from_utf8(out).unwrap())
+}
+
+/// Like `try_generate()`, for a valid `description`.
+fn generate(description: &str, options: &GeneratorOptions) -> String {
+    try_generate(description, options).unwrap()
+}
+
+/// The generated code parsed with `syn`, to check its items independently of the spacing of
+/// the stringified `TokenStream`.
+struct Code(syn::File);
+
+impl Code {
+    fn parse(code: &str) -> Self {
+        Code(syn::parse_file(code).unwrap_or_else(|e| panic!("{}: {}", e, code)))
+    }
+
+    /// The normalized code, see `norm()`.
+    fn text(&self) -> String {
+        tokens(&self.0)
+    }
+
+    /// The items in the order of the code, including the items of the inline modules.
+    fn items(&self) -> Vec<&syn::Item> {
+        fn walk<'a>(items: &'a [syn::Item], all: &mut Vec<&'a syn::Item>) {
+            for item in items {
+                all.push(item);
+                if let syn::Item::Mod(syn::ItemMod {
+                    content: Some((_, ref items)),
+                    ..
+                }) = *item
+                {
+                    walk(items, all);
+                }
+            }
+        }
+        let mut all = Vec::new();
+        walk(&self.0.items, &mut all);
+        all
+    }
+
+    /// The items `name`.
+    fn named(&self, name: &str) -> Vec<&syn::Item> {
+        self.items()
+            .into_iter()
+            .filter(|item| item_name(item).map_or(false, |n| n == name))
+            .collect()
+    }
+
+    /// The only item `name`.
+    fn item(&self, name: &str) -> &syn::Item {
+        match *self.named(name).as_slice() {
+            [item] => item,
+            ref items => panic!("{} items `{}`: {}", items.len(), name, self.text()),
+        }
+    }
+
+    fn has(&self, name: &str) -> bool {
+        !self.named(name).is_empty()
+    }
+
+    /// The position of the first item `name` in `items()`.
+    fn position(&self, name: &str) -> usize {
+        self.items()
+            .iter()
+            .position(|item| item_name(item).map_or(false, |n| n == name))
+            .unwrap_or_else(|| panic!("no item `{}`", name))
+    }
+
+    /// The normalized attributes of the item `name`.
+    fn attrs(&self, name: &str) -> Vec<String> {
+        item_attrs(self.item(name)).iter().map(tokens).collect()
+    }
+
+    /// The doc comment lines of the item `name`.
+    fn docs(&self, name: &str) -> Vec<String> {
+        docs(item_attrs(self.item(name)))
+    }
+
+    fn struct_fields(&self, name: &str) -> &syn::Fields {
+        match *self.item(name) {
+            syn::Item::Struct(ref s) => &s.fields,
+            ref item => panic!("no struct: {}", tokens(item)),
+        }
+    }
+
+    /// The normalized fields of the struct `name`, without their attributes.
+    fn fields(&self, name: &str) -> Vec<String> {
+        self.struct_fields(name)
+            .iter()
+            .map(|field| {
+                let mut field = field.clone();
+                field.attrs.clear();
+                tokens(&field)
+            })
+            .collect()
+    }
+
+    fn has_field(&self, name: &str, field: &str) -> bool {
+        self.fields(name).contains(&norm(field))
+    }
+
+    /// The normalized attributes of the field `field` of the struct `name`.
+    fn field_attrs(&self, name: &str, field: &str) -> Vec<String> {
+        self.struct_fields(name)
+            .iter()
+            .find(|f| f.ident.as_ref().map_or(false, |i| ident(i) == field))
+            .unwrap_or_else(|| panic!("no field `{}.{}`", name, field))
+            .attrs
+            .iter()
+            .map(tokens)
+            .collect()
+    }
+
+    /// The normalized variants of the enum `name`, with their attributes.
+    fn variants(&self, name: &str) -> Vec<String> {
+        match *self.item(name) {
+            syn::Item::Enum(ref e) => e.variants.iter().map(tokens).collect(),
+            ref item => panic!("no enum: {}", tokens(item)),
+        }
+    }
+
+    /// The `impl` blocks of the trait `trait_`, or the inherent ones for `""`, for `self_ty`.
+    fn impls(&self, trait_: &str, self_ty: &str) -> Vec<&syn::ItemImpl> {
+        self.items()
+            .into_iter()
+            .filter_map(|item| match *item {
+                syn::Item::Impl(ref i) => Some(i),
+                _ => None,
+            })
+            .filter(|i| {
+                let path = i.trait_.as_ref().map_or(String::new(), |t| tokens(&t.1));
+                path == norm(trait_) && tokens(&i.self_ty) == norm(self_ty)
+            })
+            .collect()
+    }
+
+    /// The normalized only `impl` block of `trait_` for `self_ty`, see `impls()`.
+    fn impl_(&self, trait_: &str, self_ty: &str) -> String {
+        match *self.impls(trait_, self_ty).as_slice() {
+            [i] => tokens(i),
+            ref impls => panic!("{} impls `{}` for `{}`", impls.len(), trait_, self_ty),
+        }
+    }
+
+    fn has_impl(&self, trait_: &str, self_ty: &str) -> bool {
+        !self.impls(trait_, self_ty).is_empty()
+    }
+
+    /// The attributes and the body, if any, of the functions and methods `name`.
+    fn fn_parts(&self, name: &str) -> Vec<(&[syn::Attribute], Option<&syn::Block>, String)> {
+        let mut fns = Vec::new();
+        for item in self.items() {
+            match *item {
+                syn::Item::Fn(ref f) if ident(&f.ident) == name => {
+                    fns.push((&f.attrs[..], Some(&*f.block), tokens(f)))
+                }
+                syn::Item::Trait(ref t) => {
+                    for i in &t.items {
+                        if let syn::TraitItem::Method(ref m) = *i {
+                            if ident(&m.sig.ident) == name {
+                                fns.push((&m.attrs[..], m.default.as_ref(), tokens(m)));
+                            }
+                        }
+                    }
+                }
+                syn::Item::Impl(ref t) => {
+                    for i in &t.items {
+                        if let syn::ImplItem::Method(ref m) = *i {
+                            if ident(&m.sig.ident) == name {
+                                fns.push((&m.attrs[..], Some(&m.block), tokens(m)));
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        fns
+    }
+
+    /// The normalized functions and methods `name`, with their attributes.
+    fn fns(&self, name: &str) -> Vec<String> {
+        self.fn_parts(name).into_iter().map(|f| f.2).collect()
+    }
+
+    /// Whether a function or method `name` contains `code`.
+    fn has_fn(&self, name: &str, code: &str) -> bool {
+        self.fns(name).iter().any(|f| f.contains(&norm(code)))
+    }
+
+    /// The doc comment lines of each function or method `name`.
+    fn fn_docs(&self, name: &str) -> Vec<Vec<String>> {
+        self.fn_parts(name).into_iter().map(|f| docs(f.0)).collect()
+    }
+
+    /// The string literals returned by the functions and methods `name`.
+    fn returned_strs(&self, name: &str) -> Vec<String> {
+        self.fn_parts(name)
+            .into_iter()
+            .filter_map(|f| match f.1.map(|b| b.stmts.as_slice()) {
+                Some(
+                    [syn::Stmt::Expr(syn::Expr::Lit(syn::ExprLit {
+                        lit: syn::Lit::Str(ref s),
+                        ..
+                    }))],
+                ) => Some(s.value()),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// `code` without whitespace and raw identifier prefixes, to compare it independently of its
+/// spacing.
+fn norm(code: &str) -> String {
+    code.replace("r#", "").split_whitespace().collect()
+}
+
+fn norm_all(code: &[&str]) -> Vec<String> {
+    code.iter().map(|c| norm(c)).collect()
+}
+
+/// The normalized code of `t`, see `norm()`.
+fn tokens<T: ToTokens>(t: &T) -> String {
+    norm(&t.into_token_stream().to_string())
+}
+
+/// `i` without its raw identifier prefix.
+fn ident(i: &syn::Ident) -> String {
+    i.to_string().trim_start_matches("r#").to_string()
+}
+
+fn item_name(item: &syn::Item) -> Option<String> {
+    match *item {
+        syn::Item::Struct(ref i) => Some(ident(&i.ident)),
+        syn::Item::Enum(ref i) => Some(ident(&i.ident)),
+        syn::Item::Trait(ref i) => Some(ident(&i.ident)),
+        syn::Item::Fn(ref i) => Some(ident(&i.ident)),
+        syn::Item::Type(ref i) => Some(ident(&i.ident)),
+        syn::Item::Mod(ref i) => Some(ident(&i.ident)),
+        syn::Item::Const(ref i) => Some(ident(&i.ident)),
+        _ => None,
+    }
+}
+
+fn item_attrs(item: &syn::Item) -> &[syn::Attribute] {
+    match *item {
+        syn::Item::Struct(ref i) => &i.attrs,
+        syn::Item::Enum(ref i) => &i.attrs,
+        syn::Item::Trait(ref i) => &i.attrs,
+        syn::Item::Fn(ref i) => &i.attrs,
+        syn::Item::Type(ref i) => &i.attrs,
+        syn::Item::Mod(ref i) => &i.attrs,
+        syn::Item::Const(ref i) => &i.attrs,
+        syn::Item::Impl(ref i) => &i.attrs,
+        syn::Item::Use(ref i) => &i.attrs,
+        _ => &[],
+    }
+}
+
+/// The lines of the `#[doc]` attributes in `attrs`.
+fn docs(attrs: &[syn::Attribute]) -> Vec<String> {
+    attrs
+        .iter()
+        .filter_map(|attr| match attr.parse_meta() {
+            Ok(syn::Meta::NameValue(syn::MetaNameValue {
+                ref ident,
+                lit: syn::Lit::Str(ref s),
+                ..
+            })) if ident == "doc" => Some(s.value()),
+            _ => None,
+        })
+        .collect()
+}
+
 #[cfg(unix)]
 #[test]
 fn test_generate() {
@@ -39,3 +323,1300 @@ fn test_generate() {
     }
     let _ = std::fs::remove_file(path);
 }
+
+#[test]
+fn test_changelog() {
+    let mut old = "interface org.example.changelog
+
+type State (a: int)
+
+method Ping(ping: string) -> (pong: string)
+
+error Failed ()
+"
+    .as_bytes();
+
+    let mut new = "interface org.example.changelog
+
+type State (a: int, b: string)
+
+method Ping(ping: string) -> (pong: string)
+
+method Reset(hard: bool) -> ()
+"
+    .as_bytes();
+
+    let mut out = Vec::new();
+    varlink_generator::generate_changelog(&mut old, &mut new, &mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+
+    assert!(
+        out.starts_with("interface org.example.changelog: 1 added, 1 removed, 1 changed\n"),
+        "{}",
+        out
+    );
+    assert!(
+        out.contains(
+            "Added:\n  method Reset(hard: bool) -> ()  \
+             [fn reset, struct Reset_Args, struct Reset_Reply]\n"
+        ),
+        "{}",
+        out
+    );
+    assert!(
+        out.contains("Removed:\n  error Failed ()  [ErrorKind::Failed, struct Failed_Args]\n"),
+        "{}",
+        out
+    );
+    assert!(
+        out.contains("Changed:\n  type State\n    - (a: int)\n    + (a: int, b: string)\n"),
+        "{}",
+        out
+    );
+    assert!(!out.contains("Ping"), "{}", out);
+}
+
+#[test]
+fn test_recursive_types() {
+    let e = try_generate(
+        "interface org.example.recursive\ntype Node (value: int, next: Node)\n",
+        &GeneratorOptions::default(),
+    )
+    .unwrap_err();
+    assert!(e.contains("Node -> Node"), "{}", e);
+
+    let e = try_generate(
+        "interface org.example.recursive\ntype A (b: (c: B))\ntype B (a: A)\n",
+        &GeneratorOptions::default(),
+    )
+    .unwrap_err();
+    assert!(e.contains("A -> B -> A"), "{}", e);
+
+    // Optional fields in a recursion are boxed, other optional fields are not.
+    let code = Code::parse(&generate(
+        "interface org.example.recursive\n\
+         type Node (next: ?Node, children: []Node, dict: [string]Node)\n\
+         type Tree (root: ?Node)\n",
+        &GeneratorOptions::default(),
+    ));
+    assert_eq!(
+        code.fields("Node"),
+        norm_all(&[
+            "pub next: Option<Box<Node>>",
+            "pub children: Vec<Node>",
+            "pub dict: varlink::StringHashMap<Node>",
+        ])
+    );
+    assert_eq!(code.fields("Tree"), norm_all(&["pub root: Option<Node>"]));
+}
+
+#[test]
+fn test_default_values() {
+    let code = Code::parse(&generate(
+        "interface org.example.defaults\ntype T (a: int = 5, b: ?string)\n",
+        &GeneratorOptions::default(),
+    ));
+    let default = code.impl_("Default", "T");
+    assert!(default.contains(&norm("a: 5,")), "{}", default);
+    assert!(
+        default.contains(&norm("b: Default::default(),")),
+        "{}",
+        default
+    );
+
+    // Without declared defaults, no Default implementation is generated.
+    let code = Code::parse(&generate(
+        "interface org.example.defaults\ntype T (a: int, b: ?string)\n",
+        &GeneratorOptions::default(),
+    ));
+    assert!(!code.has_impl("Default", "T"));
+
+    let e = try_generate(
+        "interface org.example.defaults\ntype T (a: (b: bool = 1))\n",
+        &GeneratorOptions::default(),
+    )
+    .unwrap_err();
+    assert!(
+        e.contains("Default value `1` does not match the type of field 'T.a.b'"),
+        "{}",
+        e
+    );
+
+    // The other fields of a struct with a declared default need a `Default`.
+    for (description, field) in &[
+        ("type T (a: int = 1, e: (x, y))", "T.e"),
+        ("type T (a: int = 1, s: (b: bool))", "T.s"),
+        ("type T (a: int = 1, u: U)\ntype U (b: bool)", "T.u"),
+        ("type T (a: (b: int = 1, u: U))\ntype U (b: bool)", "T.a.u"),
+    ] {
+        let e = try_generate(
+            &format!("interface org.example.defaults\n{}\n", description),
+            &GeneratorOptions::default(),
+        )
+        .unwrap_err();
+        assert!(
+            e.contains(&format!(
+                "Field '{}' needs a default value, because its type has no `Default`",
+                field
+            )),
+            "{}",
+            e
+        );
+    }
+    let code = Code::parse(&generate(
+        "interface org.example.defaults\ntype T (a: int = 1, e: ?(x, y), u: []U)\ntype U (b: bool)\n",
+        &GeneratorOptions::default(),
+    ));
+    let default = code.impl_("Default", "T");
+    assert!(
+        default.contains(&norm("e: Default::default(),")),
+        "{}",
+        default
+    );
+
+    // A type with a declared default has a `Default` itself.
+    let code = Code::parse(&generate(
+        "interface org.example.defaults
+type T (a: int = 1, u: U)
+type U (b: bool = true)
+",
+        &GeneratorOptions::default(),
+    ));
+    let default = code.impl_("Default", "T");
+    assert!(
+        default.contains(&norm("u: Default::default(),")),
+        "{}",
+        default
+    );
+}
+
+#[test]
+fn test_structural_conversions() {
+    let mut v1 = "interface org.example.v1\n\
+                  type Same (a: int, b: ?[]string)\n\
+                  type Ref (same: Same)\n\
+                  type Changed (a: int)\n"
+        .as_bytes();
+    let mut v2 = "interface org.example.v2\n\
+                  type Same (a: int, b: ?[]string)\n\
+                  type Ref (same: Same)\n\
+                  type Changed (a: string)\n"
+        .as_bytes();
+    let mut out1 = Vec::new();
+    let mut out2 = Vec::new();
+
+    varlink_generator::generate_many_with_options(
+        &mut [&mut v1, &mut v2],
+        &mut [&mut out1, &mut out2],
+        &GeneratorOptions {
+            structural_conversions: true,
+            ..Default::default()
+        },
+        false,
+    )
+    .unwrap();
+
+    let code1 = Code::parse(&String::from_utf8(out1).unwrap());
+    let code2 = Code::parse(&String::from_utf8(out2).unwrap());
+    for t in &["Same", "Ref", "Changed"] {
+        let convertible = *t != "Changed";
+        assert_eq!(
+            code1.has_impl(&format!("From<super::org_example_v2::{}>", t), t),
+            convertible,
+            "{}",
+            t
+        );
+        assert_eq!(
+            code2.has_impl(&format!("From<super::org_example_v1::{}>", t), t),
+            convertible,
+            "{}",
+            t
+        );
+    }
+}
+
+#[test]
+fn test_newtype_fields() {
+    let description = "interface org.example.ids\n\
+                       type T (id: string, ids: ?[]string, other: string, anon: (id: [string]string))\n\
+                       method M(id: string, n: int) -> (id: ?string)\n\
+                       error E (id: string)\n";
+    let code = Code::parse(&generate(
+        description,
+        &GeneratorOptions {
+            newtype_fields: Some(vec![("id", "Id"), ("ids", "Id")]),
+            ..Default::default()
+        },
+    ));
+    assert_eq!(code.fields("Id"), norm_all(&["pub String"]));
+    for (name, field) in &[
+        ("T", "pub id: Id"),
+        ("T", "pub ids: Option<Vec<Id>>"),
+        ("T", "pub other: String"),
+        ("T_anon", "pub id: varlink::StringHashMap<Id>"),
+        ("M_Args", "pub id: Id"),
+        ("M_Reply", "pub id: Option<Id>"),
+        ("E_Args", "pub id: Id"),
+    ] {
+        assert!(code.has_field(name, field), "{}: {}", name, field);
+    }
+    assert!(code.has_fn("m", "fn m(&mut self, id: Id, n: i64)"));
+    assert!(code.has_fn("reply_e", "fn reply_e(&mut self, id: Id)"));
+}
+
+#[test]
+fn test_emit_tests() {
+    let description = "interface org.example.tests\n\
+                       type MyType (self: ?MyType, enum: (type, crate))\n\
+                       method M() -> ()\n\
+                       method Get(type: MyType, filter: ?(name: string)) -> (ids: []int)\n\
+                       error NotFound (id: int)\n";
+    let code = Code::parse(&generate(
+        description,
+        &GeneratorOptions {
+            emit_tests: true,
+            ..Default::default()
+        },
+    ));
+    assert_eq!(
+        code.attrs("generated_tests"),
+        norm_all(&["#[cfg(test)]", "#[allow(non_snake_case)]"])
+    );
+    for name in &[
+        "MyType",
+        "M_Args",
+        "M_Reply",
+        "Get_Args",
+        "Get_Reply",
+        "NotFound_Args",
+    ] {
+        assert!(code.has(&format!("round_trip_{}", name)), "{}", name);
+    }
+    assert!(code.has_fn(
+        "sample_MyType",
+        "self_: if depth > 0 { let depth = depth - 1; Some(Box::new(sample_MyType(depth))) }"
+    ));
+    assert!(code.has_fn("sample_MyType", "enum: MyType_enum::type,"));
+    assert!(code.has_fn(
+        "sample_Get_Args",
+        "filter: if depth > 0 { let depth = depth - 1; \
+         Some(Get_Args_filter { name: \"sample\".into(), }) } else { None },"
+    ));
+    assert!(code.has_fn("sample_Get_Args", "type: sample_MyType(depth),"));
+}
+
+#[test]
+fn test_description_normalized() {
+    let description = "\u{feff}# A service  \r\n\
+                       interface org.example.crlf\r\n\
+                       \r\n\
+                       method Ping(ping: string) -> (pong: string)\t\r\n\
+                       \r\n";
+    let code = Code::parse(&generate(description, &GeneratorOptions::default()));
+    assert_eq!(
+        code.returned_strs("get_description"),
+        ["# A service\ninterface org.example.crlf\n\nmethod Ping(ping: string) -> (pong: string)\n"]
+    );
+}
+
+#[test]
+fn test_integer_enums() {
+    let description = "interface org.example.enums\n\
+                       type Level (low, mid, high)\n\
+                       method M() -> ()\n";
+    let code = Code::parse(&generate(
+        description,
+        &GeneratorOptions {
+            integer_enums: true,
+            enum_discriminants: Some(vec![("Level.mid", 10)]),
+            ..Default::default()
+        },
+    ));
+    assert!(code.attrs("Level").contains(&norm("#[repr(i64)]")));
+    assert_eq!(
+        code.variants("Level"),
+        norm_all(&["low", "mid = 10i64", "high"])
+    );
+    assert!(code.has_fn(
+        "from_i64",
+        "0i64 => Some(Level::low), 10i64 => Some(Level::mid), 11i64 => Some(Level::high),"
+    ));
+}
+
+#[test]
+fn test_null_fields() {
+    let description = "interface org.example.null\n\
+                       method Get() -> (absent: ?string, null: ?string)\n\
+                       error NotFound (absent: ?string, null: ?string)\n";
+    let code = Code::parse(&generate(
+        description,
+        &GeneratorOptions {
+            null_fields: Some(vec!["Get_Reply.null", "NotFound_Args.null"]),
+            ..Default::default()
+        },
+    ));
+    for name in &["Get_Reply", "NotFound_Args"] {
+        assert_eq!(
+            code.fields(name),
+            norm_all(&["pub absent: Option<String>", "pub null: Option<String>"])
+        );
+        assert_eq!(
+            code.field_attrs(name, "absent"),
+            norm_all(&["#[serde(skip_serializing_if = \"Option::is_none\")]"])
+        );
+        assert!(code.field_attrs(name, "null").is_empty(), "{}", name);
+    }
+}
+
+#[test]
+fn test_reply_bridge() {
+    let description = "interface org.example.bridge\n\
+                       method M() -> ()\n\
+                       error Failed (reason: string)\n";
+    let code = Code::parse(&generate(
+        description,
+        &GeneratorOptions {
+            reply_bridge: true,
+            ..Default::default()
+        },
+    ));
+    let bridge = code.impl_("varlink::VarlinkErrorReply", "ErrorKind");
+    assert!(
+        bridge.contains(&norm(
+            "ErrorKind::Failed(v) => Some(varlink::Reply::error(\"org.example.bridge.Failed\","
+        )),
+        "{}",
+        bridge
+    );
+}
+
+#[test]
+fn test_ordered_maps() {
+    let description = "interface org.example.maps\n\
+                       type Labels (labels: [string]string, set: [string]())\n\
+                       method M() -> ()\n";
+    let code = Code::parse(&generate(
+        description,
+        &GeneratorOptions {
+            ordered_maps: true,
+            ..Default::default()
+        },
+    ));
+    assert_eq!(
+        code.fields("Labels"),
+        norm_all(&[
+            "pub labels: varlink::StringBTreeMap<String>",
+            "pub set: varlink::StringHashSet",
+        ])
+    );
+}
+
+#[test]
+fn test_doc_comments() {
+    let description = "\
+# The hostname interface
+interface org.example.doc
+
+# A host
+#
+# with a nested address
+type Host (name: string, address: (ip: string))
+
+# The kind of a host
+type Kind (physical, virtual)
+
+# Returns the current hostname
+method GetHostname() -> (hostname: string)
+
+# No hostname is set
+error NoHostname ()
+";
+    let code = Code::parse(&generate(description, &GeneratorOptions::default()));
+    // the client and the server method
+    assert_eq!(
+        code.fn_docs("get_hostname")
+            .iter()
+            .filter(|docs| *docs == &[" Returns the current hostname"])
+            .count(),
+        2
+    );
+    assert_eq!(code.docs("Host"), [" A host", "", " with a nested address"]);
+    assert_eq!(code.docs("Kind"), [" The kind of a host"]);
+    assert!(code.variants("ErrorKind").contains(&norm(
+        "#[doc = \" No hostname is set\"] NoHostname(Option<NoHostname_Args>)"
+    )));
+}
+
+#[test]
+fn test_extra_attributes() {
+    let description = "interface org.example.attrs\n\
+                       type Key (name: string, nested: (id: int))\n\
+                       method Get(key: Key) -> (value: string)\n\
+                       error NotFound (key: Key)\n";
+    let out = generate(
+        description,
+        &GeneratorOptions {
+            type_attributes: Some("#[derive(Eq, Hash)]".parse().unwrap()),
+            method_attributes: Some(
+                "#[cfg_attr(feature = \"schema\", derive(JsonSchema))]"
+                    .parse()
+                    .unwrap(),
+            ),
+            error_attributes: Some("#[derive(Eq)]".parse().unwrap()),
+            ..Default::default()
+        },
+    );
+    let code = Code::parse(&out);
+    let derives = "#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]";
+    let schema = "#[cfg_attr(feature = \"schema\", derive(JsonSchema))]";
+    for (name, attr) in &[
+        ("Key_nested", "#[derive(Eq, Hash)]"),
+        ("Key", "#[derive(Eq, Hash)]"),
+        ("Get_Reply", schema),
+        ("Get_Args", schema),
+        ("NotFound_Args", "#[derive(Eq)]"),
+    ] {
+        assert!(
+            code.attrs(name).ends_with(&norm_all(&[derives, attr])),
+            "{}: {:?}",
+            name,
+            code.attrs(name)
+        );
+    }
+}
+
+#[test]
+fn test_generate_str() {
+    let source = "interface org.example.ping\nmethod Ping(ping: string) -> (pong: string)\n";
+    let code = varlink_generator::generate_str(source).unwrap();
+
+    let mut out = Vec::new();
+    varlink_generator::generate(&mut source.as_bytes(), &mut out, true).unwrap();
+    assert_eq!(code, String::from_utf8(out).unwrap());
+    let header = &Code::parse(&code).0.attrs;
+    assert!(tokens(&header[0]).starts_with("#![doc="), "{}", code);
+
+    assert!(varlink_generator::generate_str("interface org.example.ping\n").is_err());
+}
+
+#[test]
+fn test_multiple_interfaces() {
+    let code = varlink_generator::generate_str(
+        "interface org.example.one\n\
+         method F() -> ()\n\
+         interface org.example.two-b\n\
+         method G() -> ()\n",
+    )
+    .unwrap();
+
+    let code = Code::parse(&code);
+    let modules: Vec<String> = code
+        .0
+        .items
+        .iter()
+        .map(|item| match *item {
+            syn::Item::Mod(ref m) => ident(&m.ident),
+            ref item => panic!("no module: {}", tokens(item)),
+        })
+        .collect();
+    assert_eq!(modules, ["org_example_one", "org_example_two_b"]);
+    assert_eq!(code.named("VarlinkClient").len(), 2);
+    assert!(code
+        .returned_strs("get_description")
+        .contains(&"interface org.example.two-b\nmethod G() -> ()\n".to_string()));
+}
+
+#[test]
+fn test_snake_case_method_names() {
+    let code = varlink_generator::generate_str(
+        "interface org.example.names\n\
+         method GetURL() -> ()\n\
+         method HTTPSProxy() -> ()\n\
+         method HTTP2Proxy() -> ()\n\
+         method OAuth2Token() -> ()\n\
+         method Test01() -> ()\n",
+    )
+    .unwrap();
+
+    let code = Code::parse(&code);
+    for name in &[
+        "get_url",
+        "https_proxy",
+        "http2_proxy",
+        "o_auth2_token",
+        "test01",
+    ] {
+        assert!(
+            code.has_fn(name, &format!("fn {}(&mut self,)", name)),
+            "{}",
+            name
+        );
+    }
+}
+
+#[test]
+fn test_default_args() {
+    let description = "interface org.example.args\n\
+                       type Kind (a, b)\n\
+                       method List(filter: ?string, limit: int, tags: []string) -> ()\n\
+                       method Get(kind: Kind) -> ()\n";
+    let code = Code::parse(&generate(
+        description,
+        &GeneratorOptions {
+            default_args: true,
+            ..Default::default()
+        },
+    ));
+    assert_eq!(
+        code.impl_("Default", "List_Args"),
+        norm(
+            "impl Default for List_Args { fn default() -> Self { List_Args { \
+             filter: Default::default(), limit: Default::default(), \
+             tags: Default::default(), } } }"
+        )
+    );
+    // `Kind` has no `Default`
+    assert!(!code.has_impl("Default", "Get_Args"));
+    assert!(!code.has_impl("Default", "List_Reply"));
+
+    let code = Code::parse(&generate(description, &GeneratorOptions::default()));
+    for name in &["List_Args", "List_Reply", "Get_Args", "Get_Reply"] {
+        assert!(!code.has_impl("Default", name), "{}", name);
+    }
+}
+
+#[test]
+fn test_nested_dicts() {
+    let code = varlink_generator::generate_str(
+        "interface org.example.dicts\n\
+         type T (\n\
+         dd: [string][string]string,\n\
+         da: [string][]string,\n\
+         ds: [string][string](),\n\
+         dos: [string]?[string](),\n\
+         ad: [][string]string,\n\
+         s: [string]()\n\
+         )\n",
+    )
+    .unwrap();
+
+    assert_eq!(
+        Code::parse(&code).fields("T"),
+        norm_all(&[
+            "pub dd: varlink::StringHashMap<varlink::StringHashMap<String>>",
+            "pub da: varlink::StringHashMap<Vec<String>>",
+            "pub ds: varlink::StringHashMap<varlink::StringHashSet>",
+            "pub dos: varlink::StringHashMap<Option<varlink::StringHashSet>>",
+            "pub ad: Vec<varlink::StringHashMap<String>>",
+            "pub s: varlink::StringHashSet",
+        ])
+    );
+}
+
+#[test]
+fn test_non_exhaustive_errors() {
+    let description = "interface org.example.errors\n\
+                       method F() -> ()\n\
+                       error Failed ()\n";
+    let code = Code::parse(&generate(
+        description,
+        &GeneratorOptions {
+            non_exhaustive_errors: true,
+            ..Default::default()
+        },
+    ));
+    assert!(code.attrs("ErrorKind").ends_with(&norm_all(&[
+        "#[derive(Clone, PartialEq, Debug)]",
+        "#[non_exhaustive]"
+    ])));
+    // the Display impl inside the crate still matches all variants
+    let display = code.impl_("::std::fmt::Display", "ErrorKind");
+    assert!(
+        display.ends_with(&norm(
+            "ErrorKind::Failed(v) => write!(f, \"org.example.errors.Failed: {:#?}\", v) } } }"
+        )),
+        "{}",
+        display
+    );
+}
+
+#[test]
+fn test_async_client() {
+    let description = "interface org.example.ping\n\
+                       method Ping(ping: string) -> (pong: string)\n\
+                       method Reset() -> ()\n";
+    let code = Code::parse(&generate(
+        description,
+        &GeneratorOptions {
+            async_client: true,
+            ..Default::default()
+        },
+    ));
+    assert_eq!(
+        code.fields("AsyncVarlinkClient"),
+        norm_all(&["connection: Arc<varlink::AsyncConnection>"])
+    );
+    assert!(code.has_fn(
+        "ping",
+        "pub async fn ping(&self, ping: String) -> Result<Ping_Reply> { \
+         self.connection.call(\"org.example.ping.Ping\", Ping_Args { ping }).await }"
+    ));
+    assert!(code.has_fn(
+        "reset",
+        "pub async fn reset(&self,) -> Result<Reset_Reply> {"
+    ));
+
+    let description = "interface org.example.ping\n\
+                       method Ping(ping: string) -> (pong: string)\n";
+    let code = Code::parse(&generate(description, &GeneratorOptions::default()));
+    assert!(!code.has("AsyncVarlinkClient"));
+}
+
+#[test]
+fn test_sync_client_methods() {
+    let description = "interface org.example.ping\n\
+                       method Ping(ping: string) -> (pong: string)\n\
+                       method Reset() -> ()\n";
+    let code = Code::parse(&generate(
+        description,
+        &GeneratorOptions {
+            sync_methods: true,
+            ..Default::default()
+        },
+    ));
+
+    assert!(code.has_fn(
+        "ping_sync",
+        "fn ping_sync(&mut self, ping: String) -> Result<Ping_Reply> { self.ping(ping).call() }"
+    ));
+    assert!(code.has_fn(
+        "reset_sync",
+        "fn reset_sync(&mut self,) -> Result<Reset_Reply> { self.reset().call() }"
+    ));
+
+    let code = Code::parse(&generate(description, &GeneratorOptions::default()));
+    assert!(code.fns("ping_sync").is_empty());
+    assert!(code.fns("reset_sync").is_empty());
+}
+
+#[test]
+fn test_name_collisions() {
+    let e = try_generate(
+        "interface org.example.collision\n\
+         type Ping (Reply: (a: int))\n\
+         method Ping() -> ()\n",
+        &GeneratorOptions::default(),
+    )
+    .unwrap_err();
+    assert!(
+        e.contains(
+            "The name `Ping_Reply` is generated for the type `Ping` and for the method `Ping`"
+        ),
+        "{}",
+        e
+    );
+
+    let e = try_generate(
+        "interface org.example.collision\n\
+         method Failed() -> ()\n\
+         error Failed ()\n",
+        &GeneratorOptions::default(),
+    )
+    .unwrap_err();
+    assert!(
+        e.contains("`Failed_Args` is generated for the method `Failed` and for the error `Failed`"),
+        "{}",
+        e
+    );
+
+    let e = try_generate(
+        "interface org.example.collision\ntype Result (a: int)\n",
+        &GeneratorOptions::default(),
+    )
+    .unwrap_err();
+    assert!(
+        e.contains("`Result` is generated for the generated `Result`"),
+        "{}",
+        e
+    );
+
+    // the helper functions of `Ping` are the functions of `PingSync` and `PingMore`
+    let e = try_generate(
+        "interface org.example.collision\n\
+         method Ping() -> ()\n\
+         method PingSync() -> ()\n",
+        &GeneratorOptions {
+            sync_methods: true,
+            ..Default::default()
+        },
+    )
+    .unwrap_err();
+    assert!(
+        e.contains(
+            "The client function `ping_sync` is generated for the method `Ping` and for the method \
+             `PingSync`"
+        ),
+        "{}",
+        e
+    );
+    let e = try_generate(
+        "interface org.example.collision\n\
+         method PingMore() -> ()\n\
+         method Ping() -> ()\n",
+        &GeneratorOptions {
+            more_methods: Some(vec!["Ping"]),
+            ..Default::default()
+        },
+    )
+    .unwrap_err();
+    assert!(
+        e.contains("The client function `ping_more` is generated for"),
+        "{}",
+        e
+    );
+
+    let e = try_generate(
+        "interface org.example.collision\n\
+         error Failed (a: int)\n\
+         error FailedStruct ()\n",
+        &GeneratorOptions::default(),
+    )
+    .unwrap_err();
+    assert!(
+        e.contains("The function `reply_failed_struct` is generated for"),
+        "{}",
+        e
+    );
+
+    // a set is no anonymous struct
+    generate(
+        "interface org.example.collision\n\
+         type Ping (Reply: [string]())\n\
+         method Ping() -> ()\n",
+        &GeneratorOptions::default(),
+    );
+}
+
+#[test]
+fn test_keyword_variants() {
+    let code = varlink_generator::generate_str(
+        "interface org.example.keywords\n\
+         type Verbs (self, type, as, move)\n",
+    )
+    .unwrap();
+
+    // the rename is an attribute of the variant it belongs to
+    assert_eq!(
+        Code::parse(&code).variants("Verbs"),
+        norm_all(&["#[serde(rename = \"self\")] self_", "type", "as", "move"])
+    );
+}
+
+#[test]
+fn test_nested_optionals() {
+    let code = varlink_generator::generate_str(
+        "interface org.example.optionals\n\
+         type T (\n\
+         ao: []?string,\n\
+         oa: ?[]string,\n\
+         oao: ?[]?string,\n\
+         aa: [][]string,\n\
+         oas: ?[](first: int, second: string)\n\
+         )\n",
+    )
+    .unwrap();
+
+    let code = Code::parse(&code);
+    assert_eq!(
+        code.fields("T"),
+        norm_all(&[
+            "pub ao: Vec<Option<String>>",
+            "pub oa: Option<Vec<String>>",
+            "pub oao: Option<Vec<Option<String>>>",
+            "pub aa: Vec<Vec<String>>",
+            "pub oas: Option<Vec<T_oas>>",
+        ])
+    );
+    assert_eq!(
+        code.fields("T_oas"),
+        norm_all(&["pub first: i64", "pub second: String"])
+    );
+}
+
+#[test]
+fn test_try_cargo_build() {
+    let dir = std::env::temp_dir().join("varlink_generator_test_try_cargo_build");
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let missing = dir.join("org.example.missing.varlink");
+    let e = varlink_generator::try_cargo_build_tosource(&missing, false).unwrap_err();
+    assert!(
+        e.to_string().starts_with("Could not read varlink input"),
+        "{}",
+        e
+    );
+
+    let invalid = dir.join("org.example.invalid.varlink");
+    std::fs::write(&invalid, "interface org.example.invalid\nmethod Foo(\n").unwrap();
+    let e = varlink_generator::try_cargo_build_tosource(&invalid, false).unwrap_err();
+    assert!(
+        e.to_string().starts_with("Could not generate rust code"),
+        "{}",
+        e
+    );
+
+    let input = dir.join("org.example.complex.varlink");
+    std::fs::copy("tests/org.example.complex.varlink", &input).unwrap();
+    let path = varlink_generator::try_cargo_build_tosource(&input, false).unwrap();
+    assert_eq!(path, dir.join("org_example_complex.rs"));
+    assert!(path.exists());
+
+    std::env::set_var("OUT_DIR", std::env::temp_dir());
+    let e = varlink_generator::try_cargo_build(&missing).unwrap_err();
+    assert!(
+        e.to_string().starts_with("Could not read varlink input"),
+        "{}",
+        e
+    );
+    let path = varlink_generator::try_cargo_build(&input).unwrap();
+    assert_eq!(path, std::env::temp_dir().join("org.example.complex.rs"));
+    assert!(path.exists());
+}
+
+#[test]
+fn test_generate_stable_order() {
+    let source = "interface org.example.order
+type Zeta (z: string)
+type Alpha (a: Zeta)
+error Zulu ()
+error AlphaError (a: Alpha)
+method Zap() -> (z: Zeta)
+method Add(a: Alpha) -> ()
+";
+    let code = varlink_generator::generate_str(source).unwrap();
+    assert_eq!(code, varlink_generator::generate_str(source).unwrap());
+
+    let reordered = "interface org.example.order
+method Add(a: Alpha) -> ()
+error AlphaError (a: Alpha)
+type Alpha (a: Zeta)
+method Zap() -> (z: Zeta)
+error Zulu ()
+type Zeta (z: string)
+";
+    let code = varlink_generator::generate_str(reordered).unwrap();
+    assert_eq!(code, varlink_generator::generate_str(reordered).unwrap());
+
+    // emitted ordered by name, not by declaration
+    let code = Code::parse(&code);
+    assert!(code.position("Alpha") < code.position("Zeta"));
+    assert!(code.position("AlphaError_Args") < code.position("Zulu_Args"));
+    assert!(code.position("Add_Reply") < code.position("Zap_Reply"));
+}
+
+#[test]
+fn test_default_fields() {
+    let description = "interface org.example.skew\n\
+                       type Item (name: string, count: int = 1)\n\
+                       type Plain (name: string)\n\
+                       method Get() -> (item: Item, plain: Plain, total: int, note: ?string)\n\
+                       error NotFound (name: string)\n";
+    let code = Code::parse(&generate(
+        description,
+        &GeneratorOptions {
+            default_fields: true,
+            ..Default::default()
+        },
+    ));
+    let default = norm("#[serde(default)]");
+    let skip_none = norm("#[serde(skip_serializing_if = \"Option::is_none\")]");
+    for (name, field, attrs) in &[
+        ("Item", "name", vec![default.clone()]),
+        ("Item", "count", vec![]),
+        ("Get_Reply", "item", vec![default.clone()]),
+        ("Get_Reply", "plain", vec![]),
+        ("Get_Reply", "total", vec![default.clone()]),
+        ("Get_Reply", "note", vec![default.clone(), skip_none]),
+        ("NotFound_Args", "name", vec![default]),
+    ] {
+        assert_eq!(&code.field_attrs(name, field), attrs, "{}.{}", name, field);
+    }
+}
+
+#[test]
+fn test_dispatch_table() {
+    let description = "interface org.example.dispatch\n\
+                       method Zeta() -> ()\n\
+                       method Alpha(a: int) -> ()\n\
+                       method Mid() -> ()\n";
+    let code = Code::parse(&generate(description, &GeneratorOptions::default()));
+    let proxy = code.impl_("", "VarlinkInterfaceProxy");
+    assert!(
+        proxy.contains(&norm(
+            "const METHODS: &'static [(&'static str, fn(&VarlinkInterfaceProxy, \
+             &mut varlink::Call) -> varlink::Result<()>)] = &[\
+             (\"org.example.dispatch.Alpha\", VarlinkInterfaceProxy::dispatch_alpha), \
+             (\"org.example.dispatch.Mid\", VarlinkInterfaceProxy::dispatch_mid), \
+             (\"org.example.dispatch.Zeta\", VarlinkInterfaceProxy::dispatch_zeta),];"
+        )),
+        "{}",
+        proxy
+    );
+    assert!(code.has_fn(
+        "dispatch_mid",
+        "fn dispatch_mid(&self, call: &mut varlink::Call) -> varlink::Result<()> { \
+         self.inner.mid(call as &mut dyn Call_Mid) }"
+    ));
+    assert!(code.has_fn(
+        "dispatch_alpha",
+        "let args: Alpha_Args = match req.parameters_as() { Some(Ok(v)) => v,"
+    ));
+    assert!(!code.text().contains("parameters.clone()"));
+    assert!(code.has_fn(
+        "call",
+        "Err(_) => call.reply_method_not_found(req.method.to_string()),"
+    ));
+}
+
+#[test]
+fn test_reply_try_from() {
+    let description = "interface org.example.ping\n\
+                       method Ping(ping: string) -> (pong: string)\n";
+    let try_from = "::std::convert::TryFrom<serde_json::Value>";
+    let code = Code::parse(&generate(
+        description,
+        &GeneratorOptions {
+            reply_try_from: true,
+            ..Default::default()
+        },
+    ));
+    assert_eq!(
+        code.impl_(try_from, "Ping_Reply"),
+        norm(
+            "impl ::std::convert::TryFrom<serde_json::Value> for Ping_Reply { \
+             type Error = ChainError<Error>; \
+             fn try_from(v: serde_json::Value) -> Result<Self> { \
+             serde_json::from_value(v).map_err(minto_cherr!()) } }"
+        )
+    );
+    assert!(!code.has_impl(try_from, "Ping_Args"));
+
+    let code = Code::parse(&generate(description, &GeneratorOptions::default()));
+    assert!(!code.has_impl(try_from, "Ping_Reply"));
+}
+
+#[test]
+fn test_deprecated_methods() {
+    let description = "interface org.example.ping\n\
+                       # @deprecated use Pong instead\n\
+                       method Ping(ping: string) -> (pong: string)\n\
+                       # Replaces Ping\n\
+                       # @deprecated\n\
+                       method Pung() -> ()\n\
+                       # not @deprecated\n\
+                       method Pong(ping: string) -> (pong: string)\n";
+    let code = Code::parse(&generate(
+        description,
+        &GeneratorOptions {
+            sync_methods: true,
+            ..Default::default()
+        },
+    ));
+    let ping = "#[deprecated(note = \"use Pong instead\")]";
+    assert!(code.has_fn(
+        "ping",
+        &format!(
+            "{} fn ping(&mut self, ping: String) -> \
+             varlink::MethodCall<Ping_Args, Ping_Reply, Error>;",
+            ping
+        )
+    ));
+    assert!(code.has_fn(
+        "ping_sync",
+        &format!(
+            "{} fn ping_sync(&mut self, ping: String) -> Result<Ping_Reply>",
+            ping
+        )
+    ));
+    assert!(code.has_fn("pung", "#[deprecated] fn pung(&mut self,) ->"));
+    assert!(!code.has_fn("pong", "#[deprecated]"));
+    // the `_Args` and `_Reply` structs
+    let derives = "#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]";
+    assert!(code
+        .attrs("Ping_Args")
+        .ends_with(&norm_all(&[ping, "#[allow(deprecated)]", derives])));
+    assert!(code.attrs("Pung_Reply").ends_with(&norm_all(&[
+        "#[deprecated]",
+        "#[allow(deprecated)]",
+        derives
+    ])));
+    // the client method, the blocking call and the `_Args` and `_Reply` structs of each
+    // deprecated method
+    assert_eq!(code.text().matches("#[deprecated").count(), 8);
+    // the generated code using them
+    let allow = norm("#[allow(deprecated)]");
+    for name in &["VarlinkClientInterface", "VarlinkInterface", "Call_Ping"] {
+        assert!(code.attrs(name).contains(&allow), "{}", name);
+    }
+    for (trait_, self_ty) in &[
+        ("VarlinkClientInterface", "VarlinkClient"),
+        ("", "VarlinkInterfaceProxy"),
+    ] {
+        assert!(
+            code.impl_(trait_, self_ty).starts_with(&allow),
+            "{} {}",
+            trait_,
+            self_ty
+        );
+    }
+
+    let code = varlink_generator::generate_str(
+        "interface org.example.ping\nmethod Ping(ping: string) -> (pong: string)\n",
+    )
+    .unwrap();
+    assert!(
+        !Code::parse(&code).text().contains("deprecated"),
+        "{}",
+        code
+    );
+}
+
+#[test]
+fn test_deprecated_types() {
+    let description = "interface org.example.items\n\
+                       # @deprecated use Item2 instead\n\
+                       type Item (name: string, kind: Kind)\n\
+                       # @deprecated\n\
+                       type Kind (old, new)\n\
+                       type Item2 (name: string)\n\
+                       method Get(kind: Kind) -> (item: Item)\n";
+    let code = Code::parse(&generate(
+        description,
+        &GeneratorOptions {
+            builders: true,
+            integer_enums: true,
+            ..Default::default()
+        },
+    ));
+    let derives = "#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]";
+    assert!(code.attrs("Item").ends_with(&norm_all(&[
+        "#[deprecated(note = \"use Item2 instead\")]",
+        "#[allow(deprecated)]",
+        derives,
+    ])));
+    assert!(code
+        .attrs("Kind")
+        .ends_with(&norm_all(&["#[deprecated]", derives, "#[repr(i64)]"])));
+    assert_eq!(code.text().matches("#[deprecated").count(), 2);
+    // the generated code using them
+    let allow = norm("#[allow(deprecated)]");
+    for name in &[
+        "Get_Args",
+        "Item2",
+        "ItemBuilder",
+        "VarlinkInterface",
+        "Call_Get",
+    ] {
+        assert!(code.attrs(name).contains(&allow), "{}", name);
+    }
+    for self_ty in &["Kind", "ItemBuilder", "Item"] {
+        assert!(code.impl_("", self_ty).starts_with(&allow), "{}", self_ty);
+    }
+}
+
+#[test]
+fn test_builders() {
+    let description = "interface org.example.builder\n\
+                       type Item (name: string, count: int = 1, note: ?string)\n\
+                       type Kind (a, b)\n\
+                       method Get() -> (item: Item)\n";
+    let code = Code::parse(&generate(
+        description,
+        &GeneratorOptions {
+            builders: true,
+            ..Default::default()
+        },
+    ));
+    assert_eq!(
+        code.fields("ItemBuilder"),
+        norm_all(&[
+            "name: Option<String>",
+            "count: Option<i64>",
+            "note: Option<String>",
+        ])
+    );
+    assert!(code.has_fn("note", "pub fn note(mut self, note: String) -> Self"));
+    assert!(code.has_fn(
+        "build",
+        "pub fn build(self) -> ::std::result::Result<Item, varlink::MissingField> { \
+         Ok(Item { name: self.name.ok_or(varlink::MissingField(\"name\"))?, \
+         count: self.count.unwrap_or_else(|| 1), note: self.note, }) }"
+    ));
+    assert!(code.has_fn("builder", "pub fn builder() -> ItemBuilder"));
+    assert!(!code.has("KindBuilder"));
+    assert!(!code.has("Get_ReplyBuilder"));
+
+    // the builder name must not collide
+    let description = "interface org.example.builder\n\
+                       type Item (name: string)\n\
+                       type ItemBuilder (name: string)\n\
+                       method Get() -> (item: Item, builder: ItemBuilder)\n";
+    assert!(try_generate(
+        description,
+        &GeneratorOptions {
+            builders: true,
+            ..Default::default()
+        },
+    )
+    .is_err());
+}
+
+#[test]
+fn test_ident_prefix() {
+    let description = "interface org.example.ping\n\
+                       method Ping(ping: string) -> (pong: string)\n\
+                       error NotFound (reason: string)\n";
+    let code = Code::parse(&generate(
+        description,
+        &GeneratorOptions {
+            ident_prefix: Some("Ping".into()),
+            http_status: Some(vec![]),
+            ..Default::default()
+        },
+    ));
+    assert!(code
+        .items()
+        .iter()
+        .any(|item| tokens(item) == norm("use self::ping_imports::*;")));
+    assert_eq!(
+        code.attrs("ping_imports"),
+        norm_all(&["#[allow(unused_imports)]"])
+    );
+    for (name, item) in &[
+        (
+            "PingVarlinkCallError",
+            "pub trait PingVarlinkCallError: varlink::CallTrait",
+        ),
+        ("PingErrorKind", "pub enum PingErrorKind {"),
+        (
+            "PingResult",
+            "pub type PingResult<T> = ChainResult<T, PingErrorKind>;",
+        ),
+        ("PingError", "pub type PingError = PingErrorKind;"),
+        ("Call_Ping", "pub trait Call_Ping: PingVarlinkCallError"),
+        ("PingVarlinkInterface", "pub trait PingVarlinkInterface {"),
+        ("PingVarlinkClient", "pub struct PingVarlinkClient {"),
+    ] {
+        let code = tokens(code.item(name));
+        assert!(code.contains(&norm(item)), "{}: {}", item, code);
+    }
+    assert!(code.has_impl("PingVarlinkClientInterface", "PingVarlinkClient"));
+    assert!(code.has_fn(
+        "ping_new",
+        "pub fn ping_new(inner: Box<dyn PingVarlinkInterface + Send + Sync>) \
+         -> PingVarlinkInterfaceProxy"
+    ));
+    assert!(code.has_fn(
+        "ping_http_status",
+        "pub fn ping_http_status(e: &ChainError<PingErrorKind>) -> u16"
+    ));
+    assert!(!code.has("VarlinkClient"));
+    assert!(!code.has("new"));
+
+    let options = GeneratorOptions {
+        ident_prefix: Some("Ping".into()),
+        ..Default::default()
+    };
+    assert!(try_generate(
+        "interface org.example.ping\ntype Result (a: int)\n",
+        &options
+    )
+    .is_ok());
+    let e = try_generate(
+        "interface org.example.ping\ntype PingResult (a: int)\n",
+        &options,
+    )
+    .unwrap_err();
+    assert!(
+        e.contains("`PingResult` is generated for the generated `PingResult` and for the type"),
+        "{}",
+        e
+    );
+}
+
+#[test]
+fn test_call_contract_methods() {
+    let description = "interface org.example.ping\n\
+                       method Ping(ping: string) -> (pong: string)\n\
+                       method Watch() -> ()\n";
+    assert!(try_generate(
+        description,
+        &GeneratorOptions {
+            more_methods: Some(vec!["Watch"]),
+            oneway_methods: Some(vec!["Ping"]),
+            ..Default::default()
+        },
+    )
+    .is_ok());
+
+    // a typo does not silently disable the check
+    let e = try_generate(
+        description,
+        &GeneratorOptions {
+            more_methods: Some(vec!["Wtach"]),
+            ..Default::default()
+        },
+    )
+    .unwrap_err();
+    assert!(
+        e.contains("`Wtach` of `more_methods` is not a method of the interface"),
+        "{}",
+        e
+    );
+    let e = try_generate(
+        description,
+        &GeneratorOptions {
+            oneway_methods: Some(vec!["org.example.ping.Ping"]),
+            ..Default::default()
+        },
+    )
+    .unwrap_err();
+    assert!(
+        e.contains("`org.example.ping.Ping` of `oneway_methods` is not a method of the interface"),
+        "{}",
+        e
+    );
+}
+
+#[test]
+fn test_more_reply() {
+    let description = "interface org.example.ping\n\
+                       method Ping(ping: string) -> (pong: string)\n\
+                       method Watch() -> ()\n";
+    let code = Code::parse(&generate(
+        description,
+        &GeneratorOptions {
+            more_methods: Some(vec!["Watch"]),
+            ..Default::default()
+        },
+    ));
+    assert!(code.has("Watch_MoreReply"));
+    assert!(code.has_fn(
+        "watch_more",
+        "pub fn watch_more(&mut self,) -> Result<Watch_MoreReply>"
+    ));
+    assert!(!code.has("Ping_MoreReply"));
+    assert!(code.fns("ping_more").is_empty());
+
+    let code = Code::parse(&generate(description, &GeneratorOptions::default()));
+    assert!(!code.has("Watch_MoreReply"));
+    assert!(code.fns("watch_more").is_empty());
+
+    // without the iterator, `Ping` does not clash with `PingMore`
+    generate(
+        "interface org.example.ping\n\
+         method PingMore() -> ()\n\
+         method Ping() -> ()\n",
+        &GeneratorOptions::default(),
+    );
+}
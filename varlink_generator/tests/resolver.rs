@@ -0,0 +1,105 @@
+#![cfg(feature = "resolver")]
+
+use std::io::BufRead;
+use std::{thread, time};
+
+use serde_json::json;
+use varlink::{Call, CallTrait, Interface, ListenConfig, Reply, ShutdownHandle, VarlinkService};
+
+const RESOLVER: &str = "unix:test_description_from_resolver";
+
+/// A resolver, which only knows itself.
+struct Resolver;
+
+impl Interface for Resolver {
+    fn get_description(&self) -> &'static str {
+        "interface org.varlink.resolver\n\
+         method Resolve(interface: string) -> (address: string)\n\
+         error InterfaceNotFound (interface: string)\n"
+    }
+
+    fn get_name(&self) -> &'static str {
+        "org.varlink.resolver"
+    }
+
+    fn call_upgraded(
+        &self,
+        _call: &mut Call,
+        _bufreader: &mut dyn BufRead,
+    ) -> varlink::Result<Vec<u8>> {
+        Ok(Vec::new())
+    }
+
+    fn call(&self, call: &mut Call) -> varlink::Result<()> {
+        let interface = call
+            .request
+            .and_then(|r| r.parameters.as_ref())
+            .and_then(|p| p["interface"].as_str())
+            .unwrap_or_default()
+            .to_string();
+        if interface == "org.varlink.resolver" {
+            call.reply_struct(Reply::parameters(Some(json!({ "address": RESOLVER }))))
+        } else {
+            call.reply_struct(Reply::error(
+                "org.varlink.resolver.InterfaceNotFound",
+                Some(json!({ "interface": interface })),
+            ))
+        }
+    }
+}
+
+#[test]
+fn test_description_from_resolver() {
+    let handle = ShutdownHandle::new();
+
+    let server_handle = handle.clone();
+    let child = thread::spawn(move || {
+        let service = VarlinkService::new(
+            "org.varlink",
+            "test resolver",
+            "0.1",
+            "http://varlink.org",
+            vec![Box::new(Resolver)],
+        );
+
+        let config = ListenConfig {
+            shutdown: Some(server_handle),
+            ..Default::default()
+        };
+
+        varlink::listen_with_config(service, RESOLVER, &config)
+    });
+
+    // wait for the socket
+    for _ in 0..50 {
+        if varlink::Connection::with_address(RESOLVER).is_ok() {
+            break;
+        }
+        thread::sleep(time::Duration::from_millis(100));
+    }
+
+    let description =
+        varlink_generator::description_from_resolver(RESOLVER, "org.varlink.resolver").unwrap();
+    assert!(description.starts_with("interface org.varlink.resolver\n"));
+
+    let code = varlink_generator::generate_str(&description).unwrap();
+    assert!(code.contains("pub struct Resolve_Reply"), "{}", code);
+
+    let e =
+        varlink_generator::description_from_resolver(RESOLVER, "org.example.unknown").unwrap_err();
+    assert!(e
+        .to_string()
+        .contains("could not resolve `org.example.unknown`"));
+
+    let e = varlink_generator::description_from_resolver(
+        "unix:test_description_from_resolver_missing",
+        "org.varlink.resolver",
+    )
+    .unwrap_err();
+    assert!(e
+        .to_string()
+        .contains("Could not connect to the varlink resolver"));
+
+    handle.shutdown();
+    child.join().unwrap().unwrap();
+}
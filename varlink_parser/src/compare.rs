@@ -0,0 +1,259 @@
+//! Compare two versions of the definition of an interface
+
+use std::fmt;
+
+use crate::{Argument, VStruct, VStructOrEnum, IDL};
+
+/// A difference between the local and the remote definition of an interface, as returned by
+/// [compare_interfaces](fn.compare_interfaces.html).
+///
+/// `member` names the struct with the differing field, e.g. `method Ping input`,
+/// `method Ping output`, `type Foo` or `error Bar`. Types are written as in the interface
+/// definition, including a default value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Mismatch {
+    /// The interface is only defined locally.
+    MissingInterface { interface: String },
+    /// The method is only defined locally.
+    MissingMethod { interface: String, method: String },
+    /// The method is only defined remotely.
+    AddedMethod { interface: String, method: String },
+    /// The type is only defined locally.
+    MissingType { interface: String, name: String },
+    /// The error is only defined locally.
+    MissingError { interface: String, error: String },
+    /// The type is a struct on one side and an enum on the other, or the values of the enum
+    /// differ.
+    ChangedType {
+        interface: String,
+        name: String,
+        local: String,
+        remote: String,
+    },
+    /// The field is only defined locally.
+    MissingField {
+        interface: String,
+        member: String,
+        field: String,
+        vtype: String,
+    },
+    /// The field is only defined remotely.
+    AddedField {
+        interface: String,
+        member: String,
+        field: String,
+        vtype: String,
+    },
+    /// The field has a different type.
+    ChangedField {
+        interface: String,
+        member: String,
+        field: String,
+        local: String,
+        remote: String,
+    },
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Mismatch::MissingInterface { interface } => {
+                write!(f, "{}: interface missing remotely", interface)
+            }
+            Mismatch::MissingMethod { interface, method } => {
+                write!(f, "{}: method `{}` missing remotely", interface, method)
+            }
+            Mismatch::AddedMethod { interface, method } => {
+                write!(f, "{}: method `{}` added remotely", interface, method)
+            }
+            Mismatch::MissingType { interface, name } => {
+                write!(f, "{}: type `{}` missing remotely", interface, name)
+            }
+            Mismatch::MissingError { interface, error } => {
+                write!(f, "{}: error `{}` missing remotely", interface, error)
+            }
+            Mismatch::ChangedType {
+                interface,
+                name,
+                local,
+                remote,
+            } => write!(
+                f,
+                "{}: type `{}` changed from `{}` to `{}` remotely",
+                interface, name, local, remote
+            ),
+            Mismatch::MissingField {
+                interface,
+                member,
+                field,
+                vtype,
+            } => write!(
+                f,
+                "{}: {}: field `{}: {}` missing remotely",
+                interface, member, field, vtype
+            ),
+            Mismatch::AddedField {
+                interface,
+                member,
+                field,
+                vtype,
+            } => write!(
+                f,
+                "{}: {}: field `{}: {}` added remotely",
+                interface, member, field, vtype
+            ),
+            Mismatch::ChangedField {
+                interface,
+                member,
+                field,
+                local,
+                remote,
+            } => write!(
+                f,
+                "{}: {}: field `{}` changed from `{}` to `{}` remotely",
+                interface, member, field, local, remote
+            ),
+        }
+    }
+}
+
+fn field_type(arg: &Argument) -> String {
+    format!("{}{}", arg.vtype, arg.default_suffix())
+}
+
+fn compare_fields(
+    interface: &str,
+    member: &str,
+    local: &VStruct,
+    remote: &VStruct,
+    mismatches: &mut Vec<Mismatch>,
+) {
+    for l in &local.elts {
+        match remote.elts.iter().find(|r| r.name == l.name) {
+            None => mismatches.push(Mismatch::MissingField {
+                interface: interface.into(),
+                member: member.into(),
+                field: l.name.into(),
+                vtype: field_type(l),
+            }),
+            Some(r) if field_type(l) != field_type(r) => mismatches.push(Mismatch::ChangedField {
+                interface: interface.into(),
+                member: member.into(),
+                field: l.name.into(),
+                local: field_type(l),
+                remote: field_type(r),
+            }),
+            Some(_) => {}
+        }
+    }
+
+    for r in &remote.elts {
+        if !local.elts.iter().any(|l| l.name == r.name) {
+            mismatches.push(Mismatch::AddedField {
+                interface: interface.into(),
+                member: member.into(),
+                field: r.name.into(),
+                vtype: field_type(r),
+            });
+        }
+    }
+}
+
+/// Compare the `local` definition of an interface with the `remote` one, e.g. the
+/// description returned by `GetInterfaceDescription` of a service.
+///
+/// Fields are matched by name, so their order does not matter, just like the order of the
+/// values of an enum. Types and errors only defined remotely are not reported, because
+/// anything using them shows up as a changed or added field or method.
+///
+/// # Examples
+///
+/// ```rust
+/// use varlink_parser::{compare_interfaces, Mismatch, IDL};
+///
+/// let local = IDL::from_string("interface org.example.ping\nmethod Ping(ping: string) -> (pong: string)").unwrap();
+/// let remote = IDL::from_string("interface org.example.ping\nmethod Ping(ping: string, count: ?int) -> (pong: string)").unwrap();
+/// assert_eq!(
+///     compare_interfaces(&local, &remote),
+///     vec![Mismatch::AddedField {
+///         interface: "org.example.ping".into(),
+///         member: "method Ping input".into(),
+///         field: "count".into(),
+///         vtype: "?int".into(),
+///     }]
+/// );
+/// ```
+pub fn compare_interfaces(local: &IDL, remote: &IDL) -> Vec<Mismatch> {
+    let interface = local.name;
+    let mut mismatches = Vec::new();
+
+    for name in &local.method_keys {
+        let l = &local.methods[name];
+        match remote.methods.get(name) {
+            Some(r) => {
+                let member = format!("method {} input", name);
+                compare_fields(interface, &member, &l.input, &r.input, &mut mismatches);
+                let member = format!("method {} output", name);
+                compare_fields(interface, &member, &l.output, &r.output, &mut mismatches);
+            }
+            None => mismatches.push(Mismatch::MissingMethod {
+                interface: interface.into(),
+                method: (*name).into(),
+            }),
+        }
+    }
+
+    for name in &remote.method_keys {
+        if !local.methods.contains_key(name) {
+            mismatches.push(Mismatch::AddedMethod {
+                interface: interface.into(),
+                method: (*name).into(),
+            });
+        }
+    }
+
+    for name in &local.typedef_keys {
+        let l = &local.typedefs[name];
+        let r = match remote.typedefs.get(name) {
+            Some(r) => r,
+            None => {
+                mismatches.push(Mismatch::MissingType {
+                    interface: interface.into(),
+                    name: (*name).into(),
+                });
+                continue;
+            }
+        };
+        match (&l.elt, &r.elt) {
+            (VStructOrEnum::VStruct(l), VStructOrEnum::VStruct(r)) => {
+                let member = format!("type {}", name);
+                compare_fields(interface, &member, l, r, &mut mismatches);
+            }
+            (VStructOrEnum::VEnum(le), VStructOrEnum::VEnum(re))
+                if le.elts.iter().all(|v| re.elts.contains(v))
+                    && re.elts.iter().all(|v| le.elts.contains(v)) => {}
+            (l, r) => mismatches.push(Mismatch::ChangedType {
+                interface: interface.into(),
+                name: (*name).into(),
+                local: l.to_string(),
+                remote: r.to_string(),
+            }),
+        }
+    }
+
+    for name in &local.error_keys {
+        let l = &local.errors[name];
+        match remote.errors.get(name) {
+            Some(r) => {
+                let member = format!("error {}", name);
+                compare_fields(interface, &member, &l.parm, &r.parm, &mut mismatches);
+            }
+            None => mismatches.push(Mismatch::MissingError {
+                interface: interface.into(),
+                error: (*name).into(),
+            }),
+        }
+    }
+
+    mismatches
+}
@@ -135,26 +135,46 @@ impl<'a> fmt::Display for VStructOrEnum<'a> {
     }
 }
 
+impl<'a> Argument<'a> {
+    pub(crate) fn default_suffix(&self) -> String {
+        match self.default {
+            Some(d) => format!(" = {}", d),
+            None => String::new(),
+        }
+    }
+}
+
 impl<'a> Format for Argument<'a> {
     fn get_oneline(&self) -> String {
-        format!("{}: {}", self.name, self.vtype)
+        format!("{}: {}{}", self.name, self.vtype, self.default_suffix())
     }
 
     fn get_multiline(&self, indent: usize, max: usize) -> String {
-        format!("{}: {}", self.name, self.vtype.get_multiline(indent, max))
+        format!(
+            "{}: {}{}",
+            self.name,
+            self.vtype.get_multiline(indent, max),
+            self.default_suffix()
+        )
     }
 }
 
 impl<'a> FormatColored for Argument<'a> {
     fn get_oneline_colored(&self) -> String {
-        format!("{}: {}", self.name, self.vtype.get_oneline_colored())
+        format!(
+            "{}: {}{}",
+            self.name,
+            self.vtype.get_oneline_colored(),
+            self.default_suffix()
+        )
     }
 
     fn get_multiline_colored(&self, indent: usize, max: usize) -> String {
         format!(
-            "{}: {}",
+            "{}: {}{}",
             self.name,
-            self.vtype.get_multiline_colored(indent, max)
+            self.vtype.get_multiline_colored(indent, max),
+            self.default_suffix()
         )
     }
 }
@@ -50,8 +50,10 @@ use std::collections::HashSet;
 
 use chainerror::*;
 
+mod compare;
 mod format;
 
+pub use compare::{compare_interfaces, Mismatch};
 pub use format::{Format, FormatColored};
 
 #[cfg(test)]
@@ -88,6 +90,10 @@ pub enum VTypeExt<'a> {
 pub struct Argument<'a> {
     pub name: &'a str,
     pub vtype: VTypeExt<'a>,
+    /// The default value declared with `name: type = value`.
+    ///
+    /// A `bool`, number or string literal, as written in the interface definition.
+    pub default: Option<&'a str>,
 }
 
 pub struct VStruct<'a> {
@@ -218,4 +224,46 @@ impl<'a> IDL<'a> {
             Ok(interface)
         }
     }
+
+    /// Parse `s`, which may contain several interfaces one after the other.
+    ///
+    /// Each interface starts with the comment lines before its `interface` keyword, the
+    /// `description` of an interface is only its own part of `s`.
+    pub fn from_string_many(s: &'a str) -> ChainResult<Vec<Self>, Error> {
+        let mut starts = Vec::new();
+        let mut comment_start = None;
+        let mut depth = 0i64;
+        let mut offset = 0;
+
+        for line in s.split('\n') {
+            let trimmed = line.trim();
+            if trimmed.starts_with('#') {
+                comment_start = comment_start.or(Some(offset));
+            } else if !trimmed.is_empty() {
+                let code = trimmed.split('#').next().unwrap_or("");
+                if depth == 0
+                    && (code == "interface"
+                        || code.starts_with("interface ")
+                        || code.starts_with("interface\t"))
+                {
+                    starts.push(comment_start.unwrap_or(offset));
+                }
+                depth += code.matches('(').count() as i64 - code.matches(')').count() as i64;
+                comment_start = None;
+            }
+            offset += line.len() + 1;
+        }
+
+        if starts.len() < 2 {
+            return Ok(vec![IDL::from_string(s)?]);
+        }
+
+        // anything before the first interface belongs to it
+        starts[0] = 0;
+        starts.push(s.len());
+        starts
+            .windows(2)
+            .map(|w| IDL::from_string(&s[w[0]..w[1]]))
+            .collect()
+    }
 }
@@ -337,3 +337,158 @@ Interface `foo.example`: multiple definitions of type `T`!'
 "
     );
 }
+
+#[test]
+fn test_default_values() {
+    let v = IDL::from_string(
+        "interface foo.bar\ntype I (b: bool = true, i: ?int=-3, f: float = 1.5, s: string = \"a b\", n: int)\nmethod F()->()",
+    )
+    .unwrap();
+    let elts = match v.typedefs["I"].elt {
+        VStructOrEnum::VStruct(ref s) => &s.elts,
+        _ => panic!("not a struct"),
+    };
+    assert_eq!(
+        elts.iter().map(|e| e.default).collect::<Vec<_>>(),
+        vec![Some("true"), Some("-3"), Some("1.5"), Some("\"a b\""), None]
+    );
+    assert_eq!(
+        v.to_string(),
+        "interface foo.bar\n\ntype I (b: bool = true, i: ?int = -3, f: float = 1.5, s: string = \"a b\", n: int)\n\nmethod F() -> ()\n"
+    );
+
+    assert!(IDL::from_string("interface foo.bar\n type I (b: bool =)\nmethod F()->()").is_err());
+    assert!(IDL::from_string("interface foo.bar\n type I (b: int = 1.)\nmethod F()->()").is_err());
+    assert!(
+        IDL::from_string("interface foo.bar\n type I (b: string = foo)\nmethod F()->()").is_err()
+    );
+}
+
+#[test]
+fn test_many() {
+    let s = "\
+# The first interface
+interface org.example.one
+method F(interface: string) -> ()
+
+# The second interface
+# with a two line comment
+interface org.example.two
+
+type T (
+  interface: string
+)
+
+method G() -> ()
+";
+    let v = IDL::from_string_many(s).unwrap();
+    assert_eq!(
+        v.iter().map(|i| i.name).collect::<Vec<_>>(),
+        vec!["org.example.one", "org.example.two"]
+    );
+    assert_eq!(v[0].doc, "# The first interface");
+    assert_eq!(
+        v[0].description,
+        "# The first interface\ninterface org.example.one\nmethod F(interface: string) -> ()\n\n"
+    );
+    assert_eq!(
+        v[1].doc,
+        "# The second interface\n# with a two line comment"
+    );
+    assert!(v[1].description.starts_with("# The second interface\n"));
+    assert!(v[1].description.ends_with("method G() -> ()\n"));
+
+    let v = IDL::from_string_many("interface foo.bar\nmethod F()->()").unwrap();
+    assert_eq!(v.len(), 1);
+    assert_eq!(v[0].description, "interface foo.bar\nmethod F()->()");
+
+    assert!(IDL::from_string_many("interface foo.bar\n").is_err());
+}
+
+#[test]
+fn test_compare_interfaces() {
+    let local = IDL::from_string(
+        "
+interface org.example.compare
+type Color (red, green, blue)
+type Point (x: int, y: int)
+type Shape (kind: string)
+method Draw(point: Point, color: Color) -> (id: int)
+method Erase(id: int) -> ()
+error NotFound (id: int)
+error Busy ()
+",
+    )
+    .unwrap();
+
+    let remote = IDL::from_string(
+        "
+interface org.example.compare
+type Color (blue, green, red)
+type Point (y: int, x: float, z: ?int)
+type Shape (circle, square)
+method Draw(point: Point, color: Color, width: int = 1) -> (id: int)
+method Clear() -> ()
+error NotFound (id: string)
+",
+    )
+    .unwrap();
+
+    assert!(compare_interfaces(&local, &local).is_empty());
+
+    let interface = String::from("org.example.compare");
+    assert_eq!(
+        compare_interfaces(&local, &remote),
+        vec![
+            Mismatch::AddedField {
+                interface: interface.clone(),
+                member: "method Draw input".into(),
+                field: "width".into(),
+                vtype: "int = 1".into(),
+            },
+            Mismatch::MissingMethod {
+                interface: interface.clone(),
+                method: "Erase".into(),
+            },
+            Mismatch::AddedMethod {
+                interface: interface.clone(),
+                method: "Clear".into(),
+            },
+            Mismatch::ChangedField {
+                interface: interface.clone(),
+                member: "type Point".into(),
+                field: "x".into(),
+                local: "int".into(),
+                remote: "float".into(),
+            },
+            Mismatch::AddedField {
+                interface: interface.clone(),
+                member: "type Point".into(),
+                field: "z".into(),
+                vtype: "?int".into(),
+            },
+            Mismatch::ChangedType {
+                interface: interface.clone(),
+                name: "Shape".into(),
+                local: "(kind: string)".into(),
+                remote: "(circle, square)".into(),
+            },
+            Mismatch::ChangedField {
+                interface: interface.clone(),
+                member: "error NotFound".into(),
+                field: "id".into(),
+                local: "int".into(),
+                remote: "string".into(),
+            },
+            Mismatch::MissingError {
+                interface,
+                error: "Busy".into(),
+            },
+        ]
+    );
+
+    assert_eq!(
+        compare_interfaces(&local, &remote)[1].to_string(),
+        "org.example.compare: method `Erase` missing remotely"
+    );
+}
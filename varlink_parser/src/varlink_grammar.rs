@@ -1132,6 +1132,277 @@ fn __parse_type<'input>(
     }
 }
 
+fn __parse_default_value<'input>(
+    __input: &'input str,
+    __state: &mut ParseState<'input>,
+    __pos: usize,
+) -> RuleResult<&'input str> {
+    #![allow(non_snake_case, unused)]
+    {
+        let __choice_res = {
+            __state.suppress_fail += 1;
+            let res = {
+                let str_start = __pos;
+                match {
+                    let __choice_res = slice_eq(__input, __state, __pos, "true");
+                    match __choice_res {
+                        Matched(__pos, __value) => Matched(__pos, __value),
+                        Failed => {
+                            let __choice_res = slice_eq(__input, __state, __pos, "false");
+                            match __choice_res {
+                                Matched(__pos, __value) => Matched(__pos, __value),
+                                Failed => {
+                                    let __choice_res = {
+                                        let __seq_res = match slice_eq(__input, __state, __pos, "-")
+                                        {
+                                            Matched(__newpos, __value) => {
+                                                Matched(__newpos, Some(__value))
+                                            }
+                                            Failed => Matched(__pos, None),
+                                        };
+                                        match __seq_res {
+                                            Matched(__pos, _) => {
+                                                let __seq_res = {
+                                                    let mut __repeat_pos = __pos;
+                                                    let mut __repeat_value = vec![];
+                                                    loop {
+                                                        let __pos = __repeat_pos;
+                                                        let __step_res = if __input.len() > __pos {
+                                                            let (__ch, __next) =
+                                                                char_range_at(__input, __pos);
+                                                            match __ch {
+                                                                '0'...'9' => Matched(__next, ()),
+                                                                _ => __state
+                                                                    .mark_failure(__pos, "[0-9]"),
+                                                            }
+                                                        } else {
+                                                            __state.mark_failure(__pos, "[0-9]")
+                                                        };
+                                                        match __step_res {
+                                                            Matched(__newpos, __value) => {
+                                                                __repeat_pos = __newpos;
+                                                                __repeat_value.push(__value);
+                                                            }
+                                                            Failed => {
+                                                                break;
+                                                            }
+                                                        }
+                                                    }
+                                                    if __repeat_value.len() >= 1 {
+                                                        Matched(__repeat_pos, ())
+                                                    } else {
+                                                        Failed
+                                                    }
+                                                };
+                                                match __seq_res {
+                                                    Matched(__pos, _) => match {
+                                                        let __seq_res =
+                                                            slice_eq(__input, __state, __pos, ".");
+                                                        match __seq_res {
+                                                            Matched(__pos, _) => {
+                                                                let mut __repeat_pos = __pos;
+                                                                let mut __repeat_value = vec![];
+                                                                loop {
+                                                                    let __pos = __repeat_pos;
+                                                                    let __step_res = if __input
+                                                                        .len()
+                                                                        > __pos
+                                                                    {
+                                                                        let (__ch, __next) =
+                                                                            char_range_at(
+                                                                                __input, __pos,
+                                                                            );
+                                                                        match __ch {
+                                                                            '0'...'9' => {
+                                                                                Matched(__next, ())
+                                                                            }
+                                                                            _ => __state
+                                                                                .mark_failure(
+                                                                                    __pos, "[0-9]",
+                                                                                ),
+                                                                        }
+                                                                    } else {
+                                                                        __state.mark_failure(
+                                                                            __pos, "[0-9]",
+                                                                        )
+                                                                    };
+                                                                    match __step_res {
+                                                                        Matched(
+                                                                            __newpos,
+                                                                            __value,
+                                                                        ) => {
+                                                                            __repeat_pos = __newpos;
+                                                                            __repeat_value
+                                                                                .push(__value);
+                                                                        }
+                                                                        Failed => {
+                                                                            break;
+                                                                        }
+                                                                    }
+                                                                }
+                                                                if __repeat_value.len() >= 1 {
+                                                                    Matched(__repeat_pos, ())
+                                                                } else {
+                                                                    Failed
+                                                                }
+                                                            }
+                                                            Failed => Failed,
+                                                        }
+                                                    } {
+                                                        Matched(__newpos, __value) => {
+                                                            Matched(__newpos, Some(__value))
+                                                        }
+                                                        Failed => Matched(__pos, None),
+                                                    },
+                                                    Failed => Failed,
+                                                }
+                                            }
+                                            Failed => Failed,
+                                        }
+                                    };
+                                    match __choice_res {
+                                        Matched(__pos, __value) => Matched(__pos, ()),
+                                        Failed => {
+                                            let __seq_res = slice_eq(__input, __state, __pos, "\"");
+                                            match __seq_res {
+                                                Matched(__pos, _) => {
+                                                    let __seq_res = {
+                                                        let mut __repeat_pos = __pos;
+                                                        loop {
+                                                            let __pos = __repeat_pos;
+                                                            let __step_res = if __input.len()
+                                                                > __pos
+                                                            {
+                                                                let (__ch, __next) =
+                                                                    char_range_at(__input, __pos);
+                                                                match __ch {
+                                                                    '"' | '\\' | '\n' | '\r' => {
+                                                                        __state.mark_failure(
+                                                                            __pos,
+                                                                            "[^\"\\\n\r]",
+                                                                        )
+                                                                    }
+                                                                    _ => Matched(__next, ()),
+                                                                }
+                                                            } else {
+                                                                __state.mark_failure(
+                                                                    __pos,
+                                                                    "[^\"\\\n\r]",
+                                                                )
+                                                            };
+                                                            match __step_res {
+                                                                Matched(__newpos, __value) => {
+                                                                    __repeat_pos = __newpos;
+                                                                }
+                                                                Failed => {
+                                                                    break;
+                                                                }
+                                                            }
+                                                        }
+                                                        Matched(__repeat_pos, ())
+                                                    };
+                                                    match __seq_res {
+                                                        Matched(__pos, _) => {
+                                                            slice_eq(__input, __state, __pos, "\"")
+                                                        }
+                                                        Failed => Failed,
+                                                    }
+                                                }
+                                                Failed => Failed,
+                                            }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                } {
+                    Matched(__newpos, _) => Matched(__newpos, &__input[str_start..__newpos]),
+                    Failed => Failed,
+                }
+            };
+            __state.suppress_fail -= 1;
+            res
+        };
+        match __choice_res {
+            Matched(__pos, __value) => Matched(__pos, __value),
+            Failed => {
+                __state.mark_failure(__pos, "<default value>");
+                Failed
+            }
+        }
+    }
+}
+
+fn __parse_default<'input>(
+    __input: &'input str,
+    __state: &mut ParseState<'input>,
+    __pos: usize,
+) -> RuleResult<&'input str> {
+    #![allow(non_snake_case, unused)]
+    {
+        let __seq_res = {
+            let mut __repeat_pos = __pos;
+            loop {
+                let __pos = __repeat_pos;
+                let __step_res = match __parse__(__input, __state, __pos) {
+                    Matched(pos, _) => Matched(pos, ()),
+                    Failed => Failed,
+                };
+                match __step_res {
+                    Matched(__newpos, __value) => {
+                        __repeat_pos = __newpos;
+                    }
+                    Failed => {
+                        break;
+                    }
+                }
+            }
+            Matched(__repeat_pos, ())
+        };
+        match __seq_res {
+            Matched(__pos, _) => {
+                let __seq_res = slice_eq(__input, __state, __pos, "=");
+                match __seq_res {
+                    Matched(__pos, _) => {
+                        let __seq_res = {
+                            let mut __repeat_pos = __pos;
+                            loop {
+                                let __pos = __repeat_pos;
+                                let __step_res = match __parse__(__input, __state, __pos) {
+                                    Matched(pos, _) => Matched(pos, ()),
+                                    Failed => Failed,
+                                };
+                                match __step_res {
+                                    Matched(__newpos, __value) => {
+                                        __repeat_pos = __newpos;
+                                    }
+                                    Failed => {
+                                        break;
+                                    }
+                                }
+                            }
+                            Matched(__repeat_pos, ())
+                        };
+                        match __seq_res {
+                            Matched(__pos, _) => {
+                                let __seq_res = __parse_default_value(__input, __state, __pos);
+                                match __seq_res {
+                                    Matched(__pos, d) => Matched(__pos, { d }),
+                                    Failed => Failed,
+                                }
+                            }
+                            Failed => Failed,
+                        }
+                    }
+                    Failed => Failed,
+                }
+            }
+            Failed => Failed,
+        }
+    }
+}
+
 fn __parse_object_field<'input>(
     __input: &'input str,
     __state: &mut ParseState<'input>,
@@ -1221,9 +1492,26 @@ fn __parse_object_field<'input>(
                                                 let __seq_res =
                                                     __parse_type(__input, __state, __pos);
                                                 match __seq_res {
-                                                    Matched(__pos, v) => Matched(__pos, {
-                                                        Argument { name: n, vtype: v }
-                                                    }),
+                                                    Matched(__pos, v) => {
+                                                        let __seq_res = match __parse_default(
+                                                            __input, __state, __pos,
+                                                        ) {
+                                                            Matched(__newpos, __value) => {
+                                                                Matched(__newpos, Some(__value))
+                                                            }
+                                                            Failed => Matched(__pos, None),
+                                                        };
+                                                        match __seq_res {
+                                                            Matched(__pos, d) => Matched(__pos, {
+                                                                Argument {
+                                                                    name: n,
+                                                                    vtype: v,
+                                                                    default: d,
+                                                                }
+                                                            }),
+                                                            Failed => Failed,
+                                                        }
+                                                    }
                                                     Failed => Failed,
                                                 }
                                             }
@@ -1,6 +1,7 @@
 #![doc = "This file was automatically generated by the varlink rust generator"]
 #![allow(non_camel_case_types)]
 #![allow(non_snake_case)]
+#![allow(dead_code)]
 
 use chainerror::*;
 use serde_derive::{Deserialize, Serialize};
@@ -9,6 +10,9 @@ use std::io::BufRead;
 use std::sync::{Arc, RwLock};
 use varlink::{self, CallTrait};
 
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct ExpectedMore_Args {}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct InterfaceNotFound_Args {
     pub r#interface: String,
@@ -29,6 +33,9 @@ pub struct MethodNotImplemented_Args {
     pub r#method: String,
 }
 
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct PermissionDenied_Args {}
+
 #[allow(dead_code)]
 #[derive(Clone, PartialEq, Debug)]
 pub enum ErrorKind {
@@ -37,10 +44,12 @@ pub enum ErrorKind {
     Varlink_Error,
     VarlinkReply_Error(varlink::Reply),
     Generic,
+    ExpectedMore(Option<ExpectedMore_Args>),
     InterfaceNotFound(Option<InterfaceNotFound_Args>),
     InvalidParameter(Option<InvalidParameter_Args>),
     MethodNotFound(Option<MethodNotFound_Args>),
     MethodNotImplemented(Option<MethodNotImplemented_Args>),
+    PermissionDenied(Option<PermissionDenied_Args>),
 }
 
 impl ::std::fmt::Display for ErrorKind {
@@ -51,6 +60,7 @@ impl ::std::fmt::Display for ErrorKind {
             ErrorKind::Varlink_Error => write!(f, "Varlink Error"),
             ErrorKind::VarlinkReply_Error(v) => write!(f, "Unknown error reply: '{:#?}'", v),
             ErrorKind::Generic => Ok(()),
+            ErrorKind::ExpectedMore(v) => write!(f, "org.varlink.service.ExpectedMore: {:#?}", v),
             ErrorKind::InterfaceNotFound(v) => {
                 write!(f, "org.varlink.service.InterfaceNotFound: {:#?}", v)
             }
@@ -63,6 +73,9 @@ impl ::std::fmt::Display for ErrorKind {
             ErrorKind::MethodNotImplemented(v) => {
                 write!(f, "org.varlink.service.MethodNotImplemented: {:#?}", v)
             }
+            ErrorKind::PermissionDenied(v) => {
+                write!(f, "org.varlink.service.PermissionDenied: {:#?}", v)
+            }
         }
     }
 }
@@ -124,6 +137,18 @@ impl ChainErrorFrom<varlink::Reply> for ErrorKind {
             return into_cherr!(e);
         }
         match e {
+            varlink::Reply {
+                error: Some(ref t), ..
+            } if t == "org.varlink.service.ExpectedMore" => match e {
+                varlink::Reply {
+                    parameters: Some(p),
+                    ..
+                } => match serde_json::from_value(p) {
+                    Ok(v) => into_cherr!(ErrorKind::ExpectedMore(v)),
+                    Err(_) => into_cherr!(ErrorKind::ExpectedMore(None)),
+                },
+                _ => into_cherr!(ErrorKind::ExpectedMore(None)),
+            },
             varlink::Reply {
                 error: Some(ref t), ..
             } if t == "org.varlink.service.InterfaceNotFound" => match e {
@@ -172,6 +197,18 @@ impl ChainErrorFrom<varlink::Reply> for ErrorKind {
                 },
                 _ => into_cherr!(ErrorKind::MethodNotImplemented(None)),
             },
+            varlink::Reply {
+                error: Some(ref t), ..
+            } if t == "org.varlink.service.PermissionDenied" => match e {
+                varlink::Reply {
+                    parameters: Some(p),
+                    ..
+                } => match serde_json::from_value(p) {
+                    Ok(v) => into_cherr!(ErrorKind::PermissionDenied(v)),
+                    Err(_) => into_cherr!(ErrorKind::PermissionDenied(None)),
+                },
+                _ => into_cherr!(ErrorKind::PermissionDenied(None)),
+            },
             _ => into_cherr!(ErrorKind::VarlinkReply_Error(e)),
         }
     }
@@ -236,16 +273,16 @@ pub trait Call_GetInterfaceDescription: varlink::CallTrait {
 impl<'a> Call_GetInterfaceDescription for varlink::Call<'a> {}
 
 pub trait VarlinkInterface {
-    fn get_info(&self, call: &mut Call_GetInfo) -> varlink::Result<()>;
+    fn get_info(&self, call: &mut dyn Call_GetInfo) -> varlink::Result<()>;
     fn get_interface_description(
         &self,
-        call: &mut Call_GetInterfaceDescription,
+        call: &mut dyn Call_GetInterfaceDescription,
         r#interface: String,
     ) -> varlink::Result<()>;
     fn call_upgraded(
         &self,
         _call: &mut varlink::Call,
-        _bufreader: &mut BufRead,
+        _bufreader: &mut dyn BufRead,
     ) -> varlink::Result<Vec<u8>> {
         Ok(Vec::new())
     }
@@ -290,17 +327,54 @@ impl VarlinkClientInterface for VarlinkClient {
 
 #[allow(dead_code)]
 pub struct VarlinkInterfaceProxy {
-    inner: Box<VarlinkInterface + Send + Sync>,
+    inner: Box<dyn VarlinkInterface + Send + Sync>,
 }
 
 #[allow(dead_code)]
-pub fn new(inner: Box<VarlinkInterface + Send + Sync>) -> VarlinkInterfaceProxy {
+pub fn new(inner: Box<dyn VarlinkInterface + Send + Sync>) -> VarlinkInterfaceProxy {
     VarlinkInterfaceProxy { inner }
 }
 
+impl VarlinkInterfaceProxy {
+    #[doc = r" The dispatch functions of the methods, sorted by the method name like"]
+    #[doc = r" `idl.methods`, for the binary search."]
+    const METHODS: &'static [(
+        &'static str,
+        fn(&VarlinkInterfaceProxy, &mut varlink::Call) -> varlink::Result<()>,
+    )] = &[
+        (
+            "org.varlink.service.GetInfo",
+            VarlinkInterfaceProxy::dispatch_get_info,
+        ),
+        (
+            "org.varlink.service.GetInterfaceDescription",
+            VarlinkInterfaceProxy::dispatch_get_interface_description,
+        ),
+    ];
+    fn dispatch_get_info(&self, call: &mut varlink::Call) -> varlink::Result<()> {
+        self.inner.get_info(call as &mut dyn Call_GetInfo)
+    }
+    fn dispatch_get_interface_description(&self, call: &mut varlink::Call) -> varlink::Result<()> {
+        let req = call.request.unwrap();
+        let args: GetInterfaceDescription_Args = match req.parameters_as() {
+            Some(Ok(v)) => v,
+            Some(Err(e)) => {
+                let es = format!("{}", e);
+                let _ = call.reply_invalid_parameter(es.clone());
+                return Err(into_cherr!(varlink::ErrorKind::SerdeJsonDe(es)));
+            }
+            None => return call.reply_invalid_parameter("parameters".into()),
+        };
+        self.inner.get_interface_description(
+            call as &mut dyn Call_GetInterfaceDescription,
+            args.r#interface,
+        )
+    }
+}
+
 impl varlink::Interface for VarlinkInterfaceProxy {
     fn get_description(&self) -> &'static str {
-        "# The Varlink Service Interface is provided by every varlink service. It\n# describes the service and the interfaces it implements.\ninterface org.varlink.service\n\n# Get a list of all the interfaces a service provides and information\n# about the implementation.\nmethod GetInfo() -> (\n  vendor: string,\n  product: string,\n  version: string,\n  url: string,\n  interfaces: []string\n)\n\n# Get the description of an interface that is implemented by this service.\nmethod GetInterfaceDescription(interface: string) -> (description: string)\n\n# The requested interface was not found.\nerror InterfaceNotFound (interface: string)\n\n# The requested method was not found\nerror MethodNotFound (method: string)\n\n# The interface defines the requested method, but the service does not\n# implement it.\nerror MethodNotImplemented (method: string)\n\n# One of the passed parameters is invalid.\nerror InvalidParameter (parameter: string)\n"
+        "# The Varlink Service Interface is provided by every varlink service. It\n# describes the service and the interfaces it implements.\ninterface org.varlink.service\n\n# Get a list of all the interfaces a service provides and information\n# about the implementation.\nmethod GetInfo() -> (\n  vendor: string,\n  product: string,\n  version: string,\n  url: string,\n  interfaces: []string\n)\n\n# Get the description of an interface that is implemented by this service.\nmethod GetInterfaceDescription(interface: string) -> (description: string)\n\n# The requested interface was not found.\nerror InterfaceNotFound (interface: string)\n\n# The requested method was not found\nerror MethodNotFound (method: string)\n\n# The interface defines the requested method, but the service does not\n# implement it.\nerror MethodNotImplemented (method: string)\n\n# One of the passed parameters is invalid.\nerror InvalidParameter (parameter: string)\n\n# Permission is denied.\nerror PermissionDenied ()\n\n# Method is expected to be called with 'more' set to true, but wasn't\nerror ExpectedMore ()\n"
     }
     fn get_name(&self) -> &'static str {
         "org.varlink.service"
@@ -308,33 +382,15 @@ impl varlink::Interface for VarlinkInterfaceProxy {
     fn call_upgraded(
         &self,
         call: &mut varlink::Call,
-        bufreader: &mut BufRead,
+        bufreader: &mut dyn BufRead,
     ) -> varlink::Result<Vec<u8>> {
         self.inner.call_upgraded(call, bufreader)
     }
     fn call(&self, call: &mut varlink::Call) -> varlink::Result<()> {
         let req = call.request.unwrap();
-        match req.method.as_ref() {
-            "org.varlink.service.GetInfo" => self.inner.get_info(call as &mut Call_GetInfo),
-            "org.varlink.service.GetInterfaceDescription" => {
-                if let Some(args) = req.parameters.clone() {
-                    let args: GetInterfaceDescription_Args = match serde_json::from_value(args) {
-                        Ok(v) => v,
-                        Err(e) => {
-                            let es = format!("{}", e);
-                            let _ = call.reply_invalid_parameter(es.clone());
-                            return Err(into_cherr!(varlink::ErrorKind::SerdeJsonDe(es)));
-                        }
-                    };
-                    self.inner.get_interface_description(
-                        call as &mut Call_GetInterfaceDescription,
-                        args.r#interface,
-                    )
-                } else {
-                    call.reply_invalid_parameter("parameters".into())
-                }
-            }
-            m => call.reply_method_not_found(String::from(m)),
+        match Self::METHODS.binary_search_by(|&(name, _)| name.cmp(&*req.method)) {
+            Ok(i) => (Self::METHODS[i].1)(self, call),
+            Err(_) => call.reply_method_not_found(req.method.to_string()),
         }
     }
 }